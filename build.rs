@@ -1,5 +1,56 @@
-// generated by `sqlx migrate build-script`
+use std::{
+    collections::hash_map::DefaultHasher,
+    env, fs,
+    hash::{Hash, Hasher},
+    path::PathBuf,
+    process::Command,
+};
+
 fn main() {
-    // trigger recompilation when a new migration is added
+    // from `sqlx migrate build-script` - trigger recompilation when a new migration is added
     println!("cargo:rerun-if-changed=migrations");
+
+    build_stylesheet();
+}
+
+///compiles `assets/denim.css` with the tailwind standalone CLI (`tailwindcss`, or whatever `TAILWINDCSS` points to)
+///
+///debug builds without the CLI get an empty stylesheet, which makes `render` fall back to compiling in the browser
+fn build_stylesheet() {
+    println!("cargo:rerun-if-changed=assets");
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-env-changed=TAILWINDCSS");
+
+    let out =
+        PathBuf::from(env::var("OUT_DIR").expect("cargo always sets OUT_DIR")).join("denim.css");
+    let cli = env::var("TAILWINDCSS").unwrap_or_else(|_| "tailwindcss".to_string());
+
+    let status = Command::new(&cli)
+        .args(["--input", "assets/denim.css", "--output"])
+        .arg(&out)
+        .arg("--minify")
+        .status();
+
+    let css = match status {
+        Ok(status) if status.success() => {
+            fs::read_to_string(&out).expect("tailwind just wrote the stylesheet")
+        }
+        _ if env::var("PROFILE").as_deref() == Ok("release") => {
+            panic!(
+                "unable to run {cli:?} to build the stylesheet - install the tailwind standalone CLI, or set TAILWINDCSS to its path"
+            );
+        }
+        _ => {
+            println!(
+                "cargo:warning=unable to run {cli:?}, so styles will be compiled in the browser instead"
+            );
+            fs::write(&out, "").expect("unable to write empty stylesheet");
+            String::new()
+        }
+    };
+
+    //for cache-busting, so that the stylesheet can be cached forever
+    let mut hasher = DefaultHasher::new();
+    css.hash(&mut hasher);
+    println!("cargo:rustc-env=DENIM_CSS_VERSION={:x}", hasher.finish());
 }
@@ -0,0 +1 @@
+ALTER TABLE events ADD COLUMN max_capacity INT CHECK (max_capacity > 0);
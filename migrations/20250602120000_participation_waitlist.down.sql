@@ -0,0 +1,3 @@
+ALTER TABLE participation
+    DROP COLUMN is_waitlisted,
+    DROP COLUMN signed_up_at;
@@ -0,0 +1,3 @@
+ALTER TABLE participation
+    ADD COLUMN is_waitlisted BOOL NOT NULL DEFAULT FALSE,
+    ADD COLUMN signed_up_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
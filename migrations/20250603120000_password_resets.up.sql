@@ -0,0 +1,10 @@
+CREATE TABLE password_resets (
+    token TEXT PRIMARY KEY,
+    user_id uuid NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+
+    CONSTRAINT password_reset_user_id_fk
+        FOREIGN KEY (user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
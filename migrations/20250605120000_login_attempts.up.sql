@@ -0,0 +1,5 @@
+CREATE TABLE login_attempts (
+    email TEXT PRIMARY KEY NOT NULL,
+    failed_attempts INT NOT NULL DEFAULT 0,
+    locked_until TIMESTAMPTZ
+);
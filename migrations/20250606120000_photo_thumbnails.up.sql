@@ -0,0 +1,2 @@
+ALTER TABLE photos
+    ADD COLUMN has_thumbnail BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1 @@
+DROP TABLE house_points;
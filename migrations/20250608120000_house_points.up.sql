@@ -0,0 +1,18 @@
+CREATE TABLE house_points (
+    id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    house_id INT NOT NULL,
+    points INT NOT NULL,
+    reason TEXT NOT NULL,
+    awarded_by uuid,
+    awarded_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    CONSTRAINT house_points_house_id_fk
+        FOREIGN KEY (house_id)
+            REFERENCES houses(id)
+            ON DELETE CASCADE,
+
+    CONSTRAINT house_points_awarded_by_fk
+        FOREIGN KEY (awarded_by)
+            REFERENCES users(id)
+            ON DELETE SET NULL
+);
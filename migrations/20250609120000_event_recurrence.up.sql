@@ -0,0 +1,2 @@
+ALTER TABLE events
+    ADD COLUMN recurrence_group uuid;
@@ -0,0 +1,10 @@
+ALTER TABLE events
+    ADD COLUMN associated_staff_member uuid,
+    ADD CONSTRAINT staff_fk
+        FOREIGN KEY (associated_staff_member)
+            REFERENCES staff(user_id);
+
+-- only one staff member can survive going back
+UPDATE events e SET associated_staff_member = (SELECT es.staff_id FROM event_staff es WHERE es.event_id = e.id LIMIT 1);
+
+DROP TABLE event_staff;
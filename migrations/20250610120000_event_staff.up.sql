@@ -0,0 +1,22 @@
+CREATE TABLE event_staff (
+    event_id uuid NOT NULL,
+    staff_id uuid NOT NULL,
+
+    PRIMARY KEY (event_id, staff_id),
+
+    CONSTRAINT event_staff_event_id_fk
+        FOREIGN KEY (event_id)
+            REFERENCES events(id)
+            ON DELETE CASCADE,
+
+    CONSTRAINT event_staff_staff_id_fk
+        FOREIGN KEY (staff_id)
+            REFERENCES staff(user_id)
+            ON DELETE CASCADE
+);
+
+INSERT INTO event_staff (event_id, staff_id)
+    SELECT id, associated_staff_member FROM events WHERE associated_staff_member IS NOT NULL;
+
+ALTER TABLE events
+    DROP COLUMN associated_staff_member;
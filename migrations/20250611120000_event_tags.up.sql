@@ -0,0 +1,21 @@
+CREATE TABLE tags (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE event_tags (
+    event_id uuid NOT NULL,
+    tag_id INT NOT NULL,
+
+    PRIMARY KEY (event_id, tag_id),
+
+    CONSTRAINT event_tags_event_id_fk
+        FOREIGN KEY (event_id)
+            REFERENCES events(id)
+            ON DELETE CASCADE,
+
+    CONSTRAINT event_tags_tag_id_fk
+        FOREIGN KEY (tag_id)
+            REFERENCES tags(id)
+            ON DELETE CASCADE
+);
@@ -0,0 +1,14 @@
+CREATE TABLE audit_log (
+    id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    actor_id uuid,
+    action TEXT NOT NULL,
+    target_type TEXT NOT NULL,
+    target_id TEXT,
+    detail TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    CONSTRAINT audit_log_actor_id_fk
+        FOREIGN KEY (actor_id)
+            REFERENCES users(id)
+            ON DELETE SET NULL
+);
@@ -0,0 +1,6 @@
+DROP INDEX photos_event_id_idx;
+DROP INDEX tutor_groups_house_id_idx;
+DROP INDEX students_tutor_group_id_idx;
+DROP INDEX participation_student_id_idx;
+
+ALTER TABLE participation DROP CONSTRAINT participation_event_student_unique;
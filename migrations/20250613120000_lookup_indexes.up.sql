@@ -0,0 +1,15 @@
+-- get rid of any duplicate signups that have already snuck in before adding the constraint
+DELETE FROM participation a
+    USING participation b
+    WHERE a.ctid > b.ctid
+        AND a.event_id = b.event_id
+        AND a.student_id = b.student_id;
+
+-- also covers lookups by just event_id
+ALTER TABLE participation
+    ADD CONSTRAINT participation_event_student_unique UNIQUE (event_id, student_id);
+
+CREATE INDEX participation_student_id_idx ON participation (student_id);
+CREATE INDEX students_tutor_group_id_idx ON students (tutor_group_id);
+CREATE INDEX tutor_groups_house_id_idx ON tutor_groups (house_id);
+CREATE INDEX photos_event_id_idx ON photos (event_id);
@@ -0,0 +1,3 @@
+DROP INDEX events_deleted_at_idx;
+
+ALTER TABLE events DROP COLUMN deleted_at;
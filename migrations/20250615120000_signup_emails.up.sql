@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN wants_signup_emails BOOLEAN NOT NULL DEFAULT TRUE;
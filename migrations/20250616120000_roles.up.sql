@@ -0,0 +1,36 @@
+CREATE TABLE roles (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    --set for the built-in roles that every student/staff member/admin has
+    kind TEXT UNIQUE CHECK (kind IN ('student', 'staff', 'admin'))
+);
+
+CREATE TABLE role_permissions (
+    role_id INT NOT NULL,
+    --the name of the `PermissionsTarget` flag
+    permission TEXT NOT NULL,
+
+    PRIMARY KEY (role_id, permission),
+
+    CONSTRAINT role_permissions_role_id_fk
+        FOREIGN KEY (role_id)
+            REFERENCES roles(id)
+            ON DELETE CASCADE
+);
+
+ALTER TABLE users ADD COLUMN role_id INT;
+ALTER TABLE users ADD CONSTRAINT users_role_id_fk
+    FOREIGN KEY (role_id)
+        REFERENCES roles(id)
+        ON DELETE SET NULL;
+
+INSERT INTO roles (name, kind) VALUES ('Student', 'student'), ('Staff', 'staff'), ('Admin', 'admin');
+
+INSERT INTO role_permissions (role_id, permission)
+SELECT r.id, p.permission FROM roles r, UNNEST(ARRAY['VIEW_PHOTOS', 'VIEW_SENSITIVE_DETAILS', 'SIGN_SELF_UP']) AS p(permission) WHERE r.kind = 'student';
+
+INSERT INTO role_permissions (role_id, permission)
+SELECT r.id, p.permission FROM roles r, UNNEST(ARRAY['SIGN_OTHERS_UP', 'VERIFY_ATTENDANCE', 'CRUD_EVENTS', 'CRUD_USERS', 'VIEW_PHOTOS', 'EXPORT_CSVS', 'VIEW_SENSITIVE_DETAILS', 'UPLOAD_PHOTOS']) AS p(permission) WHERE r.kind = 'staff';
+
+INSERT INTO role_permissions (role_id, permission)
+SELECT r.id, p.permission FROM roles r, UNNEST(ARRAY['SIGN_OTHERS_UP', 'VERIFY_ATTENDANCE', 'CRUD_EVENTS', 'CRUD_USERS', 'VIEW_PHOTOS', 'IMPORT_CSVS', 'EXPORT_CSVS', 'CRUD_ADMINS', 'VIEW_SENSITIVE_DETAILS', 'RUN_ONBOARDING', 'UPLOAD_PHOTOS']) AS p(permission) WHERE r.kind = 'admin';
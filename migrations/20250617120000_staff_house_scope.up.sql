@@ -0,0 +1,6 @@
+--staff with a house can only manage students in that house
+ALTER TABLE staff ADD COLUMN house_id INT;
+ALTER TABLE staff ADD CONSTRAINT staff_house_id_fk
+    FOREIGN KEY (house_id)
+        REFERENCES houses(id)
+        ON DELETE SET NULL;
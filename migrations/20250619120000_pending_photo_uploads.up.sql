@@ -0,0 +1,9 @@
+-- no foreign key on the event, so that uploads for deleted events still get swept out of S3
+CREATE TABLE pending_photo_uploads (
+    id uuid PRIMARY KEY,
+    event_id uuid NOT NULL,
+    extension TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX pending_photo_uploads_event_id_idx ON pending_photo_uploads (event_id);
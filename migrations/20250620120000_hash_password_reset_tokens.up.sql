@@ -0,0 +1,3 @@
+-- existing links were stored in plaintext, so they can't be hashed after the fact
+DELETE FROM password_resets;
+ALTER TABLE password_resets RENAME COLUMN token TO token_hash;
@@ -0,0 +1,2 @@
+ALTER TABLE photos
+    DROP COLUMN created_at;
@@ -0,0 +1,3 @@
+-- photo ids are random, so pages need something stable to be ordered by
+ALTER TABLE photos
+    ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
@@ -1,6 +1,6 @@
 use crate::{
     auth::backend::DenimAuthBackend,
-    data::user::User,
+    data::{event::Event, user::User},
     error::{BcryptSnafu, DenimError, DenimResult, MakeQuerySnafu},
 };
 use axum_login::AuthSession;
@@ -11,8 +11,12 @@ use snafu::ResultExt;
 use sqlx::PgConnection;
 use uuid::Uuid;
 
+pub mod api_token;
 pub mod backend;
+pub mod csrf;
+pub mod login_attempts;
 pub mod postgres_store;
+pub mod totp;
 
 pub type DenimSession = AuthSession<DenimAuthBackend>;
 
@@ -20,6 +24,90 @@ pub trait AuthUtilities {
     fn can(&self, needed: PermissionsTarget) -> bool;
     fn ensure_can(&self, needed: PermissionsTarget) -> DenimResult<()>;
     fn get_permissions (&self) -> PermissionsTarget;
+    ///the only house they can act within, if they're a house-scoped staff member
+    fn house_scope(&self) -> Option<i32>;
+    fn user_id(&self) -> Option<Uuid>;
+
+    ///`house_id` is the house of whatever's being acted on, with `None` for something outside of any house
+    ///
+    ///house-scoped staff can only act within their own house
+    fn can_for_house(&self, needed: PermissionsTarget, house_id: Option<i32>) -> bool {
+        self.can(needed)
+            && self
+                .house_scope()
+                .is_none_or(|scope| house_id == Some(scope))
+    }
+
+    fn ensure_can_for_house(
+        &self,
+        needed: PermissionsTarget,
+        house_id: Option<i32>,
+    ) -> DenimResult<()> {
+        self.ensure_can(needed)?;
+        match self.house_scope() {
+            Some(scope) if house_id != Some(scope) => Err(DenimError::OutsideHouseScope { scope }),
+            _ => Ok(()),
+        }
+    }
+
+    ///for things that touch students from several houses at once
+    fn ensure_can_for_houses(
+        &self,
+        needed: PermissionsTarget,
+        house_ids: &[i32],
+    ) -> DenimResult<()> {
+        self.ensure_can(needed)?;
+        match self.house_scope() {
+            Some(scope) if house_ids.iter().any(|house_id| *house_id != scope) => {
+                Err(DenimError::OutsideHouseScope { scope })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    ///house-scoped staff can only manage events where everyone taking part is in their house
+    ///
+    ///with nobody taking part there's no house to go by, so then they need to be one of the event's staff
+    ///
+    ///`series` also checks every other occurrence of a repeating event
+    async fn ensure_can_for_event(
+        &self,
+        needed: PermissionsTarget,
+        event_id: Uuid,
+        series: bool,
+        conn: &mut PgConnection,
+    ) -> DenimResult<()> {
+        let Some(scope) = self.house_scope() else {
+            return self.ensure_can(needed);
+        };
+        let house_ids = Event::participant_houses(event_id, series, conn).await?;
+        if house_ids.is_empty() {
+            self.ensure_can(needed)?;
+            let is_event_staff = match self.user_id() {
+                Some(user_id) => Event::is_run_by(event_id, series, user_id, conn).await?,
+                None => false,
+            };
+            return if is_event_staff {
+                Ok(())
+            } else {
+                Err(DenimError::OutsideHouseScope { scope })
+            };
+        }
+        self.ensure_can_for_houses(needed, &house_ids)
+    }
+
+    async fn ensure_can_for_students(
+        &self,
+        needed: PermissionsTarget,
+        student_ids: &[Uuid],
+        conn: &mut PgConnection,
+    ) -> DenimResult<()> {
+        if self.house_scope().is_none() {
+            return self.ensure_can(needed);
+        }
+        let house_ids = User::get_student_houses(student_ids, conn).await?;
+        self.ensure_can_for_houses(needed, &house_ids)
+    }
 }
 
 impl AuthUtilities for DenimSession {
@@ -49,6 +137,14 @@ impl AuthUtilities for DenimSession {
             .as_ref()
             .map_or_else(PermissionsTarget::empty, User::get_permissions)
     }
+
+    fn house_scope(&self) -> Option<i32> {
+        self.user.as_ref().and_then(|user| user.house_scope)
+    }
+
+    fn user_id(&self) -> Option<Uuid> {
+        self.user.as_ref().map(|user| user.id)
+    }
 }
 
 bitflags! {
@@ -102,10 +198,11 @@ impl From<Uuid> for PasswordUserId {
 pub async fn add_password(
     mut current_user: PasswordUserId,
     password: SecretString,
+    bcrypt_cost: u32,
     conn: &mut PgConnection,
     is_default: bool,
 ) -> DenimResult<PasswordUserId> {
-    let hashed = hash(password.expose_secret(), bcrypt::DEFAULT_COST).context(BcryptSnafu)?;
+    let hashed = hash(password.expose_secret(), bcrypt_cost).context(BcryptSnafu)?;
 
     sqlx::query!("UPDATE users SET bcrypt_hashed_password = $2, current_password_is_default = $3 WHERE id = $1", current_user.id(), hashed, is_default).execute(&mut *conn).await.context(MakeQuerySnafu)?;
 
@@ -116,3 +213,52 @@ pub async fn add_password(
 
     Ok(current_user)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{AuthUtilities, PermissionsTarget, api_token::ApiUser};
+    use crate::data::{
+        DataType,
+        testing::{insert_event, insert_house, insert_staff},
+        user::User,
+    };
+    use sqlx::PgPool;
+
+    #[sqlx::test]
+    async fn scoped_staff_need_to_run_events_without_participants(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let house = insert_house("Test House", &mut conn).await;
+        let staff_id = insert_staff(Some(house), &mut conn).await;
+        let someone_elses = insert_event(None, &mut conn).await;
+        let their_own = insert_event(None, &mut conn).await;
+        sqlx::query!(
+            "INSERT INTO public.event_staff (event_id, staff_id) VALUES ($1, $2)",
+            their_own,
+            staff_id
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+        let staff = ApiUser(
+            User::get_from_db_by_id(staff_id, &mut conn)
+                .await
+                .unwrap()
+                .unwrap(),
+        );
+        assert_eq!(staff.house_scope(), Some(house));
+
+        assert!(
+            staff
+                .ensure_can_for_event(PermissionsTarget::empty(), someone_elses, false, &mut conn)
+                .await
+                .is_err()
+        );
+        assert!(
+            staff
+                .ensure_can_for_event(PermissionsTarget::empty(), their_own, false, &mut conn)
+                .await
+                .is_ok()
+        );
+    }
+}
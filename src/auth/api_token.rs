@@ -0,0 +1,91 @@
+use crate::{
+    auth::{AuthUtilities, PermissionsTarget},
+    data::{DataType, user::User},
+    error::{DenimError, DenimResult, InvalidApiTokenSnafu, MakeQuerySnafu},
+    state::DenimState,
+};
+use axum::{
+    extract::FromRequestParts,
+    http::{header, request::Parts},
+};
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use rand::{Rng, rng};
+use sha2::{Digest, Sha256};
+use snafu::{OptionExt, ResultExt};
+use uuid::Uuid;
+
+///only shown to the user once, and then just the hash is kept
+pub fn generate_api_token() -> String {
+    format!(
+        "denim_{}",
+        BASE64_URL_SAFE_NO_PAD.encode(rng().random::<[u8; 32]>())
+    )
+}
+
+///tokens are only ever stored hashed - unlike passwords they're long & random, so a fast hash is fine
+pub fn hash_api_token(token: &str) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()))
+}
+
+///a user authenticated with `Authorization: Bearer <token>` rather than a session cookie
+pub struct ApiUser(pub User);
+
+impl FromRequestParts<DenimState> for ApiUser {
+    type Rejection = DenimError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &DenimState) -> DenimResult<Self> {
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .context(InvalidApiTokenSnafu)?;
+
+        let mut conn = state.get_connection().await?;
+        let id = sqlx::query!(
+            "SELECT id FROM public.users WHERE access_token = $1",
+            hash_api_token(token)
+        )
+        .fetch_optional(&mut *conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .context(InvalidApiTokenSnafu)?
+        .id;
+
+        let user = User::get_from_db_by_id(id, &mut conn)
+            .await?
+            .context(InvalidApiTokenSnafu)?;
+
+        Ok(Self(user))
+    }
+}
+
+impl AuthUtilities for ApiUser {
+    fn can(&self, needed: PermissionsTarget) -> bool {
+        self.0.get_permissions().contains(needed)
+    }
+
+    fn ensure_can(&self, needed: PermissionsTarget) -> DenimResult<()> {
+        let found = self.0.get_permissions();
+
+        if found.contains(needed) {
+            Ok(())
+        } else {
+            Err(DenimError::IncorrectPermissions { needed, found })
+        }
+    }
+
+    fn get_permissions(&self) -> PermissionsTarget {
+        self.0.get_permissions()
+    }
+
+    fn house_scope(&self) -> Option<i32> {
+        self.0.house_scope
+    }
+
+    fn user_id(&self) -> Option<Uuid> {
+        Some(self.0.id)
+    }
+}
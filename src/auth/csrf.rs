@@ -0,0 +1,108 @@
+use crate::error::{CsrfBodySnafu, DenimError, DenimResult, TowerSessionSnafu};
+use axum::{
+    body::{Body, to_bytes},
+    extract::Request,
+    http::{Method, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_login::tower_sessions::Session;
+use rand::{Rng, distr::Alphanumeric, rng};
+use snafu::ResultExt;
+
+const CSRF_SESSION_KEY: &str = "csrf_token";
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+pub const CSRF_FORM_FIELD: &str = "csrf_token";
+const API_PREFIX: &str = "/api/";
+
+tokio::task_local! {
+    //so that rendering doesn't need the session threaded all the way through
+    static CSRF_TOKEN: String;
+}
+
+///the token for the session currently being responded to, if there is one
+pub fn current_token() -> Option<String> {
+    CSRF_TOKEN.try_with(Clone::clone).ok()
+}
+
+async fn get_or_create_token(session: &Session) -> DenimResult<String> {
+    if let Some(token) = session
+        .get::<String>(CSRF_SESSION_KEY)
+        .await
+        .context(TowerSessionSnafu)?
+    {
+        return Ok(token);
+    }
+
+    let token: String = rng()
+        .sample_iter(Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect();
+    session
+        .insert(CSRF_SESSION_KEY, token.clone())
+        .await
+        .context(TowerSessionSnafu)?;
+    Ok(token)
+}
+
+///htmx sends the token in a header (from `hx-headers` on the body), and plain forms send it as a field
+async fn ensure_token_matches(expected: &str, request: Request) -> DenimResult<Request> {
+    if request
+        .headers()
+        .get(CSRF_HEADER)
+        .and_then(|provided| provided.to_str().ok())
+        .is_some_and(|provided| provided == expected)
+    {
+        return Ok(request);
+    }
+
+    let is_form = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("application/x-www-form-urlencoded"));
+    if !is_form {
+        return Err(DenimError::CsrfMismatch);
+    }
+
+    //the body limit layer is further out, so no need for another limit here
+    let (parts, body) = request.into_parts();
+    let bytes = to_bytes(body, usize::MAX).await.context(CsrfBodySnafu)?;
+
+    let matches = serde_urlencoded::from_bytes::<Vec<(String, String)>>(&bytes)
+        .unwrap_or_default()
+        .into_iter()
+        .any(|(key, provided)| key == CSRF_FORM_FIELD && provided == expected);
+    if !matches {
+        return Err(DenimError::CsrfMismatch);
+    }
+
+    Ok(Request::from_parts(parts, Body::from(bytes)))
+}
+
+pub async fn csrf_protection(session: Session, request: Request, next: Next) -> Response {
+    //the API is authenticated with bearer tokens rather than cookies, so can't be forged cross-site
+    if request.uri().path().starts_with(API_PREFIX) {
+        return next.run(request).await;
+    }
+
+    let token = match get_or_create_token(&session).await {
+        Ok(token) => token,
+        Err(e) => return e.into_response(),
+    };
+
+    let request = if matches!(
+        *request.method(),
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    ) {
+        match ensure_token_matches(&token, request).await {
+            Ok(request) => request,
+            Err(e) => return e.into_response(),
+        }
+    } else {
+        request
+    };
+
+    CSRF_TOKEN.scope(token, next.run(request)).await
+}
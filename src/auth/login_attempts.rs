@@ -0,0 +1,64 @@
+use crate::error::{DenimResult, MakeQuerySnafu};
+use email_address::EmailAddress;
+use snafu::ResultExt;
+use sqlx::PgConnection;
+
+pub async fn is_locked_out(email: &EmailAddress, conn: &mut PgConnection) -> DenimResult<bool> {
+    Ok(sqlx::query!(
+        r#"SELECT EXISTS(SELECT 1 FROM public.login_attempts WHERE email = $1 AND locked_until > NOW()) as "exists!""#,
+        email.as_str()
+    )
+    .fetch_one(conn)
+    .await
+    .context(MakeQuerySnafu)?
+    .exists)
+}
+
+///locks the account once `max_failed_logins` consecutive failures have happened, returning whether it just got locked
+pub async fn record_failure(
+    email: &EmailAddress,
+    max_failed_logins: i32,
+    lockout_minutes: i32,
+    conn: &mut PgConnection,
+) -> DenimResult<bool> {
+    let failed_attempts = sqlx::query!(
+        "INSERT INTO public.login_attempts (email, failed_attempts) VALUES ($1, 1) ON CONFLICT (email) DO UPDATE SET failed_attempts = login_attempts.failed_attempts + 1 RETURNING failed_attempts",
+        email.as_str()
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .context(MakeQuerySnafu)?
+    .failed_attempts;
+
+    let locked = failed_attempts >= max_failed_logins;
+    if locked {
+        warn!(
+            ?email,
+            ?failed_attempts,
+            "Locking account after failed logins"
+        );
+
+        sqlx::query!(
+            "UPDATE public.login_attempts SET failed_attempts = 0, locked_until = NOW() + make_interval(mins => $2) WHERE email = $1",
+            email.as_str(),
+            lockout_minutes
+        )
+        .execute(conn)
+        .await
+        .context(MakeQuerySnafu)?;
+    }
+
+    Ok(locked)
+}
+
+pub async fn clear(email: &EmailAddress, conn: &mut PgConnection) -> DenimResult<()> {
+    sqlx::query!(
+        "DELETE FROM public.login_attempts WHERE email = $1",
+        email.as_str()
+    )
+    .execute(conn)
+    .await
+    .context(MakeQuerySnafu)?;
+
+    Ok(())
+}
@@ -10,6 +10,18 @@ use axum_login::tower_sessions::{
 };
 use snafu::ResultExt;
 use sqlx::PgConnection;
+use std::collections::HashMap;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+///where `axum-login` keeps the logged in user inside the session data
+const AUTH_DATA_KEY: &str = "axum-login.data";
+
+#[derive(Debug, Clone)]
+pub struct UserSession {
+    pub id: String,
+    pub expiry_date: OffsetDateTime,
+}
 
 #[derive(Debug, Clone)]
 pub struct PostgresSessionStore {
@@ -43,6 +55,65 @@ impl PostgresSessionStore {
 
         Ok(())
     }
+
+    fn session_belongs_to(data: &[u8], user_id: Uuid) -> bool {
+        //sessions we can't decode can't belong to anyone who's logged in
+        let Ok(data) = rmp_serde::from_slice::<HashMap<String, serde_json::Value>>(data) else {
+            return false;
+        };
+
+        data.get(AUTH_DATA_KEY)
+            .and_then(|auth_data| auth_data.get("user_id"))
+            .and_then(|found_id| found_id.as_str())
+            .is_some_and(|found_id| found_id == user_id.to_string())
+    }
+
+    ///most recently expiring first, which is roughly the most recently used
+    pub async fn get_sessions_for_user(
+        user_id: Uuid,
+        conn: &mut PgConnection,
+    ) -> Result<Vec<UserSession>, DenimError> {
+        Ok(sqlx::query!(
+            "SELECT * FROM public.sessions WHERE expiry_date > NOW() ORDER BY expiry_date DESC"
+        )
+        .fetch_all(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .into_iter()
+        .filter(|record| Self::session_belongs_to(&record.data, user_id))
+        .map(|record| UserSession {
+            id: record.id,
+            expiry_date: record.expiry_date,
+        })
+        .collect())
+    }
+
+    ///returns whether or not a session was actually removed
+    pub async fn revoke_session_for_user(
+        session_id: &str,
+        user_id: Uuid,
+        conn: &mut PgConnection,
+    ) -> Result<bool, DenimError> {
+        let Some(record) =
+            sqlx::query!("SELECT data FROM public.sessions WHERE id = $1", session_id)
+                .fetch_optional(&mut *conn)
+                .await
+                .context(MakeQuerySnafu)?
+        else {
+            return Ok(false);
+        };
+
+        if !Self::session_belongs_to(&record.data, user_id) {
+            return Ok(false);
+        }
+
+        sqlx::query!("DELETE FROM public.sessions WHERE id = $1", session_id)
+            .execute(conn)
+            .await
+            .context(MakeQuerySnafu)?;
+
+        Ok(true)
+    }
 }
 
 #[async_trait]
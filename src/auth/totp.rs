@@ -0,0 +1,74 @@
+use crate::error::{DenimResult, SystemTimeSnafu, TotpQrSnafu, TotpSecretSnafu, TotpUrlSnafu};
+use email_address::EmailAddress;
+use secrecy::{ExposeSecret, SecretString};
+use snafu::ResultExt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use subtle::ConstantTimeEq;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+const STEP_SECS: u64 = 30;
+const SKEW_STEPS: u8 = 1;
+
+pub fn generate_secret() -> SecretString {
+    Secret::generate_secret().to_encoded().to_string().into()
+}
+
+///6 digits every 30s, accepting codes from 1 step either side to allow for clock drift
+///
+///`issuer` is only shown in authenticator apps, so it isn't needed just to check codes
+pub fn make_totp(
+    secret: &SecretString,
+    account: &EmailAddress,
+    issuer: Option<&str>,
+) -> DenimResult<TOTP> {
+    let secret = Secret::Encoded(secret.expose_secret().to_string())
+        .to_bytes()
+        .context(TotpSecretSnafu)?;
+
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        SKEW_STEPS,
+        STEP_SECS,
+        secret,
+        //colons aren't allowed in the issuer
+        issuer.map(|issuer| issuer.replace(':', "")),
+        account.to_string(),
+    )
+    .context(TotpUrlSnafu)
+}
+
+///the time step that `code` is valid for, if any - keeping track of these stops the same code being used twice
+pub fn accepted_step(
+    secret: &SecretString,
+    account: &EmailAddress,
+    code: &str,
+) -> DenimResult<Option<i64>> {
+    let totp = make_totp(secret, account, None)?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context(SystemTimeSnafu)?
+        .as_secs();
+    let code = code.trim().as_bytes();
+
+    let current_step = now / STEP_SECS;
+    let skew = u64::from(SKEW_STEPS);
+    Ok((current_step - skew..=current_step + skew)
+        .find(|step| {
+            totp.generate(step * STEP_SECS)
+                .as_bytes()
+                .ct_eq(code)
+                .into()
+        })
+        .and_then(|step| i64::try_from(step).ok()))
+}
+
+pub fn qr_code_base64(
+    secret: &SecretString,
+    account: &EmailAddress,
+    issuer: &str,
+) -> DenimResult<String> {
+    make_totp(secret, account, Some(issuer))?
+        .get_qr_base64()
+        .map_err(|message| TotpQrSnafu { message }.build())
+}
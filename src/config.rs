@@ -1,26 +1,46 @@
 use crate::{
     config::{
         auth::AuthConfig, date_locale::DateLocaleConfig, db::DbConfig,
-        important_item::ImportantItemContainer,
+        important_item::ImportantItemContainer, photos::PhotoConfig, security::SecurityConfig,
     },
-    error::{DenimResult, S3CredsSnafu, S3Snafu},
+    error::{DenimResult, ParsePortSnafu, ParseServerAddressSnafu, S3CredsSnafu, S3Snafu},
+    mail::{Mailer, SmtpMailer},
 };
 use dotenvy::var;
 use s3::{Bucket, Region, creds::Credentials};
 use snafu::ResultExt;
-use std::sync::Arc;
+use std::{
+    fmt::Debug,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+};
+
+const DEFAULT_SERVER_ADDRESS: &str = "127.0.0.1:8080";
+const DEFAULT_PORT: u16 = 8080;
+const DEFAULT_APP_NAME: &str = "Denim";
 
 pub mod auth;
 pub mod date_locale;
 pub mod db;
 pub mod important_item;
+pub mod photos;
+pub mod security;
 
 #[derive(Clone, Debug)]
 pub struct RuntimeConfiguration {
     db_config: Arc<DbConfig>,
+    security_config: Arc<SecurityConfig>,
+    photo_config: Arc<PhotoConfig>,
     auth_config: ImportantItemContainer<AuthConfig>,
     s3_bucket: ImportantItemContainer<Bucket>,
     date_locale_config: ImportantItemContainer<DateLocaleConfig>,
+    mailer: Option<Arc<dyn Mailer>>,
+    base_url: Option<Arc<str>>,
+    app_name: Arc<str>,
+    logo_url: Option<Arc<str>>,
+    sse_replay_capacity: usize,
+    server_address: SocketAddr,
 }
 
 impl RuntimeConfiguration {
@@ -83,11 +103,35 @@ impl RuntimeConfiguration {
         let (auth_config, date_locale_config) = auth_config_and_date_locale_config
             .unwrap_or_else(|| (ImportantItemContainer::new(), ImportantItemContainer::new()));
 
+        let mailer = SmtpMailer::from_env()?.map(|mailer| Arc::new(mailer) as Arc<dyn Mailer>);
+        if mailer.is_none() {
+            info!("No SMTP_HOST set, so emails (eg. password resets) are disabled");
+        }
+
         Ok(Self {
             db_config: Arc::new(DbConfig::new()?),
+            security_config: Arc::new(SecurityConfig::new()),
+            photo_config: Arc::new(PhotoConfig::new()),
             s3_bucket,
             auth_config,
             date_locale_config,
+            mailer,
+            base_url: var("BASE_URL")
+                .ok()
+                .map(|url| url.trim_end_matches('/').into()),
+            app_name: var("DENIM_APP_NAME")
+                .ok()
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| DEFAULT_APP_NAME.to_string())
+                .into(),
+            logo_url: var("DENIM_LOGO_URL")
+                .ok()
+                .map(|url| url.trim().to_string())
+                .filter(|url| !url.is_empty())
+                .map(Into::into),
+            sse_replay_capacity: get_env_var_matching("DENIM_SSE_REPLAY_BUFFER", 128, |_| true),
+            server_address: server_address_from_env()?,
         })
     }
 
@@ -95,6 +139,14 @@ impl RuntimeConfiguration {
         self.db_config.clone()
     }
 
+    pub fn security_config(&self) -> Arc<SecurityConfig> {
+        self.security_config.clone()
+    }
+
+    pub fn photo_config(&self) -> Arc<PhotoConfig> {
+        self.photo_config.clone()
+    }
+
     pub fn auth_config(&self) -> ImportantItemContainer<AuthConfig> {
         self.auth_config.clone()
     }
@@ -107,12 +159,256 @@ impl RuntimeConfiguration {
         self.date_locale_config.clone()
     }
 
+    pub fn mailer(&self) -> Option<Arc<dyn Mailer>> {
+        self.mailer.clone()
+    }
+
+    ///the public URL denim is served from, for links that leave the site (eg. in emails)
+    pub fn base_url(&self) -> Option<Arc<str>> {
+        self.base_url.clone()
+    }
+
+    ///what the instance calls itself in page titles, the nav and emails, set via `DENIM_APP_NAME`
+    pub fn app_name(&self) -> Arc<str> {
+        self.app_name.clone()
+    }
+
+    ///shown alongside the app name if set, via `DENIM_LOGO_URL`
+    pub fn logo_url(&self) -> Option<Arc<str>> {
+        self.logo_url.clone()
+    }
+
+    ///how many recent SSE events are kept for clients reconnecting with `Last-Event-ID`, set via `DENIM_SSE_REPLAY_BUFFER` (0 turns replaying off)
+    pub const fn sse_replay_capacity(&self) -> usize {
+        self.sse_replay_capacity
+    }
+
+    pub const fn server_address(&self) -> SocketAddr {
+        self.server_address
+    }
+
+    ///safe to call as many times as needed, as it just overwrites what's in the bucket
     pub async fn save(&self) -> DenimResult<()> {
-        if let Ok(bucket) = self.s3_bucket.get() {
-            self.auth_config.save(&bucket).await?;
-            self.date_locale_config.save(&bucket).await?;
-        }
+        let Ok(bucket) = self.s3_bucket.get() else {
+            info!("No S3 bucket set up yet, so no config to save");
+            return Ok(());
+        };
+
+        self.auth_config.save(&bucket).await?;
+        self.date_locale_config.save(&bucket).await?;
 
         Ok(())
     }
 }
+
+///`DENIM_SERVER_IP` can be a full socket address, or just an IP (eg. `[::]` for IPv6) to use the default port
+///
+///`DENIM_PORT` then overrides whichever port that ends up with
+fn server_address_from_env() -> DenimResult<SocketAddr> {
+    let address = var("DENIM_SERVER_IP").unwrap_or_else(|_| DEFAULT_SERVER_ADDRESS.to_string());
+    let address = address.trim();
+
+    let mut server_address = match address.parse::<SocketAddr>() {
+        Ok(server_address) => server_address,
+        Err(_e) => {
+            let ip = address
+                .strip_prefix('[')
+                .and_then(|ip| ip.strip_suffix(']'))
+                .unwrap_or(address);
+            let ip: IpAddr = ip.parse().context(ParseServerAddressSnafu { address })?;
+            SocketAddr::new(ip, DEFAULT_PORT)
+        }
+    };
+
+    if let Ok(port) = var("DENIM_PORT") {
+        server_address.set_port(port.trim().parse().context(ParsePortSnafu)?);
+    }
+
+    Ok(server_address)
+}
+
+///for settings with sensible defaults, where falling back is better than refusing to start
+///
+///values also need to be positive, as none of these make sense as zero
+fn get_env_var_or<T: FromStr + Copy + Debug + Default + PartialOrd>(
+    name: &'static str,
+    default: T,
+) -> T {
+    get_env_var_matching(name, default, |parsed| *parsed > T::default())
+}
+
+///like [`get_env_var_or`], but for values where zero (or anything else `is_valid` allows) means something
+fn get_env_var_matching<T: FromStr + Copy + Debug>(
+    name: &'static str,
+    default: T,
+    is_valid: impl Fn(&T) -> bool,
+) -> T {
+    parse_env_var(name, var(name).ok(), default, is_valid)
+}
+
+fn parse_env_var<T: FromStr + Copy + Debug>(
+    name: &'static str,
+    value: Option<String>,
+    default: T,
+    is_valid: impl Fn(&T) -> bool,
+) -> T {
+    let Some(value) = value else {
+        debug!(?name, ?default, "Env var not set, using default");
+        return default;
+    };
+
+    match value.trim().parse() {
+        Ok(parsed) if is_valid(&parsed) => parsed,
+        _ => {
+            warn!(?name, ?value, ?default, "Invalid env var, using default");
+            default
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DEFAULT_APP_NAME, RuntimeConfiguration, auth::AuthConfig, date_locale::DateLocaleConfig,
+        db::DbConfig, important_item::ImportantItemContainer, parse_env_var, photos::PhotoConfig,
+        security::SecurityConfig,
+    };
+    use axum::{
+        Router,
+        body::Bytes,
+        extract::State,
+        http::{Method, StatusCode, Uri},
+    };
+    use s3::{Bucket, Region, creds::Credentials};
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+    use tokio::net::TcpListener;
+
+    type Objects = Arc<Mutex<HashMap<String, Bytes>>>;
+
+    ///just enough of S3 to put, get & delete objects, keyed by path
+    async fn fake_s3(
+        State(objects): State<Objects>,
+        method: Method,
+        uri: Uri,
+        body: Bytes,
+    ) -> (StatusCode, Bytes) {
+        let mut objects = objects.lock().unwrap();
+        let key = uri.path().to_string();
+        match method {
+            Method::PUT => {
+                objects.insert(key, body);
+                (StatusCode::OK, Bytes::new())
+            }
+            Method::DELETE => {
+                objects.remove(&key);
+                (StatusCode::NO_CONTENT, Bytes::new())
+            }
+            _ => objects.get(&key).map_or_else(
+                || (StatusCode::NOT_FOUND, Bytes::from_static(b"NoSuchKey")),
+                |object| (StatusCode::OK, object.clone()),
+            ),
+        }
+    }
+
+    async fn fake_bucket() -> (Bucket, Objects) {
+        let objects = Objects::default();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new().fallback(fake_s3).with_state(objects.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let creds = Credentials::new(Some("id"), Some("key"), None, None, None).unwrap();
+        let region = Region::Custom {
+            region: "test".into(),
+            endpoint,
+        };
+        let bucket = Bucket::new("denim", region, creds)
+            .unwrap()
+            .with_path_style();
+        (*bucket, objects)
+    }
+
+    fn config_with_bucket(bucket: Bucket, auth_config: AuthConfig) -> RuntimeConfiguration {
+        let config = RuntimeConfiguration {
+            db_config: Arc::new(DbConfig::for_tests()),
+            security_config: Arc::new(SecurityConfig::new()),
+            photo_config: Arc::new(PhotoConfig::new()),
+            auth_config: ImportantItemContainer::new(),
+            s3_bucket: ImportantItemContainer::new(),
+            date_locale_config: ImportantItemContainer::new(),
+            mailer: None,
+            base_url: None,
+            app_name: DEFAULT_APP_NAME.into(),
+            logo_url: None,
+            sse_replay_capacity: 128,
+            server_address: ([127, 0, 0, 1], 8080).into(),
+        };
+
+        let date_locale_config = DateLocaleConfig::new(
+            "Europe/London".into(),
+            "en-GB".into(),
+            "h23".into(),
+            "gregorian".into(),
+        )
+        .unwrap();
+        assert!(config.s3_bucket.set(bucket).is_ok());
+        assert!(config.auth_config.set(auth_config).is_ok());
+        assert!(config.date_locale_config.set(date_locale_config).is_ok());
+        config
+    }
+
+    fn snapshot(objects: &Objects) -> HashMap<String, Bytes> {
+        objects.lock().unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn save_is_idempotent() {
+        let (bucket, objects) = fake_bucket().await;
+        let auth_config = AuthConfig::default()
+            .with_custom_words(Some(AuthConfig::parse_words("cat\ndog\nhorse")));
+        let config = config_with_bucket(bucket.clone(), auth_config);
+
+        config.save().await.unwrap();
+        let after_first = snapshot(&objects);
+        config.save().await.unwrap();
+        let after_second = snapshot(&objects);
+
+        assert_eq!(after_first.len(), 3);
+        assert_eq!(after_first, after_second);
+
+        let reloaded = ImportantItemContainer::<AuthConfig>::new();
+        assert!(reloaded.try_set_from_bucket(&bucket).await.unwrap());
+        assert_eq!(reloaded.get().unwrap().custom_word_count(), Some(3));
+    }
+
+    #[tokio::test]
+    async fn save_without_custom_words_is_idempotent() {
+        let (bucket, objects) = fake_bucket().await;
+        let config = config_with_bucket(bucket, AuthConfig::default());
+
+        config.save().await.unwrap();
+        let after_first = snapshot(&objects);
+        config.save().await.unwrap();
+
+        assert_eq!(after_first.len(), 2);
+        assert_eq!(after_first, snapshot(&objects));
+    }
+
+    #[test]
+    fn env_vars_only_fall_back_when_invalid() {
+        let positive = |parsed: &usize| *parsed > 0;
+        let anything = |_: &usize| true;
+
+        assert_eq!(parse_env_var("TEST", None, 128, anything), 128);
+        assert_eq!(parse_env_var("TEST", Some(" 5 ".into()), 128, positive), 5);
+        assert_eq!(parse_env_var("TEST", Some("0".into()), 128, positive), 128);
+        assert_eq!(parse_env_var("TEST", Some("0".into()), 128, anything), 0);
+        assert_eq!(
+            parse_env_var("TEST", Some("lots".into()), 128, anything),
+            128
+        );
+    }
+}
@@ -15,55 +15,127 @@ use std::{
     sync::{Arc, LazyLock},
 };
 
+pub const MAX_SEPARATOR_LEN: usize = 3;
+pub const MAX_WORD_COUNT: usize = 4;
+const CUSTOM_WORDS_KEY: &str = "auth_words.txt";
+
+pub type WordsByLength = HashMap<usize, Vec<Arc<str>>>;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthConfig {
     pub word_len_range: Range<usize>,
     pub numbers_range: Range<usize>,
+    ///defaulted so that configs saved before these existed still load as `word_number`
+    #[serde(default = "AuthConfig::default_separator")]
+    pub separator: String,
+    #[serde(default = "AuthConfig::default_word_count")]
+    pub word_count: usize,
+    ///admin-supplied word list, which lives in its own object in the bucket rather than in `auth_config.bin`
+    #[serde(skip)]
+    custom_words: Option<Arc<WordsByLength>>,
 }
 
 impl AuthConfig {
-    fn words() -> &'static HashMap<usize, Vec<Arc<str>>> {
-        static WORDS: LazyLock<HashMap<usize, Vec<Arc<str>>>> = LazyLock::new(|| {
-            let all_words = include_str!("words.txt");
-            let mut map: HashMap<usize, Vec<Arc<str>>> = HashMap::new();
-
-            for (len, word) in all_words
-                .lines()
-                .map(str::trim)
-                .map(|word| (word.len(), word))
-            {
-                map.entry(len).or_default().push(word.into());
-            }
+    fn default_separator() -> String {
+        "_".to_string()
+    }
+
+    const fn default_word_count() -> usize {
+        1
+    }
+
+    ///commas & quotes would break the CSVs that passwords get exported in
+    pub fn separator_is_valid(separator: &str) -> bool {
+        separator.chars().count() <= MAX_SEPARATOR_LEN
+            && !separator
+                .chars()
+                .any(|c| c.is_whitespace() || c == ',' || c == '"')
+    }
+
+    ///whether every word length in the range has at least one word to pick from
+    pub fn word_len_range_is_usable(&self, word_len_range: &Range<usize>) -> bool {
+        let words = self.words();
+        word_len_range.clone().all(|len| words.contains_key(&len))
+    }
+
+    ///an example of the format, eg. `word-word-number`
+    pub fn format_example(&self) -> String {
+        let mut example = vec!["word"; self.word_count];
+        example.push("number");
+        example.join(&self.separator)
+    }
 
-            map
-        });
+    ///one word per line - anything that isn't purely letters gets ignored
+    pub fn parse_words(all_words: &str) -> WordsByLength {
+        let mut map: WordsByLength = HashMap::new();
+
+        for (len, word) in all_words
+            .lines()
+            .map(str::trim)
+            .filter(|word| !word.is_empty() && word.chars().all(char::is_alphabetic))
+            .map(|word| (word.chars().count(), word))
+        {
+            map.entry(len).or_default().push(word.into());
+        }
+
+        map
+    }
+
+    fn bundled_words() -> &'static WordsByLength {
+        static WORDS: LazyLock<WordsByLength> =
+            LazyLock::new(|| AuthConfig::parse_words(include_str!("words.txt")));
         &WORDS
     }
 
+    fn words(&self) -> &WordsByLength {
+        self.custom_words
+            .as_deref()
+            .unwrap_or_else(|| Self::bundled_words())
+    }
+
+    ///how many words are in the custom list, if there is one
+    pub fn custom_word_count(&self) -> Option<usize> {
+        self.custom_words
+            .as_ref()
+            .map(|words| words.values().map(Vec::len).sum())
+    }
+
+    #[must_use]
+    pub fn with_custom_words(&self, custom_words: Option<WordsByLength>) -> Self {
+        Self {
+            custom_words: custom_words.map(Arc::new),
+            ..self.clone()
+        }
+    }
+
     pub fn generate(&self) -> DenimResult<String> {
         let mut rng = rng();
 
-        let word_len = rng.random_range(self.word_len_range.clone());
-        let list_to_pick_from = Self::words()
-            .get(&word_len)
-            .context(GeneratePasswordSnafu)?;
-        let chosen_word = list_to_pick_from[rng.random_range(0..list_to_pick_from.len())].clone();
+        let mut parts = Vec::with_capacity(self.word_count + 1);
+        for _ in 0..self.word_count {
+            let word_len = rng.random_range(self.word_len_range.clone());
+            let list_to_pick_from = self.words().get(&word_len).context(GeneratePasswordSnafu)?;
+            parts.push(list_to_pick_from[rng.random_range(0..list_to_pick_from.len())].to_string());
+        }
 
-        let chosen_number = rng.random_range(self.numbers_range.clone());
+        parts.push(rng.random_range(self.numbers_range.clone()).to_string());
 
-        Ok(format!("{chosen_word}_{chosen_number}"))
+        Ok(parts.join(&self.separator))
     }
 }
 
 impl Default for AuthConfig {
     fn default() -> Self {
-        let _ = Self::words();
+        let _ = Self::bundled_words();
         let default_word_len_range = 5..9;
         let default_numbers_range = 1_000..10_000;
 
         Self {
             word_len_range: default_word_len_range,
             numbers_range: default_numbers_range,
+            separator: Self::default_separator(),
+            word_count: Self::default_word_count(),
+            custom_words: None,
         }
     }
 }
@@ -78,9 +150,24 @@ impl ImportantItem for AuthConfig {
             Ok(rsp) => rsp,
         };
 
-        rmp_serde::from_slice(rsp.bytes())
-            .context(RmpSerdeDecodeSnafu)
-            .map(Some)
+        let config: Self = rmp_serde::from_slice(rsp.bytes()).context(RmpSerdeDecodeSnafu)?;
+
+        let custom_words = match bucket.get_object(CUSTOM_WORDS_KEY).await {
+            Err(S3Error::HttpFailWithBody(404, _)) => None,
+            Err(e) => return Err(DenimError::S3 { source: e }),
+            Ok(rsp) => match std::str::from_utf8(rsp.bytes()) {
+                Ok(all_words) => Some(Self::parse_words(all_words)),
+                Err(e) => {
+                    warn!(
+                        ?e,
+                        "Custom word list wasn't valid UTF-8, using the bundled list"
+                    );
+                    None
+                }
+            },
+        };
+
+        Ok(Some(config.with_custom_words(custom_words)))
     }
 
     async fn save_to_bucket(&self, bucket: &Bucket) -> DenimResult<()> {
@@ -93,6 +180,61 @@ impl ImportantItem for AuthConfig {
             )
             .await
             .context(S3Snafu)?;
+
+        if let Some(custom_words) = &self.custom_words {
+            let all_words = custom_words
+                .values()
+                .flatten()
+                .map(AsRef::as_ref)
+                .collect::<Vec<&str>>()
+                .join("\n");
+            bucket
+                .put_object_with_content_type(CUSTOM_WORDS_KEY, all_words.as_bytes(), "text/plain")
+                .await
+                .context(S3Snafu)?;
+        } else {
+            match bucket.delete_object(CUSTOM_WORDS_KEY).await {
+                Ok(_) | Err(S3Error::HttpFailWithBody(404, _)) => {}
+                Err(e) => return Err(DenimError::S3 { source: e }),
+            }
+        }
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::AuthConfig;
+
+    fn with_words(words: &str) -> AuthConfig {
+        AuthConfig::default().with_custom_words(Some(AuthConfig::parse_words(words)))
+    }
+
+    #[test]
+    fn range_with_words_for_every_length_is_usable() {
+        let config = with_words("cat\ndog\nfish\nhorse");
+        assert!(config.word_len_range_is_usable(&(3..6)));
+        assert!(config.word_len_range_is_usable(&(4..5)));
+    }
+
+    #[test]
+    fn range_with_a_missing_length_is_not_usable() {
+        let config = with_words("cat\nhorse");
+        assert!(!config.word_len_range_is_usable(&(3..6)));
+        assert!(!config.word_len_range_is_usable(&(7..8)));
+    }
+
+    #[test]
+    fn bundled_words_cover_the_default_range() {
+        let config = AuthConfig::default();
+        assert!(config.word_len_range_is_usable(&config.word_len_range));
+    }
+
+    #[test]
+    fn non_alphabetic_words_are_ignored() {
+        let config = with_words("cat\nd0g\n  \nfi-sh\n");
+        assert_eq!(config.custom_word_count(), Some(1));
+        assert!(!config.word_len_range_is_usable(&(3..5)));
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{
     config::important_item::{ImportantItem, ImportantItemTy},
+    data::user::User,
     error::{
         BadDateTimeFormatterSnafu, DenimError, DenimResult, InvalidLocaleSnafu,
         InvalidTimezoneSnafu, RmpSerdeDecodeSnafu, RmpSerdeEncodeSnafu, S3Snafu,
@@ -16,17 +17,54 @@ use icu::{
     locale::Locale,
     time::ZonedDateTime,
 };
-use jiff::{Zoned, tz::TimeZone};
+use jiff::{Timestamp, Zoned, tz::TimeZone};
 use jiff_icu::ConvertFrom;
 use s3::{Bucket, error::S3Error};
 use serde::{Deserialize, Serialize};
 use snafu::ResultExt;
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub struct DateLocaleConfig {
     pub timezone: TimeZone,
     pub locale: Locale,
     dtf_prefs: DateTimeFormatterPreferences,
+    formatters: Arc<Formatters>,
+}
+
+///making formatters is expensive, so make them once per config rather than per call
+#[derive(Debug)]
+struct Formatters {
+    short_ymdet: DateTimeFormatter<YMDET>,
+    long_ymdet: DateTimeFormatter<YMDET>,
+    short_ymd: DateTimeFormatter<YMD>,
+}
+
+impl Formatters {
+    fn new(dtf_prefs: DateTimeFormatterPreferences) -> DenimResult<Self> {
+        Ok(Self {
+            short_ymdet: DateTimeFormatter::try_new(dtf_prefs, {
+                let mut fieldset = YMDET::short();
+                fieldset.alignment = Some(Alignment::Column);
+                fieldset.time_precision = Some(TimePrecision::Minute);
+                fieldset
+            })
+            .context(BadDateTimeFormatterSnafu)?,
+            long_ymdet: DateTimeFormatter::try_new(dtf_prefs, {
+                let mut fieldset = YMDET::long();
+                fieldset.alignment = Some(Alignment::Column);
+                fieldset.time_precision = Some(TimePrecision::Minute);
+                fieldset
+            })
+            .context(BadDateTimeFormatterSnafu)?,
+            short_ymd: DateTimeFormatter::try_new(dtf_prefs, {
+                let mut fieldset = YMD::short();
+                fieldset.alignment = Some(Alignment::Column);
+                fieldset
+            })
+            .context(BadDateTimeFormatterSnafu)?,
+        })
+    }
 }
 
 #[derive(Deserialize, Serialize)]
@@ -42,21 +80,8 @@ impl From<&DateLocaleConfig> for DateLocaleConfigInterchange {
         Self {
             tz: value.timezone.iana_name().unwrap_or("UTC").to_string(),
             locale: value.locale.to_string(),
-            hour_cycle: match value.dtf_prefs.hour_cycle {
-                Some(HourCycle::H11) => "h11",
-                Some(HourCycle::H12) => "h12",
-                _ => "h23",
-            }
-            .to_string(),
-            calendar_algorithm: match value.dtf_prefs.calendar_algorithm {
-                Some(CalendarAlgorithm::Buddhist) => "buddhist",
-                Some(CalendarAlgorithm::Chinese) => "chinese",
-                Some(CalendarAlgorithm::Japanese) => "japanese",
-                Some(CalendarAlgorithm::Hebrew) => "hebrew",
-                Some(CalendarAlgorithm::Dangi) => "dangi",
-                _ => "gregorian",
-            }
-            .to_string(),
+            hour_cycle: value.hour_cycle().to_string(),
+            calendar_algorithm: value.calendar_algorithm().to_string(),
         }
     }
 }
@@ -133,20 +158,50 @@ impl DateLocaleConfig {
         let (locale, dtf_prefs) =
             Self::dtf_prefs_and_locale_from_strings(locale, hour_cycle, calendar_algorithm)?;
 
+        let formatters = Arc::new(Formatters::new(dtf_prefs)?);
+
         Ok(Self {
             timezone,
             locale,
             dtf_prefs,
+            formatters,
         })
     }
 
-    //TODO: optimise these to not re-gen every run
+    ///the same config, but using the user's own timezone if they've picked one
+    pub fn for_user(&self, user: Option<&User>) -> Self {
+        let mut config = self.clone();
+        if let Some(timezone) = user.and_then(|user| user.timezone.clone()) {
+            config.timezone = timezone;
+        }
+        config
+    }
+
+    pub const fn hour_cycle(&self) -> &'static str {
+        match self.dtf_prefs.hour_cycle {
+            Some(HourCycle::H11) => "h11",
+            Some(HourCycle::H12) => "h12",
+            _ => "h23",
+        }
+    }
+
+    pub const fn calendar_algorithm(&self) -> &'static str {
+        match self.dtf_prefs.calendar_algorithm {
+            Some(CalendarAlgorithm::Buddhist) => "buddhist",
+            Some(CalendarAlgorithm::Chinese) => "chinese",
+            Some(CalendarAlgorithm::Japanese) => "japanese",
+            Some(CalendarAlgorithm::Hebrew) => "hebrew",
+            Some(CalendarAlgorithm::Dangi) => "dangi",
+            _ => "gregorian",
+        }
+    }
+
     pub fn format(
         &self,
         zoned: &Zoned,
         date_format: DateFormat,
         set_to_global_timezone: bool,
-    ) -> DenimResult<String> {
+    ) -> String {
         let zdt = if set_to_global_timezone {
             let new_tz = zoned.with_time_zone(self.timezone.clone());
             ZonedDateTime::convert_from(&new_tz)
@@ -154,47 +209,73 @@ impl DateLocaleConfig {
             ZonedDateTime::convert_from(zoned)
         };
 
-        Ok(match date_format {
-            DateFormat::ShortYMDET => DateTimeFormatter::try_new(self.dtf_prefs, {
-                let mut fieldset = YMDET::short();
-                fieldset.alignment = Some(Alignment::Column);
-                fieldset.time_precision = Some(TimePrecision::Minute);
-                fieldset
-            })
-            .context(BadDateTimeFormatterSnafu)?
-            .format(&zdt)
-            .to_string(),
-            DateFormat::LongYMDET => DateTimeFormatter::try_new(self.dtf_prefs, {
-                let mut fieldset = YMDET::long();
-                fieldset.alignment = Some(Alignment::Column);
-                fieldset.time_precision = Some(TimePrecision::Minute);
-                fieldset
-            })
-            .context(BadDateTimeFormatterSnafu)?
-            .format(&zdt)
-            .to_string(),
-            DateFormat::ShortYMD => DateTimeFormatter::try_new(self.dtf_prefs, {
-                let mut fieldset = YMD::short();
-                fieldset.alignment = Some(Alignment::Column);
-                fieldset
-            })
-            .context(BadDateTimeFormatterSnafu)?
-            .format(&zdt)
-            .to_string(),
-        })
+        match date_format {
+            DateFormat::ShortYMDET => self.formatters.short_ymdet.format(&zdt).to_string(),
+            DateFormat::LongYMDET => self.formatters.long_ymdet.format(&zdt).to_string(),
+            DateFormat::ShortYMD => self.formatters.short_ymd.format(&zdt).to_string(),
+        }
     }
 
-    pub fn short_ymdet(&self, zoned: &Zoned) -> DenimResult<String> {
+    ///the name of the timezone `zoned` is in, if it's not the one everything's being shown in
+    pub fn differing_timezone<'a>(&self, zoned: &'a Zoned) -> Option<&'a str> {
+        zoned
+            .time_zone()
+            .iana_name()
+            .zip(self.timezone.iana_name())
+            .and_then(|(own_tz, shown_tz)| (own_tz != shown_tz).then_some(own_tz))
+    }
+
+    pub fn short_ymdet(&self, zoned: &Zoned) -> String {
         self.format(zoned, DateFormat::ShortYMDET, true)
     }
-    pub fn long_ymdet(&self, zoned: &Zoned) -> DenimResult<String> {
+    pub fn long_ymdet(&self, zoned: &Zoned) -> String {
         self.format(zoned, DateFormat::LongYMDET, true)
     }
 
-    pub fn short_ymd(&self, zoned: &Zoned) -> DenimResult<String> {
+    pub fn short_ymd(&self, zoned: &Zoned) -> String {
         self.format(zoned, DateFormat::ShortYMD, true)
     }
 
+    ///coarse human phrasing like "in 3 days" or "2 hours ago", relative to right now
+    pub fn relative(&self, zoned: &Zoned) -> String {
+        const MINUTE: i64 = 60;
+        const HOUR: i64 = 60 * MINUTE;
+        const DAY: i64 = 24 * HOUR;
+        const WEEK: i64 = 7 * DAY;
+        const YEAR: i64 = 365 * DAY;
+
+        let now = Timestamp::now().to_zoned(self.timezone.clone());
+        let secs = now.duration_until(zoned).as_secs();
+        let is_future = secs > 0;
+        let secs = secs.abs();
+
+        let (n, unit) = if secs < MINUTE {
+            return "just now".to_string();
+        } else if secs < HOUR {
+            (secs / MINUTE, "minute")
+        } else if secs < DAY {
+            (secs / HOUR, "hour")
+        } else if secs < WEEK {
+            (secs / DAY, "day")
+        } else if secs < YEAR {
+            (secs / WEEK, "week")
+        } else {
+            //past a year, the exact number of weeks stops being useful
+            return if is_future {
+                "in over a year".to_string()
+            } else {
+                "over a year ago".to_string()
+            };
+        };
+
+        let plural = if n == 1 { "" } else { "s" };
+        if is_future {
+            format!("in {n} {unit}{plural}")
+        } else {
+            format!("{n} {unit}{plural} ago")
+        }
+    }
+
     pub fn serialise(&self) -> DenimResult<Vec<u8>> {
         let interchange: DateLocaleConfigInterchange = self.into();
         rmp_serde::to_vec(&interchange).context(RmpSerdeEncodeSnafu)
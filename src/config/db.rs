@@ -1,7 +1,15 @@
-use crate::error::{BadEnvVarSnafu, DenimResult, ParsePortSnafu};
+use crate::{
+    config::get_env_var_or,
+    error::{BadEnvVarSnafu, DenimResult, ParsePortSnafu},
+};
 use dotenvy::var;
 use secrecy::{ExposeSecret, SecretString};
 use snafu::ResultExt;
+use sqlx::postgres::PgPoolOptions;
+use std::time::Duration;
+
+///requests only hold one connection at a time, but the student import job holds one of its own for as long as it runs
+pub const MIN_SANE_MAX_CONNECTIONS: u32 = 2;
 
 #[derive(Debug)]
 pub struct DbConfig {
@@ -10,9 +18,27 @@ pub struct DbConfig {
     path: String,
     port: u16,
     database: String,
+    ///set via `DENIM_DB_MAX_CONNECTIONS`
+    max_connections: u32,
+    ///set via `DENIM_DB_ACQUIRE_TIMEOUT_SECS`
+    acquire_timeout_secs: u64,
 }
 
 impl DbConfig {
+    ///never connected to, for tests that need a [`super::RuntimeConfiguration`]
+    #[cfg(test)]
+    pub fn for_tests() -> Self {
+        Self {
+            user: "denim".into(),
+            password: SecretString::from("denim"),
+            path: "localhost".into(),
+            port: 5432,
+            database: "denim".into(),
+            max_connections: 15,
+            acquire_timeout_secs: 30,
+        }
+    }
+
     pub fn new() -> DenimResult<Self> {
         let get_env_var = |name| var(name).context(BadEnvVarSnafu { name });
 
@@ -22,9 +48,25 @@ impl DbConfig {
             path: get_env_var("DB_PATH")?,
             port: get_env_var("DB_PORT")?.parse().context(ParsePortSnafu)?,
             database: get_env_var("DB_NAME")?,
+            max_connections: get_env_var_or("DENIM_DB_MAX_CONNECTIONS", 15),
+            acquire_timeout_secs: get_env_var_or("DENIM_DB_ACQUIRE_TIMEOUT_SECS", 30),
         })
     }
 
+    pub fn pool_options(&self) -> PgPoolOptions {
+        if self.max_connections < MIN_SANE_MAX_CONNECTIONS {
+            warn!(
+                max_connections = self.max_connections,
+                minimum = MIN_SANE_MAX_CONNECTIONS,
+                "DENIM_DB_MAX_CONNECTIONS is below the minimum sane value, requests may time out waiting for connections"
+            );
+        }
+
+        PgPoolOptions::new()
+            .max_connections(self.max_connections)
+            .acquire_timeout(Duration::from_secs(self.acquire_timeout_secs))
+    }
+
     pub fn get_db_path(&self) -> String {
         format!(
             "postgres://{}:{}@{}:{}/{}",
@@ -2,7 +2,7 @@ use crate::error::DenimResult;
 use s3::Bucket;
 use std::{
     fmt::Debug,
-    sync::{Arc, OnceLock},
+    sync::{Arc, PoisonError, RwLock},
 };
 
 #[derive(Copy, Clone, Debug)]
@@ -13,11 +13,11 @@ pub enum ImportantItemTy {
 }
 
 #[derive(Debug)]
-pub struct ImportantItemContainer<T: ImportantItem>(Arc<OnceLock<Arc<T>>>);
+pub struct ImportantItemContainer<T: ImportantItem>(Arc<RwLock<Option<Arc<T>>>>);
 
 impl<T: ImportantItem> ImportantItemContainer<T> {
     pub fn new() -> Self {
-        Self(Arc::new(OnceLock::new()))
+        Self(Arc::new(RwLock::new(None)))
     }
 
     #[allow(dead_code)]
@@ -25,11 +25,7 @@ impl<T: ImportantItem> ImportantItemContainer<T> {
         match T::get_from_bucket(bucket).await {
             Ok(None) => Ok(Self::new()),
             Err(e) => Err(e),
-            Ok(Some(worked)) => {
-                let ol = OnceLock::new();
-                let _ = ol.set(Arc::new(worked)); //not quite sure how this one could fail lol
-                Ok(Self(Arc::new(ol)))
-            }
+            Ok(Some(worked)) => Ok(Self(Arc::new(RwLock::new(Some(Arc::new(worked)))))),
         }
     }
 
@@ -45,34 +41,57 @@ impl<T: ImportantItem> ImportantItemContainer<T> {
             Err(e) => Err(e),
             Ok(Some(found)) => {
                 info!(ty = ?<T as ImportantItem>::TY, "Loaded important item");
-                let _ = self.0.set(Arc::new(found));
+                let _ = self.set(found);
                 Ok(true)
             }
         }
     }
 
+    //nothing in here can panic whilst holding the lock, so poisoning isn't a worry
+    fn current(&self) -> Option<Arc<T>> {
+        self.0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
     pub fn exists(&self) -> bool {
-        self.0.get().is_some()
+        self.current().is_some()
     }
 
     pub fn get(&self) -> DenimResult<Arc<T>> {
-        self.0
-            .get()
-            .cloned()
+        self.current()
             .ok_or_else(|| <T as ImportantItem>::TY.into())
     }
 
+    ///only sets if there isn't anything there yet - use [`Self::replace`] to change an existing item
     pub fn set(&self, item: T) -> Result<(), Arc<T>> {
-        self.0.set(Arc::new(item))
+        let mut lock = self.0.write().unwrap_or_else(PoisonError::into_inner);
+        if let Some(existing) = lock.as_ref() {
+            return Err(existing.clone());
+        }
+        *lock = Some(Arc::new(item));
+        drop(lock);
+        Ok(())
+    }
+
+    ///swaps out the current item (if any), and saves the new one to the bucket
+    #[allow(clippy::future_not_send)]
+    pub async fn replace(&self, item: T, bucket: &Bucket) -> DenimResult<()> {
+        item.save_to_bucket(bucket).await?;
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(item));
+        info!(ty = ?<T as ImportantItem>::TY, "Replaced important item");
+        Ok(())
     }
 
     #[allow(clippy::future_not_send)]
     pub async fn save(&self, bucket: &Bucket) -> DenimResult<()> {
-        if let Some(item) = self.0.get() {
-            item.save_to_bucket(bucket).await
-        } else {
-            Ok(())
+        if let Some(item) = self.current() {
+            item.save_to_bucket(bucket).await?;
+            info!(ty = ?T::TY, "Saved to bucket");
         }
+
+        Ok(())
     }
 }
 
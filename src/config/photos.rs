@@ -0,0 +1,16 @@
+use crate::config::get_env_var_or;
+
+#[derive(Debug)]
+pub struct PhotoConfig {
+    pub max_photo_bytes: usize,
+    pub max_photos_per_event: i64,
+}
+
+impl PhotoConfig {
+    pub fn new() -> Self {
+        Self {
+            max_photo_bytes: get_env_var_or("DENIM_MAX_PHOTO_BYTES", 10 * 1000 * 1000), //10MB
+            max_photos_per_event: get_env_var_or("DENIM_MAX_PHOTOS_PER_EVENT", 200),
+        }
+    }
+}
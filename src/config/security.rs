@@ -0,0 +1,138 @@
+use crate::config::get_env_var_or;
+use dotenvy::var;
+
+///the range that `bcrypt` will accept for the cost factor
+const BCRYPT_COST_RANGE: std::ops::RangeInclusive<u32> = 4..=31;
+
+#[derive(Debug)]
+pub struct SecurityConfig {
+    pub max_failed_logins: i32,
+    pub lockout_minutes: i32,
+    pub session_expiry_days: i64,
+    ///only used when producing new hashes - verifying doesn't need it as the cost is stored inside each hash,
+    ///so existing passwords keep working if this changes
+    pub bcrypt_cost: u32,
+    ///only checked for passwords that people choose themselves, not generated defaults
+    pub password_min_length: usize,
+    ///needs 3 of: lowercase, uppercase, digits & symbols
+    pub password_require_complexity: bool,
+}
+
+impl SecurityConfig {
+    pub fn new() -> Self {
+        let bcrypt_cost = get_env_var_or("DENIM_BCRYPT_COST", bcrypt::DEFAULT_COST);
+        let bcrypt_cost = if BCRYPT_COST_RANGE.contains(&bcrypt_cost) {
+            bcrypt_cost
+        } else {
+            warn!(
+                ?bcrypt_cost,
+                default = ?bcrypt::DEFAULT_COST,
+                "DENIM_BCRYPT_COST must be between 4 and 31, using default"
+            );
+            bcrypt::DEFAULT_COST
+        };
+
+        Self {
+            max_failed_logins: get_env_var_or("DENIM_LOGIN_MAX_ATTEMPTS", 5),
+            lockout_minutes: get_env_var_or("DENIM_LOGIN_LOCKOUT_MINUTES", 15),
+            session_expiry_days: get_env_var_or("DENIM_SESSION_EXPIRY_DAYS", 5),
+            bcrypt_cost,
+            password_min_length: get_env_var_or("DENIM_PASSWORD_MIN_LENGTH", 8),
+            password_require_complexity: var("DENIM_PASSWORD_REQUIRE_COMPLEXITY")
+                .is_ok_and(|value| value.trim().eq_ignore_ascii_case("true")),
+        }
+    }
+
+    pub fn password_is_too_short(&self, password: &str) -> bool {
+        password.chars().count() < self.password_min_length
+    }
+
+    pub fn password_is_too_simple(&self, password: &str) -> bool {
+        if !self.password_require_complexity {
+            return false;
+        }
+
+        let classes_used = [
+            password.chars().any(char::is_lowercase),
+            password.chars().any(char::is_uppercase),
+            password.chars().any(|c| c.is_ascii_digit()),
+            password.chars().any(|c| !c.is_alphanumeric()),
+        ]
+        .into_iter()
+        .filter(|used| *used)
+        .count();
+
+        classes_used < 3
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SecurityConfig;
+
+    fn config(password_min_length: usize, password_require_complexity: bool) -> SecurityConfig {
+        SecurityConfig {
+            max_failed_logins: 5,
+            lockout_minutes: 15,
+            session_expiry_days: 5,
+            bcrypt_cost: 4,
+            password_min_length,
+            password_require_complexity,
+        }
+    }
+
+    #[test]
+    fn password_length() {
+        let config = config(8, false);
+
+        for (password, too_short) in [
+            ("", true),
+            ("1234567", true),
+            ("12345678", false),
+            ("a much longer password", false),
+            //characters, not bytes
+            ("ééééééé", true),
+            ("éééééééé", false),
+        ] {
+            assert_eq!(
+                config.password_is_too_short(password),
+                too_short,
+                "{password:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn password_complexity() {
+        let config = config(8, true);
+
+        for (password, too_simple) in [
+            ("password", true),
+            ("PASSWORD", true),
+            ("12345678", true),
+            ("Password", true),
+            ("password1", true),
+            ("password!", true),
+            ("Password1", false),
+            ("Password!", false),
+            ("password1!", false),
+            ("PASSWORD1!", false),
+            ("Password1!", false),
+        ] {
+            assert_eq!(
+                config.password_is_too_simple(password),
+                too_simple,
+                "{password:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn password_complexity_can_be_turned_off() {
+        let config = config(8, false);
+
+        for password in ["password", "12345678", "Password1!"] {
+            assert!(!config.password_is_too_simple(password), "{password:?}");
+        }
+    }
+}
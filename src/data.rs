@@ -1,13 +1,17 @@
-use crate::error::{CommitTransactionSnafu, DenimResult, MakeQuerySnafu};
-use futures::{TryStreamExt, stream::BoxStream};
+use crate::error::{CommitTransactionSnafu, DenimResult};
 use serde::Deserialize;
 use snafu::ResultExt;
 use sqlx::{PgConnection, Pool, Postgres, Transaction};
 use uuid::Uuid;
 
+pub mod audit_log;
 pub mod event;
+pub mod house_points;
 pub mod photo;
+pub mod role;
 pub mod student_groups;
+#[cfg(test)]
+pub mod testing;
 pub mod user;
 
 #[derive(Deserialize)]
@@ -16,6 +20,7 @@ pub struct IdForm {
 }
 
 #[derive(Deserialize)]
+#[allow(dead_code)]
 pub struct IntIdForm {
     pub id: i32,
 }
@@ -25,6 +30,50 @@ pub struct FilterQuery {
     pub filter: Option<String>,
 }
 
+///the furthest page that can be asked for, so that neither `page * page_size` nor the page after it can overflow
+pub const fn clamp_page(page: i64, page_size: i64) -> i64 {
+    if page < 0 {
+        0
+    } else if page > i64::MAX / page_size - 1 {
+        i64::MAX / page_size - 1
+    } else {
+        page
+    }
+}
+
+///the `OFFSET` for a page, clamped first as `page` tends to come straight from the query string
+pub const fn page_offset(page: i64, page_size: i64) -> i64 {
+    clamp_page(page, page_size) * page_size
+}
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortDirection {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Asc => "asc",
+            Self::Desc => "desc",
+        }
+    }
+
+    pub const fn flipped(self) -> Self {
+        match self {
+            Self::Asc => Self::Desc,
+            Self::Desc => Self::Asc,
+        }
+    }
+
+    pub const fn is_descending(self) -> bool {
+        matches!(self, Self::Desc)
+    }
+}
+
 //NB: would love to use something more generic
 //and i tried
 //but
@@ -37,11 +86,11 @@ pub trait DataType: Sized {
 
     async fn get_from_db_by_id(id: Self::Id, conn: &mut PgConnection) -> DenimResult<Option<Self>>;
 
-    //takes in a pool rather than a connection due to probably needing multiple requests and faffery with streams
+    //takes in a pool rather than a connection due to needing multiple requests
+    //only ever hold one connection from it though - collect the ids, then use `get_from_iter_of_ids` on the same connection
 
     async fn get_all(conn: &Pool<Postgres>) -> DenimResult<Vec<Self>>;
 
-    #[allow(dead_code)]
     async fn get_from_iter_of_ids(
         ids: impl IntoIterator<Item = Self::Id>,
         conn: &mut PgConnection,
@@ -57,21 +106,6 @@ pub trait DataType: Sized {
         Ok(all)
     }
 
-    async fn get_from_fetch_stream_of_ids(
-        mut ids: BoxStream<'_, Result<Self::Id, sqlx::Error>>,
-        conn: &mut PgConnection,
-    ) -> DenimResult<Vec<Self>> {
-        let mut all = vec![];
-
-        while let Some(next_id) = ids.try_next().await.context(MakeQuerySnafu)? {
-            if let Some(next_event) = Self::get_from_db_by_id(next_id, conn).await? {
-                all.push(next_event);
-            }
-        }
-
-        Ok(all)
-    }
-
     async fn insert_into_database(
         to_be_added: Self::FormForAdding,
         conn: &mut PgConnection,
@@ -88,3 +122,68 @@ pub trait DataType: Sized {
 
     async fn remove_from_database(id: Self::Id, conn: &mut PgConnection) -> DenimResult<()>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DataType, clamp_page,
+        event::Event,
+        page_offset,
+        photo::Photo,
+        role::Role,
+        student_groups::{HouseGroup, TutorGroup},
+        testing::{insert_event, insert_house, insert_staff, insert_student, insert_tutor_group},
+        user::User,
+    };
+    use sqlx::{PgPool, postgres::PgPoolOptions};
+    use std::time::Duration;
+
+    ///if any of these held onto a connection whilst getting another, they'd time out here
+    #[sqlx::test]
+    async fn get_all_works_with_one_connection(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let house = insert_house("Test House", &mut conn).await;
+        let tutor_group = insert_tutor_group(house, &mut conn).await;
+        let student = insert_student(tutor_group, &mut conn).await;
+        insert_staff(Some(house), &mut conn).await;
+        let event = insert_event(None, &mut conn).await;
+        Event::sign_up(event, student, false, &mut conn)
+            .await
+            .unwrap();
+        drop(conn);
+
+        let pool = PgPoolOptions::new()
+            .max_connections(1)
+            .acquire_timeout(Duration::from_secs(5))
+            .connect_with((*pool.connect_options()).clone())
+            .await
+            .unwrap();
+
+        assert_eq!(User::get_all(&pool).await.unwrap().len(), 3);
+        assert_eq!(User::get_all_staff(&pool).await.unwrap().len(), 2);
+        assert_eq!(User::get_all_students(&pool).await.unwrap().len(), 1);
+        assert!(
+            User::get_all_students_with_filter(&pool, "", Some(event), None)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(Event::get_all(&pool).await.unwrap().len(), 1);
+        assert_eq!(HouseGroup::get_all(&pool).await.unwrap().len(), 1);
+        assert_eq!(TutorGroup::get_all(&pool).await.unwrap().len(), 1);
+        assert!(!Role::get_all(&pool).await.unwrap().is_empty());
+        assert!(Photo::get_all(&pool).await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn silly_pages_are_clamped() {
+        assert_eq!(clamp_page(-3, 24), 0);
+        assert_eq!(clamp_page(2, 24), 2);
+        assert_eq!(page_offset(2, 24), 48);
+
+        let last = clamp_page(i64::MAX, 24);
+        assert!(last < i64::MAX);
+        assert!(page_offset(i64::MAX, 24).checked_add(24).is_some());
+        assert_eq!(page_offset(i64::MAX, 24), last * 24);
+    }
+}
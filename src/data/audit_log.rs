@@ -0,0 +1,169 @@
+use crate::{
+    data::page_offset,
+    error::{DenimResult, MakeQuerySnafu, UnrepresentableTimeSnafu},
+};
+use jiff::Timestamp;
+use snafu::ResultExt;
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+#[derive(Debug, Copy, Clone)]
+pub enum AuditAction {
+    Create,
+    Edit,
+    Delete,
+    Restore,
+    Import,
+}
+
+impl AuditAction {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Edit => "edit",
+            Self::Delete => "delete",
+            Self::Restore => "restore",
+            Self::Import => "import",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum AuditTarget {
+    Event(Uuid),
+    Person(Uuid),
+    House(i32),
+    TutorGroup(Uuid),
+    Role(i32),
+    ///for bulk actions that touch lots of events
+    Events,
+    ///for bulk actions that touch lots of people
+    People,
+    ///for bulk actions that touch lots of houses & tutor groups
+    Groups,
+    ///one of the onboarding/settings sections
+    Config(&'static str),
+}
+
+impl AuditTarget {
+    fn type_and_id(self) -> (&'static str, Option<String>) {
+        match self {
+            Self::Event(id) => ("event", Some(id.to_string())),
+            Self::Person(id) => ("person", Some(id.to_string())),
+            Self::House(id) => ("house", Some(id.to_string())),
+            Self::TutorGroup(id) => ("tutor_group", Some(id.to_string())),
+            Self::Role(id) => ("role", Some(id.to_string())),
+            Self::Events => ("events", None),
+            Self::People => ("people", None),
+            Self::Groups => ("groups", None),
+            Self::Config(section) => ("config", Some(section.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    pub actor_id: Option<Uuid>,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: Option<String>,
+    pub detail: Option<String>,
+    pub created_at: Timestamp,
+}
+
+impl AuditEntry {
+    pub async fn record(
+        actor_id: Option<Uuid>,
+        action: AuditAction,
+        target: AuditTarget,
+        detail: Option<&str>,
+        conn: &mut PgConnection,
+    ) -> DenimResult<()> {
+        let (target_type, target_id) = target.type_and_id();
+
+        sqlx::query!(
+            "INSERT INTO public.audit_log (actor_id, action, target_type, target_id, detail) VALUES ($1, $2, $3, $4, $5)",
+            actor_id,
+            action.as_str(),
+            target_type,
+            target_id,
+            detail
+        )
+        .execute(conn)
+        .await
+        .context(MakeQuerySnafu)?;
+
+        Ok(())
+    }
+
+    ///newest first, with `filter` matching against the action, target & detail
+    ///
+    ///returns whether there's another page after this one
+    pub async fn get_page(
+        filter: Option<&str>,
+        page: i64,
+        page_size: i64,
+        conn: &mut PgConnection,
+    ) -> DenimResult<(Vec<Self>, bool)> {
+        let mut entries = sqlx::query!(
+            "SELECT actor_id, action, target_type, target_id, detail, created_at FROM public.audit_log WHERE $1::text IS NULL OR strpos(lower(action || ' ' || target_type || ' ' || coalesce(target_id, '') || ' ' || coalesce(detail, '')), lower($1)) > 0 ORDER BY created_at DESC LIMIT $2 OFFSET $3",
+            filter,
+            page_size + 1,
+            page_offset(page, page_size)
+        )
+        .fetch_all(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .into_iter()
+        .map(|record| {
+            Ok(Self {
+                actor_id: record.actor_id,
+                action: record.action,
+                target_type: record.target_type,
+                target_id: record.target_id,
+                detail: record.detail,
+                created_at: Timestamp::from_second(record.created_at.unix_timestamp())
+                    .context(UnrepresentableTimeSnafu)?,
+            })
+        })
+        .collect::<DenimResult<Vec<_>>>()?;
+
+        let page_size = usize::try_from(page_size).unwrap_or(usize::MAX);
+        let has_more = entries.len() > page_size;
+        entries.truncate(page_size);
+
+        Ok((entries, has_more))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AuditAction, AuditEntry, AuditTarget};
+    use sqlx::PgPool;
+
+    #[sqlx::test]
+    async fn filters_match_wildcards_literally(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        for detail in ["100% of students", "1000 students"] {
+            AuditEntry::record(
+                None,
+                AuditAction::Import,
+                AuditTarget::People,
+                Some(detail),
+                &mut conn,
+            )
+            .await
+            .unwrap();
+        }
+
+        let (entries, has_more) = AuditEntry::get_page(Some("100%"), 0, 10, &mut conn)
+            .await
+            .unwrap();
+        assert!(!has_more);
+        let details: Vec<_> = entries
+            .into_iter()
+            .filter_map(|entry| entry.detail)
+            .collect();
+        assert_eq!(details, ["100% of students"]);
+    }
+}
@@ -1,16 +1,22 @@
 use crate::{
-    data::{DataType, IdForm, photo::Photo, user::User},
+    data::{DataType, IdForm, SortDirection, photo::Photo, user::User},
     error::{
         DenimError, DenimResult, GetDatabaseConnectionSnafu, InvalidTimezoneSnafu, MakeQuerySnafu,
+        MissingEventSnafu,
     },
+    metrics::METRICS,
 };
-use futures::{StreamExt, TryStreamExt};
+use futures::TryStreamExt;
 use jiff::{Timestamp, Zoned, tz::TimeZone};
-use snafu::ResultExt;
+use serde::Deserialize;
+use snafu::{OptionExt, ResultExt};
 use sqlx::{PgConnection, Pool, Postgres};
 use time::{Date, Month, PrimitiveDateTime, Time};
 use uuid::Uuid;
 
+///how long after deleting an event it can still be restored, before it gets purged for good
+pub const EVENT_UNDO_WINDOW_SECS: i64 = 30;
+
 #[derive(Debug)]
 pub struct Event {
     pub id: Uuid,
@@ -18,24 +24,118 @@ pub struct Event {
     pub datetime: Zoned,
     pub location: Option<String>,
     pub extra_info: Option<String>,
-    pub associated_staff_member: Option<User>,
+    pub associated_staff: Vec<User>,
+    pub max_capacity: Option<i32>,
+    ///shared by every occurrence of a repeating event
+    pub recurrence_group: Option<Uuid>,
+    pub tags: Vec<String>,
     pub signed_up: Vec<Uuid>,
     pub verified: Vec<Uuid>,
+    ///in order of who gets promoted first
+    pub waitlisted: Vec<Uuid>,
+    #[allow(dead_code)]
     pub photos: Vec<Photo>,
 }
 
+#[derive(Clone)]
 pub struct AddEvent {
     pub name: String,
     pub date: Zoned,
     pub location: Option<String>,
     pub extra_info: Option<String>,
-    pub associated_staff_member: Option<Uuid>,
+    pub associated_staff: Vec<Uuid>,
+    pub max_capacity: Option<i32>,
+    ///only used when adding - editing an event leaves this alone
+    pub recurrence_group: Option<Uuid>,
+    pub tags: Vec<String>,
+}
+
+///narrows down the future/past event lists
+#[derive(Default, Clone)]
+pub struct EventsFilter<'a> {
+    pub tag: Option<&'a str>,
+    ///matched case-insensitively against whatever `search_scope` says
+    pub search: Option<&'a str>,
+    pub search_scope: EventSearchScope,
+    ///only events this staff member is running
+    pub staff_id: Option<Uuid>,
+    ///only events this student is signed up to (including waitlisted & verified)
+    pub participant_id: Option<Uuid>,
+    ///inclusive
+    pub from: Option<Zoned>,
+    ///exclusive
+    pub to: Option<Zoned>,
+    ///`None` keeps the usual order of soonest first for future events, and most recent first for past events
+    pub sort: Option<EventSortColumn>,
+    pub sort_direction: SortDirection,
+}
+
+impl EventsFilter<'_> {
+    fn sql_range(&self) -> (Option<PrimitiveDateTime>, Option<PrimitiveDateTime>) {
+        (
+            self.from.as_ref().map(|from| zoned_to_sql("from", from).0),
+            self.to.as_ref().map(|to| zoned_to_sql("to", to).0),
+        )
+    }
+}
+
+///which part of an event the search box looks at
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventSearchScope {
+    #[default]
+    All,
+    Name,
+    Location,
+    Staff,
+}
+
+impl EventSearchScope {
+    pub const ALL: [Self; 4] = [Self::All, Self::Name, Self::Location, Self::Staff];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::All => "all",
+            Self::Name => "name",
+            Self::Location => "location",
+            Self::Staff => "staff",
+        }
+    }
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::All => "Everything",
+            Self::Name => "Name",
+            Self::Location => "Location",
+            Self::Staff => "Staff Member",
+        }
+    }
+}
+
+///which column the event lists are sorted by
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventSortColumn {
+    Name,
+    Date,
+    Location,
+}
+
+impl EventSortColumn {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Date => "date",
+            Self::Location => "location",
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum EventSignUpState {
     Nothing,
     SignedUp,
+    Waitlisted,
     Verified,
 }
 
@@ -45,49 +145,24 @@ impl DataType for Event {
     type FormForAdding = AddEvent;
 
     async fn get_from_db_by_id(id: Self::Id, conn: &mut PgConnection) -> DenimResult<Option<Self>> {
-        let Some(most_bits) = sqlx::query!("SELECT * FROM public.events WHERE id = $1", id)
-            .fetch_optional(&mut *conn)
-            .await
-            .context(MakeQuerySnafu)?
+        let Some(most_bits) = sqlx::query!(
+            "SELECT * FROM public.events WHERE id = $1 AND deleted_at IS NULL",
+            id
+        )
+        .fetch_optional(&mut *conn)
+        .await
+        .context(MakeQuerySnafu)?
         else {
             return Ok(None);
         };
-        let associated_staff_member = match most_bits.associated_staff_member {
-            Some(id) => User::get_from_db_by_id(id, &mut *conn).await?,
-            None => None,
-        };
+        let associated_staff = Self::get_associated_staff(id, &mut *conn).await?;
+        let tags = Self::get_tags(id, &mut *conn).await?;
 
         let timezone =
             TimeZone::get(&most_bits.tz).context(InvalidTimezoneSnafu { tz: most_bits.tz })?;
+        let datetime = sql_to_zoned(most_bits.date, timezone);
 
-        #[allow(clippy::cast_sign_loss, clippy::cast_possible_wrap)]
-        let datetime = {
-            let date = most_bits.date.assume_utc();
-            Timestamp::new(date.unix_timestamp(), date.nanosecond() as _)
-                .expect("`date` guarantees timestamps are in valid intervals")
-                .to_zoned(timezone)
-        };
-
-        let mut signed_up = vec![];
-        let mut verified = vec![];
-
-        let mut participation_stream = sqlx::query!(
-            "SELECT student_id, is_verified FROM participation WHERE event_id = $1",
-            id
-        )
-        .fetch(&mut *conn);
-        while let Some(record) = participation_stream
-            .try_next()
-            .await
-            .context(MakeQuerySnafu)?
-        {
-            if record.is_verified {
-                verified.push(record.student_id);
-            } else {
-                signed_up.push(record.student_id);
-            }
-        }
-        drop(participation_stream);
+        let (signed_up, verified, waitlisted) = Self::get_participation(id, &mut *conn).await?;
 
         let photos = Photo::get_by_event_id(id, conn).await?;
 
@@ -97,22 +172,28 @@ impl DataType for Event {
             datetime,
             location: most_bits.location,
             extra_info: most_bits.extra_info,
-            associated_staff_member,
+            associated_staff,
+            max_capacity: most_bits.max_capacity,
+            recurrence_group: most_bits.recurrence_group,
+            tags,
             signed_up,
             verified,
+            waitlisted,
             photos,
         }))
     }
 
     async fn get_all(pool: &Pool<Postgres>) -> DenimResult<Vec<Self>> {
-        let mut first_conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
-        let mut second_conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
+        let mut conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
 
-        let ids = sqlx::query!("SELECT id FROM public.events")
-            .fetch(&mut *first_conn)
-            .map(|result| result.map(|record| record.id))
-            .boxed();
-        Self::get_from_fetch_stream_of_ids(ids, &mut second_conn).await
+        let ids: Vec<_> = sqlx::query!("SELECT id FROM public.events WHERE deleted_at IS NULL")
+            .fetch_all(&mut *conn)
+            .await
+            .context(MakeQuerySnafu)?
+            .into_iter()
+            .map(|record| record.id)
+            .collect();
+        Self::get_from_iter_of_ids(ids, &mut conn).await
     }
 
     async fn insert_into_database(
@@ -124,55 +205,22 @@ impl DataType for Event {
             date,
             location,
             extra_info,
-            associated_staff_member,
+            associated_staff,
+            max_capacity,
+            recurrence_group,
+            tags,
         } = to_be_added;
 
-        //verify that the staff member exists :)
-        if let Some(asm) = &associated_staff_member {
-            if !sqlx::query!(
-                "SELECT exists(SELECT 1 FROM public.staff WHERE user_id = $1) as \"exists!\"",
-                asm
-            )
-            .fetch_one(&mut *conn)
-            .await
-            .context(MakeQuerySnafu)?
-            .exists
-            {
-                return Err(DenimError::MissingUser { id: *asm });
-            }
-        }
-
-        let timestamp = {
-            let back_to_utc = date.with_time_zone(TimeZone::UTC);
-            let date = back_to_utc.date();
-            let time = back_to_utc.time();
-
-            #[allow(clippy::cast_lossless, clippy::cast_sign_loss)]
-            PrimitiveDateTime::new(
-                Date::from_calendar_date(
-                    date.year() as _,
-                    Month::try_from(date.month() as u8)
-                        .expect("`jiff` assures me the date is in range"),
-                    date.day() as _,
-                )
-                .expect("`jiff` assures me the values are sensible"),
-                Time::from_hms_nano(
-                    time.hour() as _,
-                    time.minute() as _,
-                    time.second() as _,
-                    time.subsec_nanosecond() as _,
-                )
-                .expect("`jiff` assures me the values are sensible"),
-            )
-        };
-
-        let timezone = date.time_zone().iana_name().unwrap_or_else(|| {
-            warn!(%name, %date, "Unable to find IANA timezone, using UTC");
-            "UTC"
-        });
+        ensure_staff_members_exist(&associated_staff, &mut *conn).await?;
+        let (timestamp, timezone) = zoned_to_sql(&name, &date);
 
         //gets weird when i try to use query_as, idk
-        Ok(sqlx::query!("INSERT INTO public.events (name, date, location, extra_info, associated_staff_member, tz) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id", name, timestamp, location, extra_info, associated_staff_member, timezone).fetch_one(conn).await.context(MakeQuerySnafu)?.id)
+        let id = sqlx::query!("INSERT INTO public.events (name, date, location, extra_info, tz, max_capacity, recurrence_group) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING id", name, timestamp, location, extra_info, timezone, max_capacity, recurrence_group).fetch_one(&mut *conn).await.context(MakeQuerySnafu)?.id;
+        Self::set_associated_staff(id, &associated_staff, &mut *conn).await?;
+        Self::set_tags(id, &tags, conn).await?;
+        METRICS.record_event_created();
+
+        Ok(id)
     }
 
     async fn remove_from_database(id: Self::Id, conn: &mut PgConnection) -> DenimResult<()> {
@@ -184,28 +232,306 @@ impl DataType for Event {
     }
 }
 
+//verify that the staff members exist :)
+async fn ensure_staff_members_exist(
+    associated_staff: &[Uuid],
+    conn: &mut PgConnection,
+) -> DenimResult<()> {
+    let missing = sqlx::query!(
+        "SELECT id as \"id!\" FROM UNNEST($1::uuid[]) as ids(id) WHERE NOT EXISTS(SELECT 1 FROM public.staff WHERE user_id = ids.id) LIMIT 1",
+        associated_staff
+    )
+    .fetch_optional(conn)
+    .await
+    .context(MakeQuerySnafu)?;
+
+    if let Some(missing) = missing {
+        return Err(DenimError::MissingUser { id: missing.id });
+    }
+
+    Ok(())
+}
+
+///the opposite of [`zoned_to_sql`]
+#[allow(clippy::cast_sign_loss, clippy::cast_possible_wrap)]
+fn sql_to_zoned(date: PrimitiveDateTime, timezone: TimeZone) -> Zoned {
+    let date = date.assume_utc();
+    Timestamp::new(date.unix_timestamp(), date.nanosecond() as _)
+        .expect("`date` guarantees timestamps are in valid intervals")
+        .to_zoned(timezone)
+}
+
+///splits a `Zoned` into the UTC timestamp and the IANA timezone name that get stored in SQL
+fn zoned_to_sql<'a>(name: &str, date: &'a Zoned) -> (PrimitiveDateTime, &'a str) {
+    let timestamp = {
+        let back_to_utc = date.with_time_zone(TimeZone::UTC);
+        let date = back_to_utc.date();
+        let time = back_to_utc.time();
+
+        #[allow(clippy::cast_lossless, clippy::cast_sign_loss)]
+        PrimitiveDateTime::new(
+            Date::from_calendar_date(
+                date.year() as _,
+                Month::try_from(date.month() as u8)
+                    .expect("`jiff` assures me the date is in range"),
+                date.day() as _,
+            )
+            .expect("`jiff` assures me the values are sensible"),
+            Time::from_hms_nano(
+                time.hour() as _,
+                time.minute() as _,
+                time.second() as _,
+                time.subsec_nanosecond() as _,
+            )
+            .expect("`jiff` assures me the values are sensible"),
+        )
+    };
+
+    let timezone = date.time_zone().iana_name().unwrap_or_else(|| {
+        warn!(%name, %date, "Unable to find IANA timezone, using UTC");
+        "UTC"
+    });
+
+    (timestamp, timezone)
+}
+
 impl Event {
-    pub async fn get_future_events(pool: &Pool<Postgres>) -> DenimResult<Vec<Self>> {
-        let mut first_conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
-        let mut second_conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
+    ///updates everything apart from the participation & photos, which are left alone - apart from promoting anyone who now fits from the waitlist
+    ///
+    ///returns whether anyone was promoted
+    pub async fn update_in_database(
+        id: Uuid,
+        AddEvent {
+            name,
+            date,
+            location,
+            extra_info,
+            associated_staff,
+            max_capacity,
+            recurrence_group: _,
+            tags,
+        }: AddEvent,
+        conn: &mut PgConnection,
+    ) -> DenimResult<bool> {
+        ensure_staff_members_exist(&associated_staff, &mut *conn).await?;
+        let (timestamp, timezone) = zoned_to_sql(&name, &date);
+
+        let updated = sqlx::query!("UPDATE public.events SET name = $2, date = $3, location = $4, extra_info = $5, tz = $6, max_capacity = $7 WHERE id = $1 AND deleted_at IS NULL", id, name, timestamp, location, extra_info, timezone, max_capacity)
+            .execute(&mut *conn)
+            .await
+            .context(MakeQuerySnafu)?;
+
+        if updated.rows_affected() == 0 {
+            return Err(DenimError::MissingEvent { id });
+        }
+
+        Self::set_associated_staff(id, &associated_staff, &mut *conn).await?;
+        Self::set_tags(id, &tags, &mut *conn).await?;
+
+        //the update holds the row lock, so no sign-ups can sneak in whilst this runs
+        let mut promoted_any = false;
+        while Self::promote_from_waitlist(id, &mut *conn).await? {
+            promoted_any = true;
+        }
+        Ok(promoted_any)
+    }
+
+    ///gets the staff running an event, ordered by surname
+    pub async fn get_associated_staff(
+        event_id: Uuid,
+        conn: &mut PgConnection,
+    ) -> DenimResult<Vec<User>> {
+        let ids: Vec<Uuid> = sqlx::query!(
+            "SELECT staff_id FROM public.event_staff WHERE event_id = $1",
+            event_id
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .into_iter()
+        .map(|record| record.staff_id)
+        .collect();
+
+        let mut staff = User::get_many_by_ids(&ids, conn).await?;
+        staff.sort_by(|a, b| a.surname.cmp(&b.surname));
+        Ok(staff)
+    }
+
+    ///replaces whoever was running the event with `staff`
+    async fn set_associated_staff(
+        event_id: Uuid,
+        staff: &[Uuid],
+        conn: &mut PgConnection,
+    ) -> DenimResult<()> {
+        sqlx::query!(
+            "DELETE FROM public.event_staff WHERE event_id = $1",
+            event_id
+        )
+        .execute(&mut *conn)
+        .await
+        .context(MakeQuerySnafu)?;
+        sqlx::query!(
+            "INSERT INTO public.event_staff (event_id, staff_id) SELECT $1, * FROM UNNEST($2::uuid[]) ON CONFLICT DO NOTHING",
+            event_id,
+            staff
+        )
+        .execute(conn)
+        .await
+        .context(MakeQuerySnafu)?;
 
-        let ids = sqlx::query!("SELECT id FROM public.events WHERE date > NOW() ORDER BY date")
-            .fetch(&mut *first_conn)
-            .map(|result| result.map(|record| record.id))
-            .boxed();
-        Self::get_from_fetch_stream_of_ids(ids, &mut second_conn).await
+        Ok(())
     }
 
-    pub async fn get_past_events(pool: &Pool<Postgres>) -> DenimResult<Vec<Self>> {
-        let mut first_conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
-        let mut second_conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
+    ///gets the tags on an event, alphabetically
+    pub async fn get_tags(event_id: Uuid, conn: &mut PgConnection) -> DenimResult<Vec<String>> {
+        Ok(sqlx::query!(
+            "SELECT t.name FROM public.event_tags et INNER JOIN public.tags t ON t.id = et.tag_id WHERE et.event_id = $1 ORDER BY t.name",
+            event_id
+        )
+        .fetch_all(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .into_iter()
+        .map(|record| record.name)
+        .collect())
+    }
+
+    ///replaces the tags on an event, creating any that don't exist yet
+    async fn set_tags(event_id: Uuid, tags: &[String], conn: &mut PgConnection) -> DenimResult<()> {
+        sqlx::query!(
+            "DELETE FROM public.event_tags WHERE event_id = $1",
+            event_id
+        )
+        .execute(&mut *conn)
+        .await
+        .context(MakeQuerySnafu)?;
+        sqlx::query!(
+            "INSERT INTO public.tags (name) SELECT * FROM UNNEST($1::text[]) ON CONFLICT DO NOTHING",
+            tags
+        )
+        .execute(&mut *conn)
+        .await
+        .context(MakeQuerySnafu)?;
+        sqlx::query!(
+            "INSERT INTO public.event_tags (event_id, tag_id) SELECT $1, id FROM public.tags WHERE name = ANY($2::text[]) ON CONFLICT DO NOTHING",
+            event_id,
+            tags
+        )
+        .execute(conn)
+        .await
+        .context(MakeQuerySnafu)?;
 
-        let ids =
-            sqlx::query!("SELECT id FROM public.events WHERE date <= NOW() ORDER BY date DESC")
-                .fetch(&mut *first_conn)
-                .map(|result| result.map(|record| record.id))
-                .boxed();
-        Self::get_from_fetch_stream_of_ids(ids, &mut second_conn).await
+        Ok(())
+    }
+
+    ///every tag that's on at least one event, alphabetically
+    pub async fn get_all_tags(conn: &mut PgConnection) -> DenimResult<Vec<String>> {
+        Ok(sqlx::query!(
+            "SELECT name FROM public.tags t WHERE EXISTS(SELECT 1 FROM public.event_tags et WHERE et.tag_id = t.id) ORDER BY name"
+        )
+        .fetch_all(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .into_iter()
+        .map(|record| record.name)
+        .collect())
+    }
+
+    ///hides the event (or every occurrence, if `series`) until it's either restored or purged
+    pub async fn soft_delete(id: Uuid, series: bool, conn: &mut PgConnection) -> DenimResult<()> {
+        //everything deleted together shares the same `deleted_at`, so it can be restored together
+        let deleted = sqlx::query!("UPDATE public.events SET deleted_at = NOW() WHERE deleted_at IS NULL AND (id = $1 OR ($2 AND recurrence_group = (SELECT recurrence_group FROM public.events WHERE id = $1)))", id, series)
+            .execute(conn)
+            .await
+            .context(MakeQuerySnafu)?;
+
+        if deleted.rows_affected() == 0 {
+            return Err(DenimError::MissingEvent { id });
+        }
+        Ok(())
+    }
+
+    ///brings back everything that was deleted alongside `id`, as long as it's still within the undo window
+    pub async fn restore(id: Uuid, conn: &mut PgConnection) -> DenimResult<bool> {
+        let restored = sqlx::query!("UPDATE public.events e SET deleted_at = NULL FROM public.events deleted WHERE deleted.id = $1 AND deleted.deleted_at > NOW() - make_interval(secs => $2::bigint) AND e.deleted_at = deleted.deleted_at AND (e.id = deleted.id OR e.recurrence_group = deleted.recurrence_group)", id, EVENT_UNDO_WINDOW_SECS)
+            .execute(conn)
+            .await
+            .context(MakeQuerySnafu)?;
+
+        Ok(restored.rows_affected() > 0)
+    }
+
+    ///actually deletes events that have been past the undo window, returning how many went
+    pub async fn purge_deleted(conn: &mut PgConnection) -> DenimResult<u64> {
+        let purged = sqlx::query!(
+            "DELETE FROM public.events WHERE deleted_at <= NOW() - make_interval(secs => $1::bigint)",
+            EVENT_UNDO_WINDOW_SECS
+        )
+        .execute(conn)
+        .await
+        .context(MakeQuerySnafu)?;
+
+        Ok(purged.rows_affected())
+    }
+
+    pub async fn get_future_events(
+        pool: &Pool<Postgres>,
+        filter: &EventsFilter<'_>,
+    ) -> DenimResult<Vec<Self>> {
+        Self::get_filtered_events(pool, filter, false).await
+    }
+
+    pub async fn get_past_events(
+        pool: &Pool<Postgres>,
+        filter: &EventsFilter<'_>,
+    ) -> DenimResult<Vec<Self>> {
+        Self::get_filtered_events(pool, filter, true).await
+    }
+
+    ///without a sort, future events come soonest first & past events come most recent first
+    async fn get_filtered_events(
+        pool: &Pool<Postgres>,
+        filter: &EventsFilter<'_>,
+        past: bool,
+    ) -> DenimResult<Vec<Self>> {
+        let mut conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
+
+        let (from, to) = filter.sql_range();
+        let ids: Vec<_> = sqlx::query!("SELECT id FROM public.events e WHERE (date <= NOW()) = $10 AND deleted_at IS NULL AND ($1::text IS NULL OR EXISTS(SELECT 1 FROM public.event_tags et INNER JOIN public.tags t ON t.id = et.tag_id WHERE et.event_id = e.id AND t.name = $1)) AND ($2::timestamp IS NULL OR date >= $2) AND ($3::timestamp IS NULL OR date < $3) AND ($4::text IS NULL OR ($5 IN ('all', 'name') AND strpos(lower(e.name), lower($4)) > 0) OR ($5 IN ('all', 'location') AND strpos(lower(e.location), lower($4)) > 0) OR ($5 IN ('all', 'staff') AND EXISTS(SELECT 1 FROM public.event_staff es INNER JOIN public.users u ON u.id = es.staff_id WHERE es.event_id = e.id AND strpos(lower(coalesce(u.pref_name, u.first_name) || ' ' || u.surname), lower($4)) > 0))) AND ($6::uuid IS NULL OR EXISTS(SELECT 1 FROM public.event_staff es WHERE es.event_id = e.id AND es.staff_id = $6)) AND ($7::uuid IS NULL OR EXISTS(SELECT 1 FROM public.participation p WHERE p.event_id = e.id AND p.student_id = $7)) ORDER BY CASE WHEN $8::text = 'name' AND NOT $9 THEN e.name END, CASE WHEN $8::text = 'name' AND $9 THEN e.name END DESC, CASE WHEN $8::text = 'location' AND NOT $9 THEN e.location END, CASE WHEN $8::text = 'location' AND $9 THEN e.location END DESC, CASE WHEN $8::text = 'date' AND $9 AND NOT $10 THEN date END DESC, CASE WHEN $8::text = 'date' AND NOT $9 AND $10 THEN date END, CASE WHEN NOT $10 THEN date END, CASE WHEN $10 THEN date END DESC", filter.tag, from, to, filter.search, filter.search_scope.as_str(), filter.staff_id, filter.participant_id, filter.sort.map(EventSortColumn::as_str), filter.sort_direction.is_descending(), past)
+            .fetch_all(&mut *conn)
+            .await
+            .context(MakeQuerySnafu)?
+            .into_iter()
+            .map(|record| record.id)
+            .collect();
+        Self::get_from_iter_of_ids(ids, &mut conn).await
+    }
+
+    pub async fn is_full(event_id: Uuid, conn: &mut PgConnection) -> DenimResult<bool> {
+        let record = sqlx::query!("SELECT e.max_capacity, (SELECT COUNT(*) FROM public.participation p WHERE p.event_id = e.id AND NOT p.is_waitlisted) as \"taken!\" FROM public.events e WHERE e.id = $1 AND e.deleted_at IS NULL", event_id)
+            .fetch_optional(conn)
+            .await
+            .context(MakeQuerySnafu)?
+            .context(MissingEventSnafu { id: event_id })?;
+
+        Ok(record
+            .max_capacity
+            .is_some_and(|max_capacity| record.taken >= i64::from(max_capacity)))
+    }
+
+    ///whether the event has already started, and so shouldn't get any new sign-ups
+    pub async fn has_finished(event_id: Uuid, conn: &mut PgConnection) -> DenimResult<bool> {
+        let record = sqlx::query!(
+            "SELECT date, tz FROM public.events WHERE id = $1 AND deleted_at IS NULL",
+            event_id
+        )
+        .fetch_optional(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .context(MissingEventSnafu { id: event_id })?;
+
+        let timezone = TimeZone::get(&record.tz).context(InvalidTimezoneSnafu { tz: record.tz })?;
+        Ok(sql_to_zoned(record.date, timezone) <= Zoned::now())
     }
 
     pub async fn user_is_signed_up_to_event(
@@ -225,14 +551,404 @@ impl Event {
             return Ok(None);
         }
 
-        Ok(Some(match sqlx::query!("SELECT is_verified FROM public.participation WHERE event_id = $1 AND student_id = $2", event_id, student_id)
+        Ok(Some(match sqlx::query!("SELECT is_verified, is_waitlisted FROM public.participation WHERE event_id = $1 AND student_id = $2", event_id, student_id)
             .fetch_optional(conn)
             .await
             .context(MakeQuerySnafu)?
-            .map(|rec| rec.is_verified) {
+            .map(|rec| (rec.is_verified, rec.is_waitlisted)) {
             None => EventSignUpState::Nothing,
-            Some(false) => EventSignUpState::SignedUp,
-            Some(true) => EventSignUpState::Verified,
+            Some((true, _)) => EventSignUpState::Verified,
+            Some((false, true)) => EventSignUpState::Waitlisted,
+            Some((false, false)) => EventSignUpState::SignedUp,
         }))
     }
+
+    ///returns `(signed_up, verified, waitlisted)`, with each in the order they signed up
+    pub async fn get_participation(
+        event_id: Uuid,
+        conn: &mut PgConnection,
+    ) -> DenimResult<(Vec<Uuid>, Vec<Uuid>, Vec<Uuid>)> {
+        let mut signed_up = vec![];
+        let mut verified = vec![];
+        let mut waitlisted = vec![];
+
+        let mut participation_stream = sqlx::query!(
+            "SELECT student_id, is_verified, is_waitlisted FROM participation WHERE event_id = $1 ORDER BY signed_up_at",
+            event_id
+        )
+        .fetch(conn);
+        while let Some(record) = participation_stream
+            .try_next()
+            .await
+            .context(MakeQuerySnafu)?
+        {
+            if record.is_verified {
+                verified.push(record.student_id);
+            } else if record.is_waitlisted {
+                waitlisted.push(record.student_id);
+            } else {
+                signed_up.push(record.student_id);
+            }
+        }
+
+        Ok((signed_up, verified, waitlisted))
+    }
+
+    ///verifies every signed up student in `students`, or everyone signed up if that's `None`
+    ///
+    ///waitlisted students are always left alone
+    pub async fn verify_students(
+        event_id: Uuid,
+        students: Option<&[Uuid]>,
+        conn: &mut PgConnection,
+    ) -> DenimResult<()> {
+        sqlx::query!("UPDATE public.participation SET is_verified = TRUE WHERE event_id = $1 AND NOT is_verified AND NOT is_waitlisted AND ($2::uuid[] IS NULL OR student_id = ANY($2))", event_id, students)
+            .execute(conn)
+            .await
+            .context(MakeQuerySnafu)?;
+        Ok(())
+    }
+
+    ///the houses of everyone signed up to the event (or waitlisted), for checking house-scoped staff
+    pub async fn participant_houses(
+        event_id: Uuid,
+        series: bool,
+        conn: &mut PgConnection,
+    ) -> DenimResult<Vec<i32>> {
+        Ok(sqlx::query!("SELECT DISTINCT tg.house_id FROM public.events e INNER JOIN public.participation p ON p.event_id = e.id INNER JOIN public.students s ON s.user_id = p.student_id INNER JOIN public.tutor_groups tg ON tg.id = s.tutor_group_id WHERE e.id = $1 OR ($2 AND e.recurrence_group = (SELECT recurrence_group FROM public.events WHERE id = $1))", event_id, series)
+            .fetch_all(conn)
+            .await
+            .context(MakeQuerySnafu)?
+            .into_iter()
+            .map(|record| record.house_id)
+            .collect())
+    }
+
+    ///whether `staff_id` is down as staff on the event (or on every occurrence, with `series`)
+    pub async fn is_run_by(
+        event_id: Uuid,
+        series: bool,
+        staff_id: Uuid,
+        conn: &mut PgConnection,
+    ) -> DenimResult<bool> {
+        Ok(sqlx::query!("SELECT NOT EXISTS(SELECT 1 FROM public.events e WHERE (e.id = $1 OR ($2 AND e.recurrence_group = (SELECT recurrence_group FROM public.events WHERE id = $1))) AND NOT EXISTS(SELECT 1 FROM public.event_staff es WHERE es.event_id = e.id AND es.staff_id = $3)) as \"is_run_by!\"", event_id, series, staff_id)
+            .fetch_one(conn)
+            .await
+            .context(MakeQuerySnafu)?
+            .is_run_by)
+    }
+
+    ///holds back any other sign-ups (or un-sign-ups) to this event until the transaction finishes, so two can't both take the last space
+    pub async fn lock_for_sign_ups(event_id: Uuid, conn: &mut PgConnection) -> DenimResult<()> {
+        sqlx::query!(
+            "SELECT id FROM public.events WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+            event_id
+        )
+        .fetch_optional(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .context(MissingEventSnafu { id: event_id })?;
+
+        Ok(())
+    }
+
+    ///double-clicks & racing requests just leave the existing signup alone
+    ///
+    ///returns whether a new signup was made
+    pub async fn sign_up(
+        event_id: Uuid,
+        student_id: Uuid,
+        is_waitlisted: bool,
+        conn: &mut PgConnection,
+    ) -> DenimResult<bool> {
+        let inserted = sqlx::query!("INSERT INTO public.participation (event_id, student_id, is_verified, is_waitlisted) VALUES ($1, $2, FALSE, $3) ON CONFLICT (event_id, student_id) DO NOTHING", event_id, student_id, is_waitlisted)
+            .execute(conn)
+            .await
+            .context(MakeQuerySnafu)?;
+
+        Ok(inserted.rows_affected() > 0)
+    }
+
+    ///moves the earliest waitlisted student into the signed up list, if there's space for them
+    ///
+    ///returns whether anyone was promoted
+    pub async fn promote_from_waitlist(
+        event_id: Uuid,
+        conn: &mut PgConnection,
+    ) -> DenimResult<bool> {
+        if Self::is_full(event_id, &mut *conn).await? {
+            return Ok(false);
+        }
+
+        let promoted = sqlx::query!("UPDATE public.participation SET is_waitlisted = FALSE WHERE event_id = $1 AND student_id = (SELECT student_id FROM public.participation WHERE event_id = $1 AND is_waitlisted ORDER BY signed_up_at LIMIT 1)", event_id)
+            .execute(conn)
+            .await
+            .context(MakeQuerySnafu)?;
+
+        Ok(promoted.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AddEvent, Event, EventSignUpState, EventSortColumn, EventsFilter};
+    use crate::data::{
+        DataType, SortDirection,
+        testing::{insert_event, insert_house, insert_student, insert_tutor_group},
+    };
+    use futures::future::join_all;
+    use sqlx::{PgConnection, PgPool};
+    use uuid::Uuid;
+
+    #[sqlx::test]
+    async fn racing_sign_ups_only_insert_once(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let house = insert_house("Test House", &mut conn).await;
+        let tutor_group = insert_tutor_group(house, &mut conn).await;
+        let student = insert_student(tutor_group, &mut conn).await;
+        let event = insert_event(None, &mut conn).await;
+        drop(conn);
+
+        let attempts = (0..32).map(|_| {
+            let pool = pool.clone();
+            tokio::spawn(async move {
+                let mut conn = pool.acquire().await.unwrap();
+                Event::sign_up(event, student, false, &mut conn).await
+            })
+        });
+        let inserted = join_all(attempts)
+            .await
+            .into_iter()
+            .map(|attempt| attempt.unwrap().unwrap())
+            .filter(|inserted| *inserted)
+            .count();
+        assert_eq!(inserted, 1);
+
+        let rows = sqlx::query_scalar!(
+            "SELECT COUNT(*) as \"count!\" FROM public.participation WHERE event_id = $1 AND student_id = $2",
+            event,
+            student
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(rows, 1);
+    }
+
+    #[sqlx::test]
+    async fn racing_sign_ups_respect_capacity(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let house = insert_house("Test House", &mut conn).await;
+        let tutor_group = insert_tutor_group(house, &mut conn).await;
+        let event = insert_event(Some(3), &mut conn).await;
+        let mut students = vec![];
+        for _ in 0..16 {
+            students.push(insert_student(tutor_group, &mut conn).await);
+        }
+        drop(conn);
+
+        //what `internal_post_sign_others_up` does
+        let attempts = students.into_iter().map(|student| {
+            let pool = pool.clone();
+            tokio::spawn(async move {
+                let mut transaction = pool.begin().await.unwrap();
+                Event::lock_for_sign_ups(event, &mut transaction)
+                    .await
+                    .unwrap();
+                let signed_up = !Event::is_full(event, &mut transaction).await.unwrap()
+                    && Event::sign_up(event, student, false, &mut transaction)
+                        .await
+                        .unwrap();
+                transaction.commit().await.unwrap();
+                signed_up
+            })
+        });
+        let signed_up = join_all(attempts)
+            .await
+            .into_iter()
+            .filter(|attempt| *attempt.as_ref().unwrap())
+            .count();
+        assert_eq!(signed_up, 3);
+
+        let mut conn = pool.acquire().await.unwrap();
+        assert!(Event::is_full(event, &mut conn).await.unwrap());
+    }
+
+    #[sqlx::test]
+    async fn raising_capacity_promotes_from_the_waitlist(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let house = insert_house("Test House", &mut conn).await;
+        let tutor_group = insert_tutor_group(house, &mut conn).await;
+        let event_id = insert_event(Some(1), &mut conn).await;
+        let mut students = vec![];
+        for i in 0..4 {
+            let student = insert_student(tutor_group, &mut conn).await;
+            Event::sign_up(event_id, student, i > 0, &mut conn)
+                .await
+                .unwrap();
+            students.push(student);
+        }
+
+        let event = Event::get_from_db_by_id(event_id, &mut conn)
+            .await
+            .unwrap()
+            .unwrap();
+        let edit = |max_capacity| AddEvent {
+            name: event.name.clone(),
+            date: event.datetime.clone(),
+            location: None,
+            extra_info: None,
+            associated_staff: vec![],
+            max_capacity,
+            recurrence_group: None,
+            tags: vec![],
+        };
+        let state_of = async |student, conn: &mut PgConnection| {
+            Event::user_is_signed_up_to_event(event_id, student, conn)
+                .await
+                .unwrap()
+                .unwrap()
+        };
+
+        assert!(
+            !Event::update_in_database(event_id, edit(Some(1)), &mut conn)
+                .await
+                .unwrap()
+        );
+
+        assert!(
+            Event::update_in_database(event_id, edit(Some(2)), &mut conn)
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            state_of(students[1], &mut conn).await,
+            EventSignUpState::SignedUp
+        );
+        assert_eq!(
+            state_of(students[2], &mut conn).await,
+            EventSignUpState::Waitlisted
+        );
+
+        assert!(
+            Event::update_in_database(event_id, edit(None), &mut conn)
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            state_of(students[2], &mut conn).await,
+            EventSignUpState::SignedUp
+        );
+        assert_eq!(
+            state_of(students[3], &mut conn).await,
+            EventSignUpState::SignedUp
+        );
+    }
+
+    #[sqlx::test]
+    async fn participant_houses_cover_the_series(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let first_house = insert_house("First", &mut conn).await;
+        let second_house = insert_house("Second", &mut conn).await;
+        let first_student =
+            insert_student(insert_tutor_group(first_house, &mut conn).await, &mut conn).await;
+        let second_student =
+            insert_student(insert_tutor_group(second_house, &mut conn).await, &mut conn).await;
+
+        let first_event = insert_event(None, &mut conn).await;
+        let second_event = insert_event(None, &mut conn).await;
+        sqlx::query!(
+            "UPDATE public.events SET recurrence_group = $1 WHERE id = ANY($2)",
+            Uuid::new_v4(),
+            &[first_event, second_event][..]
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        Event::sign_up(first_event, first_student, false, &mut conn)
+            .await
+            .unwrap();
+        Event::sign_up(second_event, second_student, true, &mut conn)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            Event::participant_houses(first_event, false, &mut conn)
+                .await
+                .unwrap(),
+            vec![first_house]
+        );
+        let mut series = Event::participant_houses(first_event, true, &mut conn)
+            .await
+            .unwrap();
+        series.sort_unstable();
+        assert_eq!(series, vec![first_house, second_house]);
+    }
+
+    #[sqlx::test]
+    async fn future_and_past_events_keep_their_own_orders(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let mut events = vec![];
+        for (name, days_away) in [
+            ("100% fun", 14),
+            ("1000 laps", 7),
+            ("last week", -7),
+            ("last fortnight", -14),
+        ] {
+            let id = insert_event(None, &mut conn).await;
+            sqlx::query!(
+                "UPDATE public.events SET name = $2, date = (NOW() AT TIME ZONE 'UTC') + make_interval(days => $3) WHERE id = $1",
+                id,
+                name,
+                days_away
+            )
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+            events.push(id);
+        }
+        drop(conn);
+        let [far_future, near_future, near_past, far_past] = events[..] else {
+            unreachable!()
+        };
+
+        let ids = |events: Vec<Event>| events.into_iter().map(|event| event.id).collect::<Vec<_>>();
+        let default = EventsFilter::default();
+        assert_eq!(
+            ids(Event::get_future_events(&pool, &default).await.unwrap()),
+            [near_future, far_future]
+        );
+        assert_eq!(
+            ids(Event::get_past_events(&pool, &default).await.unwrap()),
+            [near_past, far_past]
+        );
+
+        let flipped = EventsFilter {
+            sort: Some(EventSortColumn::Date),
+            sort_direction: SortDirection::Desc,
+            ..EventsFilter::default()
+        };
+        assert_eq!(
+            ids(Event::get_future_events(&pool, &flipped).await.unwrap()),
+            [far_future, near_future]
+        );
+        let flipped = EventsFilter {
+            sort: Some(EventSortColumn::Date),
+            sort_direction: SortDirection::Asc,
+            ..EventsFilter::default()
+        };
+        assert_eq!(
+            ids(Event::get_past_events(&pool, &flipped).await.unwrap()),
+            [far_past, near_past]
+        );
+
+        let search = EventsFilter {
+            search: Some("100%"),
+            ..EventsFilter::default()
+        };
+        assert_eq!(
+            ids(Event::get_future_events(&pool, &search).await.unwrap()),
+            [far_future]
+        );
+    }
 }
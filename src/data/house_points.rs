@@ -0,0 +1,119 @@
+use crate::{
+    data::{DataType, student_groups::HouseGroup},
+    error::{DenimResult, MakeQuerySnafu, MissingHouseGroupSnafu, UnrepresentableTimeSnafu},
+};
+use jiff::Timestamp;
+use snafu::{OptionExt, ResultExt};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub struct HousePointsAward {
+    pub house: HouseGroup,
+    pub points: i32,
+    pub reason: String,
+    pub awarded_by: Option<Uuid>,
+    pub awarded_at: Timestamp,
+}
+
+impl HousePointsAward {
+    ///gives back the house the points went to
+    pub async fn award(
+        house_id: i32,
+        points: i32,
+        reason: &str,
+        awarded_by: Uuid,
+        conn: &mut PgConnection,
+    ) -> DenimResult<HouseGroup> {
+        let house = HouseGroup::get_from_db_by_id(house_id, &mut *conn)
+            .await?
+            .context(MissingHouseGroupSnafu { id: house_id })?;
+
+        sqlx::query!(
+            "INSERT INTO public.house_points (house_id, points, reason, awarded_by) VALUES ($1, $2, $3, $4)",
+            house_id,
+            points,
+            reason,
+            awarded_by
+        )
+        .execute(conn)
+        .await
+        .context(MakeQuerySnafu)?;
+
+        Ok(house)
+    }
+
+    ///every house (even those without any points yet), highest total first
+    pub async fn get_totals(conn: &mut PgConnection) -> DenimResult<Vec<(HouseGroup, i64)>> {
+        Ok(sqlx::query!(
+            r#"SELECT h.id, h.name, COALESCE(SUM(p.points), 0) as "total!" FROM public.houses h LEFT JOIN public.house_points p ON p.house_id = h.id GROUP BY h.id, h.name ORDER BY 3 DESC, h.name"#
+        )
+        .fetch_all(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .into_iter()
+        .map(|record| {
+            (
+                HouseGroup {
+                    id: record.id,
+                    name: record.name,
+                },
+                record.total,
+            )
+        })
+        .collect())
+    }
+
+    pub async fn get_most_recent(limit: i64, conn: &mut PgConnection) -> DenimResult<Vec<Self>> {
+        sqlx::query!(
+            "SELECT p.points, p.reason, p.awarded_by, p.awarded_at, h.id as house_id, h.name as house_name FROM public.house_points p INNER JOIN public.houses h ON h.id = p.house_id ORDER BY p.awarded_at DESC LIMIT $1",
+            limit
+        )
+        .fetch_all(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .into_iter()
+        .map(|record| {
+            Ok(Self {
+                house: HouseGroup {
+                    id: record.house_id,
+                    name: record.house_name,
+                },
+                points: record.points,
+                reason: record.reason,
+                awarded_by: record.awarded_by,
+                awarded_at: Timestamp::from_second(record.awarded_at.unix_timestamp())
+                    .context(UnrepresentableTimeSnafu)?,
+            })
+        })
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HousePointsAward;
+    use crate::{
+        data::testing::{insert_house, insert_staff},
+        error::DenimError,
+    };
+    use sqlx::PgPool;
+
+    #[sqlx::test]
+    async fn awarding_to_a_missing_house_is_not_found(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let house = insert_house("Red", &mut conn).await;
+        let staff = insert_staff(None, &mut conn).await;
+
+        let awarded = HousePointsAward::award(house, 5, "tidy", staff, &mut conn)
+            .await
+            .unwrap();
+        assert_eq!(awarded.id, house);
+
+        let missing = HousePointsAward::award(house + 1, 5, "tidy", staff, &mut conn).await;
+        assert!(matches!(
+            missing,
+            Err(DenimError::MissingHouseGroup { id }) if id == house + 1
+        ));
+    }
+}
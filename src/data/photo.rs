@@ -1,26 +1,32 @@
 use crate::{
-    data::{DataType, IdForm},
+    data::{DataType, IdForm, page_offset},
     error::{
         CommitTransactionSnafu, DenimError, DenimResult, GetDatabaseConnectionSnafu,
-        MakeQuerySnafu, RollbackTransactionSnafu, S3Snafu,
+        MakeQuerySnafu, MissingEventSnafu, RollbackTransactionSnafu, S3Snafu, TooManyPhotosSnafu,
     },
 };
-use futures::StreamExt;
-use s3::Bucket;
-use snafu::ResultExt;
+use image::ImageFormat;
+use s3::{
+    Bucket,
+    error::S3Error,
+    post_policy::{PostPolicy, PostPolicyField, PostPolicyValue, PresignedPost},
+};
+use snafu::{OptionExt, ResultExt, ensure};
 use sqlx::{PgConnection, Pool, Postgres, Transaction};
-use std::sync::Arc;
+use std::{io::Cursor, sync::Arc};
 use uuid::Uuid;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Photo {
     pub id: Uuid,
     pub event_id: Uuid,
     pub extension: String,
+    pub has_thumbnail: bool,
 }
 
 pub struct NewPhotoForm {
     pub bytes: Vec<u8>,
+    pub thumbnail: Option<Vec<u8>>,
     pub content_type: &'static str,
     pub extension: &'static str,
     pub s3_bucket_to_add_to: Arc<Bucket>,
@@ -33,21 +39,23 @@ impl DataType for Photo {
     type FormForAdding = NewPhotoForm;
 
     async fn get_from_db_by_id(id: Self::Id, conn: &mut PgConnection) -> DenimResult<Option<Self>> {
-        sqlx::query_as!(Photo, "SELECT id, event_id, extension FROM photos WHERE id = $1", id)
+        sqlx::query_as!(Photo, "SELECT id, event_id, extension, has_thumbnail FROM photos WHERE id = $1", id)
             .fetch_optional(conn)
             .await
             .context(MakeQuerySnafu)
     }
 
-    async fn get_all(conn: &Pool<Postgres>) -> DenimResult<Vec<Self>> {
-        let mut first_conn = conn.acquire().await.context(GetDatabaseConnectionSnafu)?;
-        let mut second_conn = conn.acquire().await.context(GetDatabaseConnectionSnafu)?;
+    async fn get_all(pool: &Pool<Postgres>) -> DenimResult<Vec<Self>> {
+        let mut conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
 
-        let ids = sqlx::query!("SELECT id FROM photos")
-            .fetch(&mut *first_conn)
-            .map(|result| result.map(|record| record.id))
-            .boxed();
-        Self::get_from_fetch_stream_of_ids(ids, &mut second_conn).await
+        let ids: Vec<_> = sqlx::query!("SELECT id FROM photos")
+            .fetch_all(&mut *conn)
+            .await
+            .context(MakeQuerySnafu)?
+            .into_iter()
+            .map(|record| record.id)
+            .collect();
+        Self::get_from_iter_of_ids(ids, &mut conn).await
     }
 
     async fn insert_into_database(
@@ -62,6 +70,7 @@ impl DataType for Photo {
     async fn insert_into_database_transaction(
         NewPhotoForm {
             bytes,
+            thumbnail,
             content_type,
             extension,
             s3_bucket_to_add_to,
@@ -69,22 +78,26 @@ impl DataType for Photo {
         }: Self::FormForAdding,
         mut conn: Transaction<'_, Postgres>,
     ) -> DenimResult<Self::Id> {
-        let id = sqlx::query!("INSERT INTO photos (event_id, extension) VALUES ($1, $2) RETURNING id", event_id, &extension)
+        let id = sqlx::query!("INSERT INTO photos (event_id, extension, has_thumbnail) VALUES ($1, $2, $3) RETURNING id", event_id, &extension, thumbnail.is_some())
             .fetch_one(&mut *conn)
             .await
             .context(MakeQuerySnafu)?
             .id;
 
-        match s3_bucket_to_add_to
-            .put_object_with_content_type(
-                format!("/photos/{id}.{extension}"),
-                &bytes,
-                &content_type,
-            )
-            .await
-            .context(S3Snafu)
-        {
-            Ok(_) => {
+        let upload = async {
+            s3_bucket_to_add_to
+                .put_object_with_content_type(format!("/photos/{id}.{extension}"), &bytes, content_type)
+                .await?;
+            if let Some(thumbnail) = thumbnail {
+                s3_bucket_to_add_to
+                    .put_object_with_content_type(format!("/photos/{id}_thumb.jpg"), &thumbnail, "image/jpeg")
+                    .await?;
+            }
+            Ok::<_, S3Error>(())
+        };
+
+        match upload.await.context(S3Snafu) {
+            Ok(()) => {
                 conn.commit().await.context(CommitTransactionSnafu)?;
             }
             Err(e) => {
@@ -106,7 +119,217 @@ impl DataType for Photo {
     }
 }
 
+///how long the browser gets to upload a photo once it's been presigned, after which it gets swept
+const DIRECT_UPLOAD_VALID_FOR_SECS: u32 = 60 * 15;
+
+///content types we'll hand out presigned uploads for, and the extensions they get stored under
+pub const DIRECT_UPLOAD_TYPES: [(&str, &str); 7] = [
+    ("image/jpeg", "jpg"),
+    ("image/png", "png"),
+    ("image/gif", "gif"),
+    ("image/webp", "webp"),
+    ("image/heic", "heic"),
+    ("image/bmp", "bmp"),
+    ("image/tiff", "tif"),
+];
+
 impl Photo {
+    pub fn extension_for_direct_upload(content_type: &str) -> Option<&'static str> {
+        DIRECT_UPLOAD_TYPES
+            .iter()
+            .find(|(ct, _)| *ct == content_type)
+            .map(|(_, extension)| *extension)
+    }
+
+    pub fn content_type_for_direct_upload(extension: &str) -> Option<&'static str> {
+        DIRECT_UPLOAD_TYPES
+            .iter()
+            .find(|(_, ext)| *ext == extension)
+            .map(|(content_type, _)| *content_type)
+    }
+
+    ///a POST policy rather than a PUT, so that S3 itself enforces the size limit and content type
+    pub async fn presign_direct_upload(
+        id: Uuid,
+        extension: &str,
+        content_type: &str,
+        max_bytes: usize,
+        s3: &Bucket,
+    ) -> DenimResult<PresignedPost> {
+        let policy = PostPolicy::new(DIRECT_UPLOAD_VALID_FOR_SECS)
+            .condition(
+                PostPolicyField::Key,
+                PostPolicyValue::Exact(format!("photos/{id}.{extension}").into()),
+            )
+            .and_then(|policy| {
+                policy.condition(
+                    PostPolicyField::ContentType,
+                    PostPolicyValue::Exact(content_type.into()),
+                )
+            })
+            .and_then(|policy| {
+                policy.condition(
+                    PostPolicyField::ContentLengthRange,
+                    PostPolicyValue::Range(1, u32::try_from(max_bytes).unwrap_or(u32::MAX)),
+                )
+            })
+            .context(S3Snafu)?;
+
+        s3.presign_post(policy).await.context(S3Snafu)
+    }
+
+    ///holds a slot for each upload against `max_photos_per_event` until it's confirmed or swept away
+    ///
+    ///should be done in a transaction, as the event gets locked so that uploads can't race each other for the last slots
+    ///holds back anything else adding photos to the event until the transaction finishes, giving back how many it has (counting uploads that've been handed out but not confirmed yet)
+    pub async fn lock_photo_slots(event_id: Uuid, conn: &mut PgConnection) -> DenimResult<i64> {
+        sqlx::query!("SELECT id FROM events WHERE id = $1 FOR UPDATE", event_id)
+            .fetch_optional(&mut *conn)
+            .await
+            .context(MakeQuerySnafu)?
+            .context(MissingEventSnafu { id: event_id })?;
+
+        Ok(sqlx::query!(
+            r#"SELECT (SELECT COUNT(*) FROM photos WHERE event_id = $1) + (SELECT COUNT(*) FROM pending_photo_uploads WHERE event_id = $1) as "count!""#,
+            event_id
+        )
+        .fetch_one(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .count)
+    }
+
+    pub async fn reserve_direct_uploads(
+        event_id: Uuid,
+        uploads: &[(Uuid, &str)],
+        max_photos_per_event: i64,
+        conn: &mut PgConnection,
+    ) -> DenimResult<()> {
+        let taken = Self::lock_photo_slots(event_id, &mut *conn).await?;
+        ensure!(
+            taken.saturating_add(i64::try_from(uploads.len()).unwrap_or(i64::MAX))
+                <= max_photos_per_event,
+            TooManyPhotosSnafu {
+                max: max_photos_per_event
+            }
+        );
+
+        let (ids, extensions): (Vec<Uuid>, Vec<String>) = uploads
+            .iter()
+            .map(|(id, extension)| (*id, (*extension).to_string()))
+            .unzip();
+        sqlx::query!(
+            "INSERT INTO pending_photo_uploads (id, event_id, extension) SELECT UNNEST($1::uuid[]), $2, UNNEST($3::text[])",
+            &ids,
+            event_id,
+            &extensions
+        )
+        .execute(conn)
+        .await
+        .context(MakeQuerySnafu)?;
+
+        Ok(())
+    }
+
+    ///only uploads we've handed out can be confirmed, so nothing else in the bucket can be claimed
+    pub async fn is_pending_direct_upload(
+        id: Uuid,
+        event_id: Uuid,
+        extension: &str,
+        conn: &mut PgConnection,
+    ) -> DenimResult<bool> {
+        Ok(sqlx::query!(
+            r#"SELECT EXISTS(SELECT 1 FROM pending_photo_uploads WHERE id = $1 AND event_id = $2 AND extension = $3) as "exists!""#,
+            id,
+            event_id,
+            extension
+        )
+        .fetch_one(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .exists)
+    }
+
+    ///for photos that have already been put in S3 by the browser, returning `false` if it was no longer pending
+    pub async fn record_direct_upload(id: Uuid, conn: &mut PgConnection) -> DenimResult<bool> {
+        Ok(sqlx::query!(
+            "WITH pending AS (DELETE FROM pending_photo_uploads WHERE id = $1 RETURNING id, event_id, extension) INSERT INTO photos (id, event_id, extension) SELECT id, event_id, extension FROM pending",
+            id
+        )
+        .execute(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .rows_affected()
+            > 0)
+    }
+
+    ///for when the upload got rejected, so make sure it's also been removed from S3
+    pub async fn remove_pending_direct_upload(
+        id: Uuid,
+        conn: &mut PgConnection,
+    ) -> DenimResult<()> {
+        sqlx::query!("DELETE FROM pending_photo_uploads WHERE id = $1", id)
+            .execute(conn)
+            .await
+            .context(MakeQuerySnafu)?;
+
+        Ok(())
+    }
+
+    ///removes uploads which were never confirmed once their presigned policy has run out, as they could be anything
+    pub async fn purge_stale_direct_uploads(
+        s3: &Bucket,
+        conn: &mut PgConnection,
+    ) -> DenimResult<usize> {
+        let stale = sqlx::query!(
+            "SELECT id, extension FROM pending_photo_uploads WHERE created_at < NOW() - make_interval(secs => $1)",
+            f64::from(DIRECT_UPLOAD_VALID_FOR_SECS)
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .context(MakeQuerySnafu)?;
+
+        let mut purged = 0;
+        for upload in stale {
+            let path = format!("/photos/{}.{}", upload.id, upload.extension);
+            //keep it around to try again next time
+            if let Err(e) = s3.delete_object(&path).await {
+                warn!(?e, ?path, "Unable to remove stale photo upload");
+                continue;
+            }
+
+            Self::remove_pending_direct_upload(upload.id, &mut *conn).await?;
+            purged += 1;
+        }
+
+        Ok(purged)
+    }
+
+    pub async fn add_thumbnail(
+        &self,
+        thumbnail: &[u8],
+        s3: &Bucket,
+        conn: &mut PgConnection,
+    ) -> DenimResult<()> {
+        s3.put_object_with_content_type(
+            format!("/photos/{}_thumb.jpg", self.id),
+            thumbnail,
+            "image/jpeg",
+        )
+        .await
+        .context(S3Snafu)?;
+
+        sqlx::query!(
+            "UPDATE photos SET has_thumbnail = TRUE WHERE id = $1",
+            self.id
+        )
+        .execute(conn)
+        .await
+        .context(MakeQuerySnafu)?;
+
+        Ok(())
+    }
+
     pub async fn get_s3_url(&self, s3: &Bucket) -> DenimResult<String> {
         s3.presign_get(
             &format!("/photos/{}.{}", self.id, self.extension),
@@ -117,6 +340,65 @@ impl Photo {
         .context(S3Snafu)
     }
     
+    pub async fn get_bytes(&self, s3: &Bucket) -> DenimResult<Vec<u8>> {
+        Ok(s3
+            .get_object(format!("/photos/{}.{}", self.id, self.extension))
+            .await
+            .context(S3Snafu)?
+            .to_vec())
+    }
+
+    pub async fn get_thumbnail_s3_url(&self, s3: &Bucket) -> DenimResult<Option<String>> {
+        if !self.has_thumbnail {
+            return Ok(None);
+        }
+
+        s3.presign_get(
+            &format!("/photos/{}_thumb.jpg", self.id),
+            60 * 5, //5 mins
+            None,
+        )
+        .await
+        .context(S3Snafu)
+        .map(Some)
+    }
+
+    ///returns `None` for images we can't decode (eg. HEIC), which just won't get a thumbnail
+    pub fn make_thumbnail(bytes: &[u8]) -> Option<Vec<u8>> {
+        const MAX_THUMBNAIL_SIZE: u32 = 320;
+
+        let image = match image::load_from_memory(bytes) {
+            Ok(image) => image,
+            Err(e) => {
+                warn!(?e, "Unable to decode photo for thumbnail");
+                return None;
+            }
+        };
+
+        let mut thumbnail = vec![];
+        if let Err(e) = image
+            .thumbnail(MAX_THUMBNAIL_SIZE, MAX_THUMBNAIL_SIZE)
+            .to_rgb8()
+            .write_to(&mut Cursor::new(&mut thumbnail), ImageFormat::Jpeg)
+        {
+            warn!(?e, "Unable to encode thumbnail");
+            return None;
+        }
+
+        Some(thumbnail)
+    }
+
+    pub async fn count_for_event(event_id: Uuid, conn: &mut PgConnection) -> DenimResult<i64> {
+        Ok(sqlx::query!(
+            r#"SELECT COUNT(*) as "count!" FROM photos WHERE event_id = $1"#,
+            event_id
+        )
+        .fetch_one(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .count)
+    }
+
     pub async fn get_by_event_id (id: Uuid, conn: &mut PgConnection) -> DenimResult<Vec<Self>> {
         let mut photos = vec![];
         for photo_id in sqlx::query!("SELECT id FROM photos WHERE event_id = $1", id)
@@ -124,7 +406,7 @@ impl Photo {
             .await
             .context(MakeQuerySnafu)?
         {
-            if let Some(photo) = Photo::get_from_db_by_id(photo_id.id, &mut *conn).await? {
+            if let Some(photo) = Self::get_from_db_by_id(photo_id.id, &mut *conn).await? {
                 photos.push(photo);
             } else {
                 warn!(?photo_id.id, "Missing Photo?");
@@ -132,4 +414,100 @@ impl Photo {
         }
         Ok(photos)
     }
+
+    ///one page of an event's photos, along with whether there's at least one more page after it
+    pub async fn get_page_by_event_id(
+        id: Uuid,
+        page: i64,
+        page_size: i64,
+        conn: &mut PgConnection,
+    ) -> DenimResult<(Vec<Self>, bool)> {
+        //ids are random, so new uploads go on the end by time rather than landing somewhere in the middle
+        let mut photos = sqlx::query_as!(
+            Photo,
+            "SELECT id, event_id, extension, has_thumbnail FROM photos WHERE event_id = $1 ORDER BY created_at, id LIMIT $2 OFFSET $3",
+            id,
+            page_size + 1,
+            page_offset(page, page_size)
+        )
+        .fetch_all(conn)
+        .await
+        .context(MakeQuerySnafu)?;
+
+        let page_size = usize::try_from(page_size).unwrap_or(usize::MAX);
+        let has_more = photos.len() > page_size;
+        photos.truncate(page_size);
+
+        Ok((photos, has_more))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Photo;
+    use crate::data::testing::insert_event;
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    #[sqlx::test]
+    async fn pages_follow_upload_order(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let event = insert_event(None, &mut conn).await;
+
+        let mut uploaded = vec![];
+        for minutes_ago in (0..5).rev() {
+            uploaded.push(
+                sqlx::query_scalar!(
+                    "INSERT INTO photos (event_id, extension, created_at) VALUES ($1, 'jpg', NOW() - make_interval(mins => $2)) RETURNING id",
+                    event,
+                    minutes_ago
+                )
+                .fetch_one(&mut *conn)
+                .await
+                .unwrap(),
+            );
+        }
+
+        let mut seen: Vec<Uuid> = vec![];
+        let mut page = 0;
+        loop {
+            let (photos, has_more) = Photo::get_page_by_event_id(event, page, 2, &mut conn)
+                .await
+                .unwrap();
+            seen.extend(photos.into_iter().map(|photo| photo.id));
+            if !has_more {
+                break;
+            }
+            page += 1;
+        }
+        assert_eq!(seen, uploaded);
+
+        let (photos, has_more) = Photo::get_page_by_event_id(event, i64::MAX, 2, &mut conn)
+            .await
+            .unwrap();
+        assert!(photos.is_empty());
+        assert!(!has_more);
+    }
+
+    #[sqlx::test]
+    async fn photo_slots_count_pending_uploads(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let event = insert_event(None, &mut conn).await;
+
+        sqlx::query!("INSERT INTO photos (event_id, extension) VALUES ($1, 'jpg')", event)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        sqlx::query!(
+            "INSERT INTO pending_photo_uploads (id, event_id, extension) VALUES ($1, $2, 'png')",
+            Uuid::new_v4(),
+            event
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+        assert_eq!(Photo::lock_photo_slots(event, &mut conn).await.unwrap(), 2);
+        assert!(Photo::lock_photo_slots(Uuid::new_v4(), &mut conn).await.is_err());
+    }
 }
@@ -0,0 +1,242 @@
+use crate::{
+    auth::PermissionsTarget,
+    data::{DataType, IntIdForm},
+    error::{DenimError, DenimResult, GetDatabaseConnectionSnafu, MakeQuerySnafu},
+};
+use serde::Deserialize;
+use snafu::ResultExt;
+use sqlx::{PgConnection, Pool, Postgres};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub struct Role {
+    pub id: i32,
+    pub name: String,
+    ///set for the built-in roles that every student/staff member/admin has - these can't be deleted
+    pub kind: Option<String>,
+    pub permissions: PermissionsTarget,
+}
+
+#[derive(Deserialize)]
+pub struct NewRole {
+    pub name: String,
+}
+
+///the permissions are stored by flag name, so any which have since been removed just get ignored
+fn permissions_from_names(names: impl IntoIterator<Item = String>) -> PermissionsTarget {
+    names
+        .into_iter()
+        .filter_map(|name| PermissionsTarget::from_name(&name))
+        .collect()
+}
+
+impl Role {
+    pub const ADMIN_KIND: &str = "admin";
+    ///worked out here rather than stored, so that any flags added later reach admins without a migration
+    pub const ADMIN_PERMISSIONS: PermissionsTarget =
+        PermissionsTarget::all().difference(PermissionsTarget::SIGN_SELF_UP);
+
+    pub const fn is_built_in(&self) -> bool {
+        self.kind.is_some()
+    }
+
+    ///the permissions of each built-in role, by kind
+    pub async fn get_kind_permissions(
+        conn: &mut PgConnection,
+    ) -> DenimResult<HashMap<String, PermissionsTarget>> {
+        let mut kind_permissions: HashMap<String, PermissionsTarget> = HashMap::new();
+        for record in sqlx::query!(
+            r#"SELECT r.kind as "kind!", rp.permission as "permission?" FROM public.roles r LEFT JOIN public.role_permissions rp ON rp.role_id = r.id WHERE r.kind IS NOT NULL"#
+        )
+        .fetch_all(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        {
+            let permissions = kind_permissions.entry(record.kind).or_insert_with(PermissionsTarget::empty);
+            if let Some(permission) = record.permission {
+                *permissions |= permissions_from_names([permission]);
+            }
+        }
+        kind_permissions.insert(Self::ADMIN_KIND.to_string(), Self::ADMIN_PERMISSIONS);
+
+        Ok(kind_permissions)
+    }
+
+    ///the permissions from the extra role each user has been given, if any
+    pub async fn get_user_role_permissions(
+        user_ids: &[Uuid],
+        conn: &mut PgConnection,
+    ) -> DenimResult<HashMap<Uuid, PermissionsTarget>> {
+        let mut user_permissions: HashMap<Uuid, PermissionsTarget> = HashMap::new();
+        for record in sqlx::query!(
+            "SELECT u.id, rp.permission FROM public.users u INNER JOIN public.role_permissions rp ON rp.role_id = u.role_id WHERE u.id = ANY($1)",
+            user_ids
+        )
+        .fetch_all(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        {
+            *user_permissions.entry(record.id).or_insert_with(PermissionsTarget::empty) |=
+                permissions_from_names([record.permission]);
+        }
+
+        Ok(user_permissions)
+    }
+
+    ///case-insensitive
+    pub async fn name_is_taken(name: &str, conn: &mut PgConnection) -> DenimResult<bool> {
+        Ok(sqlx::query!(
+            r#"SELECT EXISTS(SELECT 1 FROM public.roles WHERE LOWER(name) = LOWER($1)) as "exists!""#,
+            name
+        )
+        .fetch_one(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .exists)
+    }
+
+    ///replaces all of the role's permissions, so should be done in a transaction
+    pub async fn set_permissions(
+        id: i32,
+        permissions: PermissionsTarget,
+        conn: &mut PgConnection,
+    ) -> DenimResult<()> {
+        let names: Vec<String> = permissions
+            .iter_names()
+            .map(|(name, _)| name.to_string())
+            .collect();
+
+        sqlx::query!("DELETE FROM public.role_permissions WHERE role_id = $1", id)
+            .execute(&mut *conn)
+            .await
+            .context(MakeQuerySnafu)?;
+        sqlx::query!(
+            "INSERT INTO public.role_permissions (role_id, permission) SELECT $1, UNNEST($2::text[])",
+            id,
+            &names
+        )
+        .execute(conn)
+        .await
+        .context(MakeQuerySnafu)?;
+
+        Ok(())
+    }
+}
+
+impl DataType for Role {
+    type Id = i32;
+    type FormForId = IntIdForm;
+    type FormForAdding = NewRole;
+
+    async fn get_from_db_by_id(id: Self::Id, conn: &mut PgConnection) -> DenimResult<Option<Self>> {
+        let Some(rec) = sqlx::query!("SELECT * FROM public.roles WHERE id = $1", id)
+            .fetch_optional(&mut *conn)
+            .await
+            .context(MakeQuerySnafu)?
+        else {
+            return Ok(None);
+        };
+
+        let permissions = sqlx::query!(
+            "SELECT permission FROM public.role_permissions WHERE role_id = $1",
+            id
+        )
+        .fetch_all(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .into_iter()
+        .map(|record| record.permission);
+        let permissions = if rec.kind.as_deref() == Some(Self::ADMIN_KIND) {
+            Self::ADMIN_PERMISSIONS
+        } else {
+            permissions_from_names(permissions)
+        };
+
+        Ok(Some(Self {
+            id,
+            name: rec.name,
+            kind: rec.kind,
+            permissions,
+        }))
+    }
+
+    async fn get_all(pool: &Pool<Postgres>) -> DenimResult<Vec<Self>> {
+        let mut conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
+
+        //built-in roles first
+        let ids: Vec<_> = sqlx::query!("SELECT id FROM public.roles ORDER BY kind IS NULL, name")
+            .fetch_all(&mut *conn)
+            .await
+            .context(MakeQuerySnafu)?
+            .into_iter()
+            .map(|record| record.id)
+            .collect();
+        Self::get_from_iter_of_ids(ids, &mut conn).await
+    }
+
+    async fn insert_into_database(
+        to_be_added: Self::FormForAdding,
+        conn: &mut PgConnection,
+    ) -> DenimResult<Self::Id> {
+        let NewRole { name } = to_be_added;
+
+        Ok(sqlx::query!(
+            "INSERT INTO public.roles (name) VALUES ($1) RETURNING id",
+            name
+        )
+        .fetch_one(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .id)
+    }
+
+    async fn remove_from_database(id: Self::Id, conn: &mut PgConnection) -> DenimResult<()> {
+        let rows_affected = sqlx::query!("DELETE FROM public.roles WHERE id = $1", id)
+            .execute(conn)
+            .await
+            .context(MakeQuerySnafu)?
+            .rows_affected();
+
+        if rows_affected == 0 {
+            return Err(DenimError::MissingRole { id });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Role;
+    use crate::{auth::PermissionsTarget, data::DataType};
+    use sqlx::PgPool;
+
+    #[sqlx::test]
+    async fn admins_get_every_permission_whatever_is_stored(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let admin_role = sqlx::query!("SELECT id FROM public.roles WHERE kind = 'admin'")
+            .fetch_one(&mut *conn)
+            .await
+            .unwrap()
+            .id;
+        //eg. a flag that was added after the role was seeded
+        sqlx::query!(
+            "DELETE FROM public.role_permissions WHERE role_id = $1 AND permission = 'UPLOAD_PHOTOS'",
+            admin_role
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+        let expected = PermissionsTarget::all() - PermissionsTarget::SIGN_SELF_UP;
+        let kind_permissions = Role::get_kind_permissions(&mut conn).await.unwrap();
+        assert_eq!(kind_permissions[Role::ADMIN_KIND].bits(), expected.bits());
+
+        let role = Role::get_from_db_by_id(admin_role, &mut conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(role.permissions.bits(), expected.bits());
+    }
+}
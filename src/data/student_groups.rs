@@ -1,8 +1,7 @@
 use crate::{
     data::{DataType, IdForm, IntIdForm},
-    error::{DenimResult, GetDatabaseConnectionSnafu, MakeQuerySnafu},
+    error::{DenimError, DenimResult, GetDatabaseConnectionSnafu, MakeQuerySnafu},
 };
-use futures::StreamExt;
 use serde::Deserialize;
 use snafu::ResultExt;
 use sqlx::{PgConnection, Pool, Postgres};
@@ -21,6 +20,36 @@ pub struct NewTutorGroup {
     pub house_id: i32,
 }
 
+impl TutorGroup {
+    pub async fn get_by_house(house_id: i32, conn: &mut PgConnection) -> DenimResult<Vec<Self>> {
+        Ok(sqlx::query!(
+            "SELECT * FROM public.tutor_groups WHERE house_id = $1",
+            house_id
+        )
+        .fetch_all(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .into_iter()
+        .map(|rec| Self {
+            id: rec.id,
+            staff_member: rec.staff_id,
+            house_id: rec.house_id,
+        })
+        .collect())
+    }
+
+    pub async fn count_students(id: Uuid, conn: &mut PgConnection) -> DenimResult<i64> {
+        Ok(sqlx::query!(
+            r#"SELECT COUNT(*) as "count!" FROM public.students WHERE tutor_group_id = $1"#,
+            id
+        )
+        .fetch_one(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .count)
+    }
+}
+
 impl DataType for TutorGroup {
     type Id = Uuid;
     type FormForId = IdForm;
@@ -43,15 +72,16 @@ impl DataType for TutorGroup {
     }
 
     async fn get_all(pool: &Pool<Postgres>) -> DenimResult<Vec<Self>> {
-        let mut first_conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
-        let mut second_conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
-
-        let ids = sqlx::query!("SELECT id FROM public.tutor_groups")
-            .fetch(&mut *first_conn)
-            .map(|result| result.map(|record| record.id))
-            .boxed();
+        let mut conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
 
-        Self::get_from_fetch_stream_of_ids(ids, &mut second_conn).await
+        let ids: Vec<_> = sqlx::query!("SELECT id FROM public.tutor_groups")
+            .fetch_all(&mut *conn)
+            .await
+            .context(MakeQuerySnafu)?
+            .into_iter()
+            .map(|record| record.id)
+            .collect();
+        Self::get_from_iter_of_ids(ids, &mut conn).await
     }
 
     async fn insert_into_database(
@@ -90,6 +120,53 @@ pub struct NewHouse {
     pub name: String,
 }
 
+impl HouseGroup {
+    ///returns `(tutor_groups, students)` in the house
+    pub async fn count_members(id: i32, conn: &mut PgConnection) -> DenimResult<(i64, i64)> {
+        let rec = sqlx::query!(
+            r#"SELECT (SELECT COUNT(*) FROM public.tutor_groups WHERE house_id = $1) as "tutor_groups!", (SELECT COUNT(*) FROM public.students s INNER JOIN public.tutor_groups t ON t.id = s.tutor_group_id WHERE t.house_id = $1) as "students!""#,
+            id
+        )
+        .fetch_one(conn)
+        .await
+        .context(MakeQuerySnafu)?;
+
+        Ok((rec.tutor_groups, rec.students))
+    }
+
+    ///case-insensitive, optionally ignoring one house (eg. the one being renamed)
+    pub async fn name_is_taken(
+        name: &str,
+        except: Option<i32>,
+        conn: &mut PgConnection,
+    ) -> DenimResult<bool> {
+        Ok(sqlx::query!(
+            r#"SELECT EXISTS(SELECT 1 FROM public.houses WHERE LOWER(name) = LOWER($1) AND id IS DISTINCT FROM $2) as "exists!""#,
+            name,
+            except
+        )
+        .fetch_one(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .exists)
+    }
+
+    pub async fn update_name(id: i32, name: &str, conn: &mut PgConnection) -> DenimResult<()> {
+        let rows_affected =
+            sqlx::query!("UPDATE public.houses SET name = $1 WHERE id = $2", name, id)
+                .execute(conn)
+                .await
+                .context(MakeQuerySnafu)?
+                .rows_affected();
+
+        if rows_affected == 0 {
+            return Err(DenimError::MissingHouseGroup { id });
+        }
+
+        Ok(())
+    }
+}
+
 impl DataType for HouseGroup {
     type Id = i32;
     type FormForId = IntIdForm;
@@ -0,0 +1,108 @@
+//! fixtures & helpers shared between the database tests
+
+use sqlx::PgConnection;
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::{Registry, layer::Context, prelude::*};
+use uuid::Uuid;
+
+///counts the statements sqlx logs, which it does once per query
+#[derive(Clone, Default)]
+struct QueryCounter(Arc<AtomicUsize>);
+
+impl<S: Subscriber> tracing_subscriber::Layer<S> for QueryCounter {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if event.metadata().target() == "sqlx::query" {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+///runs `fut`, returning its output along with how many queries it made
+///
+///the subscriber is thread-local, which is fine for the current-thread runtime `sqlx::test` uses
+pub async fn count_queries<T>(fut: impl Future<Output = T>) -> (T, usize) {
+    let counter = QueryCounter::default();
+    let _guard = tracing::subscriber::set_default(Registry::default().with(counter.clone()));
+    let output = fut.await;
+    (output, counter.0.load(Ordering::Relaxed))
+}
+
+pub async fn insert_user(conn: &mut PgConnection) -> Uuid {
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        "INSERT INTO public.users (id, first_name, surname, email) VALUES ($1, 'Test', 'User', $2)",
+        id,
+        format!("{id}@example.com")
+    )
+    .execute(&mut *conn)
+    .await
+    .expect("insert user");
+    id
+}
+
+pub async fn insert_house(name: &str, conn: &mut PgConnection) -> i32 {
+    sqlx::query!(
+        "INSERT INTO public.houses (name) VALUES ($1) RETURNING id",
+        name
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .expect("insert house")
+    .id
+}
+
+pub async fn insert_staff(house_id: Option<i32>, conn: &mut PgConnection) -> Uuid {
+    let id = insert_user(conn).await;
+    sqlx::query!(
+        "INSERT INTO public.staff (user_id, house_id) VALUES ($1, $2)",
+        id,
+        house_id
+    )
+    .execute(&mut *conn)
+    .await
+    .expect("insert staff");
+    id
+}
+
+///makes a new tutor group (and form tutor) in the house
+pub async fn insert_tutor_group(house_id: i32, conn: &mut PgConnection) -> Uuid {
+    let staff_id = insert_staff(Some(house_id), conn).await;
+    sqlx::query!(
+        "INSERT INTO public.tutor_groups (staff_id, house_id) VALUES ($1, $2) RETURNING id",
+        staff_id,
+        house_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .expect("insert tutor group")
+    .id
+}
+
+pub async fn insert_student(tutor_group_id: Uuid, conn: &mut PgConnection) -> Uuid {
+    let id = insert_user(conn).await;
+    sqlx::query!(
+        "INSERT INTO public.students (user_id, tutor_group_id) VALUES ($1, $2)",
+        id,
+        tutor_group_id
+    )
+    .execute(&mut *conn)
+    .await
+    .expect("insert student");
+    id
+}
+
+///an event a week from now, with an optional capacity
+pub async fn insert_event(max_capacity: Option<i32>, conn: &mut PgConnection) -> Uuid {
+    sqlx::query!(
+        "INSERT INTO public.events (name, date, tz, max_capacity) VALUES ('Test Event', (NOW() AT TIME ZONE 'UTC') + INTERVAL '7 days', 'UTC', $1) RETURNING id",
+        max_capacity
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .expect("insert event")
+    .id
+}
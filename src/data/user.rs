@@ -1,25 +1,32 @@
 use crate::{
     auth::PermissionsTarget,
     data::{
-        DataType, IdForm,
+        DataType, IdForm, SortDirection, page_offset,
+        role::Role,
         student_groups::{HouseGroup, TutorGroup},
     },
     error::{
-        BcryptSnafu, DenimResult, EmailSnafu, GetDatabaseConnectionSnafu, MakeQuerySnafu,
-        MissingHouseGroupSnafu, MissingTutorGroupSnafu,
+        BcryptSnafu, DenimError, DenimResult, EmailSnafu, GetDatabaseConnectionSnafu,
+        MakeQuerySnafu,
     },
     maud_conveniences::subtitle,
+    metrics::METRICS,
 };
 use axum_login::AuthUser;
-use bcrypt::DEFAULT_COST;
 use bitflags::bitflags;
 use email_address::EmailAddress;
-use futures::StreamExt;
+use jiff::tz::TimeZone;
 use maud::{Markup, Render, html};
 use secrecy::{ExposeSecret, SecretString};
-use snafu::{OptionExt, ResultExt};
+use serde::Deserialize;
+use snafu::ResultExt;
 use sqlx::{PgConnection, Pool, Postgres};
-use std::{fmt::Write, str::FromStr, sync::LazyLock};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    str::FromStr,
+    sync::LazyLock,
+};
 use uuid::Uuid;
 
 #[derive(Debug, Clone)]
@@ -42,8 +49,20 @@ pub struct User {
     pub surname: String,
     pub email: EmailAddress,
     pub bcrypt_hashed_password: Option<SecretString>,
+    ///the hash of their API token, if they have one
     pub access_token: Option<SecretString>,
+    pub totp_secret: Option<SecretString>,
+    ///overrides the global timezone when showing dates to this user
+    pub timezone: Option<TimeZone>,
     pub current_password_is_default: bool,
+    ///whether to email them when someone else signs them up to an event
+    pub wants_signup_emails: bool,
+    ///an extra role on top of the built-in one for their kind
+    pub role_id: Option<i32>,
+    ///from their kind's built-in role, along with their extra role if they have one
+    pub permissions: PermissionsTarget,
+    ///for staff who can only manage the students in one house
+    pub house_scope: Option<i32>,
     pub kind: UserKind,
 }
 
@@ -55,6 +74,34 @@ pub struct AddPerson {
     pub password: Option<SecretString>,
     pub current_password_is_default: bool,
     pub user_kind: AddUserKind,
+    pub bcrypt_cost: u32,
+}
+
+///what the people page can be sorted by - staff and admins don't have houses, so only ever get sorted by name
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PeopleSortColumn {
+    #[default]
+    Name,
+    House,
+}
+
+impl PeopleSortColumn {
+    pub const ALL: [Self; 2] = [Self::Name, Self::House];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::House => "house",
+        }
+    }
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Name => "Name",
+            Self::House => "House",
+        }
+    }
 }
 
 pub enum AddUserKind {
@@ -71,93 +118,28 @@ impl DataType for User {
     type FormForAdding = AddPerson;
 
     async fn get_from_db_by_id(id: Self::Id, conn: &mut PgConnection) -> DenimResult<Option<Self>> {
-        let Some(most_bits) = sqlx::query!("SELECT * FROM public.users WHERE id = $1", id)
-            .fetch_optional(&mut *conn)
-            .await
-            .context(MakeQuerySnafu)?
-        else {
-            return Ok(None);
-        };
+        Ok(Self::get_many_by_ids(&[id], conn).await?.pop())
+    }
 
-        let user_kind = if sqlx::query!("SELECT * FROM public.admins WHERE user_id = $1", id)
-            .fetch_optional(&mut *conn)
-            .await
-            .context(MakeQuerySnafu)?
-            .is_some()
-        {
-            UserKind::Admin
-        } else if sqlx::query!("SELECT * FROM public.staff WHERE user_id = $1", id)
-            .fetch_optional(&mut *conn)
-            .await
-            .context(MakeQuerySnafu)?
-            .is_some()
-        {
-            UserKind::Staff
-        } else if let Some(record) =
-            sqlx::query!("SELECT * FROM public.students WHERE user_id = $1", id)
-                .fetch_optional(&mut *conn)
-                .await
-                .context(MakeQuerySnafu)?
-        {
-            let tutor_group = Box::pin(TutorGroup::get_from_db_by_id(
-                record.tutor_group_id,
-                &mut *conn,
-            ))
-            .await?
-            .context(MissingTutorGroupSnafu {
-                id: record.tutor_group_id,
-            })?;
-
-            let house = HouseGroup::get_from_db_by_id(tutor_group.house_id, &mut *conn)
-                .await?
-                .context(MissingHouseGroupSnafu {
-                    id: tutor_group.house_id,
-                })?;
-
-            let events_participated = sqlx::query!(
-                "SELECT event_id FROM public.participation WHERE student_id = $1",
-                id
-            )
+    async fn get_from_iter_of_ids(
+        ids: impl IntoIterator<Item = Self::Id>,
+        conn: &mut PgConnection,
+    ) -> DenimResult<Vec<Self>> {
+        let ids: Vec<Uuid> = ids.into_iter().collect();
+        Self::get_many_by_ids(&ids, conn).await
+    }
+
+    async fn get_all(pool: &Pool<Postgres>) -> DenimResult<Vec<Self>> {
+        let mut conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
+
+        let ids: Vec<Uuid> = sqlx::query!("SELECT id FROM public.users")
             .fetch_all(&mut *conn)
             .await
             .context(MakeQuerySnafu)?
             .into_iter()
-            .map(|x| x.event_id)
+            .map(|record| record.id)
             .collect();
-
-            UserKind::Student {
-                tutor_group,
-                house,
-                events_participated,
-            }
-        } else {
-            UserKind::User
-        };
-
-        let email = EmailAddress::from_str(&most_bits.email).context(EmailSnafu)?;
-
-        Ok(Some(Self {
-            id,
-            first_name: most_bits.first_name,
-            pref_name: most_bits.pref_name,
-            surname: most_bits.surname,
-            email,
-            bcrypt_hashed_password: most_bits.bcrypt_hashed_password.map(SecretString::from),
-            access_token: most_bits.access_token.map(SecretString::from),
-            current_password_is_default: most_bits.current_password_is_default,
-            kind: user_kind,
-        }))
-    }
-
-    async fn get_all(pool: &Pool<Postgres>) -> DenimResult<Vec<Self>> {
-        let mut first_conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
-        let mut second_conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
-
-        let ids = sqlx::query!("SELECT id FROM public.users")
-            .fetch(&mut *first_conn)
-            .map(|result| result.map(|record| record.id))
-            .boxed();
-        Self::get_from_fetch_stream_of_ids(ids, &mut second_conn).await
+        Self::get_many_by_ids(&ids, &mut conn).await
     }
 
     async fn insert_into_database(
@@ -172,6 +154,7 @@ impl DataType for User {
             password,
             current_password_is_default,
             user_kind,
+            bcrypt_cost,
         } = to_be_added;
 
         let pref_name = if pref_name.is_empty() {
@@ -183,7 +166,7 @@ impl DataType for User {
         let bcrypt_hashed_password = if let Some(password) = password {
             Some(
                 tokio::task::spawn_blocking(move || {
-                    bcrypt::hash(password.expose_secret().as_bytes(), DEFAULT_COST)
+                    bcrypt::hash(password.expose_secret().as_bytes(), bcrypt_cost)
                 })
                 .await
                 .expect("unable to join tokio task")
@@ -221,6 +204,7 @@ impl DataType for User {
                     .context(MakeQuerySnafu)?;
             }
         }
+        METRICS.record_person_created();
 
         Ok(id)
     }
@@ -235,6 +219,17 @@ impl DataType for User {
 }
 
 impl UserKind {
+    ///which built-in role this kind gets its permissions from
+    pub const fn role_kind(&self) -> Option<&'static str> {
+        match self {
+            Self::User => None,
+            Self::Student { .. } => Some("student"),
+            Self::Staff => Some("staff"),
+            Self::Admin => Some(Role::ADMIN_KIND),
+        }
+    }
+
+    ///the defaults, which the built-in roles were seeded with - only used if a role has gone missing, apart from for admins who always get these
     pub fn get_permissions(&self) -> PermissionsTarget {
         match self {
             Self::User => {
@@ -256,89 +251,465 @@ impl UserKind {
                     | PermissionsTarget::VIEW_SENSITIVE_DETAILS
                     | PermissionsTarget::UPLOAD_PHOTOS
             }
-            Self::Admin => PermissionsTarget::all() - PermissionsTarget::SIGN_SELF_UP,
+            Self::Admin => Role::ADMIN_PERMISSIONS,
         }
     }
 }
 
 impl User {
-    pub fn get_permissions(&self) -> PermissionsTarget {
-        self.kind.get_permissions()
+    ///loads everyone in a fixed number of queries, rather than several per user
+    ///
+    ///users are returned in the same order as `ids`, skipping any that don't exist
+    #[allow(clippy::too_many_lines)]
+    pub async fn get_many_by_ids(ids: &[Uuid], conn: &mut PgConnection) -> DenimResult<Vec<Self>> {
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut base_rows: HashMap<Uuid, _> =
+            sqlx::query!("SELECT * FROM public.users WHERE id = ANY($1)", ids)
+                .fetch_all(&mut *conn)
+                .await
+                .context(MakeQuerySnafu)?
+                .into_iter()
+                .map(|record| (record.id, record))
+                .collect();
+
+        let admins: HashSet<Uuid> = sqlx::query!(
+            "SELECT user_id FROM public.admins WHERE user_id = ANY($1)",
+            ids
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .into_iter()
+        .map(|record| record.user_id)
+        .collect();
+
+        let staff: HashMap<Uuid, Option<i32>> = sqlx::query!(
+            "SELECT user_id, house_id FROM public.staff WHERE user_id = ANY($1)",
+            ids
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .into_iter()
+        .map(|record| (record.user_id, record.house_id))
+        .collect();
+
+        let mut students: HashMap<Uuid, (TutorGroup, HouseGroup)> = sqlx::query!(
+            "SELECT s.user_id, tg.id as tutor_group_id, tg.staff_id, h.id as house_id, h.name as house_name FROM public.students s INNER JOIN public.tutor_groups tg ON tg.id = s.tutor_group_id INNER JOIN public.houses h ON h.id = tg.house_id WHERE s.user_id = ANY($1)",
+            ids
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .into_iter()
+        .map(|record| {
+            (
+                record.user_id,
+                (
+                    TutorGroup {
+                        id: record.tutor_group_id,
+                        staff_member: record.staff_id,
+                        house_id: record.house_id,
+                    },
+                    HouseGroup {
+                        id: record.house_id,
+                        name: record.house_name,
+                    },
+                ),
+            )
+        })
+        .collect();
+
+        let mut participation: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+        for record in sqlx::query!(
+            "SELECT p.student_id, p.event_id FROM public.participation p INNER JOIN public.events e ON e.id = p.event_id WHERE p.student_id = ANY($1) AND e.deleted_at IS NULL",
+            ids
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .context(MakeQuerySnafu)?
+        {
+            participation
+                .entry(record.student_id)
+                .or_default()
+                .push(record.event_id);
+        }
+
+        let kind_permissions = Role::get_kind_permissions(conn).await?;
+        let mut extra_permissions = Role::get_user_role_permissions(ids, conn).await?;
+
+        let mut users = Vec::with_capacity(base_rows.len());
+        for id in ids {
+            let Some(most_bits) = base_rows.remove(id) else {
+                continue;
+            };
+
+            let kind = if admins.contains(id) {
+                UserKind::Admin
+            } else if staff.contains_key(id) {
+                UserKind::Staff
+            } else if let Some((tutor_group, house)) = students.remove(id) {
+                UserKind::Student {
+                    tutor_group,
+                    house,
+                    events_participated: participation.remove(id).unwrap_or_default(),
+                }
+            } else {
+                UserKind::User
+            };
+            let permissions = kind
+                .role_kind()
+                .and_then(|role_kind| kind_permissions.get(role_kind).copied())
+                .unwrap_or_else(|| kind.get_permissions())
+                | extra_permissions
+                    .remove(id)
+                    .unwrap_or_else(PermissionsTarget::empty);
+            let house_scope = if matches!(kind, UserKind::Staff) {
+                staff.get(id).copied().flatten()
+            } else {
+                None
+            };
+
+            users.push(Self {
+                id: *id,
+                first_name: most_bits.first_name,
+                pref_name: most_bits.pref_name,
+                surname: most_bits.surname,
+                email: EmailAddress::from_str(&most_bits.email).context(EmailSnafu)?,
+                bcrypt_hashed_password: most_bits.bcrypt_hashed_password.map(SecretString::from),
+                access_token: most_bits.access_token.map(SecretString::from),
+                totp_secret: most_bits.totp_secret.map(SecretString::from),
+                timezone: most_bits.timezone.and_then(|tz| TimeZone::get(&tz).ok()),
+                current_password_is_default: most_bits.current_password_is_default,
+                wants_signup_emails: most_bits.wants_signup_emails,
+                role_id: most_bits.role_id,
+                permissions,
+                house_scope,
+                kind,
+            });
+        }
+
+        Ok(users)
     }
 
-    pub async fn get_all_staff(pool: &Pool<Postgres>) -> DenimResult<Vec<Self>> {
-        let mut first_conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
-        let mut second_conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
+    ///optionally ignoring one user (eg. the one changing their email)
+    pub async fn email_is_taken(
+        email: &EmailAddress,
+        except: Option<Uuid>,
+        conn: &mut PgConnection,
+    ) -> DenimResult<bool> {
+        Ok(sqlx::query!(
+            r#"SELECT EXISTS(SELECT 1 FROM public.users WHERE email = $1 AND id IS DISTINCT FROM $2) as "exists!""#,
+            email.as_str(),
+            except
+        )
+        .fetch_one(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .exists)
+    }
 
-        let ids = sqlx::query!("SELECT user_id FROM public.staff")
-            .fetch(&mut *first_conn)
-            .map(|result| result.map(|record| record.user_id))
-            .boxed();
-        Self::get_from_fetch_stream_of_ids(ids, &mut second_conn).await
+    ///`None` turns two-factor authentication off
+    pub async fn set_totp_secret(
+        id: Uuid,
+        secret: Option<&SecretString>,
+        conn: &mut PgConnection,
+    ) -> DenimResult<()> {
+        sqlx::query!(
+            "UPDATE public.users SET totp_secret = $1, totp_last_step = NULL WHERE id = $2",
+            secret.map(ExposeSecret::expose_secret),
+            id
+        )
+        .execute(conn)
+        .await
+        .context(MakeQuerySnafu)?;
+
+        Ok(())
     }
 
-    pub async fn get_all_staff_with_filter(
-        pool: &Pool<Postgres>,
-        filter: &str,
-    ) -> DenimResult<Vec<Self>> {
-        let mut first_conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
-        let mut second_conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
+    ///marks the TOTP code for `step` as used, returning `false` if that step (or a later one) already has been
+    pub async fn use_totp_step(id: Uuid, step: i64, conn: &mut PgConnection) -> DenimResult<bool> {
+        Ok(sqlx::query!(
+            "UPDATE public.users SET totp_last_step = $2 WHERE id = $1 AND (totp_last_step IS NULL OR totp_last_step < $2)",
+            id,
+            step
+        )
+        .execute(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .rows_affected()
+            > 0)
+    }
+
+    ///takes the hash of the token, which is all that gets stored
+    pub async fn set_api_token_hash(
+        id: Uuid,
+        hash: Option<&str>,
+        conn: &mut PgConnection,
+    ) -> DenimResult<()> {
+        sqlx::query!(
+            "UPDATE public.users SET access_token = $1 WHERE id = $2",
+            hash,
+            id
+        )
+        .execute(conn)
+        .await
+        .context(MakeQuerySnafu)?;
+
+        Ok(())
+    }
+
+    ///`None` goes back to using the global timezone
+    pub async fn set_timezone(
+        id: Uuid,
+        timezone: Option<&TimeZone>,
+        conn: &mut PgConnection,
+    ) -> DenimResult<()> {
+        sqlx::query!(
+            "UPDATE public.users SET timezone = $1 WHERE id = $2",
+            timezone.and_then(TimeZone::iana_name),
+            id
+        )
+        .execute(conn)
+        .await
+        .context(MakeQuerySnafu)?;
 
-        let ids = sqlx::query!("SELECT s.user_id FROM public.staff s INNER JOIN public.users u ON u.id = s.user_id WHERE LOWER(coalesce(u.pref_name, u.first_name) || ' ' || u.surname) ~ $1", filter)
-            .fetch(&mut *first_conn)
-            .map(|result| result.map(|record| record.user_id))
-            .boxed();
-        Self::get_from_fetch_stream_of_ids(ids, &mut second_conn).await
+        Ok(())
+    }
+
+    pub async fn set_wants_signup_emails(
+        id: Uuid,
+        wants_signup_emails: bool,
+        conn: &mut PgConnection,
+    ) -> DenimResult<()> {
+        sqlx::query!(
+            "UPDATE public.users SET wants_signup_emails = $1 WHERE id = $2",
+            wants_signup_emails,
+            id
+        )
+        .execute(conn)
+        .await
+        .context(MakeQuerySnafu)?;
+
+        Ok(())
+    }
+
+    pub const fn get_permissions(&self) -> PermissionsTarget {
+        self.permissions
+    }
+
+    ///the house they're in, if they're a student
+    pub const fn house_id(&self) -> Option<i32> {
+        match &self.kind {
+            UserKind::Student { house, .. } => Some(house.id),
+            _ => None,
+        }
+    }
+
+    ///the houses the students in `ids` are in - anyone who isn't a student gets skipped
+    pub async fn get_student_houses(
+        ids: &[Uuid],
+        conn: &mut PgConnection,
+    ) -> DenimResult<Vec<i32>> {
+        Ok(sqlx::query!("SELECT DISTINCT tg.house_id FROM public.students s INNER JOIN public.tutor_groups tg ON tg.id = s.tutor_group_id WHERE s.user_id = ANY($1)", ids)
+            .fetch_all(conn)
+            .await
+            .context(MakeQuerySnafu)?
+            .into_iter()
+            .map(|record| record.house_id)
+            .collect())
+    }
+
+    pub async fn set_house_scope(
+        id: Uuid,
+        house_id: Option<i32>,
+        conn: &mut PgConnection,
+    ) -> DenimResult<()> {
+        let rows_affected = sqlx::query!(
+            "UPDATE public.staff SET house_id = $1 WHERE user_id = $2",
+            house_id,
+            id
+        )
+        .execute(conn)
+        .await
+        .context(MakeQuerySnafu)?
+        .rows_affected();
+
+        if rows_affected == 0 {
+            return Err(DenimError::MissingUser { id });
+        }
+
+        Ok(())
+    }
+
+    pub async fn set_role(
+        id: Uuid,
+        role_id: Option<i32>,
+        conn: &mut PgConnection,
+    ) -> DenimResult<()> {
+        sqlx::query!(
+            "UPDATE public.users SET role_id = $1 WHERE id = $2",
+            role_id,
+            id
+        )
+        .execute(conn)
+        .await
+        .context(MakeQuerySnafu)?;
+
+        Ok(())
+    }
+
+    pub async fn get_all_staff(pool: &Pool<Postgres>) -> DenimResult<Vec<Self>> {
+        let mut conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
+
+        let ids: Vec<Uuid> = sqlx::query!("SELECT user_id FROM public.staff")
+            .fetch_all(&mut *conn)
+            .await
+            .context(MakeQuerySnafu)?
+            .into_iter()
+            .map(|record| record.user_id)
+            .collect();
+        Self::get_many_by_ids(&ids, &mut conn).await
     }
 
     pub async fn get_all_students(pool: &Pool<Postgres>) -> DenimResult<Vec<Self>> {
-        let mut first_conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
-        let mut second_conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
+        let mut conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
 
-        let ids = sqlx::query!("SELECT user_id FROM public.students")
-            .fetch(&mut *first_conn)
-            .map(|result| result.map(|record| record.user_id))
-            .boxed();
-        Self::get_from_fetch_stream_of_ids(ids, &mut second_conn).await
+        let ids: Vec<Uuid> = sqlx::query!("SELECT user_id FROM public.students")
+            .fetch_all(&mut *conn)
+            .await
+            .context(MakeQuerySnafu)?
+            .into_iter()
+            .map(|record| record.user_id)
+            .collect();
+        Self::get_many_by_ids(&ids, &mut conn).await
     }
 
+    ///students whose name contains `filter` (case-insensitively), optionally skipping those already signed up to an event
+    ///`house_id` limits it to the students in that house
     pub async fn get_all_students_with_filter(
         pool: &Pool<Postgres>,
         filter: &str,
+        not_signed_up_to: Option<Uuid>,
+        house_id: Option<i32>,
     ) -> DenimResult<Vec<Self>> {
-        let mut first_conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
-        let mut second_conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
+        let mut conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
 
-        let ids = sqlx::query!("SELECT s.user_id FROM public.students s INNER JOIN public.users u ON u.id = s.user_id WHERE LOWER(coalesce(u.pref_name, u.first_name) || ' ' || u.surname) ~ $1", filter)
-            .fetch(&mut *first_conn)
-            .map(|result| result.map(|record| record.user_id))
-            .boxed();
-        Self::get_from_fetch_stream_of_ids(ids, &mut second_conn).await
+        let ids: Vec<Uuid> = sqlx::query!("SELECT s.user_id FROM public.students s INNER JOIN public.users u ON u.id = s.user_id INNER JOIN public.tutor_groups tg ON tg.id = s.tutor_group_id WHERE strpos(lower(coalesce(u.pref_name, u.first_name) || ' ' || u.surname), lower($1)) > 0 AND NOT EXISTS(SELECT 1 FROM public.participation p WHERE p.student_id = s.user_id AND p.event_id = $2) AND ($3::int IS NULL OR tg.house_id = $3) ORDER BY u.surname, u.first_name, u.id", filter, not_signed_up_to, house_id)
+            .fetch_all(&mut *conn)
+            .await
+            .context(MakeQuerySnafu)?
+            .into_iter()
+            .map(|record| record.user_id)
+            .collect();
+        Self::get_many_by_ids(&ids, &mut conn).await
     }
 
-    pub async fn get_all_admins(pool: &Pool<Postgres>) -> DenimResult<Vec<Self>> {
-        let mut first_conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
-        let mut second_conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
+    pub async fn get_students_by_house(
+        house_id: i32,
+        conn: &mut PgConnection,
+    ) -> DenimResult<Vec<Self>> {
+        let ids: Vec<Uuid> = sqlx::query!("SELECT s.user_id FROM public.students s INNER JOIN public.tutor_groups tg ON tg.id = s.tutor_group_id INNER JOIN public.users u ON u.id = s.user_id WHERE tg.house_id = $1 ORDER BY u.surname, u.first_name, u.id", house_id)
+            .fetch_all(&mut *conn)
+            .await
+            .context(MakeQuerySnafu)?
+            .into_iter()
+            .map(|record| record.user_id)
+            .collect();
+        Self::get_many_by_ids(&ids, conn).await
+    }
 
-        let ids = sqlx::query!("SELECT user_id FROM public.admins")
-            .fetch(&mut *first_conn)
-            .map(|result| result.map(|record| record.user_id))
-            .boxed();
-        Self::get_from_fetch_stream_of_ids(ids, &mut second_conn).await
+    pub async fn get_students_by_tutor_group(
+        tutor_group_id: Uuid,
+        conn: &mut PgConnection,
+    ) -> DenimResult<Vec<Self>> {
+        let ids: Vec<Uuid> = sqlx::query!("SELECT s.user_id FROM public.students s INNER JOIN public.users u ON u.id = s.user_id WHERE s.tutor_group_id = $1 ORDER BY u.surname, u.first_name, u.id", tutor_group_id)
+            .fetch_all(&mut *conn)
+            .await
+            .context(MakeQuerySnafu)?
+            .into_iter()
+            .map(|record| record.user_id)
+            .collect();
+        Self::get_many_by_ids(&ids, conn).await
     }
 
-    pub async fn get_all_admins_with_filter(
+    ///one page of people, ordered by surname (reversed if `sort_direction` is descending), optionally only those whose name contains `filter`
+    ///
+    ///the bool is whether there's at least one more page after this one
+    pub async fn get_staff_page(
         pool: &Pool<Postgres>,
-        filter: &str,
-    ) -> DenimResult<Vec<Self>> {
-        let mut first_conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
-        let mut second_conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
+        filter: Option<&str>,
+        page: i64,
+        page_size: i64,
+        sort_direction: SortDirection,
+    ) -> DenimResult<(Vec<Self>, bool)> {
+        let mut conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
+
+        let ids: Vec<Uuid> = sqlx::query!("SELECT s.user_id FROM public.staff s INNER JOIN public.users u ON u.id = s.user_id WHERE $1::text IS NULL OR strpos(lower(coalesce(u.pref_name, u.first_name) || ' ' || u.surname), lower($1)) > 0 ORDER BY CASE WHEN $4 THEN u.surname END DESC, CASE WHEN $4 THEN u.first_name END DESC, u.surname, u.first_name, u.id LIMIT $2 OFFSET $3", filter, page_size + 1, page_offset(page, page_size), sort_direction.is_descending())
+            .fetch_all(&mut *conn)
+            .await
+            .context(MakeQuerySnafu)?
+            .into_iter()
+            .map(|record| record.user_id)
+            .collect();
+        Self::get_page_by_ids(ids, page_size, &mut conn).await
+    }
+
+    ///`house_id` limits it to the students in that house
+    pub async fn get_students_page(
+        pool: &Pool<Postgres>,
+        filter: Option<&str>,
+        page: i64,
+        page_size: i64,
+        sort: PeopleSortColumn,
+        sort_direction: SortDirection,
+        house_id: Option<i32>,
+    ) -> DenimResult<(Vec<Self>, bool)> {
+        let mut conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
+
+        let ids: Vec<Uuid> = sqlx::query!("SELECT s.user_id FROM public.students s INNER JOIN public.users u ON u.id = s.user_id INNER JOIN public.tutor_groups tg ON tg.id = s.tutor_group_id INNER JOIN public.houses h ON h.id = tg.house_id WHERE ($1::text IS NULL OR strpos(lower(coalesce(u.pref_name, u.first_name) || ' ' || u.surname), lower($1)) > 0) AND ($6::int IS NULL OR h.id = $6) ORDER BY CASE WHEN $4::text = 'house' AND NOT $5 THEN h.name END, CASE WHEN $4::text = 'house' AND $5 THEN h.name END DESC, CASE WHEN $4::text = 'name' AND $5 THEN u.surname END DESC, CASE WHEN $4::text = 'name' AND $5 THEN u.first_name END DESC, u.surname, u.first_name, u.id LIMIT $2 OFFSET $3", filter, page_size + 1, page_offset(page, page_size), sort.as_str(), sort_direction.is_descending(), house_id)
+            .fetch_all(&mut *conn)
+            .await
+            .context(MakeQuerySnafu)?
+            .into_iter()
+            .map(|record| record.user_id)
+            .collect();
+        Self::get_page_by_ids(ids, page_size, &mut conn).await
+    }
+
+    pub async fn get_admins_page(
+        pool: &Pool<Postgres>,
+        filter: Option<&str>,
+        page: i64,
+        page_size: i64,
+        sort_direction: SortDirection,
+    ) -> DenimResult<(Vec<Self>, bool)> {
+        let mut conn = pool.acquire().await.context(GetDatabaseConnectionSnafu)?;
+
+        let ids: Vec<Uuid> = sqlx::query!("SELECT a.user_id FROM public.admins a INNER JOIN public.users u ON u.id = a.user_id WHERE $1::text IS NULL OR strpos(lower(coalesce(u.pref_name, u.first_name) || ' ' || u.surname), lower($1)) > 0 ORDER BY CASE WHEN $4 THEN u.surname END DESC, CASE WHEN $4 THEN u.first_name END DESC, u.surname, u.first_name, u.id LIMIT $2 OFFSET $3", filter, page_size + 1, page_offset(page, page_size), sort_direction.is_descending())
+            .fetch_all(&mut *conn)
+            .await
+            .context(MakeQuerySnafu)?
+            .into_iter()
+            .map(|record| record.user_id)
+            .collect();
+        Self::get_page_by_ids(ids, page_size, &mut conn).await
+    }
+
+    ///the page queries ask for one extra row, just to see whether there's another page
+    async fn get_page_by_ids(
+        mut ids: Vec<Uuid>,
+        page_size: i64,
+        conn: &mut PgConnection,
+    ) -> DenimResult<(Vec<Self>, bool)> {
+        let page_size = usize::try_from(page_size).unwrap_or(usize::MAX);
+        let has_more = ids.len() > page_size;
+        ids.truncate(page_size);
 
-        let ids = sqlx::query!("SELECT a.user_id FROM public.admins a INNER JOIN public.users u ON u.id = a.user_id WHERE LOWER(coalesce(u.pref_name, u.first_name) || ' ' || u.surname) ~ $1", filter)
-            .fetch(&mut *first_conn)
-            .map(|result| result.map(|record| record.user_id))
-            .boxed();
-        Self::get_from_fetch_stream_of_ids(ids, &mut second_conn).await
+        Ok((Self::get_many_by_ids(&ids, conn).await?, has_more))
     }
 }
 
@@ -421,14 +792,154 @@ impl AuthUser for User {
         static EMPTY_SECRET_STRING: LazyLock<SecretString> =
             LazyLock::new(|| SecretString::from(""));
 
-        self.access_token
+        //not the API token, as making a new one shouldn't log everyone out
+        self.bcrypt_hashed_password
             .as_ref()
-            .unwrap_or_else(|| {
-                self.bcrypt_hashed_password
-                    .as_ref()
-                    .unwrap_or(&EMPTY_SECRET_STRING)
-            })
+            .unwrap_or(&EMPTY_SECRET_STRING)
             .expose_secret()
             .as_bytes()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{User, UserKind};
+    use crate::{
+        auth::PermissionsTarget,
+        data::{
+            DataType, SortDirection,
+            role::{NewRole, Role},
+            testing::{
+                count_queries, insert_house, insert_staff, insert_student, insert_tutor_group,
+            },
+        },
+    };
+    use sqlx::PgPool;
+
+    #[sqlx::test]
+    async fn get_many_by_ids_query_count_does_not_grow(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let house = insert_house("Test House", &mut conn).await;
+        let tutor_group = insert_tutor_group(house, &mut conn).await;
+
+        let mut ids = vec![];
+        for _ in 0..10 {
+            ids.push(insert_student(tutor_group, &mut conn).await);
+            ids.push(insert_staff(Some(house), &mut conn).await);
+        }
+
+        let (one, one_queries) = count_queries(User::get_many_by_ids(&ids[..1], &mut conn)).await;
+        let (all, all_queries) = count_queries(User::get_many_by_ids(&ids, &mut conn)).await;
+
+        assert_eq!(one.unwrap().len(), 1);
+        let all = all.unwrap();
+        assert_eq!(all.iter().map(|user| user.id).collect::<Vec<_>>(), ids);
+        assert!(matches!(all[0].kind, UserKind::Student { .. }));
+        assert!(matches!(all[1].kind, UserKind::Staff));
+
+        assert!(one_queries > 0);
+        assert_eq!(one_queries, all_queries);
+    }
+
+    ///permissions get worked out when axum-login loads the user at the start of a request, so checking them is free
+    #[sqlx::test]
+    async fn permissions_are_only_evaluated_when_loading(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let house = insert_house("Test House", &mut conn).await;
+        let tutor_group = insert_tutor_group(house, &mut conn).await;
+        let student = insert_student(tutor_group, &mut conn).await;
+
+        let role = Role::insert_into_database(
+            NewRole {
+                name: "Photographer".into(),
+            },
+            &mut conn,
+        )
+        .await
+        .unwrap();
+        Role::set_permissions(role, PermissionsTarget::UPLOAD_PHOTOS, &mut conn)
+            .await
+            .unwrap();
+        User::set_role(student, Some(role), &mut conn)
+            .await
+            .unwrap();
+
+        let (user, load_queries) = count_queries(User::get_from_db_by_id(student, &mut conn)).await;
+        let user = user.unwrap().unwrap();
+        assert!(load_queries > 0);
+
+        //roughly what rendering the events list does for each row
+        let (checks, check_queries) = count_queries(async {
+            (0..100)
+                .filter(|_| {
+                    user.get_permissions().contains(
+                        PermissionsTarget::SIGN_SELF_UP | PermissionsTarget::UPLOAD_PHOTOS,
+                    )
+                })
+                .count()
+        })
+        .await;
+        assert_eq!(checks, 100);
+        assert_eq!(check_queries, 0);
+    }
+
+    #[sqlx::test]
+    async fn name_filters_match_wildcards_literally(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let literal = insert_staff(None, &mut conn).await;
+        let lookalike = insert_staff(None, &mut conn).await;
+        sqlx::query!(
+            "UPDATE public.users SET first_name = '100%', surname = 'a_b' WHERE id = $1",
+            literal
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        sqlx::query!(
+            "UPDATE public.users SET first_name = '1000', surname = 'axb' WHERE id = $1",
+            lookalike
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        drop(conn);
+
+        for filter in ["100%", "A_B"] {
+            let (staff, _) = User::get_staff_page(&pool, Some(filter), 0, 10, SortDirection::Asc)
+                .await
+                .unwrap();
+            let ids: Vec<_> = staff.into_iter().map(|user| user.id).collect();
+            assert_eq!(ids, [literal], "{filter}");
+        }
+    }
+
+    #[sqlx::test]
+    async fn sign_up_search_matches_wildcards_literally(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let house = insert_house("Test House", &mut conn).await;
+        let tutor_group = insert_tutor_group(house, &mut conn).await;
+        let literal = insert_student(tutor_group, &mut conn).await;
+        let lookalike = insert_student(tutor_group, &mut conn).await;
+        sqlx::query!(
+            "UPDATE public.users SET surname = 'a_b' WHERE id = $1",
+            literal
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        sqlx::query!(
+            "UPDATE public.users SET surname = 'axb' WHERE id = $1",
+            lookalike
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        drop(conn);
+
+        let students = User::get_all_students_with_filter(&pool, "A_B", None, None)
+            .await
+            .unwrap();
+        let ids: Vec<_> = students.into_iter().map(|user| user.id).collect();
+        assert_eq!(ids, [literal]);
+    }
+}
@@ -1,16 +1,20 @@
 use crate::{
     auth::{PermissionsTarget, backend::DenimAuthBackend},
     config::important_item::ImportantItemTy,
+    request_id::current_request_id,
 };
 use axum::{
-    http::StatusCode,
+    Json,
+    extract::Request,
+    http::{StatusCode, header},
+    middleware::Next,
     response::{IntoResponse, Response},
 };
 use icu::datetime::DateTimeFormatterLoadError;
 use maud::html;
 use rand::{Rng, rng};
 use snafu::Snafu;
-use std::num::ParseIntError;
+use std::{net::AddrParseError, num::ParseIntError};
 use uuid::Uuid;
 
 pub type DenimResult<T> = Result<T, DenimError>;
@@ -41,11 +45,37 @@ pub enum DenimError {
     },
     #[snafu(display("Unable to parse IP port"))]
     ParsePort { source: ParseIntError },
+    #[snafu(display(
+        "Unable to parse server address {address:?} - expected an IP with an optional port, eg. `127.0.0.1:8080` or `[::]`"
+    ))]
+    ParseServerAddress {
+        source: AddrParseError,
+        address: String,
+    },
+    #[snafu(display("Unable to parse capacity {:?}", original))]
+    ParseCapacity {
+        source: ParseIntError,
+        original: String,
+    },
+    #[snafu(display("Invalid repeat settings: {}", reason))]
+    BadRecurrence { reason: &'static str },
     #[snafu(display("Unable to parse date {:?}", original))]
     ParseTime {
         source: jiff::Error,
         original: String,
     },
+    #[snafu(display("Unable to parse date {original:?} - expected something like {example:?}"))]
+    ParseImportTime {
+        source: jiff::Error,
+        original: String,
+        example: &'static str,
+    },
+    #[snafu(display("Unable to read spreadsheet"))]
+    Spreadsheet { source: calamine::XlsxError },
+    #[snafu(display(
+        "Spreadsheets need exactly one sheet to import from, but this one has {sheets}"
+    ))]
+    SpreadsheetSheets { sheets: usize },
     #[snafu(display("Unable to parse uuid {:?}", original))]
     ParseUuid {
         source: uuid::Error,
@@ -53,18 +83,57 @@ pub enum DenimError {
     },
     #[snafu(display("Unable to find event with UUID: {}", id))]
     MissingEvent { id: Uuid },
+    #[snafu(display("Event {} has already happened, so nobody new can sign up to it", id))]
+    EventFinished { id: Uuid },
     #[snafu(display("Unable to find user with UUID: {}", id))]
     MissingUser { id: Uuid },
     #[snafu(display("Unable to find house with ID: {}", id))]
     MissingHouseGroup { id: i32 },
     #[snafu(display("Unable to find tutor group with UUID: {}", id))]
     MissingTutorGroup { id: Uuid },
+    #[snafu(display(
+        "Unable to delete house {:?} - it still has {} tutor group(s) and {} student(s)",
+        name,
+        tutor_groups,
+        students
+    ))]
+    HouseStillInUse {
+        name: String,
+        tutor_groups: i64,
+        students: i64,
+    },
+    #[snafu(display(
+        "Unable to delete tutor group with UUID {} - it still has {} student(s)",
+        id,
+        students
+    ))]
+    TutorGroupStillInUse { id: Uuid, students: i64 },
+    #[snafu(display("Unable to find role with ID: {}", id))]
+    MissingRole { id: i32 },
+    #[snafu(display("Unable to parse house ID {:?}", original))]
+    ParseHouseId {
+        source: ParseIntError,
+        original: String,
+    },
+    #[snafu(display("Unable to parse role ID {:?}", original))]
+    ParseRoleId {
+        source: ParseIntError,
+        original: String,
+    },
+    #[snafu(display("Unable to {} the built-in {:?} role", action, name))]
+    BuiltInRole { name: String, action: &'static str },
     #[snafu(display("Error with hashing/password verification"))]
     Bcrypt { source: bcrypt::BcryptError },
     #[snafu(display("Error with sessions"))]
     TowerSession {
         source: axum_login::tower_sessions::session::Error,
     },
+    #[snafu(display("Missing or incorrect CSRF token - try refreshing the page"))]
+    CsrfMismatch,
+    #[snafu(display("Unable to read request body to check CSRF token"))]
+    CsrfBody { source: axum::Error },
+    #[snafu(display("Missing or invalid API token"))]
+    InvalidApiToken,
     #[snafu(display("Unable to generate password"))]
     GeneratePassword,
     #[snafu(display(
@@ -76,6 +145,11 @@ pub enum DenimError {
         needed: PermissionsTarget,
         found: PermissionsTarget,
     },
+    #[snafu(display(
+        "Tried to act outside of house {}, which is the only one they can manage",
+        scope
+    ))]
+    OutsideHouseScope { scope: i32 },
     #[snafu(display(
         "Tried to get the new student form, but no houses and/or no tutor groups existed to add them into"
     ))]
@@ -120,10 +194,33 @@ pub enum DenimError {
         datatype_name
     ))]
     TransactionMustBeUsed { datatype_name: &'static str },
-    #[snafu(display("Invalid Image uploaded of mime type: {:?} - should be an image type", found_mime))]
-    InvalidImage {
-        found_mime: Option<&'static str>
-    }
+    #[snafu(display("Error sending email via SMTP"))]
+    Smtp {
+        source: lettre::transport::smtp::Error,
+    },
+    #[snafu(display("Error building email"))]
+    BuildEmail { source: lettre::error::Error },
+    #[snafu(display("Error parsing email mailbox"))]
+    ParseMailbox {
+        source: lettre::address::AddressError,
+    },
+    #[snafu(display(
+        "Invalid Image uploaded of mime type: {:?} - should be an image type",
+        found_mime
+    ))]
+    InvalidImage { found_mime: Option<&'static str> },
+    #[snafu(display("Photo is too large - the maximum size is {max_bytes} bytes"))]
+    PhotoTooLarge { max_bytes: usize },
+    #[snafu(display("Too many photos - events can have at most {max} photos"))]
+    TooManyPhotos { max: i64 },
+    #[snafu(display("Invalid TOTP secret stored"))]
+    TotpSecret { source: totp_rs::SecretParseError },
+    #[snafu(display("Unable to create TOTP"))]
+    TotpUrl { source: totp_rs::TotpUrlError },
+    #[snafu(display("Unable to create TOTP QR code: {message}"))]
+    TotpQr { message: String },
+    #[snafu(display("System time is before the UNIX epoch"))]
+    SystemTime { source: std::time::SystemTimeError },
 }
 
 impl From<axum_login::Error<DenimAuthBackend>> for DenimError {
@@ -166,6 +263,9 @@ impl IntoResponse for DenimError {
                     img src={(url) (status_code.as_u16()) ".jpg"} class="";
                     br;
                     p class="text-italic" {"Please contact your admin."}
+                    @if let Some(request_id) = current_request_id() {
+                        p class="text-xs" {"Request ID: " (request_id)}
+                    }
                 }
             }
         };
@@ -182,17 +282,34 @@ impl IntoResponse for DenimError {
             Self::RmpSerdeDecode { .. } => BI,
             Self::BadEnvVar { .. } => ISE,
             Self::ParsePort { .. } => ISE,
+            Self::ParseServerAddress { .. } => ISE,
+            Self::ParseCapacity { .. } => BI,
+            Self::BadRecurrence { .. } => BI,
             Self::ParseTime { .. } => BI,
+            Self::ParseImportTime { .. } => BI,
+            Self::Spreadsheet { .. } => BI,
+            Self::SpreadsheetSheets { .. } => BI,
             Self::ParseUuid { .. } => BI,
             Self::MissingEvent { .. } => NF,
+            Self::EventFinished { .. } => BI,
             Self::MissingUser { .. } => NF,
             Self::MissingHouseGroup { .. } => NF,
             Self::MissingTutorGroup { .. } => NF,
+            Self::HouseStillInUse { .. } => BI,
+            Self::TutorGroupStillInUse { .. } => BI,
+            Self::MissingRole { .. } => NF,
+            Self::ParseRoleId { .. } => BI,
+            Self::ParseHouseId { .. } => BI,
+            Self::BuiltInRole { .. } => BI,
             Self::Bcrypt { .. } => ISE,
             Self::TowerSession { .. } => ISE,
+            Self::CsrfMismatch => NA,
+            Self::CsrfBody { .. } => BI,
+            Self::InvalidApiToken => StatusCode::UNAUTHORIZED,
             Self::GeneratePassword => ISE,
             Self::UnableToFindUserInfo => NF,
             Self::IncorrectPermissions { .. } => NA,
+            Self::OutsideHouseScope { .. } => NA,
             Self::NoHousesOrNoTutorGroups => ISE,
             Self::Multipart { source } => source.status(),
             Self::Email { .. } => ISE,
@@ -209,10 +326,52 @@ impl IntoResponse for DenimError {
             Self::InvalidLocale { .. } => BI,
             Self::TransactionMustBeUsed { .. } => ISE,
             Self::InvalidImage { .. } => BI,
+            Self::PhotoTooLarge { .. } | Self::TooManyPhotos { .. } => BI,
+            Self::Smtp { .. } | Self::BuildEmail { .. } => ISE,
+            Self::ParseMailbox { .. } => BI,
+            Self::TotpSecret { .. } | Self::TotpUrl { .. } | Self::TotpQr { .. } => ISE,
+            Self::SystemTime { .. } => ISE,
         };
 
         //painfully, has to return a 200 OK to get by with htmx, smh
-        error!(?self, "Error!");
-        basic_error(status_code, self.to_string()).into_response()
+        //the real status gets stashed away for `negotiate_error_response` to use for anyone else
+        let request_id = current_request_id();
+        error!(?self, ?request_id, "Error!");
+        let message = self.to_string();
+        let mut response = basic_error(status_code, &message).into_response();
+        response.extensions_mut().insert(ErrorDetails {
+            status_code,
+            message,
+        });
+        response
+    }
+}
+
+#[derive(Clone)]
+struct ErrorDetails {
+    status_code: StatusCode,
+    message: String,
+}
+
+///htmx & browsers get the friendly HTML error page, but everything else (API consumers, monitoring etc) gets the real status code with a JSON body
+pub async fn negotiate_error_response(request: Request, next: Next) -> Response {
+    let is_htmx = request.headers().contains_key("HX-Request");
+    let wants_html = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"));
+
+    let response = next.run(request).await;
+    if is_htmx || wants_html {
+        return response;
+    }
+
+    match response.extensions().get::<ErrorDetails>() {
+        Some(ErrorDetails {
+            status_code,
+            message,
+        }) => (*status_code, Json(serde_json::json!({ "error": message }))).into_response(),
+        None => response,
     }
 }
@@ -0,0 +1,57 @@
+use crate::error::{BadEnvVarSnafu, BuildEmailSnafu, DenimResult, ParseMailboxSnafu, SmtpSnafu};
+use async_trait::async_trait;
+use dotenvy::var;
+use email_address::EmailAddress;
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor, message::Mailbox,
+    transport::smtp::authentication::Credentials,
+};
+use snafu::ResultExt;
+
+#[async_trait]
+pub trait Mailer: Send + Sync + std::fmt::Debug {
+    async fn send(&self, to: &EmailAddress, subject: &str, body: String) -> DenimResult<()>;
+}
+
+#[derive(Debug)]
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    ///returns `Ok(None)` if `SMTP_HOST` isn't set, as email is optional
+    pub fn from_env() -> DenimResult<Option<Self>> {
+        let Ok(host) = var("SMTP_HOST") else {
+            return Ok(None);
+        };
+        let get_env_var = |name| var(name).context(BadEnvVarSnafu { name });
+
+        let credentials =
+            Credentials::new(get_env_var("SMTP_USERNAME")?, get_env_var("SMTP_PASSWORD")?);
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::relay(&host)
+            .context(SmtpSnafu)?
+            .credentials(credentials)
+            .build();
+        let from = get_env_var("SMTP_FROM")?
+            .parse()
+            .context(ParseMailboxSnafu)?;
+
+        Ok(Some(Self { transport, from }))
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, to: &EmailAddress, subject: &str, body: String) -> DenimResult<()> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to.as_str().parse().context(ParseMailboxSnafu)?)
+            .subject(subject)
+            .body(body)
+            .context(BuildEmailSnafu)?;
+
+        self.transport.send(message).await.context(SmtpSnafu)?;
+        Ok(())
+    }
+}
@@ -2,62 +2,112 @@
 #![allow(clippy::single_match_else)]
 
 use crate::{
-    auth::{backend::DenimAuthBackend, postgres_store::PostgresSessionStore},
+    auth::{
+        backend::DenimAuthBackend, csrf::csrf_protection, postgres_store::PostgresSessionStore,
+    },
     config::RuntimeConfiguration,
+    error::negotiate_error_response,
+    metrics::{get_metrics, track_request_latency},
+    request_id::{assign_request_id, make_request_span, record_user_id},
     routes::{
         all_events::{
-            delete_event, get_events, internal_get_add_events_form, internal_get_event_in_detail,
-            internal_get_events, put_new_event,
+            delete_event, get_events, get_events_ics, get_my_events, get_my_signups,
+            internal_get_add_events_form, internal_get_delete_event_confirm,
+            internal_get_edit_event_form, internal_get_event_in_detail, internal_get_events,
+            internal_post_duplicate_event, internal_post_restore_event, put_edit_event,
+            put_new_event,
         },
         all_people::{
-            delete_person, get_people, internal_get_add_dev_or_staff_form,
-            internal_get_add_student_form, internal_get_people, internal_get_person_in_detail,
+            delete_people_bulk, delete_person, get_people, internal_get_add_dev_or_staff_form,
+            internal_get_add_student_form, internal_get_delete_person_confirm, internal_get_people,
+            internal_get_person_in_detail, internal_post_person_reset_password,
             internal_put_new_staff_or_dev, internal_put_new_student,
+            internal_put_person_house_scope,
         },
+        api::{delete_api_event, get_api_event, get_api_events, post_api_events, put_api_event},
+        audit::{get_audit_log, internal_get_audit_log},
         event_in_detail::{
-            get_event, internal_get_sign_others_up, internal_get_signed_up,
-            internal_get_signup_button, internal_post_sign_others_up,
-            internal_post_toggle_self_sign_up, internal_post_verify,
+            get_event, get_event_attendance_csv, get_event_photos_zip, get_event_roster,
+            internal_get_sign_others_up, internal_get_signed_up, internal_get_signup_button,
+            internal_post_sign_others_up, internal_post_toggle_self_sign_up,
+            internal_post_unverify, internal_post_verify, internal_post_verify_all,
+            internal_post_verify_selected,
         },
+        health::{get_healthz, get_readyz},
         import_export::{
-            get_import_export_page, get_students_import_checker, put_add_new_events,
-            put_add_new_students, put_fully_import_events,
+            delete_unsaved_passwords, get_export_events, get_export_people, get_import_export_page,
+            get_latest_passwords, get_students_import_checker, internal_get_unsaved_passwords,
+            put_add_new_events, put_add_new_houses, put_add_new_staff, put_add_new_students,
+            put_add_new_tutor_groups, put_fully_import_events,
         },
         index::get_index_route,
-        login::{get_login, post_login, post_logout},
+        leaderboard::{get_leaderboard, internal_get_leaderboard, internal_put_house_points},
+        login::{get_login, get_login_2fa, post_login, post_login_2fa, post_logout},
         new_admin_flow::{
             get_start_onboarding, internal_post_add_new_admin, internal_post_setup_auth_config,
             internal_post_setup_s3, internal_post_setup_timezone,
         },
+        password_reset::{
+            get_forgot_password, get_reset_password, post_forgot_password, post_reset_password,
+        },
         profile::{
-            get_profile, internal_get_profile_edit_email, internal_get_profile_edit_first_name,
+            delete_profile_api_token, get_profile, internal_get_person_edit_email,
+            internal_get_person_edit_first_name, internal_get_person_edit_pref_name,
+            internal_get_person_edit_surname, internal_get_profile_api_token,
+            internal_get_profile_edit_email, internal_get_profile_edit_first_name,
             internal_get_profile_edit_password, internal_get_profile_edit_pref_name,
-            internal_get_profile_edit_surname, internal_get_profile_student_display,
-            internal_get_profile_student_form_house_display, internal_post_profile_edit_email,
+            internal_get_profile_edit_surname, internal_get_profile_edit_tutor_group,
+            internal_get_profile_sessions, internal_get_profile_signup_emails,
+            internal_get_profile_student_display, internal_get_profile_student_form_house_display,
+            internal_get_profile_timezone, internal_get_profile_totp,
+            internal_get_profile_totp_enrol, internal_post_person_edit_email,
+            internal_post_person_edit_first_name, internal_post_person_edit_pref_name,
+            internal_post_person_edit_surname, internal_post_profile_edit_email,
             internal_post_profile_edit_first_name, internal_post_profile_edit_password,
             internal_post_profile_edit_pref_name, internal_post_profile_edit_surname,
+            internal_post_profile_edit_tutor_group, internal_post_profile_signup_emails,
+            internal_post_profile_timezone, internal_post_profile_totp_disable,
+            internal_post_profile_totp_enable, post_profile_api_token, post_revoke_session,
+        },
+        roles::{
+            delete_role, get_roles, internal_get_roles, internal_put_new_role,
+            internal_put_person_role, internal_put_role_permissions,
         },
         set_new_password::{get_replace_default_password, post_replace_default_password},
-        sse::sse_feed,
+        settings::{
+            delete_settings_word_list, get_settings, internal_post_settings_auth_config,
+            internal_post_settings_date_locale, internal_post_settings_s3,
+            internal_post_settings_word_list,
+        },
+        sse::{sse_feed, sse_feed_me},
+        static_assets::get_denim_css,
+        student_groups::{
+            delete_house, delete_tutor_group, get_house, get_tutor_group, internal_get_houses,
+            internal_get_tutor_groups, internal_post_rename_house, internal_put_new_house,
+            internal_put_new_tutor_group,
+        },
     },
     state::DenimState,
+    theme::{post_toggle_theme, theme_scope},
 };
 use axum::{
-    Router,
-    routing::{get, post, put},
+    Router, middleware,
+    routing::{delete, get, post, put},
 };
 use axum_login::{
     AuthManagerLayerBuilder,
     tower_sessions::{Expiry, SessionManagerLayer, cookie::time::Duration},
 };
-use sqlx::postgres::PgPoolOptions;
 use std::env;
 use tokio::{net::TcpListener, signal};
 use tower_http::compression::CompressionLayer;
 use tower_http::limit::RequestBodyLimitLayer;
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
-use crate::routes::event_in_detail::{internal_get_photos, internal_post_photos};
+use crate::routes::event_in_detail::{
+    internal_get_photos, internal_get_photos_page, internal_get_presign_photo_upload,
+    internal_post_confirm_photo_upload, internal_post_photos,
+};
 
 #[macro_use]
 extern crate tracing;
@@ -66,9 +116,13 @@ mod auth;
 mod config;
 mod data;
 mod error;
+mod mail;
 mod maud_conveniences;
+mod metrics;
+mod request_id;
 mod routes;
 mod state;
+mod theme;
 
 async fn shutdown_signal(state: DenimState) {
     let ctrl_c = async {
@@ -113,21 +167,27 @@ async fn main() {
 
     info!("`tracing` online");
 
-    let options = PgPoolOptions::new().max_connections(15);
+    metrics::install(env::var("DENIM_METRICS_TOKEN").ok());
+
     let config = RuntimeConfiguration::new()
         .await
         .expect("unable to create config");
+    let options = config.db_config().pool_options();
     let state = DenimState::new(options, config.clone())
         .await
         .expect("unable to create state");
 
+    tokio::task::spawn(state.clone().purge_deleted_events());
+    tokio::task::spawn(state.clone().purge_stale_photo_uploads());
+
     let session_store = PostgresSessionStore::new(state.clone());
-    let session_layer = SessionManagerLayer::new(session_store)
-        .with_expiry(Expiry::OnInactivity(Duration::days(5)));
+    let session_layer = SessionManagerLayer::new(session_store).with_expiry(Expiry::OnInactivity(
+        Duration::days(config.security_config().session_expiry_days),
+    ));
     let auth_backend = DenimAuthBackend::new(state.clone());
     let auth_layer = AuthManagerLayerBuilder::new(auth_backend, session_layer).build();
 
-    let trace_layer = TraceLayer::new_for_http();
+    let trace_layer = TraceLayer::new_for_http().make_span_with(make_request_span);
 
     let app = Router::new()
         .route("/", get(get_index_route))
@@ -135,18 +195,51 @@ async fn main() {
             "/events",
             get(get_events).put(put_new_event).delete(delete_event),
         )
+        .route("/events.ics", get(get_events_ics))
+        .route("/events/{id}", put(put_edit_event))
         .route("/event/{id}", get(get_event))
+        .route("/event/{id}/attendance.csv", get(get_event_attendance_csv))
+        .route("/event/{id}/photos.zip", get(get_event_photos_zip))
+        .route("/event/{id}/roster", get(get_event_roster))
         .route("/people", get(get_people).delete(delete_person))
+        .route("/people/bulk", delete(delete_people_bulk))
+        .route("/houses", delete(delete_house))
+        .route("/house/{id}", get(get_house))
+        .route("/tutor_group/{id}", get(get_tutor_group))
+        .route("/tutor_groups", delete(delete_tutor_group))
         .route("/profile", get(get_profile))
+        .route("/profile/sessions/{id}/revoke", post(post_revoke_session))
+        .route(
+            "/profile/api_tokens",
+            post(post_profile_api_token).delete(delete_profile_api_token),
+        )
         .route("/login", get(get_login).post(post_login))
+        .route("/login/2fa", get(get_login_2fa).post(post_login_2fa))
         .route("/logout", post(post_logout))
+        .route(
+            "/forgot_password",
+            get(get_forgot_password).post(post_forgot_password),
+        )
+        .route(
+            "/reset_password",
+            get(get_reset_password).post(post_reset_password),
+        )
         .route(
             "/replace_default_password",
             get(get_replace_default_password).post(post_replace_default_password),
         )
         .route("/import_export", get(get_import_export_page))
         .route("/import_export/import_people", put(put_add_new_students))
+        .route("/import_export/import_staff", put(put_add_new_staff))
+        .route("/import_export/import_houses", put(put_add_new_houses))
+        .route(
+            "/import_export/import_tutor_groups",
+            put(put_add_new_tutor_groups),
+        )
+        .route("/import_export/export_people", get(get_export_people))
         .route("/import_export/import_events", put(put_add_new_events))
+        .route("/import_export/export_events", get(get_export_events))
+        .route("/import_export/latest_passwords", get(get_latest_passwords))
         .route(
             "/import_export/fully_import_events",
             put(put_fully_import_events),
@@ -155,8 +248,44 @@ async fn main() {
             "/import_export/import_people_fetch",
             get(get_students_import_checker),
         )
+        .route(
+            "/internal/import_export/unsaved_passwords",
+            get(internal_get_unsaved_passwords).delete(delete_unsaved_passwords),
+        )
         .route("/onboarding", get(get_start_onboarding))
+        .route("/leaderboard", get(get_leaderboard))
+        .route("/internal/leaderboard", get(internal_get_leaderboard))
+        .route(
+            "/internal/houses/{id}/points",
+            put(internal_put_house_points),
+        )
+        .route("/settings", get(get_settings))
+        .route(
+            "/internal/settings/auth_config",
+            post(internal_post_settings_auth_config),
+        )
+        .route(
+            "/internal/settings/date_locale",
+            post(internal_post_settings_date_locale),
+        )
+        .route("/internal/settings/s3", post(internal_post_settings_s3))
+        .route(
+            "/internal/settings/word_list",
+            post(internal_post_settings_word_list).delete(delete_settings_word_list),
+        )
         .route("/internal/get_people", get(internal_get_people))
+        .route(
+            "/internal/houses",
+            get(internal_get_houses).put(internal_put_new_house),
+        )
+        .route(
+            "/internal/houses/{id}/rename",
+            post(internal_post_rename_house),
+        )
+        .route(
+            "/internal/tutor_groups",
+            get(internal_get_tutor_groups).put(internal_put_new_tutor_group),
+        )
         .route("/internal/get_events", get(internal_get_events))
         .route("/internal/get_person", get(internal_get_person_in_detail))
         .route("/internal/get_event", get(internal_get_event_in_detail))
@@ -180,6 +309,34 @@ async fn main() {
             "/internal/profile/get_student_form_house_display",
             get(internal_get_profile_student_form_house_display),
         )
+        .route(
+            "/internal/people/{id}/edit_first_name",
+            get(internal_get_person_edit_first_name).post(internal_post_person_edit_first_name),
+        )
+        .route(
+            "/internal/people/{id}/edit_pref_name",
+            get(internal_get_person_edit_pref_name).post(internal_post_person_edit_pref_name),
+        )
+        .route(
+            "/internal/people/{id}/edit_surname",
+            get(internal_get_person_edit_surname).post(internal_post_person_edit_surname),
+        )
+        .route(
+            "/internal/people/{id}/edit_email",
+            get(internal_get_person_edit_email).post(internal_post_person_edit_email),
+        )
+        .route(
+            "/internal/people/{id}/reset_password",
+            post(internal_post_person_reset_password),
+        )
+        .route(
+            "/internal/people/{id}/delete_confirm",
+            get(internal_get_delete_person_confirm),
+        )
+        .route(
+            "/internal/profile/edit_tutor_group",
+            get(internal_get_profile_edit_tutor_group).post(internal_post_profile_edit_tutor_group),
+        )
         .route(
             "/internal/profile/edit_first_name",
             get(internal_get_profile_edit_first_name).post(internal_post_profile_edit_first_name),
@@ -200,6 +357,35 @@ async fn main() {
             "/internal/profile/edit_password",
             get(internal_get_profile_edit_password()).post(internal_post_profile_edit_password),
         )
+        .route("/internal/profile/totp", get(internal_get_profile_totp))
+        .route(
+            "/internal/profile/api_token",
+            get(internal_get_profile_api_token),
+        )
+        .route(
+            "/internal/profile/signup_emails",
+            get(internal_get_profile_signup_emails).post(internal_post_profile_signup_emails),
+        )
+        .route(
+            "/internal/profile/timezone",
+            get(internal_get_profile_timezone).post(internal_post_profile_timezone),
+        )
+        .route(
+            "/internal/profile/sessions",
+            get(internal_get_profile_sessions),
+        )
+        .route(
+            "/internal/profile/totp/enrol",
+            get(internal_get_profile_totp_enrol),
+        )
+        .route(
+            "/internal/profile/totp/enable",
+            post(internal_post_profile_totp_enable),
+        )
+        .route(
+            "/internal/profile/totp/disable",
+            post(internal_post_profile_totp_disable),
+        )
         .route(
             "/internal/onboarding/create_admin",
             post(internal_post_add_new_admin),
@@ -224,6 +410,18 @@ async fn main() {
             "/internal/event/{id}/post_verify",
             post(internal_post_verify),
         )
+        .route(
+            "/internal/event/{id}/unverify",
+            post(internal_post_unverify),
+        )
+        .route(
+            "/internal/event/{id}/verify_all",
+            post(internal_post_verify_all),
+        )
+        .route(
+            "/internal/event/{id}/verify_selected",
+            post(internal_post_verify_selected),
+        )
         .route(
             "/internal/event/{id}/signed_up_and_verified",
             get(internal_get_signed_up),
@@ -232,26 +430,93 @@ async fn main() {
             "/internal/event/{id}/signup_button",
             get(internal_get_signup_button),
         )
+        .route(
+            "/internal/event/{id}/edit_form",
+            get(internal_get_edit_event_form),
+        )
+        .route(
+            "/internal/event/{id}/duplicate",
+            post(internal_post_duplicate_event),
+        )
+        .route(
+            "/internal/event/{id}/delete_confirm",
+            get(internal_get_delete_event_confirm),
+        )
+        .route(
+            "/internal/event/{id}/restore",
+            post(internal_post_restore_event),
+        )
         .route(
             "/internal/event/{id}/sign_others_up",
             get(internal_get_sign_others_up).post(internal_post_sign_others_up),
         )
+        .route(
+            "/internal/event/{id}/photos/presign_upload",
+            get(internal_get_presign_photo_upload),
+        )
+        .route(
+            "/internal/event/{id}/photos/confirm",
+            post(internal_post_confirm_photo_upload),
+        )
+        .route(
+            "/internal/event/{id}/photos/page",
+            get(internal_get_photos_page),
+        )
         .route("/internal/event/{id}/photos",
             get(internal_get_photos).post(internal_post_photos)
         )
+        .route("/audit", get(get_audit_log))
+        .route("/roles", get(get_roles))
+        .route(
+            "/internal/roles",
+            get(internal_get_roles).put(internal_put_new_role),
+        )
+        .route("/internal/roles/{id}", delete(delete_role))
+        .route(
+            "/internal/roles/{id}/permissions",
+            put(internal_put_role_permissions),
+        )
+        .route("/internal/people/{id}/role", put(internal_put_person_role))
+        .route(
+            "/internal/people/{id}/house_scope",
+            put(internal_put_person_house_scope),
+        )
+        .route("/internal/audit", get(internal_get_audit_log))
+        .route("/api/events", get(get_api_events).post(post_api_events))
+        .route(
+            "/api/events/{id}",
+            get(get_api_event)
+                .put(put_api_event)
+                .delete(delete_api_event),
+        )
+        .route("/my_events", get(get_my_events))
+        .route("/my_signups", get(get_my_signups))
+        .route("/theme", post(post_toggle_theme))
+        .route("/static/denim.css", get(get_denim_css))
         .route("/sse_feed", get(sse_feed))
+        .route("/sse_feed/me", get(sse_feed_me))
+        .layer(middleware::from_fn(csrf_protection))
+        .layer(middleware::from_fn(negotiate_error_response))
+        .layer(middleware::from_fn(theme_scope))
+        .layer(middleware::from_fn(record_user_id))
         .layer(auth_layer)
         .layer(trace_layer)
+        .layer(middleware::from_fn(assign_request_id))
+        .layer(middleware::from_fn(track_request_latency))
         .layer(RequestBodyLimitLayer::new(50 * 1000 * 1000)) //50MB
         .layer(CompressionLayer::new())
+        //added after all the layers so that load balancers & scrapers don't go through the auth
+        .route("/healthz", get(get_healthz))
+        .route("/readyz", get(get_readyz))
+        .route("/metrics", get(get_metrics))
         .with_state(state.clone());
 
-    let server_ip = env::var("DENIM_SERVER_IP").unwrap_or_else(|_| "127.0.0.1:8080".to_string());
-    let listener = TcpListener::bind(&server_ip)
+    let server_address = config.server_address();
+    let listener = TcpListener::bind(server_address)
         .await
-        .expect("unable to listen on server ip");
+        .unwrap_or_else(|e| panic!("unable to listen on {server_address}: {e}"));
 
-    info!(?server_ip, "Listening");
+    info!(%server_address, "Listening");
     axum::serve(listener, app)
         .with_graceful_shutdown(shutdown_signal(state))
         .await
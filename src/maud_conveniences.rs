@@ -1,14 +1,78 @@
+use crate::{
+    auth::csrf::{CSRF_FORM_FIELD, CSRF_HEADER, current_token},
+    data::SortDirection,
+};
 use email_address::EmailAddress;
 use jiff::tz::{TimeZone, TimeZoneName, db};
 use maud::{Escaper, Markup, PreEscaped, Render, html};
 use std::fmt::Write;
+use uuid::Uuid;
 
 #[inline]
-#[allow(clippy::needless_pass_by_value)]
 pub fn table<const N: usize>(
     overall_title: Markup,
     titles: [&str; N],
     items: Vec<[impl Render; N]>,
+) -> Markup {
+    table_with_headers(overall_title, titles.map(|title| html! {(title)}), items)
+}
+
+///where a sortable table's headers send the user when clicked
+pub struct TableSort<'a> {
+    pub url: &'a str,
+    pub target: &'a str,
+    ///the other inputs to send along so that filters are kept - shouldn't include the `sort` or `dir` inputs
+    pub include: &'a str,
+    pub current: Option<&'a str>,
+    pub direction: SortDirection,
+}
+
+impl TableSort<'_> {
+    ///a button which sorts by `key`, or flips the direction if we're already sorting by it
+    pub fn header(&self, title: &str, key: &str) -> Markup {
+        let is_current = self.current == Some(key);
+        let next_direction = if is_current {
+            self.direction.flipped()
+        } else {
+            SortDirection::Asc
+        };
+        let vals = format!(
+            "{{\"sort\": \"{key}\", \"dir\": \"{}\"}}",
+            next_direction.as_str()
+        );
+
+        html! {
+            button type="button" class="font-semibold hover:text-blue-300" hx-get=(self.url) hx-target=(self.target) hx-include=(self.include) hx-vals=(vals) {
+                (title)
+                @if is_current {
+                    @match self.direction {
+                        SortDirection::Asc => " ▲",
+                        SortDirection::Desc => " ▼",
+                    }
+                }
+            }
+        }
+    }
+}
+
+///a table where the columns with a sort key can be clicked on to re-sort by that column
+#[inline]
+pub fn sortable_table<const N: usize>(
+    overall_title: Markup,
+    titles: [(&str, Option<&str>); N],
+    sort: &TableSort,
+    items: Vec<[impl Render; N]>,
+) -> Markup {
+    let headers = titles
+        .map(|(title, key)| key.map_or_else(|| html! {(title)}, |key| sort.header(title, key)));
+    table_with_headers(overall_title, headers, items)
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn table_with_headers<const N: usize>(
+    overall_title: Markup,
+    headers: [Markup; N],
+    items: Vec<[impl Render; N]>,
 ) -> Markup {
     html! {
         div class="container mx-auto" {
@@ -17,8 +81,8 @@ pub fn table<const N: usize>(
                 table class="min-w-full bg-gray-800 rounded shadow-md" {
                     thead class="bg-gray-700" {
                         tr {
-                            @for title in titles {
-                                th class="py-2 px-4 text-left font-semibold text-gray-300" {(title)}
+                            @for header in headers {
+                                th class="py-2 px-4 text-left font-semibold text-gray-300" {(header)}
                             }
                         }
                     }
@@ -37,6 +101,14 @@ pub fn table<const N: usize>(
     }
 }
 
+///goes where a list would be if it had anything in it, ideally saying what to do about that
+#[inline]
+pub fn empty_state(message: impl Render) -> Markup {
+    html! {
+        p class="text-gray-400 italic text-center p-4 m-4" {(message)}
+    }
+}
+
 #[inline]
 #[allow(dead_code)]
 pub fn escape(s: impl AsRef<str>) -> PreEscaped<String> {
@@ -126,6 +198,78 @@ pub fn errors_list(title: Option<&'static str>, list: impl Iterator<Item = impl
     }
 }
 
+///needs to go in every plain `form method="post"` - htmx requests get it from the headers instead
+#[inline]
+pub fn csrf_field() -> Markup {
+    html! {
+        @if let Some(token) = current_token() {
+            input type="hidden" name=(CSRF_FORM_FIELD) value=(token);
+        }
+    }
+}
+
+///for `hx-headers` on the body, so that every htmx request carries the CSRF token
+#[inline]
+pub fn csrf_hx_headers() -> Option<String> {
+    current_token().map(|token| serde_json::json!({ CSRF_HEADER: token }).to_string())
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum ToastKind {
+    Success,
+    Error,
+}
+
+///gets swapped out-of-band into the `#toasts` live region (see `DenimState::render`) so screen readers announce it, then disappears on its own
+pub fn toast(kind: ToastKind, message: impl Render) -> Markup {
+    let (role, colours) = match kind {
+        ToastKind::Success => ("status", "bg-green-100 border-green-400 text-green-700"),
+        ToastKind::Error => ("alert", "bg-red-100 border-red-400 text-red-700"),
+    };
+
+    html! {
+        div hx-swap-oob="beforeend:#toasts" {
+            div role=(role) class={"border px-4 py-2 rounded shadow " (colours)} hx-on::load="setTimeout(() => this.remove(), 5000)" {
+                (message)
+            }
+        }
+    }
+}
+
+///swapped in for a delete button so nothing gets deleted on a single click - `Cancel` just reloads `cancel_url` for the same `id`
+pub fn delete_confirmation(
+    question: &str,
+    delete_url: &str,
+    delete_vals: &str,
+    cancel_url: &str,
+    id: Uuid,
+) -> Markup {
+    html! {
+        div class="inline-flex flex-row items-center space-x-2" {
+            span class="text-gray-200 font-semibold" {(question)}
+            button class="bg-red-600 hover:bg-red-800 font-bold py-2 px-4 rounded" hx-delete=(delete_url) hx-vals=(delete_vals) hx-target="#in_focus" {
+                "Yes"
+            }
+            button class="bg-gray-600 hover:bg-gray-800 font-bold py-2 px-4 rounded" hx-get=(cancel_url) hx-vals={"{\"id\": \"" (id) "\"}" } hx-target="#in_focus" {
+                "Cancel"
+            }
+        }
+    }
+}
+
+#[inline]
+pub fn tag_chips(tags: &[String]) -> Markup {
+    html! {
+        @if !tags.is_empty() {
+            div class="flex flex-row flex-wrap gap-1" {
+                @for tag in tags {
+                    span class="bg-blue-900 text-blue-200 text-xs font-medium px-2 py-0.5 rounded-full" {(tag)}
+                }
+            }
+        }
+    }
+}
+
 pub fn timezone_picker(current: Option<TimeZone>) -> Markup {
     let current = current.map_or_else(
         || match TimeZone::try_system() {
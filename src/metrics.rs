@@ -0,0 +1,181 @@
+use axum::{
+    extract::Request,
+    http::{HeaderMap, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt::Write,
+    sync::{
+        OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+use subtle::ConstantTimeEq;
+
+///upper bounds of the latency histogram buckets, in seconds
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+pub static METRICS: Metrics = Metrics::new();
+
+///bearer token needed to see `/metrics` - if it isn't set then the endpoint doesn't exist
+static METRICS_TOKEN: OnceLock<Option<String>> = OnceLock::new();
+
+///sets the token needed for `/metrics`, and should only be called once at startup
+pub fn install(token: Option<String>) {
+    if METRICS_TOKEN.set(token).is_err() {
+        warn!("Tried to install metrics twice");
+    }
+}
+
+pub struct Metrics {
+    logins: AtomicU64,
+    events_created: AtomicU64,
+    people_created: AtomicU64,
+    imports_run: AtomicU64,
+    ///one count for each of `LATENCY_BUCKETS`, with an extra on the end for anything slower
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    latency_sum_micros: AtomicU64,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            logins: AtomicU64::new(0),
+            events_created: AtomicU64::new(0),
+            people_created: AtomicU64::new(0),
+            imports_run: AtomicU64::new(0),
+            latency_buckets: [const { AtomicU64::new(0) }; LATENCY_BUCKETS.len() + 1],
+            latency_sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record_login(&self) {
+        self.logins.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_event_created(&self) {
+        self.events_created.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_person_created(&self) {
+        self.people_created.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_import(&self) {
+        self.imports_run.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_latency(&self, latency: Duration) {
+        let secs = latency.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|upper_bound| secs <= *upper_bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+
+        self.latency_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.latency_sum_micros.fetch_add(
+            u64::try_from(latency.as_micros()).unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+    }
+
+    ///renders everything in the prometheus text format
+    #[allow(clippy::cast_precision_loss)]
+    fn render(&self) -> String {
+        let mut output = String::new();
+
+        for (name, help, counter) in [
+            ("denim_logins_total", "Successful logins", &self.logins),
+            (
+                "denim_events_created_total",
+                "Events created",
+                &self.events_created,
+            ),
+            (
+                "denim_people_created_total",
+                "People created",
+                &self.people_created,
+            ),
+            (
+                "denim_imports_run_total",
+                "CSV imports run",
+                &self.imports_run,
+            ),
+        ] {
+            let _ = writeln!(output, "# HELP {name} {help}");
+            let _ = writeln!(output, "# TYPE {name} counter");
+            let _ = writeln!(output, "{name} {}", counter.load(Ordering::Relaxed));
+        }
+
+        let name = "denim_request_duration_seconds";
+        let _ = writeln!(output, "# HELP {name} Time taken to respond to requests");
+        let _ = writeln!(output, "# TYPE {name} histogram");
+
+        //prometheus buckets are cumulative
+        let mut cumulative = 0;
+        for (upper_bound, count) in LATENCY_BUCKETS.iter().zip(&self.latency_buckets) {
+            cumulative += count.load(Ordering::Relaxed);
+            let _ = writeln!(output, "{name}_bucket{{le=\"{upper_bound}\"}} {cumulative}");
+        }
+        cumulative += self.latency_buckets[LATENCY_BUCKETS.len()].load(Ordering::Relaxed);
+        let _ = writeln!(output, "{name}_bucket{{le=\"+Inf\"}} {cumulative}");
+
+        let sum = self.latency_sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(output, "{name}_sum {sum}");
+        let _ = writeln!(output, "{name}_count {cumulative}");
+
+        output
+    }
+}
+
+pub async fn track_request_latency(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let response = next.run(request).await;
+    METRICS.record_latency(start.elapsed());
+    response
+}
+
+///compares digests so that neither the contents nor the length of the token leak through timing
+fn tokens_match(provided: &str, expected: &str) -> bool {
+    Sha256::digest(provided.as_bytes())
+        .ct_eq(&Sha256::digest(expected.as_bytes()))
+        .into()
+}
+
+pub async fn get_metrics(headers: HeaderMap) -> Response {
+    let Some(Some(expected_token)) = METRICS_TOKEN.get() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let provided_token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if !provided_token.is_some_and(|provided_token| tokens_match(provided_token, expected_token)) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        METRICS.render(),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::tokens_match;
+
+    #[test]
+    fn only_the_exact_token_matches() {
+        assert!(tokens_match("hunter2", "hunter2"));
+        assert!(!tokens_match("hunter3", "hunter2"));
+        assert!(!tokens_match("hunter", "hunter2"));
+        assert!(!tokens_match("", "hunter2"));
+    }
+}
@@ -0,0 +1,65 @@
+use crate::auth::DenimSession;
+use axum::{
+    extract::{MatchedPath, Request},
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use tracing::{Span, field};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    //so that errors can be logged with the request they came from without needing the request
+    static REQUEST_ID: Uuid;
+}
+
+///the id of the request currently being responded to, if there is one
+pub fn current_request_id() -> Option<Uuid> {
+    REQUEST_ID.try_with(|id| *id).ok()
+}
+
+#[derive(Debug, Copy, Clone)]
+struct RequestId(Uuid);
+
+///needs to be outside the `TraceLayer`, so the id is there when the span gets made
+pub async fn assign_request_id(mut request: Request, next: Next) -> Response {
+    let id = Uuid::new_v4();
+    request.extensions_mut().insert(RequestId(id));
+
+    let mut response = REQUEST_ID.scope(id, next.run(request)).await;
+    if let Ok(value) = HeaderValue::from_str(&id.to_string()) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+///for `TraceLayer::make_span_with` - the user gets filled in later by `record_user_id`
+pub fn make_request_span(request: &Request) -> Span {
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .map(|RequestId(id)| field::display(id));
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(MatchedPath::as_str);
+
+    info_span!(
+        "request",
+        request_id,
+        method = %request.method(),
+        uri = %request.uri(),
+        route,
+        user_id = field::Empty,
+    )
+}
+
+///needs to be inside the auth layer to get at the session
+pub async fn record_user_id(session: DenimSession, request: Request, next: Next) -> Response {
+    if let Some(user) = &session.user {
+        Span::current().record("user_id", field::display(user.id));
+    }
+    next.run(request).await
+}
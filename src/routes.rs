@@ -1,10 +1,19 @@
 pub mod all_events;
 pub mod all_people;
+pub mod api;
+pub mod audit;
 pub mod event_in_detail;
+pub mod health;
 pub mod import_export;
 pub mod index;
+pub mod leaderboard;
 pub mod login;
 pub mod new_admin_flow;
+pub mod password_reset;
 pub mod profile;
+pub mod roles;
 pub mod set_new_password;
+pub mod settings;
 pub mod sse;
+pub mod static_assets;
+pub mod student_groups;
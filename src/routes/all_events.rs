@@ -1,28 +1,43 @@
 use crate::{
     auth::{AuthUtilities, DenimSession, PermissionsTarget},
+    config::date_locale::{DateFormat, DateLocaleConfig},
     data::{
-        DataType, IdForm,
-        event::{AddEvent, Event},
+        DataType, IdForm, SortDirection,
+        audit_log::{AuditAction, AuditTarget},
+        event::{
+            AddEvent, EVENT_UNDO_WINDOW_SECS, Event, EventSearchScope, EventSortColumn,
+            EventsFilter,
+        },
         user::User,
     },
     error::{
-        DenimError, DenimResult, InvalidTimezoneSnafu, ParseTimeSnafu, ParseUuidSnafu,
+        CommitTransactionSnafu, DenimError, DenimResult, InvalidTimezoneSnafu, MissingEventSnafu,
+        ParseCapacitySnafu, ParseTimeSnafu, ParseUuidSnafu, UnableToFindUserInfoSnafu,
         UnrepresentableTimeSnafu,
     },
     maud_conveniences::{
-        form_element, form_submit_button, simple_form_element, table, timezone_picker, title,
+        TableSort, ToastKind, delete_confirmation, empty_state, errors_list, form_element,
+        form_submit_button, simple_form_element, sortable_table, table, tag_chips, timezone_picker,
+        title, toast,
     },
     routes::sse::SseEvent,
     state::DenimState,
 };
 use axum::{
     Form,
-    extract::{Query, State},
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use jiff::{
+    Span, Timestamp, Zoned,
+    civil::{Date, DateTime},
+    tz::TimeZone,
 };
-use jiff::{civil::DateTime, tz::TimeZone};
 use maud::{Markup, PreEscaped, html};
 use serde::Deserialize;
-use snafu::ResultExt;
+use snafu::{OptionExt, ResultExt};
+use std::num::NonZeroU16;
 use uuid::Uuid;
 
 #[axum::debug_handler]
@@ -64,112 +79,554 @@ pub async fn internal_get_add_events_form(
             (simple_form_element("date", "Date/Time", true, Some("datetime-local"), None))
             (timezone_picker(dlc.map(|x| x.timezone.clone())))
             (simple_form_element("location", "Location (optional)", false, None, None))
+            (simple_form_element("max_capacity", "Maximum Capacity (optional)", false, Some("number"), None))
+            (simple_form_element("tags", "Tags (optional, comma separated)", false, None, None))
             (form_element("extra_info", "Extra Information (optional)", html!{
                 textarea id="extra_info" name="extra_info" rows="2" class="w-full bg-gray-700 text-gray-100 rounded px-4 py-2 border border-gray-600 focus:outline-none focus:ring focus:ring-blue-500 placeholder-gray-400 resize-y" {}
             }))
-            (form_element("associated_staff_member", "Associated Staff Member", html!{
-                select id="associated_staff_member" name="associated_staff_member" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600" {
-                    option value="" {"Select a Staff Member (optional)"}
+            (form_element("associated_staff", "Associated Staff (optional, hold Ctrl to pick several)", html!{
+                select id="associated_staff" name="associated_staff" multiple class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600" {
                     @for staff_member in staff {
                         option value={(staff_member.id)} {(staff_member)}
                     }
                 }
             }))
+            (form_element("repeat", "Repeat", html!{
+                select id="repeat" name="repeat" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600" {
+                    option value="" selected {"Doesn't repeat"}
+                    option value="weekly" {"Weekly"}
+                    option value="fortnightly" {"Fortnightly"}
+                    option value="monthly" {"Monthly"}
+                }
+            }))
+            (simple_form_element("repeat_count", "Number of Occurrences (if repeating)", false, Some("number"), None))
+            (simple_form_element("repeat_until", "Repeat Until (if repeating, instead of a number)", false, Some("date"), None))
 
             (form_submit_button(Some("Add Event")))
         }
     })
 }
 
-#[derive(Deserialize)]
+#[derive(Default)]
 pub struct NewEventForm {
     name: String,
     date: String,
     location: String,
     extra_info: String,
-    associated_staff_member: String,
+    associated_staff: Vec<String>,
     tz: String,
+    max_capacity: String,
+    tags: String,
+    //only on the add form, not the edit form
+    repeat: String,
+    repeat_count: String,
+    repeat_until: String,
+}
+
+const MAX_OCCURRENCES: i64 = 104;
+
+#[derive(Copy, Clone)]
+enum RepeatEvery {
+    Week,
+    Fortnight,
+    Month,
+}
+
+struct Recurrence {
+    every: RepeatEvery,
+    count: Option<i64>,
+    until: Option<Date>,
+}
+
+impl Recurrence {
+    fn from_form(repeat: &str, count: &str, until: &str) -> DenimResult<Option<Self>> {
+        let every = match repeat {
+            "" => return Ok(None),
+            "weekly" => RepeatEvery::Week,
+            "fortnightly" => RepeatEvery::Fortnight,
+            "monthly" => RepeatEvery::Month,
+            _ => {
+                return Err(DenimError::BadRecurrence {
+                    reason: "unknown repeat interval",
+                });
+            }
+        };
+
+        let count = if count.trim().is_empty() {
+            None
+        } else {
+            match count.trim().parse() {
+                Ok(count) if (1..=MAX_OCCURRENCES).contains(&count) => Some(count),
+                _ => {
+                    return Err(DenimError::BadRecurrence {
+                        reason: "the number of occurrences must be between 1 and 104",
+                    });
+                }
+            }
+        };
+        let until = if until.trim().is_empty() {
+            None
+        } else {
+            Some(
+                Date::strptime("%Y-%m-%d", until.trim()).context(ParseTimeSnafu {
+                    original: until.to_string(),
+                })?,
+            )
+        };
+
+        if count.is_none() && until.is_none() {
+            return Err(DenimError::BadRecurrence {
+                reason: "repeating events need a number of occurrences or an end date",
+            });
+        }
+
+        Ok(Some(Self {
+            every,
+            count,
+            until,
+        }))
+    }
+
+    ///every occurrence, including the first one at `start`
+    fn dates(&self, start: &Zoned) -> DenimResult<Vec<Zoned>> {
+        let mut dates = vec![];
+        for n in 0..=MAX_OCCURRENCES {
+            if self.count.is_some_and(|count| n >= count) {
+                break;
+            }
+
+            //always go from the start, so monthly events on the 31st don't drift to the 28th
+            let span = match self.every {
+                RepeatEvery::Week => Span::new().weeks(n),
+                RepeatEvery::Fortnight => Span::new().weeks(n * 2),
+                RepeatEvery::Month => Span::new().months(n),
+            };
+            let next = start.checked_add(span).context(UnrepresentableTimeSnafu)?;
+            if self.until.is_some_and(|until| next.date() > until) {
+                break;
+            }
+
+            if n == MAX_OCCURRENCES {
+                return Err(DenimError::BadRecurrence {
+                    reason: "events can repeat at most 104 times",
+                });
+            }
+            dates.push(next);
+        }
+
+        if dates.is_empty() {
+            return Err(DenimError::BadRecurrence {
+                reason: "the end date is before the event starts",
+            });
+        }
+
+        Ok(dates)
+    }
+}
+
+impl NewEventForm {
+    ///`Form` can't deal with the repeated `associated_staff` keys from the multi-select, so pick the pairs apart by hand
+    pub fn from_pairs(pairs: Vec<(String, String)>) -> Self {
+        let mut form = Self::default();
+        for (key, value) in pairs {
+            match key.as_str() {
+                "name" => form.name = value,
+                "date" => form.date = value,
+                "location" => form.location = value,
+                "extra_info" => form.extra_info = value,
+                "associated_staff" => form.associated_staff.push(value),
+                "tz" => form.tz = value,
+                "max_capacity" => form.max_capacity = value,
+                "tags" => form.tags = value,
+                "repeat" => form.repeat = value,
+                "repeat_count" => form.repeat_count = value,
+                "repeat_until" => form.repeat_until = value,
+                _ => {}
+            }
+        }
+        form
+    }
+
+    pub fn into_add_event(self) -> DenimResult<AddEvent> {
+        let Self {
+            name,
+            date,
+            location,
+            extra_info,
+            associated_staff,
+            tz,
+            max_capacity,
+            tags,
+            repeat: _,
+            repeat_count: _,
+            repeat_until: _,
+        } = self;
+
+        let tz = TimeZone::get(&tz).context(InvalidTimezoneSnafu { tz })?;
+
+        let date = DateTime::strptime("%Y-%m-%dT%H:%M", &date)
+            .context(ParseTimeSnafu { original: date })?
+            .to_zoned(tz)
+            .context(UnrepresentableTimeSnafu)?;
+
+        let location = if location.is_empty() {
+            None
+        } else {
+            Some(location)
+        };
+        let extra_info = if extra_info.is_empty() {
+            None
+        } else {
+            Some(extra_info)
+        };
+        let associated_staff = associated_staff
+            .into_iter()
+            .filter(|id| !id.is_empty())
+            .map(|id| Uuid::try_parse(&id).context(ParseUuidSnafu { original: id }))
+            .collect::<DenimResult<_>>()?;
+
+        let max_capacity = if max_capacity.is_empty() {
+            None
+        } else {
+            Some(i32::from(
+                max_capacity
+                    .parse::<NonZeroU16>()
+                    .context(ParseCapacitySnafu {
+                        original: max_capacity,
+                    })?
+                    .get(),
+            ))
+        };
+
+        let mut tags: Vec<String> = tags
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(ToString::to_string)
+            .collect();
+        tags.sort();
+        tags.dedup();
+
+        Ok(AddEvent {
+            name,
+            date,
+            location,
+            extra_info,
+            associated_staff,
+            max_capacity,
+            recurrence_group: None,
+            tags,
+        })
+    }
 }
 
 pub async fn put_new_event(
     State(state): State<DenimState>,
     session: DenimSession,
-    Form(NewEventForm {
-        name,
-        date,
-        location,
-        extra_info,
-        associated_staff_member,
-        tz,
-    }): Form<NewEventForm>,
+    Form(pairs): Form<Vec<(String, String)>>,
 ) -> DenimResult<Markup> {
     session.ensure_can(PermissionsTarget::CRUD_EVENTS)?;
+    let form = NewEventForm::from_pairs(pairs);
 
-    let tz = TimeZone::get(&tz).context(InvalidTimezoneSnafu { tz })?;
+    let recurrence = Recurrence::from_form(&form.repeat, &form.repeat_count, &form.repeat_until)?;
+    let add_event = form.into_add_event()?;
+    let name = add_event.name.clone();
 
-    let date = DateTime::strptime("%Y-%m-%dT%H:%M", &date)
-        .context(ParseTimeSnafu { original: date })?
-        .to_zoned(tz)
-        .context(UnrepresentableTimeSnafu)?;
+    let id = if let Some(recurrence) = recurrence {
+        let recurrence_group = Some(Uuid::new_v4());
+        let mut transaction = state.get_transaction().await?;
 
-    let location = if location.is_empty() {
-        None
-    } else {
-        Some(location)
-    };
-    let extra_info = if extra_info.is_empty() {
-        None
+        let mut first_id = None;
+        for date in recurrence.dates(&add_event.date)? {
+            let id = Event::insert_into_database(
+                AddEvent {
+                    date,
+                    recurrence_group,
+                    ..add_event.clone()
+                },
+                &mut transaction,
+            )
+            .await?;
+            first_id.get_or_insert(id);
+        }
+        transaction.commit().await.context(CommitTransactionSnafu)?;
+
+        first_id.expect("`Recurrence::dates` never returns an empty list")
     } else {
-        Some(extra_info)
+        Event::insert_into_database_transaction(add_event, state.get_transaction().await?).await?
     };
-    let associated_staff_member = if associated_staff_member.is_empty() {
-        None
-    } else {
-        Some(
-            Uuid::try_parse(&associated_staff_member).context(ParseUuidSnafu {
-                original: associated_staff_member,
-            })?,
+    state
+        .audit(
+            &session,
+            AuditAction::Create,
+            AuditTarget::Event(id),
+            Some(&name),
         )
-    };
-
-    let id = Event::insert_into_database(
-        AddEvent {
-            name,
-            date,
-            location,
-            extra_info,
-            associated_staff_member,
-        },
-        &mut *state.get_connection().await?,
-    )
-    .await?;
+        .await;
     state.send_sse_event(SseEvent::CrudEvent);
 
     let this_event =
         internal_get_event_in_detail(State(state.clone()), session, Query(IdForm { id })).await?;
 
     Ok(html! {
+        (toast(ToastKind::Success, format!("Added {name}")))
         (this_event)
     })
 }
 
+pub async fn internal_get_edit_event_form(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(id): Path<Uuid>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::CRUD_EVENTS)?;
+
+    let mut conn = state.get_connection().await?;
+    let event = Event::get_from_db_by_id(id, &mut conn)
+        .await?
+        .context(MissingEventSnafu { id })?;
+    drop(conn);
+    let staff = User::get_all_staff(&state).await?;
+
+    let current_date = event.datetime.strftime("%Y-%m-%dT%H:%M").to_string();
+    let current_staff: Vec<_> = event
+        .associated_staff
+        .iter()
+        .map(|staff| staff.id)
+        .collect();
+    let current_max_capacity = event
+        .max_capacity
+        .map(|max_capacity| max_capacity.to_string());
+    let current_tags = event.tags.join(", ");
+
+    Ok(html! {
+        (title("Edit Event Form"))
+        form hx-put={"/events/" (id)} hx-trigger="submit" hx-target="#in_focus" class="p-4" {
+            (simple_form_element("name", "Name", true, None, Some(&event.name)))
+            (simple_form_element("date", "Date/Time", true, Some("datetime-local"), Some(&current_date)))
+            (timezone_picker(Some(event.datetime.time_zone().clone())))
+            (simple_form_element("location", "Location (optional)", false, None, event.location.as_deref()))
+            (simple_form_element("max_capacity", "Maximum Capacity (optional)", false, Some("number"), current_max_capacity.as_deref()))
+            (simple_form_element("tags", "Tags (optional, comma separated)", false, None, Some(&current_tags)))
+            (form_element("extra_info", "Extra Information (optional)", html!{
+                textarea id="extra_info" name="extra_info" rows="2" class="w-full bg-gray-700 text-gray-100 rounded px-4 py-2 border border-gray-600 focus:outline-none focus:ring focus:ring-blue-500 placeholder-gray-400 resize-y" {
+                    (event.extra_info.unwrap_or_default())
+                }
+            }))
+            (form_element("associated_staff", "Associated Staff (optional, hold Ctrl to pick several)", html!{
+                select id="associated_staff" name="associated_staff" multiple class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600" {
+                    @for staff_member in staff {
+                        @let selected = current_staff.contains(&staff_member.id);
+                        option value={(staff_member.id)} selected[selected] {(staff_member)}
+                    }
+                }
+            }))
+
+            (form_submit_button(Some("Save Event")))
+        }
+    })
+}
+
+pub async fn put_edit_event(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(id): Path<Uuid>,
+    Form(pairs): Form<Vec<(String, String)>>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::CRUD_EVENTS)?;
+    let form = NewEventForm::from_pairs(pairs);
+
+    let add_event = form.into_add_event()?;
+    let name = add_event.name.clone();
+
+    let mut transaction = state.get_transaction().await?;
+    session
+        .ensure_can_for_event(PermissionsTarget::CRUD_EVENTS, id, false, &mut transaction)
+        .await?;
+    let promoted_any = Event::update_in_database(id, add_event, &mut transaction).await?;
+    transaction.commit().await.context(CommitTransactionSnafu)?;
+    if promoted_any {
+        state.send_sse_event(SseEvent::ChangeSignUp { event_id: id });
+    }
+    state
+        .audit(
+            &session,
+            AuditAction::Edit,
+            AuditTarget::Event(id),
+            Some(&name),
+        )
+        .await;
+    state.send_sse_event(SseEvent::CrudEvent);
+
+    internal_get_event_in_detail(State(state), session, Query(IdForm { id })).await
+}
+
+///copies everything but the sign-ups & photos
+pub async fn internal_post_duplicate_event(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(id): Path<Uuid>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::CRUD_EVENTS)?;
+
+    let mut transaction = state.get_transaction().await?;
+    let event = Event::get_from_db_by_id(id, &mut transaction)
+        .await?
+        .context(MissingEventSnafu { id })?;
+
+    let new_id = Event::insert_into_database(
+        AddEvent {
+            name: format!("{} (copy)", event.name),
+            date: event.datetime,
+            location: event.location,
+            extra_info: event.extra_info,
+            associated_staff: event
+                .associated_staff
+                .iter()
+                .map(|staff| staff.id)
+                .collect(),
+            max_capacity: event.max_capacity,
+            recurrence_group: None,
+            tags: event.tags,
+        },
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await.context(CommitTransactionSnafu)?;
+    state
+        .audit(
+            &session,
+            AuditAction::Create,
+            AuditTarget::Event(new_id),
+            Some(&format!("duplicated from {id}")),
+        )
+        .await;
+    state.send_sse_event(SseEvent::CrudEvent);
+
+    internal_get_event_in_detail(State(state), session, Query(IdForm { id: new_id })).await
+}
+
+#[derive(Deserialize)]
+pub struct DeleteEventQuery {
+    id: Uuid,
+    ///whether to delete every occurrence of a repeating event
+    series: Option<bool>,
+}
+
 pub async fn delete_event(
     State(state): State<DenimState>,
     session: DenimSession,
-    Query(IdForm { id }): Query<IdForm>,
+    Query(DeleteEventQuery { id, series }): Query<DeleteEventQuery>,
 ) -> DenimResult<Markup> {
     session.ensure_can(PermissionsTarget::CRUD_EVENTS)?;
 
-    Event::remove_from_database(id, &mut *state.get_connection().await?).await?;
+    let mut conn = state.get_connection().await?;
+    session
+        .ensure_can_for_event(
+            PermissionsTarget::CRUD_EVENTS,
+            id,
+            series.unwrap_or(false),
+            &mut conn,
+        )
+        .await?;
+    let recurrence_group = if series.unwrap_or(false) {
+        Event::get_from_db_by_id(id, &mut conn)
+            .await?
+            .context(MissingEventSnafu { id })?
+            .recurrence_group
+    } else {
+        None
+    };
+    //only hidden for now, so that it can be undone - the sweep in `main` gets rid of it properly
+    Event::soft_delete(id, recurrence_group.is_some(), &mut conn).await?;
+    drop(conn);
+    state
+        .audit(
+            &session,
+            AuditAction::Delete,
+            AuditTarget::Event(id),
+            recurrence_group.is_some().then_some("whole series"),
+        )
+        .await;
     let form = internal_get_add_events_form(State(state.clone()), session).await?;
 
     state.delayed_send_sse_event(SseEvent::CrudEvent, 250);
 
     Ok(html! {
+        div role="status" aria-live="polite" class="bg-gray-700 border border-gray-600 px-4 py-2 rounded flex flex-row justify-between items-center" {
+            span {
+                @if recurrence_group.is_some() {"Event series deleted."} @else {"Event deleted."}
+            }
+            button class="bg-blue-600 hover:bg-blue-800 font-bold py-1 px-3 rounded" hx-post={"/internal/event/" (id) "/restore"} hx-target="#in_focus" {
+                "Undo"
+            }
+        }
+        br;
         (form)
     })
 }
 
+pub async fn internal_post_restore_event(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(id): Path<Uuid>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::CRUD_EVENTS)?;
+
+    let mut conn = state.get_connection().await?;
+    session
+        .ensure_can_for_event(PermissionsTarget::CRUD_EVENTS, id, true, &mut conn)
+        .await?;
+    let restored = Event::restore(id, &mut conn).await?;
+    drop(conn);
+
+    if !restored {
+        let form = internal_get_add_events_form(State(state), session).await?;
+        return Ok(html! {
+            (toast(ToastKind::Error, format!("Couldn't undo - events can only be restored for {EVENT_UNDO_WINDOW_SECS} seconds after being deleted")))
+            (form)
+        });
+    }
+
+    state
+        .audit(&session, AuditAction::Restore, AuditTarget::Event(id), None)
+        .await;
+    state.send_sse_event(SseEvent::CrudEvent);
+
+    internal_get_event_in_detail(State(state), session, Query(IdForm { id })).await
+}
+
+#[derive(Deserialize)]
+pub struct DeleteConfirmQuery {
+    series: Option<bool>,
+}
+
+pub async fn internal_get_delete_event_confirm(
+    session: DenimSession,
+    Path(id): Path<Uuid>,
+    Query(DeleteConfirmQuery { series }): Query<DeleteConfirmQuery>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::CRUD_EVENTS)?;
+
+    Ok(if series.unwrap_or(false) {
+        delete_confirmation(
+            "Delete every occurrence of this event?",
+            "/events",
+            &format!("{{\"id\": \"{id}\", \"series\": true}}"),
+            "/internal/get_event",
+            id,
+        )
+    } else {
+        delete_confirmation(
+            "Are you sure? This also removes everyone's signups.",
+            "/events",
+            &format!("{{\"id\": \"{id}\"}}"),
+            "/internal/get_event",
+            id,
+        )
+    })
+}
+
 pub async fn internal_get_event_in_detail(
     State(state): State<DenimState>,
     session: DenimSession,
@@ -183,7 +640,11 @@ pub async fn internal_get_event_in_detail(
     let can_view_sensitives = session.can(PermissionsTarget::VIEW_SENSITIVE_DETAILS);
     let can_delete = session.can(PermissionsTarget::CRUD_EVENTS);
 
-    let dlc = state.config().date_locale_config().get()?;
+    let dlc = state
+        .config()
+        .date_locale_config()
+        .get()?
+        .for_user(session.user.as_ref());
 
     Ok(html! {
         div hx-get="/internal/get_event" hx-target="#in_focus" hx-vals={"{\"id\": \"" (id) "\"}" } hx-trigger="sse:crud_event" {
@@ -191,6 +652,7 @@ pub async fn internal_get_event_in_detail(
                 a class="hover:text-blue-300 underline" target="_blank" href={"/event/" (id)} {(event.name)}
             }))
             div class="p-4" {
+                (tag_chips(&event.tags))
                 @if let Some(location) = event.location {
                     p class="text-gray-200 font-semibold" {
                         "Location: "
@@ -199,13 +661,22 @@ pub async fn internal_get_event_in_detail(
                 }
                 p class="text-gray-200 font-semibold" {
                     "Time: "
-                    span class="font-medium" {(dlc.long_ymdet(&event.datetime)?)}
+                    span class="font-medium" {(dlc.long_ymdet(&event.datetime))}
+                    span class="text-gray-400 italic" {" (" (dlc.relative(&event.datetime)) ")"}
+                }
+                @if let Some(event_tz) = dlc.differing_timezone(&event.datetime) {
+                    p class="text-gray-400 text-sm" {
+                        "Local Time (" span class="italic" {(event_tz)} "): " (dlc.format(&event.datetime, DateFormat::ShortYMDET, false))
+                    }
                 }
                 @if can_view_sensitives {
-                    @if let Some(staff) = event.associated_staff_member {
+                    @if !event.associated_staff.is_empty() {
                         p class="text-gray-200 font-semibold" {
-                            "Staff Member: "
-                            span class="font-medium" {(staff)}
+                            "Staff: "
+                            @for (i, staff) in event.associated_staff.into_iter().enumerate() {
+                                @if i > 0 {", "}
+                                span class="font-medium" {(staff)}
+                            }
                         }
                     }
                 }
@@ -219,14 +690,35 @@ pub async fn internal_get_event_in_detail(
                     "Signed Up: "
                     span class="font-medium" {(event.signed_up.len())}
                 }
+                @if let Some(max_capacity) = event.max_capacity {
+                    p class="text-gray-200 font-semibold" {
+                        "Capacity: "
+                        span class="font-medium" {(event.signed_up.len() + event.verified.len()) "/" (max_capacity)}
+                    }
+                }
                 p class="text-gray-200 font-semibold" {
                     "Verified: "
                     span class="font-medium" {(event.verified.len())}
                 }
                 @if can_delete {
                     br;
-                    button class="bg-red-600 hover:bg-red-800 font-bold py-2 px-4 rounded" hx-delete="/events" hx-vals={"{\"id\": \"" (id) "\"}" } hx-target="#in_focus" {
-                        "Delete event"
+                    button class="bg-blue-600 hover:bg-blue-800 font-bold py-2 px-4 rounded mr-2" hx-get={"/internal/event/" (id) "/edit_form"} hx-target="#in_focus" {
+                        "Edit event"
+                    }
+                    button class="bg-blue-600 hover:bg-blue-800 font-bold py-2 px-4 rounded mr-2" hx-post={"/internal/event/" (id) "/duplicate"} hx-target="#in_focus" {
+                        "Duplicate"
+                    }
+                    @if event.recurrence_group.is_some() {
+                        button class="bg-red-600 hover:bg-red-800 font-bold py-2 px-4 rounded mr-2" hx-get={"/internal/event/" (id) "/delete_confirm"} hx-target="this" hx-swap="outerHTML" {
+                            "Delete this occurrence"
+                        }
+                        button class="bg-red-600 hover:bg-red-800 font-bold py-2 px-4 rounded" hx-get={"/internal/event/" (id) "/delete_confirm?series=true"} hx-target="this" hx-swap="outerHTML" {
+                            "Delete series"
+                        }
+                    } @else {
+                        button class="bg-red-600 hover:bg-red-800 font-bold py-2 px-4 rounded" hx-get={"/internal/event/" (id) "/delete_confirm"} hx-target="this" hx-swap="outerHTML" {
+                            "Delete event"
+                        }
                     }
                 }
             }
@@ -238,75 +730,420 @@ pub async fn internal_get_event_in_detail(
 pub struct FuturePastFilterQuery {
     pub future: Option<String>,
     pub past: Option<String>,
+    pub tag: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub search_in: Option<EventSearchScope>,
+    pub sort: Option<EventSortColumn>,
+    pub dir: Option<SortDirection>,
+}
+
+///parses a `YYYY-MM-DD` date from the range inputs into the start of that day, with an empty input meaning no bound
+///
+///if `next_day` is set, then it goes to the start of the following day so that the whole day is included
+fn parse_range_date(
+    input: Option<&str>,
+    tz: &TimeZone,
+    next_day: bool,
+    errors: &mut Vec<String>,
+) -> Option<Zoned> {
+    let input = input.filter(|input| !input.is_empty())?;
+
+    let parsed = input.parse::<Date>().and_then(|date| {
+        let date = if next_day { date.tomorrow()? } else { date };
+        date.to_zoned(tz.clone())
+    });
+    match parsed {
+        Ok(zoned) => Some(zoned),
+        Err(e) => {
+            errors.push(format!("Invalid date {input:?}: {e}"));
+            None
+        }
+    }
 }
 
+#[allow(clippy::too_many_lines)]
 pub async fn internal_get_events(
     State(state): State<DenimState>,
-    Query(FuturePastFilterQuery { future, past }): Query<FuturePastFilterQuery>,
+    session: DenimSession,
+    Query(FuturePastFilterQuery {
+        future,
+        past,
+        tag,
+        from,
+        to,
+        search_in,
+        sort,
+        dir,
+    }): Query<FuturePastFilterQuery>,
 ) -> DenimResult<Markup> {
-    let dlc = state.config().date_locale_config().get()?;
+    let dlc = state
+        .config()
+        .date_locale_config()
+        .get()?
+        .for_user(session.user.as_ref());
+    let tag = tag.filter(|tag| !tag.is_empty());
+    let search_in = search_in.unwrap_or_default();
+    let dir = dir.unwrap_or_default();
 
-    let event_to_row = |evt: Event| {
-        Ok::<_, DenimError>([
-            html! {
-                a class="hover:text-blue-300 underline" hx-get="/internal/get_event" hx-target="#in_focus" hx-vals={"{\"id\": \"" (evt.id) "\"}" } {
-                    (evt.name)
-                }
-            },
-            { PreEscaped(dlc.short_ymdet(&evt.datetime)?) },
-            html! {
-                @if let Some(location) = evt.location {
-                    p {(location)}
-                } @else {
-                    p class="italic" {"-"}
-                }
-            },
-        ])
+    let mut range_errors = vec![];
+    let filter = EventsFilter {
+        tag: tag.as_deref(),
+        from: parse_range_date(from.as_deref(), &dlc.timezone, false, &mut range_errors),
+        to: parse_range_date(to.as_deref(), &dlc.timezone, true, &mut range_errors),
+        search: None,
+        search_scope: search_in,
+        staff_id: None,
+        participant_id: None,
+        sort,
+        sort_direction: dir,
     };
+    let is_filtered = filter.tag.is_some() || filter.from.is_some() || filter.to.is_some();
+    let can_add_events = session.can(PermissionsTarget::CRUD_EVENTS);
+    let all_tags = Event::get_all_tags(&mut *state.get_connection().await?).await?;
 
-    let future_events: Vec<_> = Event::get_future_events(&state)
+    let future_filter = EventsFilter {
+        search: future.as_deref().filter(|search| !search.is_empty()),
+        ..filter.clone()
+    };
+    let future_events: Vec<_> = Event::get_future_events(&state, &future_filter)
         .await?
         .into_iter()
-        .filter(|event| {
-            future
-                .as_ref()
-                .is_none_or(|filter| event.name.contains(filter))
-        })
-        .map(event_to_row)
-        .collect::<Result<_, _>>()?;
-    let past_events: Vec<_> = Event::get_past_events(&state)
+        .map(|event| event_to_row(&dlc, event))
+        .collect();
+    let past_filter = EventsFilter {
+        search: past.as_deref().filter(|search| !search.is_empty()),
+        ..filter
+    };
+    let past_events: Vec<_> = Event::get_past_events(&state, &past_filter)
         .await?
         .into_iter()
-        .filter(|event| {
-            past.as_ref()
-                .is_none_or(|filter| event.name.contains(filter))
-        })
-        .map(event_to_row)
-        .collect::<Result<_, _>>()?;
+        .map(|event| event_to_row(&dlc, event))
+        .collect();
+
+    let (future_events_empty, past_events_empty) =
+        (future_events.is_empty(), past_events.is_empty());
+    let (future_is_filtered, past_is_filtered) = (
+        is_filtered || future_filter.search.is_some(),
+        is_filtered || past_filter.search.is_some(),
+    );
+
+    let table_sort = TableSort {
+        url: "/internal/get_events",
+        target: "#all_events",
+        include: "[name='future'],[name='past'],[name='tag'],[name='from'],[name='to'],[name='search_in']",
+        current: sort.map(EventSortColumn::as_str),
+        direction: dir,
+    };
+    let sortable_titles = [
+        ("Name", Some(EventSortColumn::Name.as_str())),
+        ("Date", Some(EventSortColumn::Date.as_str())),
+        ("Location", Some(EventSortColumn::Location.as_str())),
+    ];
 
     Ok(html! {
         div class="flex flex-col" {
-            (table(
+            @if let Some(sort) = sort {
+                input type="hidden" name="sort" value=(sort.as_str());
+            }
+            input type="hidden" name="dir" value=(dir.as_str());
+            @if !range_errors.is_empty() {
+                (errors_list(Some("Couldn't filter by date"), range_errors.into_iter()))
+            }
+            div class="flex flex-row rounded p-4 m-4 space-x-4" {
+                label class="flex flex-col text-sm font-bold text-gray-300" {
+                    "From"
+                    input value=[from] type="date" name="from" hx-get="/internal/get_events" hx-include="[name='future'],[name='past'],[name='tag'],[name='to'],[name='search_in'],[name='sort'],[name='dir']" hx-trigger="change" hx-target="#all_events" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600";
+                }
+                label class="flex flex-col text-sm font-bold text-gray-300" {
+                    "To"
+                    input value=[to] type="date" name="to" hx-get="/internal/get_events" hx-include="[name='future'],[name='past'],[name='tag'],[name='from'],[name='search_in'],[name='sort'],[name='dir']" hx-trigger="change" hx-target="#all_events" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600";
+                }
+            }
+            div class="flex rounded p-4 m-4" {
+                label class="flex flex-col w-full text-sm font-bold text-gray-300" {
+                    "Search In"
+                    select name="search_in" hx-get="/internal/get_events" hx-include="[name='future'],[name='past'],[name='tag'],[name='from'],[name='to'],[name='sort'],[name='dir']" hx-target="#all_events" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600" {
+                        @for scope in EventSearchScope::ALL {
+                            option value=(scope.as_str()) selected[scope == search_in] {(scope.label())}
+                        }
+                    }
+                }
+            }
+            @if !all_tags.is_empty() {
+                div class="flex rounded p-4 m-4" {
+                    select name="tag" hx-get="/internal/get_events" hx-include="[name='future'],[name='past'],[name='from'],[name='to'],[name='search_in'],[name='sort'],[name='dir']" hx-target="#all_events" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600" {
+                        option value="" {"All Tags"}
+                        @for possible_tag in all_tags {
+                            @let selected = tag.as_ref() == Some(&possible_tag);
+                            option value=(possible_tag) selected[selected] {(possible_tag)}
+                        }
+                    }
+                }
+            }
+            (sortable_table(
                 html! {
                     (title("Future Events"))
                     div class="flex rounded p-4 m-4" {
-                        input value=[future] type="search" name="future" placeholder="Begin Typing To Search Events..." hx-get="/internal/get_events" hx-trigger="input changed delay:500ms, keyup[key=='Enter']" hx-target="#all_events" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600";
+                        input value=[future] type="search" name="future" placeholder="Begin Typing To Search Events..." hx-get="/internal/get_events" hx-include="[name='past'],[name='tag'],[name='from'],[name='to'],[name='search_in'],[name='sort'],[name='dir']" hx-trigger="input changed delay:500ms, keyup[key=='Enter']" hx-target="#all_events" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600";
                     }
                 },
-                ["Name", "Date", "Location"],
+                sortable_titles,
+                &table_sort,
                 future_events,
             ))
+            @if future_events_empty {
+                @if future_is_filtered {
+                    (empty_state("No upcoming events match your search."))
+                } @else if can_add_events {
+                    (empty_state("No upcoming events yet - add one above."))
+                } @else {
+                    (empty_state("No upcoming events yet - check back later."))
+                }
+            }
             div class="h-4 bg-transparent" {""}
-            (table(
+            (sortable_table(
                 html! {
                     (title("Past Events"))
                     div class="flex rounded p-4 m-4" {
-                        input value=[past] type="search" name="past" placeholder="Begin Typing To Search Events..." hx-get="/internal/get_events" hx-trigger="input changed delay:500ms, keyup[key=='Enter']" hx-target="#all_events" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600";
+                        input value=[past] type="search" name="past" placeholder="Begin Typing To Search Events..." hx-get="/internal/get_events" hx-include="[name='future'],[name='tag'],[name='from'],[name='to'],[name='search_in'],[name='sort'],[name='dir']" hx-trigger="input changed delay:500ms, keyup[key=='Enter']" hx-target="#all_events" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600";
                     }
                 },
-                ["Name", "Date", "Location"],
+                sortable_titles,
+                &table_sort,
                 past_events,
             ))
+            @if past_events_empty {
+                @if past_is_filtered {
+                    (empty_state("No past events match your search."))
+                } @else {
+                    (empty_state("No past events yet."))
+                }
+            }
         }
     })
 }
+
+///the name/date/location columns shared by every list of events
+fn event_to_row(dlc: &DateLocaleConfig, evt: Event) -> [Markup; 3] {
+    [
+        html! {
+            a class="hover:text-blue-300 underline" hx-get="/internal/get_event" hx-target="#in_focus" hx-vals={"{\"id\": \"" (evt.id) "\"}" } {
+                (evt.name)
+            }
+            (tag_chips(&evt.tags))
+        },
+        html! {
+            (PreEscaped(dlc.short_ymdet(&evt.datetime)))
+            br;
+            span class="text-gray-400 text-sm italic" {(dlc.relative(&evt.datetime))}
+            @if let Some(event_tz) = dlc.differing_timezone(&evt.datetime) {
+                br;
+                span class="text-gray-400 text-sm" {(dlc.format(&evt.datetime, DateFormat::ShortYMDET, false)) " local (" (event_tz) ")"}
+            }
+        },
+        html! {
+            @if let Some(location) = evt.location {
+                p {(location)}
+            } @else {
+                p class="italic" {"-"}
+            }
+        },
+    ]
+}
+
+///every event the logged-in staff member is running
+pub async fn get_my_events(
+    State(state): State<DenimState>,
+    session: DenimSession,
+) -> DenimResult<Markup> {
+    let staff_id = session.user.as_ref().context(UnableToFindUserInfoSnafu)?.id;
+    let dlc = state
+        .config()
+        .date_locale_config()
+        .get()?
+        .for_user(session.user.as_ref());
+
+    let filter = EventsFilter {
+        staff_id: Some(staff_id),
+        ..EventsFilter::default()
+    };
+    let upcoming_events: Vec<_> = Event::get_future_events(&state, &filter)
+        .await?
+        .into_iter()
+        .map(|event| event_to_row(&dlc, event))
+        .collect();
+    let past_events: Vec<_> = Event::get_past_events(&state, &filter)
+        .await?
+        .into_iter()
+        .map(|event| event_to_row(&dlc, event))
+        .collect();
+
+    Ok(state.render(session, html! {
+        div class="mx-auto bg-gray-800 p-8 rounded shadow-md max-w-4xl w-full flex flex-col space-y-4" {
+            div hx-ext="sse" sse-connect="/sse_feed" class="container flex flex-row justify-center space-x-4" {
+                div class="flex flex-col" {
+                    (table(
+                        title("My Upcoming Events"),
+                        ["Name", "Date", "Location"],
+                        upcoming_events,
+                    ))
+                    div class="h-4 bg-transparent" {""}
+                    (table(
+                        title("My Past Events"),
+                        ["Name", "Date", "Location"],
+                        past_events,
+                    ))
+                }
+                div id="in_focus" {}
+            }
+        }
+    }))
+}
+
+///the upcoming events a student is signed up to, with the same sign-up buttons as the event pages
+pub async fn get_my_signups(
+    State(state): State<DenimState>,
+    session: DenimSession,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::SIGN_SELF_UP)?;
+    let student_id = session.user.as_ref().context(UnableToFindUserInfoSnafu)?.id;
+    let dlc = state
+        .config()
+        .date_locale_config()
+        .get()?
+        .for_user(session.user.as_ref());
+
+    let filter = EventsFilter {
+        participant_id: Some(student_id),
+        ..EventsFilter::default()
+    };
+    let signed_up_events: Vec<_> = Event::get_future_events(&state, &filter)
+        .await?
+        .into_iter()
+        .map(|event| {
+            let id = event.id;
+            let [name, date, location] = event_to_row(&dlc, event);
+            //the button knows whether they're signed up, waitlisted or verified, and keeps itself up to date
+            let sign_up_button = html! {
+                div hx-get={"/internal/event/" (id) "/signup_button"} hx-trigger="load" hx-swap="outerHTML" {}
+            };
+            [name, date, location, sign_up_button]
+        })
+        .collect();
+
+    Ok(state.render(session, html! {
+        div class="mx-auto bg-gray-800 p-8 rounded shadow-md max-w-4xl w-full flex flex-col space-y-4" {
+            div hx-ext="sse" sse-connect="/sse_feed" class="container flex flex-row justify-center space-x-4" {
+                @if signed_up_events.is_empty() {
+                    (empty_state(html! {
+                        "You're not signed up to any upcoming events - "
+                        a href="/events" class="underline hover:text-blue-300" {"have a look at what's on"}
+                        "."
+                    }))
+                } @else {
+                    (table(
+                        title("My Sign-Ups"),
+                        ["Name", "Date", "Location", "Status"],
+                        signed_up_events,
+                    ))
+                }
+                div id="in_focus" {}
+            }
+        }
+    }))
+}
+
+pub async fn get_events_ics(State(state): State<DenimState>) -> DenimResult<Response> {
+    let events = Event::get_future_events(&state, &EventsFilter::default()).await?;
+
+    let dtstamp = Timestamp::now().strftime("%Y%m%dT%H%M%SZ");
+
+    let mut ics = String::new();
+    ics_line(&mut ics, "BEGIN:VCALENDAR");
+    ics_line(&mut ics, "VERSION:2.0");
+    ics_line(&mut ics, "PRODID:-//denim//events//EN");
+    ics_line(&mut ics, "CALSCALE:GREGORIAN");
+
+    for event in events {
+        ics_line(&mut ics, "BEGIN:VEVENT");
+        ics_line(&mut ics, &format!("UID:{}@denim", event.id));
+        ics_line(&mut ics, &format!("DTSTAMP:{dtstamp}"));
+        ics_line(&mut ics, &ics_datetime("DTSTART", &event.datetime));
+        ics_line(&mut ics, &format!("SUMMARY:{}", ics_escape(&event.name)));
+        if let Some(location) = event.location {
+            ics_line(&mut ics, &format!("LOCATION:{}", ics_escape(&location)));
+        }
+        if let Some(extra_info) = event.extra_info {
+            ics_line(&mut ics, &format!("DESCRIPTION:{}", ics_escape(&extra_info)));
+        }
+        ics_line(&mut ics, "END:VEVENT");
+    }
+
+    ics_line(&mut ics, "END:VCALENDAR");
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/calendar; charset=utf-8"),
+            (header::CONTENT_DISPOSITION, "inline; filename=\"events.ics\""),
+        ],
+        ics,
+    )
+        .into_response())
+}
+
+///always in UTC - a `TZID` would need a matching `VTIMEZONE` block, which some clients (looking at you, Outlook) won't do without
+fn ics_datetime(property: &str, zoned: &Zoned) -> String {
+    format!(
+        "{property}:{}",
+        zoned.timestamp().strftime("%Y%m%dT%H%M%SZ")
+    )
+}
+
+fn ics_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+///adds a line, folding it so that no line is longer than 75 octets as required by RFC 5545
+fn ics_line(ics: &mut String, line: &str) {
+    let mut current_len = 0;
+    for ch in line.chars() {
+        if current_len + ch.len_utf8() > 75 {
+            ics.push_str("\r\n ");
+            current_len = 1;
+        }
+        ics.push(ch);
+        current_len += ch.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ics_datetime;
+    use jiff::{civil::date, tz::TimeZone};
+
+    #[test]
+    fn ics_datetimes_are_written_in_utc() {
+        let london = TimeZone::get("Europe/London").unwrap();
+        let summer = date(2025, 7, 1)
+            .at(18, 30, 0, 0)
+            .to_zoned(london.clone())
+            .unwrap();
+        assert_eq!(ics_datetime("DTSTART", &summer), "DTSTART:20250701T173000Z");
+
+        let winter = date(2025, 12, 1).at(18, 30, 0, 0).to_zoned(london).unwrap();
+        assert_eq!(ics_datetime("DTSTART", &winter), "DTSTART:20251201T183000Z");
+    }
+}
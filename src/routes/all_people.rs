@@ -1,19 +1,32 @@
 use crate::{
-    auth::{AuthUtilities, DenimSession, PermissionsTarget},
+    auth::{AuthUtilities, DenimSession, PermissionsTarget, add_password},
     data::{
-        DataType, FilterQuery, IdForm,
+        DataType, IdForm, SortDirection,
+        audit_log::{AuditAction, AuditTarget},
+        clamp_page,
+        role::Role,
         student_groups::{HouseGroup, TutorGroup},
-        user::{AddPerson, AddUserKind, FullUserNameDisplay, User, UserKind, UsernameDisplay},
+        user::{
+            AddPerson, AddUserKind, FullUserNameDisplay, PeopleSortColumn, User, UserKind,
+            UsernameDisplay,
+        },
+    },
+    error::{
+        CommitTransactionSnafu, DenimError, DenimResult, MissingHouseGroupSnafu,
+        MissingTutorGroupSnafu, MissingUserSnafu, NoHousesOrNoTutorGroupsSnafu, ParseHouseIdSnafu,
+        ParseUuidSnafu,
+    },
+    maud_conveniences::{
+        Email, TableSort, delete_confirmation, empty_state, errors_list, form_element,
+        simple_form_element, subtitle, title,
     },
-    error::{DenimError, DenimResult, NoHousesOrNoTutorGroupsSnafu},
-    maud_conveniences::{Email, errors_list, form_element, simple_form_element, subtitle, title},
     routes::sse::SseEvent,
     state::DenimState,
 };
 use axum::{
     Form,
     body::Body,
-    extract::{Query, State},
+    extract::{Path, Query, State},
     http::Response,
     response::{IntoResponse, Redirect},
 };
@@ -21,6 +34,7 @@ use email_address::EmailAddress;
 use maud::{Markup, html};
 use secrecy::{ExposeSecret, SecretString};
 use serde::Deserialize;
+use snafu::{OptionExt, ResultExt};
 use std::{collections::HashMap, str::FromStr};
 use uuid::Uuid;
 
@@ -49,7 +63,7 @@ pub async fn internal_get_add_dev_or_staff_form(
     session: DenimSession,
     Query(IsStaffQuery { mut is_staff }): Query<IsStaffQuery>,
 ) -> DenimResult<Markup> {
-    session.ensure_can(PermissionsTarget::CRUD_USERS)?;
+    session.ensure_can_for_house(PermissionsTarget::CRUD_USERS, None)?;
 
     if !session.can(PermissionsTarget::CRUD_ADMINS) {
         is_staff = true;
@@ -90,7 +104,13 @@ pub async fn internal_get_add_student_form(
 ) -> DenimResult<Markup> {
     session.ensure_can(PermissionsTarget::CRUD_USERS)?;
 
-    let tutor_groups = TutorGroup::get_all(&state).await?;
+    let tutor_groups: Vec<_> = TutorGroup::get_all(&state)
+        .await?
+        .into_iter()
+        .filter(|tutor_group| {
+            session.can_for_house(PermissionsTarget::CRUD_USERS, Some(tutor_group.house_id))
+        })
+        .collect();
     let houses = HouseGroup::get_all(&state).await?;
 
     let house_names_by_id: HashMap<i32, String> =
@@ -144,7 +164,7 @@ pub async fn internal_put_new_staff_or_dev(
     session: DenimSession,
     Form(form): Form<NewStaffOrDevForm>,
 ) -> DenimResult<Markup> {
-    session.ensure_can(PermissionsTarget::CRUD_USERS)?;
+    session.ensure_can_for_house(PermissionsTarget::CRUD_USERS, None)?;
 
     let is_staff = form.is_staff && session.can(PermissionsTarget::CRUD_ADMINS);
     let user_kind = if is_staff {
@@ -164,6 +184,13 @@ pub async fn internal_put_new_staff_or_dev(
         }
     };
 
+    if User::email_is_taken(&email, None, &mut *state.get_connection().await?).await? {
+        return Ok(errors_list(
+            Some("Email Errors"),
+            std::iter::once("Provided email is already in use"),
+        ));
+    }
+
     let password = if form.generate_password.is_some_and(|gp| &gp == "on") {
         Some(
             state
@@ -185,9 +212,20 @@ pub async fn internal_put_new_staff_or_dev(
         password: password.clone(),
         current_password_is_default: true,
         user_kind,
+        bcrypt_cost: state.config().security_config().bcrypt_cost,
     };
 
-    User::insert_into_database(add_person_form, &mut *state.get_connection().await?).await?;
+    let detail = add_person_form.email.to_string();
+    let id =
+        User::insert_into_database(add_person_form, &mut *state.get_connection().await?).await?;
+    state
+        .audit(
+            &session,
+            AuditAction::Create,
+            AuditTarget::Person(id),
+            Some(&detail),
+        )
+        .await;
     state.send_sse_event(SseEvent::CrudPerson);
 
     internal_get_add_dev_or_staff_form(session, Query(IsStaffQuery { is_staff })).await
@@ -208,6 +246,13 @@ pub async fn internal_put_new_student(
     Form(form): Form<NewStudentForm>,
 ) -> DenimResult<Markup> {
     session.ensure_can(PermissionsTarget::CRUD_USERS)?;
+    let tutor_group =
+        TutorGroup::get_from_db_by_id(form.tutor_group, &mut *state.get_connection().await?)
+            .await?
+            .context(MissingTutorGroupSnafu {
+                id: form.tutor_group,
+            })?;
+    session.ensure_can_for_house(PermissionsTarget::CRUD_USERS, Some(tutor_group.house_id))?;
 
     let password = if form.generate_password.is_some_and(|gp| &gp == "on") {
         Some(
@@ -233,6 +278,13 @@ pub async fn internal_put_new_student(
         }
     };
 
+    if User::email_is_taken(&email, None, &mut *state.get_connection().await?).await? {
+        return Ok(errors_list(
+            Some("Email Errors"),
+            std::iter::once("Provided email is already in use"),
+        ));
+    }
+
     let add_person_form = AddPerson {
         first_name: form.first_name,
         pref_name: form.pref_name,
@@ -243,10 +295,20 @@ pub async fn internal_put_new_student(
         user_kind: AddUserKind::Student {
             tutor_group: form.tutor_group,
         },
+        bcrypt_cost: state.config().security_config().bcrypt_cost,
     };
 
+    let detail = add_person_form.email.to_string();
     let id =
         User::insert_into_database(add_person_form, &mut *state.get_connection().await?).await?;
+    state
+        .audit(
+            &session,
+            AuditAction::Create,
+            AuditTarget::Person(id),
+            Some(&detail),
+        )
+        .await;
     state.send_sse_event(SseEvent::CrudPerson);
 
     internal_get_person_in_detail(
@@ -260,6 +322,55 @@ pub async fn internal_put_new_student(
     .await
 }
 
+pub async fn internal_post_person_reset_password(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(id): Path<Uuid>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::CRUD_USERS)?;
+
+    let mut conn = state.get_connection().await?;
+    let person = User::get_from_db_by_id(id, &mut conn)
+        .await?
+        .context(MissingUserSnafu { id })?;
+    if matches!(person.kind, UserKind::Admin) {
+        session.ensure_can(PermissionsTarget::CRUD_ADMINS)?;
+    }
+    session.ensure_can_for_house(PermissionsTarget::CRUD_USERS, person.house_id())?;
+
+    let password: SecretString = state.config().auth_config().get()?.generate()?.into();
+    add_password(
+        id.into(),
+        password.clone(),
+        state.config().security_config().bcrypt_cost,
+        &mut conn,
+        true,
+    )
+    .await?;
+    drop(conn);
+
+    state
+        .audit(
+            &session,
+            AuditAction::Edit,
+            AuditTarget::Person(id),
+            Some("regenerated default password"),
+        )
+        .await;
+    state.send_sse_event(SseEvent::CrudPerson);
+    state.send_sse_event_to_user(id, SseEvent::PasswordReset);
+
+    internal_get_person_in_detail(
+        State(state),
+        session,
+        Query(InDetailForm {
+            id,
+            new_password: Some(password),
+        }),
+    )
+    .await
+}
+
 pub async fn delete_person(
     State(state): State<DenimState>,
     session: DenimSession,
@@ -267,16 +378,111 @@ pub async fn delete_person(
 ) -> DenimResult<Markup> {
     session.ensure_can(PermissionsTarget::CRUD_USERS)?;
 
-    User::remove_from_database(id, &mut *state.get_connection().await?).await?;
+    let mut conn = state.get_connection().await?;
+    let person = User::get_from_db_by_id(id, &mut conn)
+        .await?
+        .context(MissingUserSnafu { id })?;
+    session.ensure_can_for_house(PermissionsTarget::CRUD_USERS, person.house_id())?;
+
+    User::remove_from_database(id, &mut conn).await?;
+    drop(conn);
+    state
+        .audit(&session, AuditAction::Delete, AuditTarget::Person(id), None)
+        .await;
     state.send_sse_event(SseEvent::CrudPerson);
 
     Ok(html! {})
 }
 
+pub async fn internal_get_delete_person_confirm(
+    session: DenimSession,
+    Path(id): Path<Uuid>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::CRUD_USERS)?;
+
+    Ok(delete_confirmation(
+        "Are you sure? This can't be undone.",
+        "/people",
+        &format!("{{\"id\": \"{id}\"}}"),
+        "/internal/get_person",
+        id,
+    ))
+}
+
+///takes any number of `ids`, and only deletes them if every single one can be deleted
+pub async fn delete_people_bulk(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Query(params): Query<Vec<(String, String)>>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::CRUD_USERS)?;
+
+    let ids = params
+        .into_iter()
+        .filter(|(key, _)| key == "ids")
+        .map(|(_, id)| Uuid::try_parse(&id).context(ParseUuidSnafu { original: id }))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut transaction = state.get_transaction().await?;
+
+    let people = User::get_many_by_ids(&ids, &mut transaction).await?;
+    if let Some(missing) = ids
+        .iter()
+        .find(|id| !people.iter().any(|person| &person.id == *id))
+    {
+        return Err(DenimError::MissingUser { id: *missing });
+    }
+    for person in &people {
+        session.ensure_can(match person.kind {
+            UserKind::Admin => PermissionsTarget::CRUD_ADMINS,
+            _ => PermissionsTarget::CRUD_USERS,
+        })?;
+        session.ensure_can_for_house(PermissionsTarget::CRUD_USERS, person.house_id())?;
+    }
+
+    //if any of these fail, the transaction gets dropped and so rolled back
+    for person in &people {
+        User::remove_from_database(person.id, &mut transaction).await?;
+    }
+    transaction.commit().await.context(CommitTransactionSnafu)?;
+
+    for person in &people {
+        state
+            .audit(
+                &session,
+                AuditAction::Delete,
+                AuditTarget::Person(person.id),
+                Some("bulk delete"),
+            )
+            .await;
+    }
+    state.send_sse_event(SseEvent::CrudPerson);
+
+    Ok(html! {
+        p class="text-gray-300" {"Deleted " (people.len()) " people."}
+    })
+}
+
+const PEOPLE_PAGE_SIZE: i64 = 48;
+
+#[derive(Deserialize)]
+pub struct PeopleQuery {
+    pub filter: Option<String>,
+    pub page: Option<i64>,
+    pub sort: Option<PeopleSortColumn>,
+    pub dir: Option<SortDirection>,
+}
+
+#[allow(clippy::too_many_lines)]
 pub async fn internal_get_people(
     State(state): State<DenimState>,
     session: DenimSession,
-    Query(FilterQuery { filter }): Query<FilterQuery>,
+    Query(PeopleQuery {
+        filter,
+        page,
+        sort,
+        dir,
+    }): Query<PeopleQuery>,
 ) -> DenimResult<Markup> {
     session.ensure_can(PermissionsTarget::VIEW_SENSITIVE_DETAILS)?;
 
@@ -287,34 +493,71 @@ pub async fn internal_get_people(
             Some(filter)
         }
     });
-
-    let (staff, students, admins) = if let Some(filter) = &filter {
-        (
-            User::get_all_staff_with_filter(&state, filter).await?,
-            User::get_all_students_with_filter(&state, filter).await?,
-            User::get_all_admins_with_filter(&state, filter).await?,
-        )
+    let page = clamp_page(page.unwrap_or(0), PEOPLE_PAGE_SIZE);
+    let sort = sort.unwrap_or_default();
+    let dir = dir.unwrap_or_default();
+    //staff + admins have no house, so they stay in name order
+    let name_dir = if sort == PeopleSortColumn::Name {
+        dir
     } else {
-        (
-            User::get_all_staff(&state).await?,
-            User::get_all_students(&state).await?,
-            User::get_all_admins(&state).await?,
-        )
+        SortDirection::Asc
     };
 
+    let (staff, more_staff) =
+        User::get_staff_page(&state, filter.as_deref(), page, PEOPLE_PAGE_SIZE, name_dir).await?;
+    let (students, more_students) = User::get_students_page(
+        &state,
+        filter.as_deref(),
+        page,
+        PEOPLE_PAGE_SIZE,
+        sort,
+        dir,
+        session.house_scope(),
+    )
+    .await?;
+    let (admins, more_admins) =
+        User::get_admins_page(&state, filter.as_deref(), page, PEOPLE_PAGE_SIZE, name_dir).await?;
+    let has_next_page = more_staff || more_students || more_admins;
+
     let can_change_users = session.can(PermissionsTarget::CRUD_USERS);
+    //house-scoped staff can still add students, but only to their own house
+    let can_change_everyone = session.can_for_house(PermissionsTarget::CRUD_USERS, None);
     let can_change_admins = session.can(PermissionsTarget::CRUD_ADMINS);
+    let can_import = session.can(PermissionsTarget::IMPORT_CSVS);
+    let is_filtered = filter.is_some();
+    let (no_staff, no_students, no_admins) =
+        (staff.is_empty(), students.is_empty(), admins.is_empty());
+
+    //so that the SSE refresh + the prev/next buttons keep the search and sort
+    let page_vals = |page: i64| {
+        serde_json::json!({"page": page, "filter": filter, "sort": sort.as_str(), "dir": dir.as_str()})
+            .to_string()
+    };
+    let sort_vals = serde_json::json!({"sort": sort.as_str(), "dir": dir.as_str()}).to_string();
+    let table_sort = TableSort {
+        url: "/internal/get_people",
+        target: "#all_people",
+        include: "[name='filter']",
+        current: Some(sort.as_str()),
+        direction: dir,
+    };
 
     Ok(html! {
-        div hx-get="/internal/get_people" hx-trigger="sse:crud_person" class="container mx-auto flex flex-col space-y-8" {
+        div hx-get="/internal/get_people" hx-trigger="sse:crud_person" hx-vals=(page_vals(page)) hx-disinherit="hx-vals" class="container mx-auto flex flex-col space-y-8" {
             div class="flex rounded p-4 m-4" {
-                input value=[filter] type="search" name="filter" placeholder="Begin Typing To Search Users..." hx-get="/internal/get_people" hx-trigger="input changed delay:500ms, keyup[key=='Enter']" hx-target="#all_people" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600";
+                input value=[filter.as_deref()] type="search" name="filter" placeholder="Begin Typing To Search Users..." hx-get="/internal/get_people" hx-vals=(sort_vals) hx-trigger="input changed delay:500ms, keyup[key=='Enter']" hx-target="#all_people" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600";
+            }
+            div class="flex flex-row items-center space-x-4 px-4 text-gray-300" {
+                p {"Sort by:"}
+                @for column in PeopleSortColumn::ALL {
+                    (table_sort.header(column.label(), column.as_str()))
+                }
             }
 
             div {
                 div class="flex flex-row items-center justify-between" {
                     (subtitle("Staff"))
-                    @if can_change_users {
+                    @if can_change_everyone {
                         button class="bg-blue-600 hover:bg-blue-800 font-bold py-2 px-4 rounded" hx-get="/internal/people/new_staff_or_dev_form?is_staff=true" hx-target="#in_focus" {
                             "Add new Staff Member"
                         }
@@ -327,6 +570,9 @@ pub async fn internal_get_people(
                         }
                     }
                 }
+                @if no_staff {
+                    (empty_people_section("staff", is_filtered, can_import))
+                }
             }
             div {
                 div class="flex flex-row items-center justify-between" {
@@ -344,34 +590,154 @@ pub async fn internal_get_people(
                         }
                     }
                 }
+                @if no_admins {
+                    (empty_people_section("admins", is_filtered, false))
+                }
             }
             div {
                 div class="flex flex-row items-center justify-between" {
                     (subtitle("Students"))
+                    @if can_change_everyone {
+                        button class="bg-blue-600 hover:bg-blue-800 font-bold py-2 px-4 rounded" hx-get="/internal/houses" hx-target="#in_focus" {
+                            "Manage Houses"
+                        }
+                        button class="bg-blue-600 hover:bg-blue-800 font-bold py-2 px-4 rounded" hx-get="/internal/tutor_groups" hx-target="#in_focus" {
+                            "Manage Tutor Groups"
+                        }
+                    }
                     @if can_change_users {
                         button class="bg-blue-600 hover:bg-blue-800 font-bold py-2 px-4 rounded" hx-get="/internal/people/new_student_form" hx-target="#in_focus" {
                             "Add new Student"
                         }
                     }
                 }
-                div class="grid grid-cols-1 sm:grid-cols-2 md:grid-cols-3 lg:grid-cols-4 gap-4" {
+                @if can_change_users && !students.is_empty() {
+                    div class="flex flex-row justify-end my-2" {
+                        button class="bg-red-600 hover:bg-red-800 font-bold py-2 px-4 rounded" hx-delete="/people/bulk" hx-include="#bulk_students" hx-target="#in_focus" hx-confirm="Delete all of the selected students? This can't be undone." {
+                            "Delete Selected Students"
+                        }
+                    }
+                }
+                form id="bulk_students" class="grid grid-cols-1 sm:grid-cols-2 md:grid-cols-3 lg:grid-cols-4 gap-4" {
                     @for person in students {
-                        a hx-get="/internal/get_person" hx-target="#in_focus" hx-vals={"{\"id\": \"" (person.id) "\"}" } class="block rounded-lg shadow-md p-4 text-center bg-gray-700 hover:bg-gray-600" {
-                            (person)
+                        @if can_change_users {
+                            div class="flex flex-row items-center space-x-2 rounded-lg shadow-md bg-gray-700 hover:bg-gray-600 p-4" {
+                                input type="checkbox" name="ids" value=(person.id) class="leading-tight";
+                                a hx-get="/internal/get_person" hx-target="#in_focus" hx-vals={"{\"id\": \"" (person.id) "\"}" } class="block text-center grow cursor-pointer" {
+                                    (person)
+                                }
+                            }
+                        } @else {
+                            a hx-get="/internal/get_person" hx-target="#in_focus" hx-vals={"{\"id\": \"" (person.id) "\"}" } class="block rounded-lg shadow-md p-4 text-center bg-gray-700 hover:bg-gray-600" {
+                                (person)
+                            }
                         }
                     }
                 }
+                @if no_students {
+                    (empty_people_section("students", is_filtered, can_import))
+                }
+            }
+            @if page > 0 || has_next_page {
+                div class="flex flex-row items-center justify-between" {
+                    @if page > 0 {
+                        button class="bg-gray-700 hover:bg-gray-600 font-bold py-2 px-4 rounded" hx-get="/internal/get_people" hx-vals=(page_vals(page - 1)) hx-target="#all_people" {
+                            "Previous"
+                        }
+                    } @else {
+                        div {}
+                    }
+                    p class="text-gray-300" {"Page " (page + 1)}
+                    @if has_next_page {
+                        button class="bg-gray-700 hover:bg-gray-600 font-bold py-2 px-4 rounded" hx-get="/internal/get_people" hx-vals=(page_vals(page + 1)) hx-target="#all_people" {
+                            "Next"
+                        }
+                    } @else {
+                        div {}
+                    }
+                }
             }
         }
     })
 }
 
+#[derive(Deserialize)]
+pub struct HouseScopeForm {
+    ///empty for every house
+    pub house_id: String,
+}
+
+pub async fn internal_put_person_house_scope(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(id): Path<Uuid>,
+    Form(HouseScopeForm { house_id }): Form<HouseScopeForm>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::CRUD_ADMINS)?;
+
+    let house_id = if house_id.is_empty() {
+        None
+    } else {
+        Some(
+            house_id
+                .parse::<i32>()
+                .context(ParseHouseIdSnafu { original: house_id })?,
+        )
+    };
+
+    let mut conn = state.get_connection().await?;
+    let house = match house_id {
+        Some(house_id) => Some(
+            HouseGroup::get_from_db_by_id(house_id, &mut conn)
+                .await?
+                .context(MissingHouseGroupSnafu { id: house_id })?,
+        ),
+        None => None,
+    };
+    User::set_house_scope(id, house_id, &mut conn).await?;
+    drop(conn);
+
+    let detail = house.map_or_else(
+        || "can manage every house".to_string(),
+        |house| format!("can only manage {:?}", house.name),
+    );
+    state
+        .audit(
+            &session,
+            AuditAction::Edit,
+            AuditTarget::Person(id),
+            Some(&detail),
+        )
+        .await;
+    state.send_sse_event(SseEvent::CrudPerson);
+
+    Ok(html! {
+        span class="text-sm text-green-400" {"Saved."}
+    })
+}
+
+///what goes in a section of the people page with no-one in it
+fn empty_people_section(kind: &str, is_filtered: bool, can_import: bool) -> Markup {
+    if is_filtered {
+        empty_state(format!("No {kind} match your search."))
+    } else if can_import {
+        empty_state(html! {
+            "No " (kind) " yet - "
+            a href="/import_export" class="underline hover:text-blue-300" {"import them from a spreadsheet"}
+            "."
+        })
+    } else {
+        empty_state(format!("No {kind} yet."))
+    }
+}
+
 #[derive(Deserialize)]
 pub struct InDetailForm {
     pub id: Uuid,
     pub new_password: Option<SecretString>,
 }
 
+#[allow(clippy::too_many_lines)]
 pub async fn internal_get_person_in_detail(
     State(state): State<DenimState>,
     session: DenimSession,
@@ -389,10 +755,31 @@ pub async fn internal_get_person_in_detail(
         |np| html! { "{\"id\": \"" (id) "\", \"new_password\": \"" (np.expose_secret()) "\"}" },
     );
 
-    let can_delete = session.can(match person.kind {
+    let is_student = matches!(person.kind, UserKind::Student { .. });
+    let can_change = session.can(match person.kind {
         UserKind::Admin => PermissionsTarget::CRUD_ADMINS,
         _ => PermissionsTarget::CRUD_USERS,
-    });
+    }) && session.can_for_house(PermissionsTarget::CRUD_USERS, person.house_id());
+    let can_change_tutor_group =
+        is_student && session.can_for_house(PermissionsTarget::CRUD_USERS, person.house_id());
+    let is_staff = matches!(person.kind, UserKind::Staff);
+    let can_change_admins = session.can(PermissionsTarget::CRUD_ADMINS);
+    let houses = if is_staff && can_change_admins {
+        let mut houses = HouseGroup::get_all(&state).await?;
+        houses.sort_by(|a, b| a.name.cmp(&b.name));
+        houses
+    } else {
+        vec![]
+    };
+    let assignable_roles: Vec<_> = if can_change_admins {
+        Role::get_all(&state)
+            .await?
+            .into_iter()
+            .filter(|role| !role.is_built_in())
+            .collect()
+    } else {
+        vec![]
+    };
 
     Ok(html! {
         div hx-get="/internal/get_person" hx-trigger="sse:crud_person" hx-vals=(hx_vals) class="container mx-auto" {
@@ -417,18 +804,18 @@ pub async fn internal_get_person_in_detail(
 
                     @match person.kind {
                         UserKind::Student {
-                            tutor_group: TutorGroup {id: _, house_id: _, staff_member},
-                            house: HouseGroup {id: _, name: house_name},
+                            tutor_group: TutorGroup {id: tutor_group_id, house_id: _, staff_member},
+                            house: HouseGroup {id: house_id, name: house_name},
                             events_participated
                         } => {
                             div class="py-4" {
                                 p class="text-gray-200 font-semibold" {
-                                    "House: " //TODO: link to house group
-                                    span class="font-medium" {(house_name)}
+                                    "House: "
+                                    a href={"/house/" (house_id)} class="font-medium underline hover:text-blue-300" {(house_name)}
                                 }
                                 p class="text-gray-200 font-semibold" {
-                                    "Tutor Group: " //TODO: Link to tutor group
-                                    span class="font-medium" {(staff_member)}
+                                    "Tutor Group: "
+                                    a href={"/tutor_group/" (tutor_group_id)} class="font-medium underline hover:text-blue-300" {(staff_member)}
                                 }
                                 p class="text-gray-200 font-semibold" {
                                     "House Events: "
@@ -439,9 +826,65 @@ pub async fn internal_get_person_in_detail(
                         _ => {}
                     }
 
-                    @if can_delete {
+                    @if can_change {
+                        br;
+                        div class="flex flex-row flex-wrap gap-2" {
+                            @for (item, name) in [("first_name", "First Name"), ("pref_name", "Preferred Name"), ("surname", "Surname"), ("email", "Email")] {
+                                button hx-get={"/internal/people/" (id) "/edit_" (item)} hx-target="#form_contents" class="bg-gray-700 hover:bg-gray-600 text-gray-300 font-bold py-2 px-4 rounded focus:outline-none focus:shadow-outline" {"Edit " (name)}
+                            }
+                        }
+                        div id="form_contents" class="py-4" {}
+                    }
+
+                    @if !assignable_roles.is_empty() {
+                        br;
+                        div class="flex flex-row items-end space-x-2" {
+                            label class="flex flex-col text-sm font-bold text-gray-300" {
+                                "Extra Role"
+                                select name="role_id" hx-put={"/internal/people/" (id) "/role"} hx-target="#role_saved" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600" {
+                                    option value="" selected[person.role_id.is_none()] {"None"}
+                                    @for role in assignable_roles {
+                                        option value=(role.id) selected[person.role_id == Some(role.id)] {(role.name)}
+                                    }
+                                }
+                            }
+                            span id="role_saved" {}
+                        }
+                    }
+
+                    @if is_staff && can_change_admins {
+                        br;
+                        div class="flex flex-row items-end space-x-2" {
+                            label class="flex flex-col text-sm font-bold text-gray-300" {
+                                "Only Manages"
+                                select name="house_id" hx-put={"/internal/people/" (id) "/house_scope"} hx-target="#house_scope_saved" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600" {
+                                    option value="" selected[person.house_scope.is_none()] {"All Houses"}
+                                    @for house in houses {
+                                        option value=(house.id) selected[person.house_scope == Some(house.id)] {(house.name)}
+                                    }
+                                }
+                            }
+                            span id="house_scope_saved" {}
+                        }
+                    }
+
+                    @if can_change_tutor_group {
+                        br;
+                        button class="bg-blue-600 hover:bg-blue-800 font-bold py-2 px-4 rounded" hx-get="/internal/profile/edit_tutor_group" hx-vals={"{\"id\": \"" (id) "\"}" } hx-target="#in_focus" {
+                            "Change Tutor Group"
+                        }
+                    }
+
+                    @if can_change {
+                        br;
+                        button class="bg-yellow-600 hover:bg-yellow-800 font-bold py-2 px-4 rounded" hx-post={"/internal/people/" (id) "/reset_password"} hx-confirm="This will replace their current password - are you sure?" hx-target="#in_focus" {
+                            "Regenerate default password"
+                        }
+                    }
+
+                    @if can_change {
                         br;
-                        button class="bg-red-600 hover:bg-red-800 font-bold py-2 px-4 rounded" hx-delete="/people" hx-vals={"{\"id\": \"" (id) "\"}" } hx-target="#in_focus" {
+                        button class="bg-red-600 hover:bg-red-800 font-bold py-2 px-4 rounded" hx-get={"/internal/people/" (id) "/delete_confirm"} hx-target="this" hx-swap="outerHTML" {
                             "Delete person"
                         }
                     }
@@ -0,0 +1,287 @@
+use crate::{
+    auth::{AuthUtilities, PermissionsTarget, api_token::ApiUser},
+    data::{
+        DataType,
+        audit_log::{AuditAction, AuditTarget},
+        event::{AddEvent, Event},
+    },
+    error::{CommitTransactionSnafu, DenimResult, MissingEventSnafu, ParseTimeSnafu},
+    routes::sse::SseEvent,
+    state::DenimState,
+};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use jiff::{Timestamp, Zoned, tz::TimeZone};
+use serde::{Deserialize, Serialize};
+use snafu::{OptionExt, ResultExt};
+use std::num::NonZeroU16;
+use uuid::Uuid;
+
+#[derive(Serialize)]
+pub struct EventDto {
+    id: Uuid,
+    name: String,
+    datetime: Zoned,
+    location: Option<String>,
+    extra_info: Option<String>,
+    associated_staff: Vec<Uuid>,
+    max_capacity: Option<i32>,
+    recurrence_group: Option<Uuid>,
+    tags: Vec<String>,
+    ///who's taking part is only included for those who could see it in the web UI
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signed_up: Option<Vec<Uuid>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    verified: Option<Vec<Uuid>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    waitlisted: Option<Vec<Uuid>>,
+}
+
+impl EventDto {
+    fn new(event: Event, include_participants: bool) -> Self {
+        Self {
+            id: event.id,
+            name: event.name,
+            datetime: event.datetime,
+            location: event.location,
+            extra_info: event.extra_info,
+            associated_staff: event
+                .associated_staff
+                .into_iter()
+                .map(|staff| staff.id)
+                .collect(),
+            max_capacity: event.max_capacity,
+            recurrence_group: event.recurrence_group,
+            tags: event.tags,
+            signed_up: include_participants.then_some(event.signed_up),
+            verified: include_participants.then_some(event.verified),
+            waitlisted: include_participants.then_some(event.waitlisted),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct EventInput {
+    name: String,
+    ///either RFC 9557 (eg. `2025-06-01T10:00:00+01:00[Europe/London]`), or RFC 3339 which then gets the default timezone
+    datetime: String,
+    #[serde(default)]
+    location: Option<String>,
+    #[serde(default)]
+    extra_info: Option<String>,
+    #[serde(default)]
+    associated_staff: Vec<Uuid>,
+    #[serde(default)]
+    max_capacity: Option<NonZeroU16>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+impl EventInput {
+    fn into_add_event(self, default_tz: TimeZone) -> DenimResult<AddEvent> {
+        let Self {
+            name,
+            datetime,
+            location,
+            extra_info,
+            associated_staff,
+            max_capacity,
+            tags,
+        } = self;
+
+        let date = match datetime.parse::<Zoned>() {
+            Ok(zoned) => zoned,
+            Err(_e) => datetime
+                .parse::<Timestamp>()
+                .context(ParseTimeSnafu {
+                    original: datetime.clone(),
+                })?
+                .to_zoned(default_tz),
+        };
+
+        let mut tags: Vec<String> = tags
+            .iter()
+            .map(|tag| tag.trim())
+            .filter(|tag| !tag.is_empty())
+            .map(ToString::to_string)
+            .collect();
+        tags.sort();
+        tags.dedup();
+
+        Ok(AddEvent {
+            name,
+            date,
+            location: location.filter(|location| !location.is_empty()),
+            extra_info: extra_info.filter(|extra_info| !extra_info.is_empty()),
+            associated_staff,
+            max_capacity: max_capacity.map(|capacity| i32::from(capacity.get())),
+            recurrence_group: None,
+            tags,
+        })
+    }
+}
+
+async fn get_event_dto(state: &DenimState, api_user: &ApiUser, id: Uuid) -> DenimResult<EventDto> {
+    let event = Event::get_from_db_by_id(id, &mut *state.get_connection().await?)
+        .await?
+        .context(MissingEventSnafu { id })?;
+    Ok(EventDto::new(
+        event,
+        api_user.can(PermissionsTarget::VIEW_SENSITIVE_DETAILS),
+    ))
+}
+
+pub async fn get_api_events(
+    State(state): State<DenimState>,
+    api_user: ApiUser,
+) -> DenimResult<Json<Vec<EventDto>>> {
+    let include_participants = api_user.can(PermissionsTarget::VIEW_SENSITIVE_DETAILS);
+    let events = Event::get_all(&state).await?;
+    Ok(Json(
+        events
+            .into_iter()
+            .map(|event| EventDto::new(event, include_participants))
+            .collect(),
+    ))
+}
+
+pub async fn post_api_events(
+    State(state): State<DenimState>,
+    api_user: ApiUser,
+    Json(input): Json<EventInput>,
+) -> DenimResult<(StatusCode, Json<EventDto>)> {
+    api_user.ensure_can(PermissionsTarget::CRUD_EVENTS)?;
+
+    let default_tz = state.config().date_locale_config().get()?.timezone.clone();
+    let add_event = input.into_add_event(default_tz)?;
+    let name = add_event.name.clone();
+
+    let id =
+        Event::insert_into_database_transaction(add_event, state.get_transaction().await?).await?;
+    state
+        .audit_as(
+            Some(api_user.0.id),
+            AuditAction::Create,
+            AuditTarget::Event(id),
+            Some(&name),
+        )
+        .await;
+    state.send_sse_event(SseEvent::CrudEvent);
+
+    Ok((
+        StatusCode::CREATED,
+        Json(get_event_dto(&state, &api_user, id).await?),
+    ))
+}
+
+pub async fn get_api_event(
+    State(state): State<DenimState>,
+    api_user: ApiUser,
+    Path(id): Path<Uuid>,
+) -> DenimResult<Json<EventDto>> {
+    Ok(Json(get_event_dto(&state, &api_user, id).await?))
+}
+
+pub async fn put_api_event(
+    State(state): State<DenimState>,
+    api_user: ApiUser,
+    Path(id): Path<Uuid>,
+    Json(input): Json<EventInput>,
+) -> DenimResult<Json<EventDto>> {
+    api_user.ensure_can(PermissionsTarget::CRUD_EVENTS)?;
+
+    let default_tz = state.config().date_locale_config().get()?.timezone.clone();
+    let add_event = input.into_add_event(default_tz)?;
+
+    let mut transaction = state.get_transaction().await?;
+    api_user
+        .ensure_can_for_event(PermissionsTarget::CRUD_EVENTS, id, false, &mut transaction)
+        .await?;
+    let promoted_any = Event::update_in_database(id, add_event, &mut transaction).await?;
+    transaction.commit().await.context(CommitTransactionSnafu)?;
+    if promoted_any {
+        state.send_sse_event(SseEvent::ChangeSignUp { event_id: id });
+    }
+    state
+        .audit_as(
+            Some(api_user.0.id),
+            AuditAction::Edit,
+            AuditTarget::Event(id),
+            None,
+        )
+        .await;
+    state.send_sse_event(SseEvent::CrudEvent);
+
+    Ok(Json(get_event_dto(&state, &api_user, id).await?))
+}
+
+pub async fn delete_api_event(
+    State(state): State<DenimState>,
+    api_user: ApiUser,
+    Path(id): Path<Uuid>,
+) -> DenimResult<StatusCode> {
+    api_user.ensure_can(PermissionsTarget::CRUD_EVENTS)?;
+
+    let mut conn = state.get_connection().await?;
+    api_user
+        .ensure_can_for_event(PermissionsTarget::CRUD_EVENTS, id, false, &mut conn)
+        .await?;
+    //same as the web UI - hidden until the purge sweep, so it can still be restored from there
+    Event::soft_delete(id, false, &mut conn).await?;
+    drop(conn);
+
+    state
+        .audit_as(
+            Some(api_user.0.id),
+            AuditAction::Delete,
+            AuditTarget::Event(id),
+            None,
+        )
+        .await;
+    state.send_sse_event(SseEvent::CrudEvent);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EventDto;
+    use crate::data::{DataType, event::Event, testing::insert_event};
+    use sqlx::PgPool;
+
+    #[sqlx::test]
+    async fn participants_are_left_out_unless_included(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let id = insert_event(None, &mut conn).await;
+
+        let hidden = serde_json::to_value(EventDto::new(
+            Event::get_from_db_by_id(id, &mut conn)
+                .await
+                .unwrap()
+                .unwrap(),
+            false,
+        ))
+        .unwrap();
+        for field in ["signed_up", "verified", "waitlisted"] {
+            assert!(hidden.get(field).is_none(), "{field}");
+        }
+
+        let shown = serde_json::to_value(EventDto::new(
+            Event::get_from_db_by_id(id, &mut conn)
+                .await
+                .unwrap()
+                .unwrap(),
+            true,
+        ))
+        .unwrap();
+        for field in ["signed_up", "verified", "waitlisted"] {
+            assert!(
+                shown.get(field).is_some_and(serde_json::Value::is_array),
+                "{field}"
+            );
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{
+    auth::{AuthUtilities, DenimSession, PermissionsTarget},
+    data::{audit_log::AuditEntry, clamp_page, user::User},
+    error::DenimResult,
+    maud_conveniences::{table, title},
+    state::DenimState,
+};
+use axum::extract::{Query, State};
+use maud::{Markup, html};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+const AUDIT_PAGE_SIZE: i64 = 50;
+
+pub async fn get_audit_log(
+    State(state): State<DenimState>,
+    session: DenimSession,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::CRUD_ADMINS)?;
+
+    let audit_log = internal_get_audit_log(
+        State(state.clone()),
+        session.clone(),
+        Query(AuditQuery {
+            filter: None,
+            page: None,
+        }),
+    )
+    .await?;
+
+    Ok(state.render(session, html! {
+        div class="mx-auto bg-gray-800 p-8 rounded shadow-md max-w-6xl w-full flex flex-col space-y-4" {
+            div id="audit_log" {
+                (audit_log)
+            }
+        }
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    pub filter: Option<String>,
+    pub page: Option<i64>,
+}
+
+pub async fn internal_get_audit_log(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Query(AuditQuery { filter, page }): Query<AuditQuery>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::CRUD_ADMINS)?;
+
+    let filter = filter.filter(|filter| !filter.trim().is_empty());
+    let page = clamp_page(page.unwrap_or(0), AUDIT_PAGE_SIZE);
+
+    let mut conn = state.get_connection().await?;
+    let (entries, has_next_page) =
+        AuditEntry::get_page(filter.as_deref(), page, AUDIT_PAGE_SIZE, &mut conn).await?;
+
+    let actors: Vec<_> = entries.iter().filter_map(|entry| entry.actor_id).collect();
+    let actors: HashMap<_, _> = User::get_many_by_ids(&actors, &mut conn)
+        .await?
+        .into_iter()
+        .map(|user| (user.id, user))
+        .collect();
+    drop(conn);
+
+    let dlc = state
+        .config()
+        .date_locale_config()
+        .get()?
+        .for_user(session.user.as_ref());
+
+    let page_vals = |page: i64| serde_json::json!({"page": page, "filter": filter}).to_string();
+
+    Ok(html! {
+        div class="flex flex-col space-y-4" {
+            div class="flex rounded p-4 m-4" {
+                input value=[filter.as_deref()] type="search" name="filter" placeholder="Begin Typing To Search the Audit Log..." hx-get="/internal/audit" hx-trigger="input changed delay:500ms, keyup[key=='Enter']" hx-target="#audit_log" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600";
+            }
+
+            (table(
+                title("Audit Log"),
+                ["When", "Who", "Action", "Target", "Detail"],
+                entries.into_iter().map(|entry| [
+                    html! {(dlc.short_ymdet(&entry.created_at.to_zoned(dlc.timezone.clone())))},
+                    html! {
+                        @match entry.actor_id.and_then(|id| actors.get(&id)) {
+                            Some(user) => (user),
+                            None => span class="italic" {"-"},
+                        }
+                    },
+                    html! {(entry.action)},
+                    html! {
+                        (entry.target_type)
+                        @if let Some(target_id) = entry.target_id {
+                            br;
+                            span class="text-gray-400 text-sm" {(target_id)}
+                        }
+                    },
+                    html! {
+                        @if let Some(detail) = entry.detail {
+                            (detail)
+                        } @else {
+                            span class="italic" {"-"}
+                        }
+                    },
+                ]).collect(),
+            ))
+
+            @if page > 0 || has_next_page {
+                div class="flex flex-row items-center justify-between" {
+                    @if page > 0 {
+                        button class="bg-gray-700 hover:bg-gray-600 font-bold py-2 px-4 rounded" hx-get="/internal/audit" hx-vals=(page_vals(page - 1)) hx-target="#audit_log" {
+                            "Previous"
+                        }
+                    } @else {
+                        div {}
+                    }
+                    p class="text-gray-300" {"Page " (page + 1)}
+                    @if has_next_page {
+                        button class="bg-gray-700 hover:bg-gray-600 font-bold py-2 px-4 rounded" hx-get="/internal/audit" hx-vals=(page_vals(page + 1)) hx-target="#audit_log" {
+                            "Next"
+                        }
+                    } @else {
+                        div {}
+                    }
+                }
+            }
+        }
+    })
+}
@@ -2,31 +2,44 @@ use crate::{
     auth::{AuthUtilities, DenimSession, PermissionsTarget},
     config::date_locale::DateFormat,
     data::{
-        DataType, FilterQuery, IdForm,
+        DataType, FilterQuery, IdForm, clamp_page,
         event::{Event, EventSignUpState},
         user::User,
         photo::Photo,
     },
-    error::{DenimResult, MakeQuerySnafu, MissingEventSnafu},
-    maud_conveniences::supertitle,
-    routes::sse::SseEvent,
+    error::{
+        CommitTransactionSnafu, CsvSnafu, DenimResult, MakeQuerySnafu, MissingEventSnafu,
+        MissingUserSnafu, ParseUuidSnafu, S3Snafu,
+    },
+    maud_conveniences::{supertitle, tag_chips},
+    routes::{import_export::csv_download, sse::SseEvent},
     state::DenimState,
 };
 use axum::{
-    Form,
+    Form, Json,
+    body::{Body, Bytes},
     extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
 };
-use futures::TryStreamExt;
-use maud::{Markup, html};
+use maud::{DOCTYPE, Markup, PreEscaped, html};
+use serde::{Deserialize, Serialize};
 use snafu::{ensure, OptionExt, ResultExt};
 use sqlx::PgConnection;
-use std::collections::HashSet;
+use std::{
+    collections::HashMap,
+    io::{Seek, SeekFrom, Write},
+    sync::{Arc, Mutex as StdMutex, PoisonError},
+};
+use tokio_stream::wrappers::ReceiverStream;
+use futures::{StreamExt, TryStreamExt};
+use zip::{CompressionMethod, ZipWriter, result::ZipError, write::SimpleFileOptions};
 use axum::extract::Multipart;
 use infer::MatcherType;
 use uuid::Uuid;
 use crate::data::photo::NewPhotoForm;
-use crate::error::{DenimError, InvalidImageSnafu, MultipartSnafu};
-use crate::maud_conveniences::{form_submit_button, subtitle};
+use crate::error::{ZipSnafu, DenimError, InvalidImageSnafu, MultipartSnafu, PhotoTooLargeSnafu, TooManyPhotosSnafu};
+use crate::maud_conveniences::{empty_state, errors_list, form_submit_button, subtitle};
 
 #[allow(clippy::too_many_lines)]
 pub async fn get_event(
@@ -39,12 +52,14 @@ pub async fn get_event(
         .await?
         .context(MissingEventSnafu { id })?;
 
+    let can_verify = session.can(PermissionsTarget::VERIFY_ATTENDANCE);
     let signed_up_and_verified = if session.can(PermissionsTarget::VIEW_SENSITIVE_DETAILS) {
         Some(
             internal_get_signed_up_with_list(
                 &mut *state.get_connection().await?,
                 &event.signed_up,
                 &event.verified,
+                &event.waitlisted,
                 session.can(PermissionsTarget::VERIFY_ATTENDANCE),
                 id,
             )
@@ -91,24 +106,28 @@ pub async fn get_event(
         None
     };
 
-    let dlc = state.config().date_locale_config().get()?;
+    let dlc = state
+        .config()
+        .date_locale_config()
+        .get()?
+        .for_user(session.user.as_ref());
 
     Ok(state.render(session, html!{
         div class="container mx-auto px-4 py-8" {
             div class="bg-gray-800 p-6 md:p-8 rounded-lg shadow-xl" hx-ext="sse" sse-connect="/sse_feed" {
                 (supertitle(event.name))
+                (tag_chips(&event.tags))
 
                 div class="grid grid-cols-1 md:grid-cols-2 gap-6 mb-8" {
                     div {
                         p class="text-gray-300 text-sm" {"Date:"}
-                        p class="text-gray-100 text-lg" {(dlc.long_ymdet(&event.datetime)?)}
-                        @if let Some((event_tz, global_tz)) = event.datetime.time_zone().iana_name().zip(dlc.timezone.iana_name()) {
-                            @if event_tz != global_tz {
-                                p class="text-gray-100 text-md" {
-                                    "Local Time (" 
-                                    span class="italic" {(event_tz)}
-                                    "): "(dlc.format(&event.datetime, DateFormat::ShortYMDET, false)?)
-                                }
+                        p class="text-gray-100 text-lg" {(dlc.long_ymdet(&event.datetime))}
+                        p class="text-gray-400 text-md italic" {(dlc.relative(&event.datetime))}
+                        @if let Some(event_tz) = dlc.differing_timezone(&event.datetime) {
+                            p class="text-gray-100 text-md" {
+                                "Local Time (" 
+                                span class="italic" {(event_tz)}
+                                "): "(dlc.format(&event.datetime, DateFormat::ShortYMDET, false))
                             }
                         }
                     }
@@ -121,9 +140,11 @@ pub async fn get_event(
                         }
                     }
                     div {
-                        p class="text-gray-300 text-sm" {"Staff Member:"}
-                        @if let Some(staff) = event.associated_staff_member {
-                            p class="text-gray-100 text-lg" {(staff)}
+                        p class="text-gray-300 text-sm" {"Staff:"}
+                        @if !event.associated_staff.is_empty() {
+                            @for staff in event.associated_staff {
+                                p class="text-gray-100 text-lg" {(staff)}
+                            }
                         } @else {
                             p class="text-gray-500 text-lg" {"None Assigned"}
                         }
@@ -151,6 +172,16 @@ pub async fn get_event(
 
                 @if let Some(signed_up_and_verified) = signed_up_and_verified {
                     (signed_up_and_verified)
+                    div class="mt-6" {
+                        a href={"/event/" (id) "/attendance.csv"} download class="bg-pink-600 hover:bg-pink-700 font-bold py-2 px-4 rounded" {
+                            "Download Attendance as CSV"
+                        }
+                        @if can_verify {
+                            a href={"/event/" (id) "/roster"} target="_blank" class="bg-pink-600 hover:bg-pink-700 font-bold py-2 px-4 rounded ml-2" {
+                                "Print Roster"
+                            }
+                        }
+                    }
                 }
             }
         }
@@ -158,6 +189,111 @@ pub async fn get_event(
 }
 
 pub async fn internal_get_photos(State(state): State<DenimState>, session: DenimSession, Path(event_id): Path<Uuid>) -> DenimResult<Markup> {
+    get_photos_markup(&state, &session, event_id, vec![]).await
+}
+
+const PHOTOS_PAGE_SIZE: i64 = 24;
+
+#[derive(Deserialize)]
+pub struct PhotosPageQuery {
+    pub page: Option<i64>,
+}
+
+///the next page of photos, which gets swapped in over the "Load More" button at the end of the previous one
+pub async fn internal_get_photos_page(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(event_id): Path<Uuid>,
+    Query(PhotosPageQuery { page }): Query<PhotosPageQuery>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::VIEW_PHOTOS)?;
+    let page = clamp_page(page.unwrap_or(0), PHOTOS_PAGE_SIZE);
+
+    Ok(get_photos_page_markup(&state, event_id, page).await?.0)
+}
+
+///only presigns the photos on this page, so big events don't need hundreds of presigns just to show the first few
+///
+///the bool is whether the page was empty
+async fn get_photos_page_markup(
+    state: &DenimState,
+    event_id: Uuid,
+    page: i64,
+) -> DenimResult<(Markup, bool)> {
+    let bucket = state.config().s3_bucket().get()?;
+    let (photos, has_more) = Photo::get_page_by_event_id(
+        event_id,
+        page,
+        PHOTOS_PAGE_SIZE,
+        &mut *state.get_connection().await?,
+    )
+    .await?;
+    let is_empty = photos.is_empty();
+
+    let mut links = vec![];
+    for photo in photos {
+        links.push((
+            photo.get_s3_url(&bucket).await?,
+            photo.get_thumbnail_s3_url(&bucket).await?,
+        ));
+    }
+
+    Ok((
+        html! {
+            @for (number, (link, thumbnail)) in (page * PHOTOS_PAGE_SIZE + 1..).zip(links) {
+                a href={(link)} target="_blank" class="block aspect-square bg-gray-700 rounded overflow-hidden flex items-center justify-center text-gray-100 hover:text-blue-300 underline" {
+                    @if let Some(thumbnail) = thumbnail {
+                        img src={(thumbnail)} alt={"Photo " (number)} loading="lazy" class="w-full h-full object-cover";
+                    } @else {
+                        "Photo " (number)
+                    }
+                }
+            }
+            @if has_more {
+                button class="col-span-full bg-gray-700 hover:bg-gray-600 font-bold py-2 px-4 rounded" hx-get={"/internal/event/" (event_id) "/photos/page"} hx-vals={"{\"page\": " (page + 1) "}"} hx-target="this" hx-swap="outerHTML" {
+                    "Load More"
+                }
+            }
+        },
+        is_empty,
+    ))
+}
+
+///uploads straight to S3 with presigned URLs, falling back to the normal multipart form if anything goes wrong (eg. no CORS on the bucket)
+const DIRECT_PHOTO_UPLOAD_JS: &str = r"
+window.denimDirectPhotoUpload = function (evt, eventId) {
+    const files = Array.from(evt.target.querySelector('input[type=file]').files);
+    if (files.length === 0) {
+        return;
+    }
+    evt.preventDefault();
+
+    const contentTypes = files.map((file) => file.type).join(',');
+    fetch(`/internal/event/${eventId}/photos/presign_upload?content_types=${encodeURIComponent(contentTypes)}`)
+        .then((response) => {
+            if (!response.ok) {
+                throw new Error('unable to presign uploads');
+            }
+            return response.json();
+        })
+        .then((uploads) => Promise.all(uploads.map((upload, i) => {
+            const body = new FormData();
+            Object.entries(upload.fields).forEach(([name, value]) => body.append(name, value));
+            body.append('file', files[i]);
+            return fetch(upload.url, {method: 'POST', body})
+                .then((response) => {
+                    if (!response.ok) {
+                        throw new Error('unable to upload photo');
+                    }
+                    return upload.key;
+                });
+        })))
+        .then((keys) => htmx.ajax('POST', `/internal/event/${eventId}/photos/confirm`, {target: '#photos', swap: 'outerHTML', values: {keys: keys.join(',')}}))
+        .catch(() => evt.detail.issueRequest(true));
+};
+";
+
+async fn get_photos_markup(state: &DenimState, session: &DenimSession, event_id: Uuid, upload_errors: Vec<String>) -> DenimResult<Markup> {
     let (can_view_photos, can_upload_photos) = (session.can(PermissionsTarget::VIEW_PHOTOS), session.can(PermissionsTarget::UPLOAD_PHOTOS));
 
     if !(can_view_photos || can_upload_photos) {
@@ -168,27 +304,25 @@ pub async fn internal_get_photos(State(state): State<DenimState>, session: Denim
     }
 
     let links = if can_view_photos {
-        let mut links = vec![];
-        let bucket = state.config().s3_bucket().get()?;
-        for photo in Photo::get_by_event_id(event_id, &mut *state.get_connection().await?).await? {
-            links.push(photo.get_s3_url(&bucket).await?);
-        }
+        let (first_page, is_empty) = get_photos_page_markup(state, event_id, 0).await?;
 
         Some(html!{
             div class="flex flex-col space-y-2" {
-                p class="text-gray-300 text-sm" {"Photos:"}
-                ul class="list-disc pl-5 overflow-y-clip overflow-y-scroll max-h-64 p-2 m-4" {
-                    @if links.is_empty() {
-                        p class="text-gray-100 italic text-sm" {"(no photos uploaded yet)"}
-                        br;
+                div class="flex flex-row justify-between items-center" {
+                    p class="text-gray-300 text-sm" {"Photos:"}
+                    @if !is_empty {
+                        a href={"/event/" (event_id) "/photos.zip"} class="text-sm text-gray-400 underline hover:text-blue-300" {"Download All"}
+                    }
+                }
+                @if is_empty {
+                    @if can_upload_photos {
+                        (empty_state("No photos uploaded yet - add some below."))
                     } @else {
-                        @for (index, link) in links.into_iter().enumerate() {
-                            li {
-                                a href={(link)} target="_blank" class="text-gray-100 hover:text-blue-300 underline" {
-                                    "Photo " (index + 1)
-                                }
-                            }
-                        }
+                        (empty_state("No photos uploaded yet."))
+                    }
+                } @else {
+                    div class="grid grid-cols-2 sm:grid-cols-3 md:grid-cols-4 gap-2 overflow-y-scroll max-h-96 p-2 m-4" {
+                        (first_page)
                     }
                 }
             }
@@ -198,14 +332,18 @@ pub async fn internal_get_photos(State(state): State<DenimState>, session: Denim
     };
 
     Ok(html!{
-        div id="photos" hx-trigger={"sse:change_photos_" (event_id)} hx-swap="outerHTML" {
+        div id="photos" hx-get={"/internal/event/" (event_id) "/photos"} hx-trigger={"sse:change_photos_" (event_id)} hx-swap="outerHTML" {
+            @if !upload_errors.is_empty() {
+                (errors_list(Some("Unable to upload photos:"), upload_errors.into_iter()))
+            }
             @if let Some(links) = links {
                 (links)
             }
             @if can_upload_photos {
                 p class="text-gray-300 text-sm" {"Upload more Photos:"}
                 div class="flex flex-col space-y-2 p-2" {
-                    form hx-post={"/internal/event/" (event_id) "/photos"} hx-swap="outerHTML" hx-target="#photos" hx-encoding="multipart/form-data" {
+                    script {(PreEscaped(DIRECT_PHOTO_UPLOAD_JS))}
+                    form hx-post={"/internal/event/" (event_id) "/photos"} hx-swap="outerHTML" hx-target="#photos" hx-encoding="multipart/form-data" hx-on:htmx:confirm={"denimDirectPhotoUpload(event, '" (event_id) "')"} {
                         label for="photos" class="block text-sm font-medium text-gray-400 mb-2" {"Photos to Upload"}
                         input multiple type="file" name="photos" id="photos" accept="image/*" class="block w-full text-sm text-gray-300 file:mr-4 file:py-2 file:px-4 file:rounded file:border-0 file:text-sm file:font-semibold file:bg-violet-50 file:text-violet-700 hover:file:bg-violet-100 mb-4";
                         (form_submit_button(Some("Upload Photos")))
@@ -219,34 +357,66 @@ pub async fn internal_get_photos(State(state): State<DenimState>, session: Denim
 pub async fn internal_post_photos(State(state): State<DenimState>, session: DenimSession, Path(event_id): Path<Uuid>, mut multipart: Multipart) -> DenimResult<Markup> {
     session.ensure_can(PermissionsTarget::VIEW_PHOTOS)?;
     let bucket = state.config().s3_bucket().get()?;
-    
-    loop {
-        let Some(field) = multipart.next_field().await.context(MultipartSnafu)? else {
-            break;
-        };
-        
-        let bytes = field.bytes().await.context(MultipartSnafu)?;
-        let inferred_type = infer::get(&bytes).context(InvalidImageSnafu {found_mime: None})?;
-        
-        let content_type = inferred_type.mime_type();
-        ensure!(inferred_type.matcher_type() == MatcherType::Image, InvalidImageSnafu {found_mime: Some(content_type)});
-        
-        let transaction = state.get_transaction().await?;
-        
-        Photo::insert_into_database_transaction(
-            NewPhotoForm {
-                bytes: bytes.to_vec(),
-                content_type,
-                extension: inferred_type.extension(),
-                s3_bucket_to_add_to: bucket.clone(),
-                event_id,
-            },
-            transaction
-        ).await?;
-    }
-    
-    
-    internal_get_photos(State(state), session, Path(event_id)).await
+    let photo_config = state.config().photo_config();
+
+    let upload_result: DenimResult<()> = async {
+        while let Some(mut field) = multipart.next_field().await.context(MultipartSnafu)? {
+            //read chunk by chunk so we can bail before buffering all of a huge file
+            let mut bytes = vec![];
+            while let Some(chunk) = field.chunk().await.context(MultipartSnafu)? {
+                ensure!(bytes.len() + chunk.len() <= photo_config.max_photo_bytes, PhotoTooLargeSnafu {max_bytes: photo_config.max_photo_bytes});
+                bytes.extend_from_slice(&chunk);
+            }
+
+            let inferred_type = infer::get(&bytes).context(InvalidImageSnafu {found_mime: None})?;
+
+            let content_type = inferred_type.mime_type();
+            ensure!(inferred_type.matcher_type() == MatcherType::Image, InvalidImageSnafu {found_mime: Some(content_type)});
+
+            let thumbnail = tokio::task::spawn_blocking({
+                let bytes = bytes.clone();
+                move || Photo::make_thumbnail(&bytes)
+            })
+            .await
+            .expect("unable to join tokio task");
+
+            //checked in the same transaction as the insert, so other uploads (and direct upload reservations) can't sneak in between
+            let mut transaction = state.get_transaction().await?;
+            let taken = Photo::lock_photo_slots(event_id, &mut transaction).await?;
+            ensure!(
+                taken < photo_config.max_photos_per_event,
+                TooManyPhotosSnafu {
+                    max: photo_config.max_photos_per_event
+                }
+            );
+
+            Photo::insert_into_database_transaction(
+                NewPhotoForm {
+                    thumbnail,
+                    bytes,
+                    content_type,
+                    extension: inferred_type.extension(),
+                    s3_bucket_to_add_to: bucket.clone(),
+                    event_id,
+                },
+                transaction,
+            )
+            .await?;
+        }
+
+        Ok(())
+    }.await;
+
+    //some photos might've made it in before the error, so still let everyone know
+    state.send_sse_event(SseEvent::ChangePhotos { event_id });
+
+    let upload_errors = match upload_result {
+        Ok(()) => vec![],
+        Err(e @ (DenimError::PhotoTooLarge { .. } | DenimError::TooManyPhotos { .. } | DenimError::InvalidImage { .. })) => vec![e.to_string()],
+        Err(e) => return Err(e),
+    };
+
+    get_photos_markup(&state, &session, event_id, upload_errors).await
 }
 
 pub async fn internal_get_sign_others_up(
@@ -256,35 +426,27 @@ pub async fn internal_get_sign_others_up(
     Query(FilterQuery { filter }): Query<FilterQuery>,
 ) -> DenimResult<Markup> {
     session.ensure_can(PermissionsTarget::SIGN_OTHERS_UP)?;
-    let filter = filter.map(|filter| filter.to_lowercase());
-
-    let students = if let Some(filter) = &filter {
-        let mut all_students = User::get_all_students_with_filter(&state, filter).await?;
-
-        let so_far_here = sqlx::query!(
-            "SELECT student_id FROM participation WHERE event_id = $1",
-            event_id
-        )
-        .fetch_all(&mut *state.get_connection().await?)
-        .await
-        .context(MakeQuerySnafu)?
-        .into_iter()
-        .map(|rec| rec.student_id)
-        .collect::<HashSet<_>>();
 
-        all_students.retain(|user| !so_far_here.contains(&user.id));
+    let is_full = Event::is_full(event_id, &mut *state.get_connection().await?).await?;
 
-        all_students
+    let students = if is_full {
+        vec![]
+    } else if let Some(filter) = &filter {
+        User::get_all_students_with_filter(&state, filter, Some(event_id), session.house_scope())
+            .await?
     } else {
         vec![]
     };
 
     Ok(html! {
-        div id="sign_others_up" hx-get={"/internal/event/" (event_id) "/sign_others_up"} hx-trigger="sse:crud_person" hx-swap="outerHTML" class="container mx-auto flex flex-col space-y-8 background-gray-800 rounded-lg shadow p-4 m-4" {
+        div id="sign_others_up" hx-get={"/internal/event/" (event_id) "/sign_others_up"} hx-trigger={"sse:crud_person, sse:change_sign_up_" (event_id)} hx-swap="outerHTML" class="container mx-auto flex flex-col space-y-8 background-gray-800 rounded-lg shadow p-4 m-4" {
             (subtitle("Student Participation"))
             div class="flex rounded p-4 m-4" {
                 input value=[filter] type="search" name="filter" placeholder="Search here to sign up students..." hx-get={"/internal/event/" (event_id) "/sign_others_up"} hx-trigger="input changed delay:500ms, keyup[key=='Enter']" hx-target="#sign_others_up" hx-swap="outerHTML" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600";
             }
+            @if is_full {
+                (errors_list(Some("Event is full"), std::iter::empty::<String>()))
+            }
             ul class="space-y-2" {
                 @for student in students {
                     li class="bg-gray-700 p-3 rounded" {
@@ -298,24 +460,95 @@ pub async fn internal_get_sign_others_up(
     })
 }
 
+///does nothing if there's no mailer, or the student has opted out
+async fn email_signed_up_student(
+    state: &DenimState,
+    event_id: Uuid,
+    user_id: Uuid,
+) -> DenimResult<()> {
+    let Some(mailer) = state.config().mailer() else {
+        return Ok(());
+    };
+
+    let mut conn = state.get_connection().await?;
+    let Some(user) = User::get_from_db_by_id(user_id, &mut conn).await? else {
+        return Ok(());
+    };
+    if !user.wants_signup_emails {
+        return Ok(());
+    }
+    let Some(event) = Event::get_from_db_by_id(event_id, &mut conn).await? else {
+        return Ok(());
+    };
+    drop(conn);
+
+    let dlc = state
+        .config()
+        .date_locale_config()
+        .get()?
+        .for_user(Some(&user));
+
+    let unsign_up = state
+        .config()
+        .base_url()
+        .map_or_else(String::new, |base_url| {
+            format!(
+                "\n\nIf you can't make it, you can un-sign up here:\n\n{base_url}/event/{event_id}"
+            )
+        });
+    let body = format!(
+        "You've been signed up for {} on {}.{unsign_up}\n\nYou can turn these emails off from your profile.",
+        event.name,
+        dlc.long_ymdet(&event.datetime)
+    );
+
+    mailer
+        .send(&user.email, &format!("Signed up for {}", event.name), body)
+        .await
+}
+
 pub async fn internal_post_sign_others_up(
     State(state): State<DenimState>,
     session: DenimSession,
     Path(event_id): Path<Uuid>,
     Form(IdForm { id: user_id }): Form<IdForm>,
 ) -> DenimResult<Markup> {
-    if session.user.as_ref().is_some_and(|user| user.id == user_id) {
+    let signing_self_up = session.user.as_ref().is_some_and(|user| user.id == user_id);
+    if signing_self_up {
         session.ensure_can(PermissionsTarget::SIGN_SELF_UP)?;
     } else {
         session.ensure_can(PermissionsTarget::SIGN_OTHERS_UP)?;
     }
 
-    sqlx::query!("INSERT INTO public.participation (event_id, student_id, is_verified) VALUES ($1, $2, false)", event_id, user_id)
-        .execute(&mut *state.get_connection().await?)
-        .await
-        .context(MakeQuerySnafu)?;
+    let mut transaction = state.get_transaction().await?;
+    if !signing_self_up && session.house_scope().is_some() {
+        let student = User::get_from_db_by_id(user_id, &mut transaction)
+            .await?
+            .context(MissingUserSnafu { id: user_id })?;
+        session.ensure_can_for_house(PermissionsTarget::SIGN_OTHERS_UP, student.house_id())?;
+    }
 
-    state.send_sse_event(SseEvent::ChangeSignUp { event_id });
+    Event::lock_for_sign_ups(event_id, &mut transaction).await?;
+    if Event::has_finished(event_id, &mut transaction).await? {
+        return Err(DenimError::EventFinished { id: event_id });
+    }
+    let signed_up = !Event::is_full(event_id, &mut transaction).await?
+        && Event::sign_up(event_id, user_id, false, &mut transaction).await?;
+    transaction.commit().await.context(CommitTransactionSnafu)?;
+
+    if signed_up {
+        state.send_sse_event(SseEvent::ChangeSignUp { event_id });
+
+        if session.user.as_ref().is_none_or(|user| user.id != user_id) {
+            //best-effort - the signup has already happened, so a bad email shouldn't undo that
+            let state = state.clone();
+            tokio::spawn(async move {
+                if let Err(e) = email_signed_up_student(&state, event_id, user_id).await {
+                    warn!(?e, %event_id, %user_id, "Unable to email student about being signed up");
+                }
+            });
+        }
+    }
 
     internal_get_sign_others_up(
         State(state),
@@ -330,21 +563,26 @@ pub async fn internal_post_toggle_self_sign_up(
     State(state): State<DenimState>,
     session: DenimSession,
     Path(event_id): Path<Uuid>,
-) -> DenimResult<()> {
+) -> DenimResult<Markup> {
     session.ensure_can(PermissionsTarget::SIGN_SELF_UP)?;
     let user = session.user.expect("can't sign self up if not logged in");
-    let mut conn = state.get_connection().await?;
+    let mut conn = state.get_transaction().await?;
+    Event::lock_for_sign_ups(event_id, &mut conn).await?;
 
     if let Some(sign_up_state) =
         Event::user_is_signed_up_to_event(event_id, user.id, &mut conn).await?
     {
         match sign_up_state {
             EventSignUpState::Nothing => {
-                sqlx::query!("INSERT INTO public.participation (event_id, student_id, is_verified) VALUES ($1, $2, FALSE)", event_id, user.id)
-                    .execute(&mut *conn)
-                    .await
-                    .context(MakeQuerySnafu)?;
-                state.send_sse_event(SseEvent::ChangeSignUp { event_id });
+                if Event::has_finished(event_id, &mut conn).await? {
+                    return Err(DenimError::EventFinished { id: event_id });
+                }
+                let is_waitlisted = Event::is_full(event_id, &mut conn).await?;
+
+                //if another request signed us up in the meantime, then there's nothing to toggle
+                if !Event::sign_up(event_id, user.id, is_waitlisted, &mut conn).await? {
+                    return Ok(html! {});
+                }
             }
             EventSignUpState::SignedUp => {
                 sqlx::query!(
@@ -355,15 +593,30 @@ pub async fn internal_post_toggle_self_sign_up(
                 .execute(&mut *conn)
                 .await
                 .context(MakeQuerySnafu)?;
-                state.send_sse_event(SseEvent::ChangeSignUp { event_id });
+
+                Event::promote_from_waitlist(event_id, &mut conn).await?;
+            }
+            EventSignUpState::Waitlisted => {
+                sqlx::query!(
+                    "DELETE FROM public.participation WHERE event_id = $1 AND student_id = $2",
+                    event_id,
+                    user.id
+                )
+                .execute(&mut *conn)
+                .await
+                .context(MakeQuerySnafu)?;
             }
             EventSignUpState::Verified => {
                 //can't get out that easily ;)
+                return Ok(html! {});
             }
         }
+
+        conn.commit().await.context(CommitTransactionSnafu)?;
+        state.send_sse_event(SseEvent::ChangeSignUp { event_id });
     }
 
-    Ok(())
+    Ok(html! {})
 }
 
 pub async fn internal_post_verify(
@@ -374,6 +627,13 @@ pub async fn internal_post_verify(
 ) -> DenimResult<()> {
     session.ensure_can(PermissionsTarget::VERIFY_ATTENDANCE)?;
     let mut conn = state.get_connection().await?;
+    session
+        .ensure_can_for_students(
+            PermissionsTarget::VERIFY_ATTENDANCE,
+            &[student_id],
+            &mut conn,
+        )
+        .await?;
 
     match Event::user_is_signed_up_to_event(event_id, student_id, &mut conn).await? {
         Some(EventSignUpState::SignedUp) => {
@@ -390,6 +650,9 @@ pub async fn internal_post_verify(
                 "Tried to verify non-signed up student"
             );
         }
+        Some(EventSignUpState::Waitlisted) => {
+            info!(?student_id, ?event_id, "Tried to verify waitlisted student");
+        }
         Some(EventSignUpState::Verified) => {
             info!(
                 ?student_id,
@@ -405,41 +668,143 @@ pub async fn internal_post_verify(
     Ok(())
 }
 
+///for when the wrong student got verified - puts them back in the signed up list
+pub async fn internal_post_unverify(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(event_id): Path<Uuid>,
+    Form(IdForm { id: student_id }): Form<IdForm>,
+) -> DenimResult<()> {
+    session.ensure_can(PermissionsTarget::VERIFY_ATTENDANCE)?;
+    let mut conn = state.get_connection().await?;
+    session
+        .ensure_can_for_students(
+            PermissionsTarget::VERIFY_ATTENDANCE,
+            &[student_id],
+            &mut conn,
+        )
+        .await?;
+
+    if Event::user_is_signed_up_to_event(event_id, student_id, &mut conn).await?
+        == Some(EventSignUpState::Verified)
+    {
+        sqlx::query!("UPDATE public.participation SET is_verified = FALSE WHERE event_id = $1 AND student_id = $2", event_id, student_id)
+            .execute(&mut *conn)
+            .await
+            .context(MakeQuerySnafu)?;
+        state.send_sse_event(SseEvent::ChangeSignUp { event_id });
+    } else {
+        info!(
+            ?student_id,
+            ?event_id,
+            "Tried to unverify student who wasn't verified"
+        );
+    }
+
+    Ok(())
+}
+
+///verifies everyone who's signed up (but not anyone on the waitlist)
+pub async fn internal_post_verify_all(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(event_id): Path<Uuid>,
+) -> DenimResult<()> {
+    session.ensure_can(PermissionsTarget::VERIFY_ATTENDANCE)?;
+
+    let mut conn = state.get_connection().await?;
+    session
+        .ensure_can_for_event(
+            PermissionsTarget::VERIFY_ATTENDANCE,
+            event_id,
+            false,
+            &mut conn,
+        )
+        .await?;
+    Event::verify_students(event_id, None, &mut conn).await?;
+    drop(conn);
+    state.send_sse_event(SseEvent::ChangeSignUp { event_id });
+
+    Ok(())
+}
+
+///takes any number of `ids` from the checkboxes - anyone not signed up gets ignored
+pub async fn internal_post_verify_selected(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(event_id): Path<Uuid>,
+    Form(params): Form<Vec<(String, String)>>,
+) -> DenimResult<()> {
+    session.ensure_can(PermissionsTarget::VERIFY_ATTENDANCE)?;
+
+    let ids = params
+        .into_iter()
+        .filter(|(key, _)| key == "ids")
+        .map(|(_, id)| Uuid::try_parse(&id).context(ParseUuidSnafu { original: id }))
+        .collect::<DenimResult<Vec<_>>>()?;
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    let mut conn = state.get_connection().await?;
+    session
+        .ensure_can_for_students(PermissionsTarget::VERIFY_ATTENDANCE, &ids, &mut conn)
+        .await?;
+    Event::verify_students(event_id, Some(&ids), &mut conn).await?;
+    drop(conn);
+    state.send_sse_event(SseEvent::ChangeSignUp { event_id });
+
+    Ok(())
+}
+
 pub async fn internal_get_signup_button(
     State(state): State<DenimState>,
     session: DenimSession,
     Path(event_id): Path<Uuid>,
 ) -> DenimResult<Markup> {
+    let mut conn = state.get_connection().await?;
     let sign_up_state = match session.user.as_ref() {
-        Some(user) => {
-            Event::user_is_signed_up_to_event(
-                event_id,
-                user.id,
-                &mut *state.get_connection().await?,
-            )
-            .await?
-        }
+        Some(user) => Event::user_is_signed_up_to_event(event_id, user.id, &mut conn).await?,
         None => None,
     };
+    let is_full = Event::is_full(event_id, &mut conn).await?;
+    let has_finished = Event::has_finished(event_id, &mut conn).await?;
+    drop(conn);
 
     Ok(html! {
         @if let Some(sign_up_state) = sign_up_state {
             div hx-get={"/internal/event/" (event_id) "/signup_button"} hx-trigger={"sse:change_sign_up_" (event_id)} hx-swap="outerHTML" {
                 @match sign_up_state {
+                    //verified is still worth showing, but otherwise there's nothing to do
+                    EventSignUpState::Nothing | EventSignUpState::SignedUp | EventSignUpState::Waitlisted if has_finished => {
+                        p class="text-gray-400 italic py-2 px-4" {"This event has finished."}
+                    },
                     EventSignUpState::Nothing => {
-                        button class="bg-green-600 hover:bg-green-800 font-bold py-2 px-4 rounded" hx-post={"/internal/event/" (event_id) "/post_toggle_self_signup"} hx-swap="none" {
-                            "Sign Up"
+                        @if is_full {
+                            button class="bg-amber-600 hover:bg-amber-800 font-bold py-2 px-4 rounded" hx-post={"/internal/event/" (event_id) "/post_toggle_self_signup"} hx-target={"#sign_up_errors_" (event_id)} {
+                                "Full - Join Waitlist"
+                            }
+                        } @else {
+                            button class="bg-green-600 hover:bg-green-800 font-bold py-2 px-4 rounded" hx-post={"/internal/event/" (event_id) "/post_toggle_self_signup"} hx-target={"#sign_up_errors_" (event_id)} {
+                                "Sign Up"
+                            }
                         }
                     },
                     EventSignUpState::SignedUp => {
-                        button class="bg-red-600 hover:bg-red-800 font-bold py-2 px-4 rounded" hx-post={"/internal/event/" (event_id) "/post_toggle_self_signup"} hx-swap="none" {
+                        button class="bg-red-600 hover:bg-red-800 font-bold py-2 px-4 rounded" hx-post={"/internal/event/" (event_id) "/post_toggle_self_signup"} hx-target={"#sign_up_errors_" (event_id)} {
                             "Un-Sign Up"
                         }
                     },
+                    EventSignUpState::Waitlisted => {
+                        button class="bg-red-600 hover:bg-red-800 font-bold py-2 px-4 rounded" hx-post={"/internal/event/" (event_id) "/post_toggle_self_signup"} hx-target={"#sign_up_errors_" (event_id)} {
+                            "Leave Waitlist"
+                        }
+                    },
                     EventSignUpState::Verified => {
                         p class="text-gray-800 font-bold py-2 px-4 rounded" {"Verified!"}
                     }
                 }
+                div id={"sign_up_errors_" (event_id)} {}
             }
         }
     })
@@ -449,6 +814,7 @@ async fn internal_get_signed_up_with_list(
     conn: &mut PgConnection,
     signed_up: &[Uuid],
     verified: &[Uuid],
+    waitlisted: &[Uuid],
     can_verify: bool,
     id: Uuid,
 ) -> DenimResult<Markup> {
@@ -456,14 +822,25 @@ async fn internal_get_signed_up_with_list(
         User::get_from_iter_of_ids(signed_up.iter().copied(), &mut *conn).await?;
     let verified_students =
         User::get_from_iter_of_ids(verified.iter().copied(), &mut *conn).await?;
+    let waitlisted_students =
+        User::get_from_iter_of_ids(waitlisted.iter().copied(), &mut *conn).await?;
 
     Ok(html! {
-        div id="signed_up_and_verified" class="grid grid-cols-1 md:grid-cols-2 gap-6" hx-get={"/internal/event/" (id) "/signed_up_and_verified"} hx-trigger={"sse:change_sign_up_" (id)} hx-swap="outerHTML" {
+        div id="signed_up_and_verified" class="grid grid-cols-1 md:grid-cols-3 gap-6" hx-get={"/internal/event/" (id) "/signed_up_and_verified"} hx-trigger={"sse:change_sign_up_" (id)} hx-swap="outerHTML" {
             div {
                 h3 class="text-xl font-semibold text-white mb-4" {"Signed Up Students (currently " (signed_up_students.len()) "): " }
-                ul class="space-y-2 text-gray-100" {
+                @if can_verify && !signed_up_students.is_empty() {
+                    div class="flex flex-row space-x-2 mb-4" {
+                        button class="bg-green-600 hover:bg-green-800 font-bold py-1 px-3 rounded" hx-post={"/internal/event/" (id) "/verify_all"} hx-swap="none" {"Verify All"}
+                        button class="bg-green-600 hover:bg-green-800 font-bold py-1 px-3 rounded" hx-post={"/internal/event/" (id) "/verify_selected"} hx-include={"#verify_selected_" (id)} hx-swap="none" {"Verify Selected"}
+                    }
+                }
+                ul id={"verify_selected_" (id)} class="space-y-2 text-gray-100" {
                     @for student in signed_up_students {
                         li class="bg-gray-700 p-3 rounded" {
+                            @if can_verify {
+                                input type="checkbox" name="ids" value=(student.id) class="mr-2 leading-tight";
+                            }
                             (student)
                             @if can_verify {
                                 " - "
@@ -478,6 +855,22 @@ async fn internal_get_signed_up_with_list(
                     h3 class="text-xl font-semibold text-white mb-4" {"Verified Students (currently " (verified_students.len()) "): " }
                     ul class="space-y-2 text-gray-100" {
                         @for student in verified_students {
+                            li class="bg-gray-700 p-3 rounded" {
+                                (student)
+                                @if can_verify {
+                                    " - "
+                                    a class="text-red-300 hover:text-red-800 cursor-pointer underline" hx-post={"/internal/event/" (id) "/unverify"} hx-swap="none" hx-vals={"{\"id\": \"" (student.id) "\"}" } {"Undo Verification"}
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            @if !waitlisted_students.is_empty() {
+                div {
+                    h3 class="text-xl font-semibold text-white mb-4" {"Waitlist (currently " (waitlisted_students.len()) "): " }
+                    ol class="space-y-2 text-gray-100 list-decimal list-inside" {
+                        @for student in waitlisted_students {
                             li class="bg-gray-700 p-3 rounded" {(student)}
                         }
                     }
@@ -495,34 +888,552 @@ pub async fn internal_get_signed_up(
     session.ensure_can(PermissionsTarget::VIEW_SENSITIVE_DETAILS)?;
 
     let mut conn = state.get_connection().await?;
-
-    let mut signed_up = vec![];
-    let mut verified = vec![];
-
-    let mut participation_stream = sqlx::query!(
-        "SELECT student_id, is_verified FROM participation WHERE event_id = $1",
-        id
-    )
-    .fetch(&mut *conn);
-    while let Some(record) = participation_stream
-        .try_next()
-        .await
-        .context(MakeQuerySnafu)?
-    {
-        if record.is_verified {
-            verified.push(record.student_id);
-        } else {
-            signed_up.push(record.student_id);
-        }
-    }
-    drop(participation_stream);
+    let (signed_up, verified, waitlisted) = Event::get_participation(id, &mut conn).await?;
 
     internal_get_signed_up_with_list(
         &mut conn,
         &signed_up,
         &verified,
+        &waitlisted,
         session.can(PermissionsTarget::VERIFY_ATTENDANCE),
         id,
     )
     .await
 }
+
+pub async fn get_event_attendance_csv(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(id): Path<Uuid>,
+) -> DenimResult<Response> {
+    #[derive(Serialize)]
+    struct AttendanceRow<'a> {
+        name: String,
+        email: &'a str,
+        verified: bool,
+    }
+
+    session.ensure_can(PermissionsTarget::VIEW_SENSITIVE_DETAILS)?;
+
+    let mut conn = state.get_connection().await?;
+    let event = Event::get_from_db_by_id(id, &mut conn)
+        .await?
+        .context(MissingEventSnafu { id })?;
+
+    let signed_up = User::get_from_iter_of_ids(event.signed_up, &mut conn).await?;
+    let verified = User::get_from_iter_of_ids(event.verified, &mut conn).await?;
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for (student, verified) in signed_up
+        .iter()
+        .map(|student| (student, false))
+        .chain(verified.iter().map(|student| (student, true)))
+    {
+        let first_name = student
+            .pref_name
+            .as_deref()
+            .unwrap_or(student.first_name.as_str());
+
+        writer
+            .serialize(AttendanceRow {
+                name: format!("{first_name} {}", student.surname),
+                email: student.email.as_str(),
+                verified,
+            })
+            .context(CsvSnafu)?;
+    }
+
+    csv_download("attendance.csv", writer)
+}
+
+///a bare page without the nav, for printing out & ticking off by hand
+pub async fn get_event_roster(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(id): Path<Uuid>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::VERIFY_ATTENDANCE)?;
+
+    let mut conn = state.get_connection().await?;
+    let event = Event::get_from_db_by_id(id, &mut conn)
+        .await?
+        .context(MissingEventSnafu { id })?;
+
+    let signed_up = User::get_from_iter_of_ids(event.signed_up, &mut conn).await?;
+    let verified = User::get_from_iter_of_ids(event.verified, &mut conn).await?;
+    drop(conn);
+
+    let dlc = state
+        .config()
+        .date_locale_config()
+        .get()?
+        .for_user(session.user.as_ref());
+
+    Ok(html! {
+        (DOCTYPE)
+        html {
+            head {
+                meta charset="UTF-8" {}
+                title { "Roster - " (event.name) }
+                style {
+                    "body { font-family: sans-serif; margin: 2em; } "
+                    "table { border-collapse: collapse; width: 100%; } "
+                    "th, td { border: 1px solid black; padding: 0.4em; text-align: left; } "
+                    "@media print { button { display: none; } }"
+                }
+            }
+            body {
+                h1 {(event.name)}
+                p {
+                    (dlc.long_ymdet(&event.datetime))
+                    @if let Some(location) = event.location {
+                        " - " (location)
+                    }
+                }
+                button onclick="window.print()" {"Print"}
+                br;
+                br;
+                table {
+                    thead {
+                        tr {
+                            th {"Present"}
+                            th {"Name"}
+                            th {"Already Verified"}
+                        }
+                    }
+                    tbody {
+                        @for (student, is_verified) in signed_up.iter().map(|student| (student, false)).chain(verified.iter().map(|student| (student, true))) {
+                            tr {
+                                td {input type="checkbox" checked[is_verified];}
+                                td {(student)}
+                                td {@if is_verified {"Yes"}}
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+pub async fn get_event_photos_zip(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(id): Path<Uuid>,
+) -> DenimResult<Response> {
+    //don't want to have loads of S3 requests in flight at once for huge galleries
+    const CONCURRENT_DOWNLOADS: usize = 8;
+
+    session.ensure_can(PermissionsTarget::VIEW_PHOTOS)?;
+
+    let mut conn = state.get_connection().await?;
+    let event = Event::get_from_db_by_id(id, &mut conn)
+        .await?
+        .context(MissingEventSnafu { id })?;
+    let photos = Photo::get_by_event_id(id, &mut conn).await?;
+    drop(conn);
+
+    let bucket = state.config().s3_bucket().get()?;
+    //each photo gets sent on once the next one starts (and its header can't change any more), so only that one & the in-flight downloads are ever held in memory
+    let (tx, rx) = tokio::sync::mpsc::channel(1);
+    tokio::spawn(async move {
+        let buffer = StreamingZipBuffer::default();
+        let result: DenimResult<()> = async {
+            let mut zip = ZipWriter::new(buffer.clone());
+            //photos are already compressed, so no point trying again
+            let options =
+                SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+            let mut downloads = futures::stream::iter(photos)
+                .map(|photo| {
+                    let bucket = bucket.clone();
+                    async move {
+                        let bytes = photo.get_bytes(&bucket).await?;
+                        Ok::<_, DenimError>((photo, bytes))
+                    }
+                })
+                .buffered(CONCURRENT_DOWNLOADS);
+
+            let mut number = 0;
+            while let Some((photo, bytes)) = downloads.try_next().await? {
+                number += 1;
+                //starting the next file is what fills in the last one's header, so after this it won't change any more
+                let header_start = buffer.len();
+                zip.start_file(format!("photo_{number}.{}", photo.extension), options)
+                    .context(ZipSnafu)?;
+
+                if tx.send(Ok(buffer.take_before(header_start))).await.is_err() {
+                    //they've stopped downloading, so no point getting any more
+                    return Ok(());
+                }
+
+                zip.write_all(&bytes)
+                    .map_err(ZipError::from)
+                    .context(ZipSnafu)?;
+            }
+
+            zip.finish().context(ZipSnafu)?;
+            let _ = tx.send(Ok(buffer.take_before(buffer.len()))).await;
+            Ok(())
+        }
+        .await;
+
+        if let Err(e) = result {
+            error!(?e, ?id, "Unable to stream photos zip");
+            //the headers are long gone, so breaking the download is all that can be done
+            let _ = tx.send(Err(std::io::Error::other(e.to_string()))).await;
+        }
+    });
+
+    let filename: String = event
+        .name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}_photos.zip\""),
+            ),
+        ],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response())
+}
+
+///the zip writer owns what it writes to, so this lets what's been written so far be taken out from elsewhere
+///
+///it seeks back to fill in each file's header once that file's done, so only what's definitely finished should be taken
+#[derive(Clone, Default)]
+struct StreamingZipBuffer(Arc<StdMutex<StreamingZipBufferInner>>);
+
+#[derive(Default)]
+struct StreamingZipBufferInner {
+    ///how much has already been taken out, which is where `pending` starts
+    taken: u64,
+    pending: Vec<u8>,
+    position: u64,
+}
+
+impl StreamingZipBuffer {
+    ///how much has been written in total, including what's already been taken
+    fn len(&self) -> u64 {
+        let inner = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let len = inner.taken + inner.pending.len() as u64;
+        drop(inner);
+        len
+    }
+
+    ///takes everything before `end`, after which the zip writer can't go back there
+    fn take_before(&self, end: u64) -> Bytes {
+        let mut inner = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let ready = usize::try_from(end - inner.taken).expect("pending is in memory");
+        let taken: Vec<u8> = inner.pending.drain(..ready).collect();
+        inner.taken = end;
+        drop(inner);
+        Bytes::from(taken)
+    }
+}
+
+impl Write for StreamingZipBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut inner = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let start = usize::try_from(inner.position - inner.taken).expect("pending is in memory");
+
+        let overwritten = buf.len().min(inner.pending.len() - start);
+        inner.pending[start..start + overwritten].copy_from_slice(&buf[..overwritten]);
+        inner.pending.extend_from_slice(&buf[overwritten..]);
+        inner.position += buf.len() as u64;
+        drop(inner);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for StreamingZipBuffer {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let mut inner = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let end = inner.taken + inner.pending.len() as u64;
+        let position = match pos {
+            SeekFrom::Start(position) => Some(position),
+            SeekFrom::Current(offset) => inner.position.checked_add_signed(offset),
+            SeekFrom::End(offset) => end.checked_add_signed(offset),
+        };
+
+        let position = position
+            .filter(|position| (inner.taken..=end).contains(position))
+            .ok_or_else(|| {
+                std::io::Error::other("can't seek back into what's already been sent")
+            })?;
+        inner.position = position;
+        drop(inner);
+        Ok(position)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PresignUploadQuery {
+    content_types: String,
+}
+
+#[derive(Serialize)]
+pub struct PresignedUpload {
+    key: String,
+    url: String,
+    ///sent along with the file as a multipart form
+    fields: HashMap<String, String>,
+}
+
+pub async fn internal_get_presign_photo_upload(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(event_id): Path<Uuid>,
+    Query(PresignUploadQuery { content_types }): Query<PresignUploadQuery>,
+) -> DenimResult<Json<Vec<PresignedUpload>>> {
+    session.ensure_can(PermissionsTarget::UPLOAD_PHOTOS)?;
+    let bucket = state.config().s3_bucket().get()?;
+    let photo_config = state.config().photo_config();
+
+    let content_types: Vec<&str> = content_types
+        .split(',')
+        .map(str::trim)
+        .filter(|content_type| !content_type.is_empty())
+        .collect();
+
+    let mut reservations = vec![];
+    for content_type in content_types {
+        let extension = Photo::extension_for_direct_upload(content_type)
+            .context(InvalidImageSnafu { found_mime: None })?;
+        reservations.push((Uuid::new_v4(), extension, content_type));
+    }
+
+    let ids_and_extensions: Vec<_> = reservations
+        .iter()
+        .map(|(id, extension, _)| (*id, *extension))
+        .collect();
+    let mut transaction = state.get_transaction().await?;
+    Photo::reserve_direct_uploads(
+        event_id,
+        &ids_and_extensions,
+        photo_config.max_photos_per_event,
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await.context(CommitTransactionSnafu)?;
+
+    let mut uploads = vec![];
+    for (id, extension, content_type) in reservations {
+        let presigned = Photo::presign_direct_upload(
+            id,
+            extension,
+            content_type,
+            photo_config.max_photo_bytes,
+            &bucket,
+        )
+        .await?;
+
+        uploads.push(PresignedUpload {
+            key: format!("{id}.{extension}"),
+            url: presigned.url,
+            fields: presigned.fields,
+        });
+    }
+
+    Ok(Json(uploads))
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmUploadForm {
+    keys: String,
+}
+
+///enough of the start of an upload to tell what kind of file it really is
+const SNIFFED_BYTES: u64 = 8192;
+
+pub async fn internal_post_confirm_photo_upload(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(event_id): Path<Uuid>,
+    Form(ConfirmUploadForm { keys }): Form<ConfirmUploadForm>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::UPLOAD_PHOTOS)?;
+    let bucket = state.config().s3_bucket().get()?;
+    let photo_config = state.config().photo_config();
+
+    let mut conn = state.get_connection().await?;
+    let mut existing_photos = Photo::count_for_event(event_id, &mut conn).await?;
+    let mut upload_errors = vec![];
+
+    for key in keys.split(',').map(str::trim).filter(|key| !key.is_empty()) {
+        let Some((id, extension, expected_content_type)) =
+            key.split_once('.').and_then(|(id, extension)| {
+                Some((
+                    Uuid::parse_str(id).ok()?,
+                    extension,
+                    Photo::content_type_for_direct_upload(extension)?,
+                ))
+            })
+        else {
+            upload_errors.push(format!("Invalid upload {key:?}"));
+            continue;
+        };
+
+        if Photo::get_from_db_by_id(id, &mut conn).await?.is_some() {
+            continue;
+        }
+        if !Photo::is_pending_direct_upload(id, event_id, extension, &mut conn).await? {
+            upload_errors.push(format!("Invalid upload {key:?}"));
+            continue;
+        }
+
+        let path = format!("/photos/{id}.{extension}");
+        let Some(head) = bucket
+            .head_object(&path)
+            .await
+            .ok()
+            .and_then(|(head, status)| (status == 200).then_some(head))
+        else {
+            upload_errors.push(format!("Unable to find uploaded photo {key:?}"));
+            continue;
+        };
+
+        let problem = if existing_photos >= photo_config.max_photos_per_event {
+            Some(DenimError::TooManyPhotos {
+                max: photo_config.max_photos_per_event,
+            })
+        } else if head.content_type.as_deref() != Some(expected_content_type) {
+            Some(DenimError::InvalidImage { found_mime: None })
+        } else if head.content_length.is_none_or(|length| {
+            usize::try_from(length).map_or(true, |length| length > photo_config.max_photo_bytes)
+        }) {
+            Some(DenimError::PhotoTooLarge {
+                max_bytes: photo_config.max_photo_bytes,
+            })
+        } else {
+            None
+        };
+
+        //the content type is only what the browser said, so check what was actually uploaded like with normal uploads
+        //the magic bytes are right at the start, so there's no need to fetch the whole thing
+        let problem = match problem {
+            Some(problem) => Some(problem),
+            None => {
+                let start = bucket
+                    .get_object_range(&path, 0, Some(SNIFFED_BYTES - 1))
+                    .await
+                    .context(S3Snafu)?;
+                match infer::get(start.as_slice()) {
+                    Some(inferred) if inferred.matcher_type() == MatcherType::Image => None,
+                    inferred => Some(DenimError::InvalidImage {
+                        found_mime: inferred.map(|inferred| inferred.mime_type()),
+                    }),
+                }
+            }
+        };
+
+        if let Some(problem) = problem {
+            upload_errors.push(problem.to_string());
+            if let Err(e) = bucket.delete_object(&path).await {
+                warn!(?e, ?path, "Unable to remove rejected photo upload");
+            }
+            Photo::remove_pending_direct_upload(id, &mut conn).await?;
+            continue;
+        }
+
+        if !Photo::record_direct_upload(id, &mut conn).await? {
+            //someone else confirmed it at the same time
+            continue;
+        }
+        existing_photos += 1;
+
+        tokio::spawn({
+            let state = state.clone();
+            async move {
+                if let Err(e) = add_thumbnail_to_direct_upload(&state, id).await {
+                    warn!(?e, ?id, "Unable to create thumbnail for uploaded photo");
+                }
+            }
+        });
+    }
+    drop(conn);
+
+    state.send_sse_event(SseEvent::ChangePhotos { event_id });
+
+    get_photos_markup(&state, &session, event_id, upload_errors).await
+}
+
+///fetches the photo itself, so that the whole thing only gets held in memory for as long as it takes to make the thumbnail
+async fn add_thumbnail_to_direct_upload(state: &DenimState, id: Uuid) -> DenimResult<()> {
+    let bucket = state.config().s3_bucket().get()?;
+    let Some(photo) = Photo::get_from_db_by_id(id, &mut *state.get_connection().await?).await?
+    else {
+        return Ok(());
+    };
+    let bytes = photo.get_bytes(&bucket).await?;
+
+    let Some(thumbnail) = tokio::task::spawn_blocking(move || Photo::make_thumbnail(&bytes))
+        .await
+        .expect("unable to join tokio task")
+    else {
+        return Ok(());
+    };
+
+    photo
+        .add_thumbnail(&thumbnail, &bucket, &mut *state.get_connection().await?)
+        .await?;
+    state.send_sse_event(SseEvent::ChangePhotos {
+        event_id: photo.event_id,
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StreamingZipBuffer;
+    use std::io::{Cursor, Read, Write};
+    use zip::{CompressionMethod, ZipArchive, ZipWriter, write::SimpleFileOptions};
+
+    #[test]
+    fn streamed_zips_can_be_read_back() {
+        let files = [vec![1; 10], vec![2; 100_000], vec![3; 1]];
+
+        let buffer = StreamingZipBuffer::default();
+        let mut zip = ZipWriter::new(buffer.clone());
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let mut streamed = vec![];
+        for (number, contents) in files.iter().enumerate() {
+            let header_start = buffer.len();
+            zip.start_file(format!("photo_{number}.jpg"), options)
+                .unwrap();
+            let chunk = buffer.take_before(header_start);
+            //nothing past the finished files should go out early
+            assert_eq!(
+                streamed.len() + chunk.len(),
+                usize::try_from(header_start).unwrap()
+            );
+            streamed.extend_from_slice(&chunk);
+            zip.write_all(contents).unwrap();
+        }
+        zip.finish().unwrap();
+        streamed.extend_from_slice(&buffer.take_before(buffer.len()));
+
+        let mut archive = ZipArchive::new(Cursor::new(streamed)).unwrap();
+        assert_eq!(archive.len(), files.len());
+        for (number, contents) in files.iter().enumerate() {
+            let mut read_back = vec![];
+            archive
+                .by_name(&format!("photo_{number}.jpg"))
+                .unwrap()
+                .read_to_end(&mut read_back)
+                .unwrap();
+            assert_eq!(&read_back, contents);
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use crate::state::DenimState;
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+
+///always OK - just shows that the server is up
+pub async fn get_healthz() -> StatusCode {
+    StatusCode::OK
+}
+
+///checks that the database is reachable, and reports whether the bucket exists
+///
+///only the database being down makes this fail, as photos are the only thing needing the bucket
+pub async fn get_readyz(State(state): State<DenimState>) -> Response {
+    let database = match sqlx::query!("SELECT 1 as \"one!\"")
+        .fetch_one(&*state)
+        .await
+    {
+        Ok(_) => true,
+        Err(e) => {
+            warn!(?e, "Readiness check failed to reach database");
+            false
+        }
+    };
+
+    let s3_bucket = match state.config().s3_bucket().get() {
+        Ok(bucket) => match bucket.exists().await {
+            Ok(exists) => exists,
+            Err(e) => {
+                warn!(?e, "Readiness check failed to reach S3 bucket");
+                false
+            }
+        },
+        Err(_e) => false,
+    };
+
+    let status_code = if database {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status_code,
+        Json(json!({
+            "database": database,
+            "s3_bucket": s3_bucket,
+        })),
+    )
+        .into_response()
+}
@@ -2,37 +2,45 @@ use crate::{
     auth::{AuthUtilities, DenimSession, PermissionsTarget},
     data::{
         DataType,
+        audit_log::{AuditAction, AuditTarget},
         event::{AddEvent, Event},
         student_groups::{HouseGroup, NewHouse, NewTutorGroup, TutorGroup},
-        user::{AddPerson, AddUserKind, User},
+        user::{AddPerson, AddUserKind, User, UserKind},
     },
     error::{
-        B64Snafu, CommitTransactionSnafu, DenimError, DenimResult, EmailSnafu,
-        InvalidTimezoneSnafu, MakeQuerySnafu, MultipartSnafu, ParseUuidSnafu, RmpSerdeDecodeSnafu,
-        RmpSerdeEncodeSnafu, RollbackTransactionSnafu, S3Snafu, UnrepresentableTimeSnafu, ZipSnafu,
+        B64Snafu, CommitTransactionSnafu, CsvSnafu, DenimError, DenimResult, InvalidTimezoneSnafu,
+        MakeQuerySnafu, MissingUserSnafu, MultipartSnafu, ParseUuidSnafu, RmpSerdeDecodeSnafu,
+        RmpSerdeEncodeSnafu, RollbackTransactionSnafu, S3Snafu, SpreadsheetSheetsSnafu,
+        SpreadsheetSnafu, TowerSessionSnafu, UnrepresentableTimeSnafu, ZipSnafu,
     },
     maud_conveniences::{
-        Email, errors_list, form_element, form_submit_button, subsubtitle, table, timezone_picker,
-        title,
+        Email, ToastKind, errors_list, form_element, form_submit_button, subsubtitle, table,
+        timezone_picker, title, toast,
     },
+    metrics::METRICS,
     routes::sse::SseEvent,
     state::DenimState,
 };
 use axum::{
     Form,
+    body::Bytes,
     extract::{Multipart, Query, State},
+    http::header,
+    response::{IntoResponse, Redirect, Response},
 };
+use axum_login::tower_sessions::Session;
 use base64::{Engine, prelude::BASE64_URL_SAFE};
+use calamine::{Data, Reader as _, Xlsx, open_workbook_from_rs};
 use email_address::EmailAddress;
 use jiff::{civil::DateTime, tz::TimeZone};
 use maud::{Markup, Render, html};
+use s3::Bucket;
 use serde::{Deserialize, Serialize};
-use snafu::ResultExt;
+use snafu::{OptionExt, ResultExt, ensure};
 use std::{
     collections::{HashMap, HashSet},
-    fmt::Write as _,
+    fmt::{Display, Formatter, Write as _},
     io::{Cursor, Write},
-    str::FromStr,
     time::Duration,
 };
 use tokio::sync::watch::channel;
@@ -40,7 +48,7 @@ use uuid::Uuid;
 use zip::{AesMode, ZipWriter, write::SimpleFileOptions};
 use crate::maud_conveniences::subtitle;
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct NewCSVStudent {
     first_name: String,
     pref_name: String,
@@ -50,9 +58,11 @@ pub struct NewCSVStudent {
     tutor_email: EmailAddress,
 }
 
+#[allow(clippy::too_many_lines)]
 pub async fn get_import_export_page(
     State(state): State<DenimState>,
     session: DenimSession,
+    tower_session: Session,
 ) -> DenimResult<Markup> {
     session.ensure_can(PermissionsTarget::EXPORT_CSVS)?;
     let can_import = session.can(PermissionsTarget::IMPORT_CSVS);
@@ -77,6 +87,7 @@ pub async fn get_import_export_page(
             get_students_import_checker(
                 State(state.clone()),
                 session.clone(),
+                tower_session,
                 Query(ImportCheckerQuery {
                     dots: String::new(),
                 }),
@@ -87,17 +98,33 @@ pub async fn get_import_export_page(
         None
     };
 
+    //so it's easy to see whether the last import worked
+    let counts = sqlx::query!(
+        r#"SELECT (SELECT COUNT(*) FROM public.students) as "students!", (SELECT COUNT(*) FROM public.staff) as "staff!", (SELECT COUNT(*) FROM public.houses) as "houses!", (SELECT COUNT(*) FROM public.tutor_groups) as "tutor_groups!""#
+    )
+    .fetch_one(&mut *state.get_connection().await?)
+    .await
+    .context(MakeQuerySnafu)?;
+
     Ok(state.render(session, html!{
+        div class="flex flex-row justify-center rounded shadow-xl bg-gray-800 p-4 m-2 gap-x-12" {
+            @for (count, label) in [(counts.students, "Students"), (counts.staff, "Staff"), (counts.houses, "Houses"), (counts.tutor_groups, "Tutor Groups")] {
+                div class="flex flex-col items-center" {
+                    p class="text-2xl font-bold text-pink-400" {(count)}
+                    p class="text-gray-300 text-sm" {(label)}
+                }
+            }
+        }
         div class="mx-auto flex flex-row justify-center p-2 m-2 rounded gap-x-8" {
             div class="rounded shadow-xl flex flex-col p-4 m-2 bg-gray-800" {
                 (title(html!{p class="text-pink-400" {"Events"}}))
 
-                /* div class="mb-8" {
+                div class="mb-8" {
                     h3 class="text-xl font-semibold mb-4" {"Export Events"}
-                    button class="bg-pink-600 hover:bg-pink-700 font-bold py-2 px-4 rounded" {
+                    a href="/import_export/export_events" download class="bg-pink-600 hover:bg-pink-700 font-bold py-2 px-4 rounded" {
                         "Download as CSV"
                     }
-                } */
+                }
 
                 @if can_import {
                     div class="overflow-scroll overflow-clip" {
@@ -118,8 +145,16 @@ pub async fn get_import_export_page(
                             br;
 
                             form hx-put="/import_export/import_events" hx-swap="innerHTML" hx-target="#import_events_form" hx-encoding="multipart/form-data" {
+                                (csv_delimiter_picker("events_delimiter"))
+                                (form_element("events_datetime_format", "Date Format", html!{
+                                    select id="events_datetime_format" name="datetime_format" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600" {
+                                        @for format in CsvDateTimeFormat::ALL {
+                                            option value=(format.as_str()) {(format.label())}
+                                        }
+                                    }
+                                }))
                                 label for="events_csv" class="block text-sm font-medium text-gray-400 mb-2" {"Upload Events CSV"}
-                                input multiple type="file" name="events_csv" id="events_csv" accept=".csv" class="block w-full text-sm text-gray-300 file:mr-4 file:py-2 file:px-4 file:rounded file:border-0 file:text-sm file:font-semibold file:bg-violet-50 file:text-violet-700 hover:file:bg-violet-100 mb-4";
+                                input multiple type="file" name="events_csv" id="events_csv" accept=".csv,.xlsx" class="block w-full text-sm text-gray-300 file:mr-4 file:py-2 file:px-4 file:rounded file:border-0 file:text-sm file:font-semibold file:bg-violet-50 file:text-violet-700 hover:file:bg-violet-100 mb-4";
 
                                 (form_submit_button(Some("Import Events")))
                             }
@@ -128,17 +163,42 @@ pub async fn get_import_export_page(
                 }
             }
 
-            div class="rounded shadow-xl flex flex-col p-4 m-2 bg-gray-800" {
+            div hx-ext="sse" sse-connect="/sse_feed" class="rounded shadow-xl flex flex-col p-4 m-2 bg-gray-800" {
                 (title(html!{p class="text-pink-400" {"People"}}))
 
-                /* div class="mb-8" {
-                    h3 class="text-xl font-semibold mb-4" {"Export People"}
-                    button class="bg-pink-600 hover:bg-pink-700 font-bold py-2 px-4 rounded" {
+                div class="mb-8" {
+                    h3 class="text-xl font-semibold mb-4" {"Export Students"}
+                    a href="/import_export/export_people" download class="bg-pink-600 hover:bg-pink-700 font-bold py-2 px-4 rounded" {
                         "Download as CSV"
                     }
-                } */
+                }
 
                 @if can_import {
+                    div class="overflow-scroll overflow-clip mb-8" {
+                        (subtitle("Import Staff"))
+                        div id="import_staff_form" {
+                            (table(
+                                subsubtitle("CSV Format"),
+                                ["Column", "Example", "Required"],
+                                vec![
+                                    ["first_name", "Jackson", "✅"],
+                                    ["pref_name", "Jack", "❌"],
+                                    ["surname", "Programmerson", "✅"],
+                                    ["email", "tutor@example.org", "✅"]
+                                ]
+                            ))
+                            p class="italic" {"NB: Import staff first, so students' tutors already exist."}
+                            br;
+                            form hx-put="/import_export/import_staff" hx-swap="innerHTML" hx-target="#import_staff_form" hx-encoding="multipart/form-data" {
+                                (csv_delimiter_picker("staff_delimiter"))
+                                label for="staff_csv" class="block text-sm font-medium text-gray-400 mb-2" {"Upload Staff CSV"}
+                                input multiple type="file" name="staff_csv" id="staff_csv" accept=".csv,.xlsx" class="block w-full text-sm text-gray-300 file:mr-4 file:py-2 file:px-4 file:rounded file:border-0 file:text-sm file:font-semibold file:bg-violet-50 file:text-violet-700 hover:file:bg-violet-100 mb-4";
+                                (form_submit_button(Some("Import Staff")))
+                            }
+                            br;
+                            a href=(ImportedPasswordsKind::Staff.download_url()) target="_blank" class="hover:text-blue-300 underline" {"Re-download passwords from the last staff import"}
+                        }
+                    }
                     @if let Some(job_already_running) = job_already_running {
                         (job_already_running)
                     } @else {
@@ -160,10 +220,65 @@ pub async fn get_import_export_page(
                                 p class="italic" {"NB: Missing houses and tutor groups are auto-magically created."}
                                 br;
                                 form hx-put="/import_export/import_people" hx-swap="innerHTML" hx-target="#import_people_forms" hx-encoding="multipart/form-data" {
+                                    (csv_delimiter_picker("people_delimiter"))
                                     label for="people_csv" class="block text-sm font-medium text-gray-400 mb-2" {"Upload Students CSV"}
-                                    input multiple type="file" name="people_csv" id="people_csv" accept="application/csv" class="block w-full text-sm text-gray-300 file:mr-4 file:py-2 file:px-4 file:rounded file:border-0 file:text-sm file:font-semibold file:bg-violet-50 file:text-violet-700 hover:file:bg-violet-100 mb-4";
+                                    input multiple type="file" name="people_csv" id="people_csv" accept=".csv,.xlsx" class="block w-full text-sm text-gray-300 file:mr-4 file:py-2 file:px-4 file:rounded file:border-0 file:text-sm file:font-semibold file:bg-violet-50 file:text-violet-700 hover:file:bg-violet-100 mb-4";
+                                    div class="mb-4 flex items-center" {
+                                        input type="checkbox" name="dry_run" id="dry_run" class="mr-2 leading-tight";
+                                        label for="dry_run" class="text-gray-300 cursor-pointer" {"Dry Run? (preview without saving anything)"}
+                                    }
                                     (form_submit_button(Some("Import People")))
                                 }
+                                br;
+                                a href=(ImportedPasswordsKind::Students.download_url()) target="_blank" class="hover:text-blue-300 underline" {"Re-download passwords from the last student import"}
+                            }
+                        }
+                    }
+                }
+            }
+
+            @if can_import {
+                div class="rounded shadow-xl flex flex-col p-4 m-2 bg-gray-800" {
+                    (title(html!{p class="text-pink-400" {"Groups"}}))
+
+                    div class="overflow-scroll overflow-clip mb-8" {
+                        (subtitle("Import Houses"))
+                        div id="import_houses_form" {
+                            (table(
+                                subsubtitle("CSV Format"),
+                                ["Column", "Example", "Required"],
+                                vec![
+                                    ["name", "Lion", "✅"]
+                                ]
+                            ))
+                            br;
+                            form hx-put="/import_export/import_houses" hx-swap="innerHTML" hx-target="#import_houses_form" hx-encoding="multipart/form-data" {
+                                (csv_delimiter_picker("houses_delimiter"))
+                                label for="houses_csv" class="block text-sm font-medium text-gray-400 mb-2" {"Upload Houses CSV"}
+                                input multiple type="file" name="houses_csv" id="houses_csv" accept=".csv,.xlsx" class="block w-full text-sm text-gray-300 file:mr-4 file:py-2 file:px-4 file:rounded file:border-0 file:text-sm file:font-semibold file:bg-violet-50 file:text-violet-700 hover:file:bg-violet-100 mb-4";
+                                (form_submit_button(Some("Import Houses")))
+                            }
+                        }
+                    }
+
+                    div class="overflow-scroll overflow-clip" {
+                        (subtitle("Import Tutor Groups"))
+                        div id="import_tutor_groups_form" {
+                            (table(
+                                subsubtitle("CSV Format"),
+                                ["Column", "Example", "Required"],
+                                vec![
+                                    ["house", "Lion", "✅"],
+                                    ["tutor_email", "tutor@example.org", "✅"]
+                                ]
+                            ))
+                            p class="italic" {"NB: Missing houses are auto-magically created, but tutors must already be staff."}
+                            br;
+                            form hx-put="/import_export/import_tutor_groups" hx-swap="innerHTML" hx-target="#import_tutor_groups_form" hx-encoding="multipart/form-data" {
+                                (csv_delimiter_picker("tutor_groups_delimiter"))
+                                label for="tutor_groups_csv" class="block text-sm font-medium text-gray-400 mb-2" {"Upload Tutor Groups CSV"}
+                                input multiple type="file" name="tutor_groups_csv" id="tutor_groups_csv" accept=".csv,.xlsx" class="block w-full text-sm text-gray-300 file:mr-4 file:py-2 file:px-4 file:rounded file:border-0 file:text-sm file:font-semibold file:bg-violet-50 file:text-violet-700 hover:file:bg-violet-100 mb-4";
+                                (form_submit_button(Some("Import Tutor Groups")))
                             }
                         }
                     }
@@ -173,6 +288,350 @@ pub async fn get_import_export_page(
     }))
 }
 
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+enum CsvDelimiter {
+    #[default]
+    Auto,
+    Comma,
+    Semicolon,
+    Tab,
+}
+
+impl CsvDelimiter {
+    const ALL: [Self; 4] = [Self::Auto, Self::Comma, Self::Semicolon, Self::Tab];
+
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Comma => "comma",
+            Self::Semicolon => "semicolon",
+            Self::Tab => "tab",
+        }
+    }
+
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Auto => "Auto-detect",
+            Self::Comma => "Comma (,)",
+            Self::Semicolon => "Semicolon (;)",
+            Self::Tab => "Tab",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|delimiter| delimiter.as_str() == value)
+            .unwrap_or_default()
+    }
+
+    ///for `Auto`, whichever appears most in the header line - commas win ties, as that's the most likely
+    fn byte_for(self, csv: &[u8]) -> u8 {
+        match self {
+            Self::Comma => b',',
+            Self::Semicolon => b';',
+            Self::Tab => b'\t',
+            Self::Auto => {
+                let header = csv.split(|b| *b == b'\n').next().unwrap_or_default();
+                [b'\t', b';', b',']
+                    .into_iter()
+                    .max_by_key(|delimiter| header.split(|b| b == delimiter).count())
+                    .unwrap_or(b',')
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+enum CsvDateTimeFormat {
+    #[default]
+    DayFirst,
+    MonthFirst,
+    Iso,
+}
+
+impl CsvDateTimeFormat {
+    const ALL: [Self; 3] = [Self::DayFirst, Self::MonthFirst, Self::Iso];
+
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::DayFirst => "dmy",
+            Self::MonthFirst => "mdy",
+            Self::Iso => "iso",
+        }
+    }
+
+    const fn label(self) -> &'static str {
+        match self {
+            Self::DayFirst => "DD-MM-YYYY HH:MM",
+            Self::MonthFirst => "MM-DD-YYYY HH:MM",
+            Self::Iso => "ISO 8601 (YYYY-MM-DDTHH:MM)",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.as_str() == value)
+            .unwrap_or_default()
+    }
+
+    ///for `strptime`
+    const fn pattern(self) -> &'static str {
+        match self {
+            Self::DayFirst => CSV_DATETIME_FORMAT,
+            Self::MonthFirst => "%m-%d-%Y %H:%M",
+            Self::Iso => "%Y-%m-%dT%H:%M",
+        }
+    }
+
+    const fn example(self) -> &'static str {
+        match self {
+            Self::DayFirst => "14-05-2025 08:20",
+            Self::MonthFirst => "05-14-2025 08:20",
+            Self::Iso => "2025-05-14T08:20",
+        }
+    }
+}
+
+fn csv_delimiter_picker(id: &str) -> Markup {
+    html! {
+        (form_element(id, "Delimiter", html!{
+            select id=(id) name="delimiter" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600" {
+                @for delimiter in CsvDelimiter::ALL {
+                    option value=(delimiter.as_str()) {(delimiter.label())}
+                }
+            }
+        }))
+    }
+}
+
+///an error from one row of an uploaded CSV
+struct RowError {
+    row: u64,
+    error: DenimError,
+}
+
+impl RowError {
+    ///`index` is from enumerating the records - rows are numbered like in a spreadsheet, with the header as row 1
+    fn new(index: usize, error: DenimError) -> Self {
+        //records can span multiple lines, so csv's own position is better when there is one
+        let row = match &error {
+            DenimError::Csv { source } => source.position().map(csv::Position::line),
+            _ => None,
+        }
+        .unwrap_or(index as u64 + 2);
+
+        Self { row, error }
+    }
+}
+
+impl Display for RowError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Row {}: {}", self.row, self.error)
+    }
+}
+
+///everything sent with an import form
+struct CsvUploads {
+    ///along with the delimiter to read each one with
+    csvs: Vec<(Bytes, u8)>,
+    ///only sent by the events form
+    datetime_format: CsvDateTimeFormat,
+    dry_run: bool,
+}
+
+impl CsvUploads {
+    fn readers(&self) -> impl Iterator<Item = csv::Reader<&[u8]>> {
+        self.csvs.iter().map(|(bytes, delimiter)| {
+            csv::ReaderBuilder::new()
+                .delimiter(*delimiter)
+                .from_reader(bytes.as_ref())
+        })
+    }
+}
+
+///spreadsheets get turned into CSVs, so they can go through exactly the same checks
+async fn read_csv_uploads(multipart: &mut Multipart) -> DenimResult<CsvUploads> {
+    let mut files = vec![];
+    let mut delimiter = CsvDelimiter::default();
+    let mut datetime_format = CsvDateTimeFormat::default();
+    let mut dry_run = false;
+
+    while let Some(field) = multipart.next_field().await.context(MultipartSnafu)? {
+        match field.name() {
+            Some("dry_run") => dry_run = true,
+            Some("delimiter") => {
+                delimiter = CsvDelimiter::parse(&field.text().await.context(MultipartSnafu)?);
+            }
+            Some("datetime_format") => {
+                datetime_format =
+                    CsvDateTimeFormat::parse(&field.text().await.context(MultipartSnafu)?);
+            }
+            _ => files.push(field.bytes().await.context(MultipartSnafu)?),
+        }
+    }
+
+    let csvs = files
+        .into_iter()
+        .map(|bytes| {
+            if infer::get(&bytes).is_some_and(|kind| kind.extension() == "xlsx") {
+                Ok((Bytes::from(xlsx_to_csv(&bytes, datetime_format)?), b','))
+            } else {
+                let delimiter = delimiter.byte_for(&bytes);
+                Ok((bytes, delimiter))
+            }
+        })
+        .collect::<DenimResult<_>>()?;
+
+    Ok(CsvUploads {
+        csvs,
+        datetime_format,
+        dry_run,
+    })
+}
+
+///only takes the first sheet, so there can't be any others to be confused with
+fn xlsx_to_csv(bytes: &[u8], datetime_format: CsvDateTimeFormat) -> DenimResult<Vec<u8>> {
+    let mut workbook: Xlsx<_> =
+        open_workbook_from_rs(Cursor::new(bytes)).context(SpreadsheetSnafu)?;
+
+    let sheets = workbook.sheet_names().len();
+    ensure!(sheets == 1, SpreadsheetSheetsSnafu { sheets });
+    let range = workbook
+        .worksheet_range_at(0)
+        .context(SpreadsheetSheetsSnafu { sheets: 0_usize })?
+        .context(SpreadsheetSnafu)?;
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for row in range.rows() {
+        writer
+            .write_record(row.iter().map(|cell| match cell {
+                //dates are stored as numbers, so they need writing out how the date format picker expects
+                Data::DateTime(excel_datetime) if excel_datetime.is_datetime() => {
+                    let (year, month, day, hour, minute, second, _milli) =
+                        excel_datetime.to_ymd_hms_milli();
+                    format!("{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}")
+                        .parse::<DateTime>()
+                        .map_or_else(
+                            |_| cell.to_string(),
+                            |datetime| datetime.strftime(datetime_format.pattern()).to_string(),
+                        )
+                }
+                _ => cell.to_string(),
+            }))
+            .context(CsvSnafu)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| csv::Error::from(e.into_error()))
+        .context(CsvSnafu)
+}
+
+pub fn csv_download(filename: &str, writer: csv::Writer<Vec<u8>>) -> DenimResult<Response> {
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| csv::Error::from(e.into_error()))
+        .context(CsvSnafu)?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ],
+        bytes,
+    )
+        .into_response())
+}
+
+pub async fn get_export_people(
+    State(state): State<DenimState>,
+    session: DenimSession,
+) -> DenimResult<Response> {
+    session.ensure_can(PermissionsTarget::EXPORT_CSVS)?;
+
+    let tutor_emails: HashMap<_, _> = User::get_all_staff(&state)
+        .await?
+        .into_iter()
+        .map(|staff| (staff.id, staff.email))
+        .collect();
+
+    let mut students = User::get_all_students(&state).await?;
+    students.sort_by(|a, b| {
+        a.surname
+            .cmp(&b.surname)
+            .then_with(|| a.first_name.cmp(&b.first_name))
+    });
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for student in students {
+        let UserKind::Student {
+            tutor_group, house, ..
+        } = student.kind
+        else {
+            continue;
+        };
+        let tutor_email = tutor_emails
+            .get(&tutor_group.staff_member)
+            .cloned()
+            .context(MissingUserSnafu {
+                id: tutor_group.staff_member,
+            })?;
+
+        writer
+            .serialize(NewCSVStudent {
+                first_name: student.first_name,
+                pref_name: student.pref_name.unwrap_or_default(),
+                surname: student.surname,
+                email: student.email,
+                house: house.name,
+                tutor_email,
+            })
+            .context(CsvSnafu)?;
+    }
+
+    csv_download("people.csv", writer)
+}
+
+pub async fn get_export_events(
+    State(state): State<DenimState>,
+    session: DenimSession,
+) -> DenimResult<Response> {
+    session.ensure_can(PermissionsTarget::EXPORT_CSVS)?;
+
+    let mut events = Event::get_all(&state).await?;
+    events.sort_by_key(|event| event.datetime.timestamp());
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for event in events {
+        writer
+            .serialize(DraftCsvEvent {
+                name: event.name,
+                datetime: event.datetime.strftime(CSV_DATETIME_FORMAT).to_string(),
+                location: event.location,
+                extra_info: event.extra_info,
+            })
+            .context(CsvSnafu)?;
+    }
+
+    csv_download("events.csv", writer)
+}
+
+///the format used for the `datetime` column in events CSVs, both for import and export
+const CSV_DATETIME_FORMAT: &str = "%d-%m-%Y %H:%M";
+
+#[derive(Serialize, Deserialize)]
+struct DraftCsvEvent {
+    name: String,
+    datetime: String,
+    location: Option<String>,
+    extra_info: Option<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 struct DraftEvent {
     name: String,
@@ -181,32 +640,43 @@ struct DraftEvent {
     extra_info: Option<String>,
 }
 
+impl DraftEvent {
+    ///the CSV times are wall-clock times in `tz`, just like the single event form
+    fn into_add_event(self, tz: &TimeZone, associated_staff: Vec<Uuid>) -> DenimResult<AddEvent> {
+        let Self {
+            name,
+            datetime,
+            location,
+            extra_info,
+        } = self;
+
+        Ok(AddEvent {
+            name,
+            date: datetime
+                .to_zoned(tz.clone())
+                .context(UnrepresentableTimeSnafu)?,
+            location,
+            extra_info,
+            associated_staff,
+            max_capacity: None,
+            recurrence_group: None,
+            tags: vec![],
+        })
+    }
+}
+
 pub async fn put_add_new_events(
     State(state): State<DenimState>,
     session: DenimSession,
     mut multipart: Multipart,
 ) -> DenimResult<Markup> {
-    #[derive(Deserialize)]
-    struct DraftCsvEvent {
-        name: String,
-        datetime: String,
-        location: Option<String>,
-        extra_info: Option<String>,
-    }
-
     session.ensure_can(PermissionsTarget::IMPORT_CSVS)?;
 
     let mut syntax_errors = vec![];
     let mut draft_events = vec![];
-    loop {
-        let Some(field) = multipart.next_field().await.context(MultipartSnafu)? else {
-            break;
-        };
-
-        let bytes = field.bytes().await.context(MultipartSnafu)?;
-        let mut rdr = csv::Reader::from_reader(bytes.as_ref());
-
-        for record in rdr.deserialize::<DraftCsvEvent>() {
+    let uploads = read_csv_uploads(&mut multipart).await?;
+    for mut rdr in uploads.readers() {
+        for (i, record) in rdr.deserialize::<DraftCsvEvent>().enumerate() {
             let DraftCsvEvent {
                 name,
                 datetime,
@@ -215,18 +685,22 @@ pub async fn put_add_new_events(
             } = match record {
                 Ok(x) => x,
                 Err(source) => {
-                    syntax_errors.push(DenimError::Csv { source });
+                    syntax_errors.push(RowError::new(i, DenimError::Csv { source }));
                     continue;
                 }
             };
 
-            let datetime = match DateTime::strptime("%d-%m-%Y %H:%M", &datetime) {
+            let datetime = match DateTime::strptime(uploads.datetime_format.pattern(), &datetime) {
                 Ok(datetime) => datetime,
                 Err(e) => {
-                    syntax_errors.push(DenimError::ParseTime {
-                        source: e,
-                        original: datetime,
-                    });
+                    syntax_errors.push(RowError::new(
+                        i,
+                        DenimError::ParseImportTime {
+                            source: e,
+                            original: datetime,
+                            example: uploads.datetime_format.example(),
+                        },
+                    ));
                     continue;
                 }
             };
@@ -292,14 +766,14 @@ pub async fn put_fully_import_events(
 ) -> DenimResult<Markup> {
     session.ensure_can(PermissionsTarget::IMPORT_CSVS)?;
 
-    let associated_staff_member = if associated_staff_member.is_empty() {
-        None
+    let associated_staff = if associated_staff_member.is_empty() {
+        vec![]
     } else {
-        Some(
+        vec![
             Uuid::try_parse(&associated_staff_member).context(ParseUuidSnafu {
                 original: associated_staff_member,
             })?,
-        )
+        ]
     };
 
     let tz = TimeZone::get(&tz).context(InvalidTimezoneSnafu { tz })?;
@@ -311,27 +785,11 @@ pub async fn put_fully_import_events(
     let mut errors = vec![];
 
     let mut tx = state.get_transaction().await?;
-    for DraftEvent {
-        name,
-        datetime,
-        location,
-        extra_info,
-    } in draft_events
-    {
-        if let Err(e) = Event::insert_into_database(
-            AddEvent {
-                name: name.clone(),
-                date: datetime
-                    .to_zoned(tz.clone())
-                    .context(UnrepresentableTimeSnafu)?,
-                location,
-                extra_info,
-                associated_staff_member,
-            },
-            &mut tx,
-        )
-        .await
-        {
+    let num_events = draft_events.len();
+    for draft_event in draft_events {
+        let name = draft_event.name.clone();
+        let add_event = draft_event.into_add_event(&tz, associated_staff.clone())?;
+        if let Err(e) = Event::insert_into_database(add_event, &mut tx).await {
             errors.push(html! {
                 "Error adding: \"" (name) "\": " (e.to_string())
             });
@@ -348,19 +806,28 @@ pub async fn put_fully_import_events(
     }
 
     tx.commit().await.context(CommitTransactionSnafu)?;
+    METRICS.record_import();
+    state
+        .audit(
+            &session,
+            AuditAction::Import,
+            AuditTarget::Events,
+            Some(&format!("{num_events} events")),
+        )
+        .await;
     state.send_sse_event(SseEvent::CrudEvent);
 
-    Ok(html! {
-        div class="flex flex-col m-4 p-4 space-y-4 rounded shadow items-center justify-center text-center" {
-            p {"Successfully added events to database"}
-        }
-    })
+    Ok(toast(
+        ToastKind::Success,
+        format!("Successfully added {num_events} events to the database"),
+    ))
 }
 
 #[allow(clippy::too_many_lines)]
 pub async fn put_add_new_students(
     State(state): State<DenimState>,
     session: DenimSession,
+    tower_session: Session,
     mut multipart: Multipart,
 ) -> DenimResult<Markup> {
     struct DraftIndividualStudent {
@@ -377,6 +844,7 @@ pub async fn put_add_new_students(
         return get_students_import_checker(
             State(state),
             session,
+            tower_session,
             Query(ImportCheckerQuery {
                 dots: String::new(),
             }),
@@ -393,40 +861,38 @@ pub async fn put_add_new_students(
         .map(|house| (house.name, house.id))
         .collect();
 
-    let mut tutor_group_lookup = HashMap::new();
-    let mut conn = state.get_connection().await?;
-    for tutor_group in TutorGroup::get_all(&state).await? {
-        let email = sqlx::query!(
-            "SELECT email FROM users WHERE id = $1",
-            tutor_group.staff_member
-        )
-        .fetch_one(&mut *conn)
-        .await
-        .context(MakeQuerySnafu)?;
-
-        let proper_email_address = EmailAddress::from_str(&email.email).context(EmailSnafu)?;
-
-        tutor_group_lookup.insert((proper_email_address, tutor_group.house_id), tutor_group.id);
-    }
-
     let existing_teachers: HashMap<_, _> = User::get_all_staff(&state)
         .await?
         .into_iter()
         .map(|teacher| (teacher.email, teacher.id))
         .collect();
 
-    let mut transaction = state.get_transaction().await?;
-    let mut syntax_errors = vec![];
+    //every form tutor is a member of staff, so their emails are already in `existing_teachers`
+    let teacher_emails: HashMap<_, _> = existing_teachers
+        .iter()
+        .map(|(email, id)| (*id, email.clone()))
+        .collect();
+    let mut tutor_group_lookup: HashMap<_, _> = TutorGroup::get_all(&state)
+        .await?
+        .into_iter()
+        .filter_map(|tutor_group| {
+            let email = teacher_emails.get(&tutor_group.staff_member)?.clone();
+            Some(((email, tutor_group.house_id), tutor_group.id))
+        })
+        .collect();
 
-    loop {
-        let Some(field) = multipart.next_field().await.context(MultipartSnafu)? else {
-            break;
-        };
+    //read everything first, so we know whether this is a dry run before touching the DB
+    let uploads = read_csv_uploads(&mut multipart).await?;
+    let dry_run = uploads.dry_run;
 
-        let bytes = field.bytes().await.context(MultipartSnafu)?;
-        let mut rdr = csv::Reader::from_reader(bytes.as_ref());
+    let mut transaction = state.get_transaction().await?;
+    let mut syntax_errors = vec![];
+    let mut houses_to_create = vec![];
+    let mut next_placeholder_house_id = 0;
+    let mut tutor_groups_to_create = vec![];
 
-        for record in rdr.deserialize::<NewCSVStudent>() {
+    for mut rdr in uploads.readers() {
+        for (i, record) in rdr.deserialize::<NewCSVStudent>().enumerate() {
             let NewCSVStudent {
                 first_name,
                 pref_name,
@@ -437,23 +903,31 @@ pub async fn put_add_new_students(
             } = match record {
                 Ok(x) => x,
                 Err(source) => {
-                    syntax_errors.push(DenimError::Csv { source });
+                    syntax_errors.push(RowError::new(i, DenimError::Csv { source }));
                     continue;
                 }
             };
 
-            let house = if let Some(id) = houses_lookup.get(&house) {
+            let house_name = house;
+            let house = if let Some(id) = houses_lookup.get(&house_name) {
                 *id
             } else {
-                let new_index = HouseGroup::insert_into_database(
-                    NewHouse {
-                        name: house.clone(),
-                    },
-                    &mut transaction,
-                )
-                .await?;
+                //in a dry run, we just need an ID that can't clash so that later rows can find the same house
+                let new_index = if dry_run {
+                    next_placeholder_house_id -= 1;
+                    next_placeholder_house_id
+                } else {
+                    HouseGroup::insert_into_database(
+                        NewHouse {
+                            name: house_name.clone(),
+                        },
+                        &mut transaction,
+                    )
+                    .await?
+                };
 
-                houses_lookup.insert(house, new_index);
+                houses_to_create.push(house_name.clone());
+                houses_lookup.insert(house_name.clone(), new_index);
                 new_index
             };
 
@@ -461,15 +935,20 @@ pub async fn put_add_new_students(
                 if let Some(id) = tutor_group_lookup.get(&(tutor_email.clone(), house)) {
                     *id
                 } else if let Some(teacher_id) = existing_teachers.get(&tutor_email) {
-                    let new_index = TutorGroup::insert_into_database(
-                        NewTutorGroup {
-                            staff_id: *teacher_id,
-                            house_id: house,
-                        },
-                        &mut transaction,
-                    )
-                    .await?;
-
+                    let new_index = if dry_run {
+                        Uuid::new_v4()
+                    } else {
+                        TutorGroup::insert_into_database(
+                            NewTutorGroup {
+                                staff_id: *teacher_id,
+                                house_id: house,
+                            },
+                            &mut transaction,
+                        )
+                        .await?
+                    };
+
+                    tutor_groups_to_create.push((tutor_email.clone(), house_name));
                     tutor_group_lookup.insert((tutor_email, house), new_index);
                     new_index
                 } else {
@@ -487,6 +966,57 @@ pub async fn put_add_new_students(
         }
     }
 
+    if dry_run {
+        transaction
+            .rollback()
+            .await
+            .context(RollbackTransactionSnafu)?;
+
+        return Ok(html! {
+            div class="flex flex-col space-y-4" {
+                (subtitle("Dry Run Results"))
+                p {"Nothing has been saved - untick \"Dry Run\" and upload again to import for real."}
+                (table(
+                    subsubtitle("Summary"),
+                    ["", "Count"],
+                    vec![
+                        ["Students to be created".to_string(), students_to_add.len().to_string()],
+                        ["Houses to be created".to_string(), houses_to_create.len().to_string()],
+                        ["Tutor groups to be created".to_string(), tutor_groups_to_create.len().to_string()],
+                        ["Missing teachers".to_string(), teachers_to_add.len().to_string()],
+                        ["Syntax errors".to_string(), syntax_errors.len().to_string()],
+                    ]
+                ))
+                @if !houses_to_create.is_empty() {
+                    (table(
+                        subsubtitle("New Houses"),
+                        ["Name"],
+                        houses_to_create.into_iter().map(|name| [name]).collect()
+                    ))
+                }
+                @if !tutor_groups_to_create.is_empty() {
+                    (table(
+                        subsubtitle("New Tutor Groups"),
+                        ["Tutor", "House"],
+                        tutor_groups_to_create.iter().map(|(email, house)| [Email(email).render(), html!{(house)}]).collect()
+                    ))
+                }
+                @if !teachers_to_add.is_empty() {
+                    (errors_list(
+                        Some("The following teachers need to be added:"),
+                        teachers_to_add.iter().map(|email| Email(email).render()),
+                    ))
+                }
+                @if !syntax_errors.is_empty() {
+                    (errors_list(
+                        Some("The following syntax errors were found in your CSV:"),
+                        syntax_errors.iter().map(ToString::to_string),
+                    ))
+                }
+            }
+        });
+    }
+
     if !teachers_to_add.is_empty() {
         return Ok(errors_list(
             Some("The following teachers need to be added:"),
@@ -528,12 +1058,24 @@ pub async fn put_add_new_students(
     let num_students = students_to_add.len();
     let (tx, rx) = channel((0, num_students));
 
+    //if it all goes wrong, the checker takes this back out when it finds the error
+    tower_session
+        .insert(
+            ImportedPasswordsKind::Students.session_key(),
+            csv_password.clone(),
+        )
+        .await
+        .context(TowerSessionSnafu)?;
+
     let task = tokio::task::spawn({
         let state = state.clone();
+        let session = session.clone();
+        let tower_session = tower_session.clone();
         async move {
             let mut output_csv = String::from("email,default_password");
             let mut errors = vec![];
             let mut pg_connection = state.get_transaction().await?;
+            let bcrypt_cost = state.config().security_config().bcrypt_cost;
 
             for (
                 i,
@@ -558,6 +1100,7 @@ pub async fn put_add_new_students(
                         password: Some(password.clone().into()),
                         current_password_is_default: true,
                         user_kind: AddUserKind::Student { tutor_group },
+                        bcrypt_cost,
                     },
                     &mut pg_connection,
                 )
@@ -578,58 +1121,42 @@ pub async fn put_add_new_students(
             }
 
             if !errors.is_empty() {
+                //nothing got saved, so there aren't any passwords to keep
+                tower_session
+                    .remove::<String>(ImportedPasswordsKind::Students.session_key())
+                    .await
+                    .context(TowerSessionSnafu)?;
                 return Ok(errors_list(
                     Some("Errors adding students to database"),
                     errors.into_iter(),
                 ));
             }
 
-            let mut mock_file_contents = vec![];
-            let mut zip = ZipWriter::new(Cursor::new(&mut mock_file_contents));
-
-            zip.start_file(
-                "passwords.csv",
-                SimpleFileOptions::default().with_aes_encryption(AesMode::Aes256, &csv_password),
+            let presigned_get_url = upload_latest_passwords(
+                &*state.config().s3_bucket().get()?,
+                ImportedPasswordsKind::Students,
+                &output_csv,
+                &csv_password,
             )
-            .context(ZipSnafu)?;
-            zip.write_all(output_csv.as_bytes())
-                .expect("unable to write passwords to mock zip file");
-            zip.finish().context(ZipSnafu)?;
-
-            let bucket = state.config().s3_bucket().get()?;
-            bucket
-                .put_object_with_content_type(
-                    "latest_passwords.zip",
-                    mock_file_contents.as_slice(),
-                    "application/zip",
-                )
-                .await
-                .context(S3Snafu)?;
-
-            let presigned_get_url = {
-                let mut custom_queries = HashMap::new();
-                custom_queries.insert(
-                    "response-content-disposition".into(),
-                    "attachment; filename=\"latest_passwords.zip\"".into(),
-                );
-
-                bucket
-                    .presign_get(
-                        "latest_passwords.zip",
-                        2 * 24 * 60 * 60,
-                        Some(custom_queries),
-                    )
-                    .await
-                    .context(S3Snafu)?
-            };
+            .await?;
 
             pg_connection
                 .commit()
                 .await
                 .context(CommitTransactionSnafu)?; //ensure we only commit when we can defo send everything back to the user :)
+            METRICS.record_import();
+            state
+                .audit(
+                    &session,
+                    AuditAction::Import,
+                    AuditTarget::People,
+                    Some(&format!("{num_students} students")),
+                )
+                .await;
             state.send_sse_event(SseEvent::CrudPerson);
 
             Ok(html! {
+                (toast(ToastKind::Success, format!("Created {num_students} student accounts")))
                 div class="flex flex-col m-4 p-4 space-y-4 rounded shadow items-center justify-center text-center" {
                     p {"Student accounts created - ZIP password is \"" (csv_password) "\""}
 
@@ -639,10 +1166,26 @@ pub async fn put_add_new_students(
         }
     });
 
+    //forward progress to anyone watching, with one last update once the job drops the sender
+    tokio::task::spawn({
+        let state = state.clone();
+        let mut rx = rx.clone();
+        async move {
+            while rx.changed().await.is_ok() {
+                let (done, total) = *rx.borrow_and_update();
+                state.send_sse_event(SseEvent::ImportProgress { done, total });
+            }
+
+            let (done, total) = *rx.borrow();
+            state.send_sse_event(SseEvent::ImportProgress { done, total });
+        }
+    });
+
     job_submitter_token.submit_job(task, rx).await;
     get_students_import_checker(
         State(state),
         session,
+        tower_session,
         Query(ImportCheckerQuery {
             dots: String::new(),
         }),
@@ -651,27 +1194,582 @@ pub async fn put_add_new_students(
 }
 
 #[derive(Deserialize)]
-pub struct ImportCheckerQuery {
-    dots: String,
+struct NewCSVHouse {
+    name: String,
 }
 
-pub async fn get_students_import_checker(
+#[derive(Deserialize)]
+struct NewCSVTutorGroup {
+    house: String,
+    tutor_email: EmailAddress,
+}
+
+pub async fn put_add_new_houses(
     State(state): State<DenimState>,
     session: DenimSession,
-    Query(ImportCheckerQuery { dots }): Query<ImportCheckerQuery>,
+    mut multipart: Multipart,
 ) -> DenimResult<Markup> {
     session.ensure_can(PermissionsTarget::IMPORT_CSVS)?;
 
-    if !state.student_job_token_exists() {
-        tokio::time::sleep(Duration::from_millis(1000)).await;
-        return Ok(errors_list(
-            Some("No Import Job Exists"),
-            std::iter::empty::<String>(),
+    let mut syntax_errors = vec![];
+    let mut names = vec![];
+    let uploads = read_csv_uploads(&mut multipart).await?;
+    for mut rdr in uploads.readers() {
+        for (i, record) in rdr.deserialize::<NewCSVHouse>().enumerate() {
+            match record {
+                Ok(NewCSVHouse { name }) => names.push(name),
+                Err(source) => syntax_errors.push(RowError::new(i, DenimError::Csv { source })),
+            }
+        }
+    }
+
+    if !syntax_errors.is_empty() {
+        return Ok(errors_list(
+            Some("The following syntax errors were found in your CSV:"),
+            syntax_errors.into_iter().map(|e| e.to_string()),
+        ));
+    }
+
+    let mut existing: HashSet<_> = HouseGroup::get_all(&state)
+        .await?
+        .into_iter()
+        .map(|house| house.name)
+        .collect();
+
+    let mut transaction = state.get_transaction().await?;
+    let mut num_created = 0;
+    for name in names {
+        if !existing.insert(name.clone()) {
+            continue;
+        }
+
+        HouseGroup::insert_into_database(NewHouse { name }, &mut transaction).await?;
+        num_created += 1;
+    }
+    transaction.commit().await.context(CommitTransactionSnafu)?;
+
+    METRICS.record_import();
+    state
+        .audit(
+            &session,
+            AuditAction::Import,
+            AuditTarget::Groups,
+            Some(&format!("{num_created} houses")),
+        )
+        .await;
+    state.send_sse_event(SseEvent::CrudGroups);
+
+    Ok(toast(
+        ToastKind::Success,
+        format!("Created {num_created} houses, skipping any that already existed"),
+    ))
+}
+
+pub async fn put_add_new_tutor_groups(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    mut multipart: Multipart,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::IMPORT_CSVS)?;
+
+    let mut syntax_errors = vec![];
+    let mut draft_groups = vec![];
+    let uploads = read_csv_uploads(&mut multipart).await?;
+    for mut rdr in uploads.readers() {
+        for (i, record) in rdr.deserialize::<NewCSVTutorGroup>().enumerate() {
+            match record {
+                Ok(group) => draft_groups.push(group),
+                Err(source) => syntax_errors.push(RowError::new(i, DenimError::Csv { source })),
+            }
+        }
+    }
+
+    if !syntax_errors.is_empty() {
+        return Ok(errors_list(
+            Some("The following syntax errors were found in your CSV:"),
+            syntax_errors.into_iter().map(|e| e.to_string()),
+        ));
+    }
+
+    let staff_lookup: HashMap<_, _> = User::get_all_staff(&state)
+        .await?
+        .into_iter()
+        .map(|staff| (staff.email, staff.id))
+        .collect();
+
+    let unknown_tutors: HashSet<_> = draft_groups
+        .iter()
+        .filter(|group| !staff_lookup.contains_key(&group.tutor_email))
+        .map(|group| group.tutor_email.clone())
+        .collect();
+    if !unknown_tutors.is_empty() {
+        return Ok(errors_list(
+            Some("The following tutors aren't staff members, so need to be added first:"),
+            unknown_tutors.iter().map(|email| Email(email).render()),
+        ));
+    }
+
+    let mut houses_lookup: HashMap<_, _> = HouseGroup::get_all(&state)
+        .await?
+        .into_iter()
+        .map(|house| (house.name, house.id))
+        .collect();
+    let mut existing_groups: HashSet<_> = TutorGroup::get_all(&state)
+        .await?
+        .into_iter()
+        .map(|group| (group.staff_member, group.house_id))
+        .collect();
+
+    let mut transaction = state.get_transaction().await?;
+    let mut num_houses_created = 0;
+    let mut num_groups_created = 0;
+    for NewCSVTutorGroup { house, tutor_email } in draft_groups {
+        let house_id = if let Some(id) = houses_lookup.get(&house) {
+            *id
+        } else {
+            let id = HouseGroup::insert_into_database(
+                NewHouse {
+                    name: house.clone(),
+                },
+                &mut transaction,
+            )
+            .await?;
+            houses_lookup.insert(house, id);
+            num_houses_created += 1;
+            id
+        };
+
+        let staff_id = staff_lookup[&tutor_email];
+        if !existing_groups.insert((staff_id, house_id)) {
+            continue;
+        }
+
+        TutorGroup::insert_into_database(NewTutorGroup { staff_id, house_id }, &mut transaction)
+            .await?;
+        num_groups_created += 1;
+    }
+    transaction.commit().await.context(CommitTransactionSnafu)?;
+
+    METRICS.record_import();
+    state
+        .audit(
+            &session,
+            AuditAction::Import,
+            AuditTarget::Groups,
+            Some(&format!(
+                "{num_groups_created} tutor groups, {num_houses_created} houses"
+            )),
+        )
+        .await;
+    state.send_sse_event(SseEvent::CrudGroups);
+
+    Ok(toast(
+        ToastKind::Success,
+        format!(
+            "Created {num_groups_created} tutor groups and {num_houses_created} houses, skipping any that already existed"
+        ),
+    ))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewCSVStaff {
+    first_name: String,
+    pref_name: String,
+    surname: String,
+    email: EmailAddress,
+}
+
+///staff lists are small enough to do in one go, unlike students which need a background job
+#[allow(clippy::too_many_lines)]
+pub async fn put_add_new_staff(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    tower_session: Session,
+    mut multipart: Multipart,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::IMPORT_CSVS)?;
+
+    let mut syntax_errors = vec![];
+    let mut staff_to_add = vec![];
+    let uploads = read_csv_uploads(&mut multipart).await?;
+    for mut rdr in uploads.readers() {
+        for (i, record) in rdr.deserialize::<NewCSVStaff>().enumerate() {
+            match record {
+                Ok(staff) => staff_to_add.push(staff),
+                Err(source) => syntax_errors.push(RowError::new(i, DenimError::Csv { source })),
+            }
+        }
+    }
+
+    if !syntax_errors.is_empty() {
+        return Ok(errors_list(
+            Some("The following syntax errors were found in your CSV:"),
+            syntax_errors.into_iter().map(|e| e.to_string()),
+        ));
+    }
+
+    //adding over an existing account would reset their password, so leave them be
+    let mut conn = state.get_connection().await?;
+    let mut already_exist = vec![];
+    let mut seen = HashSet::new();
+    let mut new_staff = vec![];
+    for staff in staff_to_add {
+        if !seen.insert(staff.email.clone())
+            || User::email_is_taken(&staff.email, None, &mut conn).await?
+        {
+            already_exist.push(staff.email);
+        } else {
+            new_staff.push(staff);
+        }
+    }
+    drop(conn);
+
+    if new_staff.is_empty() {
+        return Ok(errors_list(
+            Some("No new staff to add - these already have accounts:"),
+            already_exist.iter().map(|email| Email(email).render()),
+        ));
+    }
+
+    new_staff.sort_by_cached_key(|staff| staff.email.to_string());
+
+    let (passwords, csv_password) = {
+        #[allow(clippy::significant_drop_tightening)]
+        let auth_config = state.config().auth_config();
+        let auth_config = auth_config.get()?;
+
+        let mut passwords = (0..=new_staff.len())
+            .map(|_| auth_config.generate())
+            .collect::<Result<Vec<_>, _>>()?;
+        let csv_password = passwords
+            .pop()
+            .expect("adding 1 to a min 0, must have an element");
+
+        (passwords, csv_password)
+    };
+    let num_staff = new_staff.len();
+
+    let mut output_csv = String::from("email,default_password");
+    let mut transaction = state.get_transaction().await?;
+    let bcrypt_cost = state.config().security_config().bcrypt_cost;
+    for (
+        NewCSVStaff {
+            first_name,
+            pref_name,
+            surname,
+            email,
+        },
+        password,
+    ) in new_staff.into_iter().zip(passwords)
+    {
+        User::insert_into_database(
+            AddPerson {
+                first_name,
+                pref_name,
+                surname,
+                email: email.clone(),
+                password: Some(password.clone().into()),
+                current_password_is_default: true,
+                user_kind: AddUserKind::Staff,
+                bcrypt_cost,
+            },
+            &mut transaction,
+        )
+        .await?;
+
+        write!(&mut output_csv, "\n{email},{password}")
+            .expect("unable to add passwords to zip file");
+    }
+
+    let presigned_get_url = upload_latest_passwords(
+        &*state.config().s3_bucket().get()?,
+        ImportedPasswordsKind::Staff,
+        &output_csv,
+        &csv_password,
+    )
+    .await?;
+
+    transaction.commit().await.context(CommitTransactionSnafu)?;
+    METRICS.record_import();
+    state
+        .audit(
+            &session,
+            AuditAction::Import,
+            AuditTarget::People,
+            Some(&format!("{num_staff} staff")),
+        )
+        .await;
+    state.send_sse_event(SseEvent::CrudPerson);
+
+    tower_session
+        .insert(
+            ImportedPasswordsKind::Staff.session_key(),
+            csv_password.clone(),
+        )
+        .await
+        .context(TowerSessionSnafu)?;
+    let include_students = !state.student_job_is_actually_running().await;
+    let banner = unsaved_passwords_banner(
+        &pending_passwords(&tower_session, include_students).await?,
+        true,
+    );
+
+    Ok(html! {
+        (toast(ToastKind::Success, format!("Created {num_staff} staff accounts")))
+        div class="flex flex-col m-4 p-4 space-y-4 rounded shadow items-center justify-center text-center" {
+            p {"Staff accounts created - ZIP password is \"" (csv_password) "\""}
+
+            a href=(presigned_get_url) target="_blank" class="text-gray-300 bg-green-900 hover:bg-green-700 px-3 py-2 rounded-md text-sm font-medium" {"Get Passwords for Staff"}
+        }
+        @if !already_exist.is_empty() {
+            (errors_list(
+                Some("These were skipped as they already have accounts:"),
+                already_exist.iter().map(|email| Email(email).render()),
+            ))
+        }
+        (banner)
+    })
+}
+
+///staff and students get their own ZIP and session slot, so one import can't clobber the other's unsaved passwords
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportedPasswordsKind {
+    Students,
+    Staff,
+}
+
+impl ImportedPasswordsKind {
+    pub const ALL: [Self; 2] = [Self::Students, Self::Staff];
+
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Students => "students",
+            Self::Staff => "staff",
+        }
+    }
+
+    const fn object_key(self) -> &'static str {
+        match self {
+            Self::Students => "latest_student_passwords.zip",
+            Self::Staff => "latest_staff_passwords.zip",
+        }
+    }
+
+    ///the ZIP password from the last import sits in the importer's session until they say they've saved it
+    const fn session_key(self) -> &'static str {
+        match self {
+            Self::Students => "unsaved_student_import_passwords",
+            Self::Staff => "unsaved_staff_import_passwords",
+        }
+    }
+
+    fn download_url(self) -> String {
+        format!("/import_export/latest_passwords?kind={}", self.as_str())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ImportedPasswordsQuery {
+    kind: ImportedPasswordsKind,
+}
+
+const LATEST_PASSWORDS_LINK_VALIDITY_SECS: u32 = 2 * 24 * 60 * 60;
+
+async fn presign_latest_passwords(
+    bucket: &Bucket,
+    kind: ImportedPasswordsKind,
+) -> DenimResult<String> {
+    let mut custom_queries = HashMap::new();
+    custom_queries.insert(
+        "response-content-disposition".into(),
+        format!("attachment; filename=\"{}\"", kind.object_key()),
+    );
+
+    bucket
+        .presign_get(
+            kind.object_key(),
+            LATEST_PASSWORDS_LINK_VALIDITY_SECS,
+            Some(custom_queries),
+        )
+        .await
+        .context(S3Snafu)
+}
+
+///replaces the passwords from the last import of this kind with a ZIP of `output_csv`, giving back a link to download it
+async fn upload_latest_passwords(
+    bucket: &Bucket,
+    kind: ImportedPasswordsKind,
+    output_csv: &str,
+    zip_password: &str,
+) -> DenimResult<String> {
+    let mut mock_file_contents = vec![];
+    let mut zip = ZipWriter::new(Cursor::new(&mut mock_file_contents));
+
+    zip.start_file(
+        "passwords.csv",
+        SimpleFileOptions::default().with_aes_encryption(AesMode::Aes256, zip_password),
+    )
+    .context(ZipSnafu)?;
+    zip.write_all(output_csv.as_bytes())
+        .expect("unable to write passwords to mock zip file");
+    zip.finish().context(ZipSnafu)?;
+
+    bucket
+        .put_object_with_content_type(
+            kind.object_key(),
+            mock_file_contents.as_slice(),
+            "application/zip",
+        )
+        .await
+        .context(S3Snafu)?;
+
+    presign_latest_passwords(bucket, kind).await
+}
+
+///gets a fresh link to the passwords from the last import, in case the original got lost
+pub async fn get_latest_passwords(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Query(ImportedPasswordsQuery { kind }): Query<ImportedPasswordsQuery>,
+) -> DenimResult<Response> {
+    session.ensure_can(PermissionsTarget::IMPORT_CSVS)?;
+
+    let bucket = state.config().s3_bucket().get()?;
+    let exists = bucket
+        .head_object(kind.object_key())
+        .await
+        .is_ok_and(|(_head, status)| status == 200);
+
+    if exists {
+        let url = presign_latest_passwords(&bucket, kind).await?;
+        return Ok(Redirect::to(&url).into_response());
+    }
+
+    Ok(state
+        .render(
+            session,
+            html! {
+                div class="flex flex-col rounded shadow-xl bg-gray-800 p-4 m-4" {
+                    p class="text-lg" {"There aren't any " (kind.as_str()) " passwords to download yet - they only get saved when " (kind.as_str()) " are imported."}
+                    br;
+                    a href="/import_export" class="hover:text-blue-300 underline" {"Back to Import/Export"}
+                }
+            },
+        )
+        .into_response())
+}
+
+///the ZIP passwords still waiting to be saved - student ones are left out while their job is still running, as the checker shows those
+async fn pending_passwords(
+    tower_session: &Session,
+    include_students: bool,
+) -> DenimResult<Vec<(ImportedPasswordsKind, String)>> {
+    let mut pending = vec![];
+    for kind in ImportedPasswordsKind::ALL {
+        if kind == ImportedPasswordsKind::Students && !include_students {
+            continue;
+        }
+
+        if let Some(zip_password) = tower_session
+            .get::<String>(kind.session_key())
+            .await
+            .context(TowerSessionSnafu)?
+        {
+            pending.push((kind, zip_password));
+        }
+    }
+    Ok(pending)
+}
+
+///goes in the slot under the nav, `oob` for when it's tagging along with another response
+fn unsaved_passwords_banner(pending: &[(ImportedPasswordsKind, String)], oob: bool) -> Markup {
+    html! {
+        div id="unsaved_passwords_banner" hx-swap-oob=[oob.then_some("true")] {
+            @for (kind, zip_password) in pending {
+                div class="flex flex-row items-center justify-between space-x-4 bg-yellow-900 rounded-md p-4 m-2" {
+                    p {
+                        @match kind {
+                            ImportedPasswordsKind::Students => "Students were imported",
+                            ImportedPasswordsKind::Staff => "Staff were imported",
+                        }
+                        " - the ZIP password is \"" (zip_password) "\". It won't be shown again once this is dismissed!"
+                    }
+                    a href=(kind.download_url()) target="_blank" class="text-gray-300 bg-green-900 hover:bg-green-700 px-3 py-2 rounded-md text-sm font-medium" {"Get Passwords"}
+                    button hx-delete={"/internal/import_export/unsaved_passwords?kind=" (kind.as_str())} hx-target="#unsaved_passwords_banner" hx-swap="outerHTML" hx-confirm="Have you saved both the passwords file and the ZIP password?" class="text-gray-300 bg-slate-900 hover:bg-slate-700 px-3 py-2 rounded-md text-sm font-medium cursor-pointer" {"I've saved the passwords"}
+                }
+            }
+        }
+    }
+}
+
+pub async fn internal_get_unsaved_passwords(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    tower_session: Session,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::IMPORT_CSVS)?;
+
+    //until the job's been collected, the checker is the one showing the student password
+    let include_students = !state.student_job_is_actually_running().await;
+    let pending = pending_passwords(&tower_session, include_students).await?;
+    Ok(unsaved_passwords_banner(&pending, false))
+}
+
+pub async fn delete_unsaved_passwords(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    tower_session: Session,
+    Query(ImportedPasswordsQuery { kind }): Query<ImportedPasswordsQuery>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::IMPORT_CSVS)?;
+
+    tower_session
+        .remove::<String>(kind.session_key())
+        .await
+        .context(TowerSessionSnafu)?;
+
+    let include_students = !state.student_job_is_actually_running().await;
+    let pending = pending_passwords(&tower_session, include_students).await?;
+    Ok(unsaved_passwords_banner(&pending, false))
+}
+
+#[derive(Deserialize)]
+pub struct ImportCheckerQuery {
+    dots: String,
+}
+
+pub async fn get_students_import_checker(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    tower_session: Session,
+    Query(ImportCheckerQuery { dots }): Query<ImportCheckerQuery>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::IMPORT_CSVS)?;
+
+    if !state.student_job_token_exists() {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        return Ok(errors_list(
+            Some("No Import Job Exists"),
+            std::iter::empty::<String>(),
         ));
     }
 
     if let Some(finished_job) = state.take_import_students_job_result().await {
-        return finished_job;
+        if finished_job.is_err() {
+            tower_session
+                .remove::<String>(ImportedPasswordsKind::Students.session_key())
+                .await
+                .context(TowerSessionSnafu)?;
+            return finished_job;
+        }
+
+        let banner =
+            unsaved_passwords_banner(&pending_passwords(&tower_session, true).await?, true);
+        return Ok(html! {
+            (finished_job?)
+            (banner)
+        });
     }
 
     let dots = match dots.as_str() {
@@ -687,7 +1785,15 @@ pub async fn get_students_import_checker(
         "}"
     };
 
-    let fmt_n_students = if let Some((done, total)) = state.check_students_job_progress().await {
+    let progress = state.check_students_job_progress().await;
+    //once all the students are in, we're just waiting on the passwords with no more updates coming, so poll until the job finishes
+    let hx_trigger = if progress.is_some_and(|(done, total)| done == total) {
+        "sse:import_progress, every 1s"
+    } else {
+        "sse:import_progress"
+    };
+
+    let fmt_n_students = if let Some((done, total)) = progress {
         html! {
             p {
                 "So far, added " (done) " student"
@@ -706,13 +1812,126 @@ pub async fn get_students_import_checker(
     };
 
     Ok(html! {
-        div hx-get="/import_export/import_people_fetch" hx-vals=(hx_vals) hx-trigger="every 1s" hx-target="this" hx-swap="outerHTML" {
+        div hx-get="/import_export/import_people_fetch" hx-vals=(hx_vals) hx-trigger=(hx_trigger) hx-target="this" hx-swap="outerHTML" {
             div class="flex flex-col items-center justify-center p-4 m-4 shadow rounded" {
                 p {"Now securing passwords" (dots)}
                 (fmt_n_students)
                 br;
-                p {"If you close this tab, remember to re-open it later to save the passwords!"}
+                p {"You can leave this page - the ZIP password will stay at the top of every page until you've saved the passwords."}
             }
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{CsvDelimiter, DraftEvent, ImportedPasswordsKind, unsaved_passwords_banner};
+    use crate::{
+        data::{DataType, event::Event},
+        routes::all_events::NewEventForm,
+    };
+    use jiff::{civil::date, tz::TimeZone};
+    use sqlx::PgPool;
+
+    #[test]
+    fn explicit_delimiters_ignore_the_csv() {
+        let csv = b"a;b;c\tq\n1;2;3\n";
+        assert_eq!(CsvDelimiter::Comma.byte_for(csv), b',');
+        assert_eq!(CsvDelimiter::Semicolon.byte_for(b"a,b,c\n"), b';');
+        assert_eq!(CsvDelimiter::Tab.byte_for(csv), b'\t');
+    }
+
+    #[test]
+    fn auto_picks_the_most_common_in_the_header() {
+        assert_eq!(CsvDelimiter::Auto.byte_for(b"a,b,c\n1,2,3\n"), b',');
+        assert_eq!(CsvDelimiter::Auto.byte_for(b"a;b;c,d\n1;2;3,4\n"), b';');
+        assert_eq!(CsvDelimiter::Auto.byte_for(b"a\tb\tc\n1\t2\t3\n"), b'\t');
+    }
+
+    #[test]
+    fn auto_only_looks_at_the_header() {
+        assert_eq!(CsvDelimiter::Auto.byte_for(b"a;b\n1,2,3,4,5\n"), b';');
+    }
+
+    #[test]
+    fn auto_breaks_ties_towards_comma() {
+        assert_eq!(CsvDelimiter::Auto.byte_for(b"a,b;c\td\n"), b',');
+        assert_eq!(CsvDelimiter::Auto.byte_for(b"a;b\tc\n"), b';');
+        assert_eq!(CsvDelimiter::Auto.byte_for(b"name\n"), b',');
+        assert_eq!(CsvDelimiter::Auto.byte_for(b""), b',');
+    }
+
+    ///both ways of adding events should store the same wall-clock time & zone, including for times in a DST gap
+    #[sqlx::test]
+    async fn single_and_bulk_events_store_the_same_timezone(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+
+        for (date_str, datetime) in [
+            ("2030-07-01T09:15", date(2030, 7, 1).at(9, 15, 0, 0)),
+            ("2030-03-31T01:30", date(2030, 3, 31).at(1, 30, 0, 0)),
+        ] {
+            let single = NewEventForm::from_pairs(vec![
+                ("name".into(), "Single".into()),
+                ("date".into(), date_str.into()),
+                ("tz".into(), "Europe/London".into()),
+            ])
+            .into_add_event()
+            .unwrap();
+            let bulk = DraftEvent {
+                name: "Bulk".into(),
+                datetime,
+                location: None,
+                extra_info: None,
+            }
+            .into_add_event(&TimeZone::get("Europe/London").unwrap(), vec![])
+            .unwrap();
+
+            let single = Event::insert_into_database(single, &mut conn)
+                .await
+                .unwrap();
+            let bulk = Event::insert_into_database(bulk, &mut conn).await.unwrap();
+
+            let stored = sqlx::query!(
+                "SELECT id, date, tz FROM public.events WHERE id = ANY($1)",
+                &[single, bulk][..]
+            )
+            .fetch_all(&mut *conn)
+            .await
+            .unwrap();
+            assert_eq!(stored.len(), 2);
+            assert_eq!(stored[0].tz, "Europe/London");
+            assert_eq!(stored[0].tz, stored[1].tz);
+            assert_eq!(stored[0].date, stored[1].date);
+
+            let single = Event::get_from_db_by_id(single, &mut conn)
+                .await
+                .unwrap()
+                .unwrap();
+            let bulk = Event::get_from_db_by_id(bulk, &mut conn)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(single.datetime, bulk.datetime);
+        }
+    }
+
+    #[test]
+    fn imported_passwords_are_kept_apart_by_kind() {
+        let [students, staff] = ImportedPasswordsKind::ALL;
+        assert_ne!(students.object_key(), staff.object_key());
+        assert_ne!(students.session_key(), staff.session_key());
+
+        let banner = unsaved_passwords_banner(&[(staff, "hunter2".into())], false).into_string();
+        assert!(banner.contains("Staff were imported"));
+        assert!(!banner.contains("Students were imported"));
+        assert!(banner.contains("kind=staff"));
+
+        let banner = unsaved_passwords_banner(
+            &[(students, "hunter2".into()), (staff, "hunter3".into())],
+            false,
+        )
+        .into_string();
+        assert!(banner.contains("Students were imported"));
+        assert!(banner.contains("Staff were imported"));
+    }
+}
@@ -20,11 +20,16 @@ pub async fn get_index_route(
     }
 
     let can_view_people = session.can(PermissionsTarget::VIEW_SENSITIVE_DETAILS);
+    let app_name = state.config().app_name();
+    let logo_url = state.config().logo_url();
 
     Ok(state.render(session, html! {
         div class="bg-gray-800 p-8 rounded shadow-md max-w-md w-full" {
+            @if let Some(logo_url) = logo_url {
+                img src=(logo_url) alt={(app_name) " logo"} class="mx-auto mb-4 max-h-24 w-auto";
+            }
             h1 class="text-2xl font-semibold mb-6 text-center" {
-                "Denim!"
+                (app_name) "!"
             }
             div class="flex flex-row space-x-4 justify-center" {
                 a href="/events" class="bg-slate-600 hover:bg-slate-800 font-bold py-2 px-4 rounded"  {
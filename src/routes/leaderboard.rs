@@ -0,0 +1,193 @@
+use crate::{
+    auth::{AuthUtilities, DenimSession, PermissionsTarget},
+    data::{
+        audit_log::{AuditAction, AuditTarget},
+        house_points::HousePointsAward,
+        user::User,
+    },
+    error::{DenimResult, UnableToFindUserInfoSnafu},
+    maud_conveniences::{errors_list, table, title},
+    routes::sse::SseEvent,
+    state::DenimState,
+};
+use axum::{
+    Form,
+    extract::{Path, State},
+};
+use maud::{Markup, html};
+use serde::Deserialize;
+use snafu::OptionExt;
+use std::collections::HashMap;
+
+const RECENT_AWARDS_SHOWN: i64 = 10;
+
+pub async fn get_leaderboard(
+    State(state): State<DenimState>,
+    session: DenimSession,
+) -> DenimResult<Markup> {
+    let leaderboard = get_leaderboard_markup(&state, &session, vec![]).await?;
+
+    Ok(state.render(session, html! {
+        div class="mx-auto bg-gray-800 p-8 rounded shadow-md max-w-4xl w-full flex flex-col space-y-4" hx-ext="sse" sse-connect="/sse_feed" {
+            (leaderboard)
+        }
+    }))
+}
+
+pub async fn internal_get_leaderboard(
+    State(state): State<DenimState>,
+    session: DenimSession,
+) -> DenimResult<Markup> {
+    get_leaderboard_markup(&state, &session, vec![]).await
+}
+
+async fn get_leaderboard_markup(
+    state: &DenimState,
+    session: &DenimSession,
+    errors: Vec<&'static str>,
+) -> DenimResult<Markup> {
+    //points affect every house, so house-scoped staff can't award them
+    let can_award = session.can_for_house(PermissionsTarget::CRUD_EVENTS, None);
+
+    let mut conn = state.get_connection().await?;
+    let totals = HousePointsAward::get_totals(&mut conn).await?;
+    let recent = HousePointsAward::get_most_recent(RECENT_AWARDS_SHOWN, &mut conn).await?;
+
+    let awarders: Vec<_> = recent.iter().filter_map(|award| award.awarded_by).collect();
+    let awarders: HashMap<_, _> = User::get_many_by_ids(&awarders, &mut conn)
+        .await?
+        .into_iter()
+        .map(|user| (user.id, user))
+        .collect();
+    drop(conn);
+
+    let dlc = state
+        .config()
+        .date_locale_config()
+        .get()?
+        .for_user(session.user.as_ref());
+
+    let totals_table = if can_award {
+        table(
+            title("House Points"),
+            ["Rank", "House", "Points", "Award Points"],
+            totals
+                .into_iter()
+                .enumerate()
+                .map(|(i, (house, total))| {
+                    [
+                        html! {(i + 1)},
+                        html! {(house.name)},
+                        html! {(total)},
+                        html! {
+                            form hx-put={"/internal/houses/" (house.id) "/points"} hx-target="#leaderboard" hx-swap="outerHTML" class="flex flex-row space-x-2" {
+                                input type="number" name="points" required placeholder="Points" class="shadow appearance-none border rounded w-24 py-1 px-2 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600";
+                                input type="text" name="reason" required placeholder="Reason" class="shadow appearance-none border rounded w-full py-1 px-2 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600";
+                                input type="submit" value="Award" class="bg-blue-500 hover:bg-blue-700 font-bold py-1 px-3 rounded cursor-pointer";
+                            }
+                        },
+                    ]
+                })
+                .collect(),
+        )
+    } else {
+        table(
+            title("House Points"),
+            ["Rank", "House", "Points"],
+            totals
+                .into_iter()
+                .enumerate()
+                .map(|(i, (house, total))| [html! {(i + 1)}, html! {(house.name)}, html! {(total)}])
+                .collect(),
+        )
+    };
+
+    Ok(html! {
+        div id="leaderboard" hx-get="/internal/leaderboard" hx-trigger="sse:change_house_points, sse:crud_groups" hx-swap="outerHTML" class="flex flex-col space-y-4" {
+            @if !errors.is_empty() {
+                (errors_list(Some("Couldn't award points"), errors.into_iter()))
+            }
+            (totals_table)
+            @if recent.is_empty() {
+                p class="text-gray-400 italic" {"No points have been awarded yet."}
+            } @else {
+                (table(
+                    title("Recent Awards"),
+                    ["House", "Points", "Reason", "Awarded By", "When"],
+                    recent.into_iter().map(|award| [
+                        html! {(award.house.name)},
+                        html! {(award.points)},
+                        html! {(award.reason)},
+                        html! {
+                            @match award.awarded_by.and_then(|id| awarders.get(&id)) {
+                                Some(user) => (user),
+                                None => span class="italic" {"-"},
+                            }
+                        },
+                        html! {(dlc.short_ymdet(&award.awarded_at.to_zoned(dlc.timezone.clone())))},
+                    ]).collect()
+                ))
+            }
+        }
+    })
+}
+
+#[derive(Deserialize)]
+pub struct AwardPointsForm {
+    points: String,
+    reason: String,
+}
+
+pub async fn internal_put_house_points(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(house_id): Path<i32>,
+    Form(AwardPointsForm { points, reason }): Form<AwardPointsForm>,
+) -> DenimResult<Markup> {
+    session.ensure_can_for_house(PermissionsTarget::CRUD_EVENTS, None)?;
+    let awarded_by = session.user.as_ref().context(UnableToFindUserInfoSnafu)?.id;
+
+    let reason = reason.trim();
+    let mut errors = vec![];
+    let points = match points.trim().parse::<i32>() {
+        Ok(0) => {
+            errors.push("Points must not be zero");
+            0
+        }
+        Ok(points) => points,
+        Err(_e) => {
+            errors.push("Points must be a whole number");
+            0
+        }
+    };
+    if reason.is_empty() {
+        errors.push("A reason must be given");
+    }
+
+    if !errors.is_empty() {
+        return get_leaderboard_markup(&state, &session, errors).await;
+    }
+
+    let house = HousePointsAward::award(
+        house_id,
+        points,
+        reason,
+        awarded_by,
+        &mut *state.get_connection().await?,
+    )
+    .await?;
+    state
+        .audit(
+            &session,
+            AuditAction::Create,
+            AuditTarget::House(house_id),
+            Some(&format!(
+                "awarded {points} points to {}: {reason}",
+                house.name
+            )),
+        )
+        .await;
+    state.send_sse_event(SseEvent::ChangeHousePoints);
+
+    internal_get_leaderboard(State(state), session).await
+}
@@ -1,7 +1,9 @@
 use crate::{
-    auth::{DenimSession, PermissionsTarget, backend::DenimAuthCredentials},
-    error::{DenimResult, MakeQuerySnafu},
-    maud_conveniences::{form_submit_button, simple_form_element, supertitle},
+    auth::{DenimSession, PermissionsTarget, backend::DenimAuthCredentials, login_attempts, totp},
+    data::{DataType, user::User},
+    error::{DenimResult, MakeQuerySnafu, TowerSessionSnafu},
+    maud_conveniences::{csrf_field, form_submit_button, simple_form_element, supertitle},
+    metrics::METRICS,
     state::DenimState,
 };
 use axum::{
@@ -11,22 +13,41 @@ use axum::{
     http::Response,
     response::{IntoResponse, Redirect},
 };
+use axum_login::tower_sessions::Session;
 use email_address::EmailAddress;
+use jiff::Timestamp;
 use maud::html;
 use secrecy::SecretString;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use snafu::ResultExt;
+use uuid::Uuid;
+
+const PENDING_2FA_KEY: &str = "pending_2fa";
+const PENDING_2FA_VALID_FOR_SECS: i64 = 5 * 60;
+
+///someone who has got their password right, but still needs to provide a TOTP code
+#[derive(Serialize, Deserialize)]
+struct Pending2fa {
+    user_id: Uuid,
+    next: Option<String>,
+    started_at: i64,
+}
 
 #[derive(Deserialize)]
 pub struct LoginOptions {
     pub to: Option<String>,
     pub login_failed: Option<bool>,
+    pub locked: Option<bool>,
 }
 
 pub async fn get_login(
     State(state): State<DenimState>,
     session: DenimSession,
-    Query(LoginOptions { to, login_failed }): Query<LoginOptions>,
+    Query(LoginOptions {
+        to,
+        login_failed,
+        locked,
+    }): Query<LoginOptions>,
 ) -> DenimResult<Response<Body>> {
     if !sqlx::query!("SELECT exists(SELECT 1 FROM public.admins) as \"exists!\"")
         .fetch_one(&mut *state.get_connection().await?)
@@ -52,11 +73,16 @@ pub async fn get_login(
                     br;
                     // avoid giving extra details for security reasons :)
                     span class="block sm:inline" {"Email/Password not found or password incorrect"}
+                    @if locked.unwrap_or(false) {
+                        br;
+                        span class="block sm:inline" {"Too many failed attempts - please try again later"}
+                    }
                 }
                 br;
             }
 
             form method="post" {
+                (csrf_field())
                 @if let Some(to) = to {
                     input type="hidden" name="next" value=(to) {} 
                 }
@@ -64,6 +90,7 @@ pub async fn get_login(
                 (simple_form_element("password", "Password", true, Some("password"), None))
                 (form_submit_button(Some("Login")))
             }
+            a href="/forgot_password" class="text-sm text-gray-400 underline hover:text-blue-300" {"Forgot password?"}
         }
     }).into_response())
 }
@@ -78,44 +105,226 @@ pub struct LoginForm {
 pub async fn post_login(
     State(state): State<DenimState>,
     mut session: DenimSession,
+    tower_session: Session,
     Form(LoginForm {
         email,
         password,
         next,
     }): Form<LoginForm>,
 ) -> DenimResult<Redirect> {
+    let failed_redirect = |locked: bool| {
+        let mut redirect = "/login?login_failed=true".to_string();
+        if locked {
+            redirect += "&locked=true";
+        }
+        if let Some(next) = &next {
+            redirect += format!("&to={next}").as_str();
+        }
+        Redirect::to(redirect.as_ref())
+    };
+
+    //don't even check the password if they're locked out, so they can't keep guessing
+    if login_attempts::is_locked_out(&email, &mut *state.get_connection().await?).await? {
+        return Ok(failed_redirect(true));
+    }
+
     match session
-        .authenticate(DenimAuthCredentials::EmailPassword { email, password })
+        .authenticate(DenimAuthCredentials::EmailPassword {
+            email: email.clone(),
+            password,
+        })
         .await
     {
         Err(e) => Err(e.into()),
-        Ok(Some(user)) => match session.login(&user).await {
-            Ok(()) => {
-                if user
-                    .get_permissions()
-                    .contains(PermissionsTarget::RUN_ONBOARDING)
-                    && !state.config().s3_bucket().exists()
-                {
-                    return Ok(Redirect::to("/onboarding"));
-                }
-
-                let next = next.as_deref().unwrap_or("/");
-                Ok(if user.current_password_is_default {
-                    Redirect::to(&format!("/replace_default_password?next={next}"))
-                } else {
-                    Redirect::to(next)
-                })
+        Ok(Some(user)) => {
+            //the failed attempts only get cleared once the second factor is right too, so that wrong codes keep counting
+            if user.totp_secret.is_some() {
+                tower_session
+                    .insert(
+                        PENDING_2FA_KEY,
+                        Pending2fa {
+                            user_id: user.id,
+                            next,
+                            started_at: Timestamp::now().as_second(),
+                        },
+                    )
+                    .await
+                    .context(TowerSessionSnafu)?;
+                return Ok(Redirect::to("/login/2fa"));
             }
-            Err(e) => Err(e.into()),
-        },
+
+            login_attempts::clear(&email, &mut *state.get_connection().await?).await?;
+            session.login(&user).await?;
+            METRICS.record_login();
+            Ok(redirect_after_login(&state, &user, next.as_deref()))
+        }
         Ok(None) => {
-            let mut redirect = "/login?login_failed=true".to_string();
-            if let Some(next) = next {
-                redirect += format!("&to={next}").as_str();
+            let security_config = state.config().security_config();
+            let locked = login_attempts::record_failure(
+                &email,
+                security_config.max_failed_logins,
+                security_config.lockout_minutes,
+                &mut *state.get_connection().await?,
+            )
+            .await?;
+
+            Ok(failed_redirect(locked))
+        }
+    }
+}
+
+fn redirect_after_login(state: &DenimState, user: &User, next: Option<&str>) -> Redirect {
+    if user
+        .get_permissions()
+        .contains(PermissionsTarget::RUN_ONBOARDING)
+        && !state.config().s3_bucket().exists()
+    {
+        return Redirect::to("/onboarding");
+    }
+
+    let next = next.unwrap_or("/");
+    if user.current_password_is_default {
+        Redirect::to(&format!("/replace_default_password?next={next}"))
+    } else {
+        Redirect::to(next)
+    }
+}
+
+async fn get_pending_2fa(tower_session: &Session) -> DenimResult<Option<Pending2fa>> {
+    let Some(pending) = tower_session
+        .get::<Pending2fa>(PENDING_2FA_KEY)
+        .await
+        .context(TowerSessionSnafu)?
+    else {
+        return Ok(None);
+    };
+
+    if Timestamp::now().as_second() - pending.started_at > PENDING_2FA_VALID_FOR_SECS {
+        tower_session
+            .remove::<Pending2fa>(PENDING_2FA_KEY)
+            .await
+            .context(TowerSessionSnafu)?;
+        return Ok(None);
+    }
+
+    Ok(Some(pending))
+}
+
+#[derive(Deserialize)]
+pub struct TwoFactorOptions {
+    pub failed: Option<bool>,
+}
+
+pub async fn get_login_2fa(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    tower_session: Session,
+    Query(TwoFactorOptions { failed }): Query<TwoFactorOptions>,
+) -> DenimResult<Response<Body>> {
+    if session.user.is_some() {
+        return Ok(Redirect::to("/").into_response());
+    }
+    if get_pending_2fa(&tower_session).await?.is_none() {
+        return Ok(Redirect::to("/login").into_response());
+    }
+
+    Ok(state.render(session, html! {
+        div class="bg-gray-800 shadow-md rounded px-8 pt-6 pb-8 mb-4 w-full max-w-sm" {
+            (supertitle("Two-Factor Authentication"))
+            @if failed.unwrap_or(false) {
+                div role="alert" class="bg-red-100 border border-red-400 text-red-700 px-4 py-4 rounded relative" {
+                    strong class="font-bold" {"Alert!"}
+                    br;
+                    span class="block sm:inline" {"Incorrect code"}
+                }
+                br;
+            }
+
+            form method="post" {
+                (csrf_field())
+                div class="mb-4" {
+                    label class="block text-sm font-bold mb-2" for="code" {"Code from your authenticator app"}
+                    input required type="text" id="code" name="code" inputmode="numeric" pattern="[0-9]{6}" autocomplete="one-time-code" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600" {}
+                }
+                (form_submit_button(Some("Verify")))
             }
-            Ok(Redirect::to(redirect.as_ref()))
         }
+    }).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct TwoFactorForm {
+    code: String,
+}
+
+pub async fn post_login_2fa(
+    State(state): State<DenimState>,
+    mut session: DenimSession,
+    tower_session: Session,
+    Form(TwoFactorForm { code }): Form<TwoFactorForm>,
+) -> DenimResult<Redirect> {
+    let Some(pending) = get_pending_2fa(&tower_session).await? else {
+        return Ok(Redirect::to("/login"));
+    };
+
+    let mut conn = state.get_connection().await?;
+    let user = User::get_from_db_by_id(pending.user_id, &mut conn).await?;
+    let Some((user, secret)) = user.and_then(|user| {
+        let secret = user.totp_secret.clone()?;
+        Some((user, secret))
+    }) else {
+        //they must've had 2FA turned off in the meantime, so just make them log in again
+        tower_session
+            .remove::<Pending2fa>(PENDING_2FA_KEY)
+            .await
+            .context(TowerSessionSnafu)?;
+        return Ok(Redirect::to("/login"));
+    };
+
+    //they have to start again with their password once locked out, which won't be checked until the lockout is over
+    let locked_redirect = || Redirect::to("/login?login_failed=true&locked=true");
+    if login_attempts::is_locked_out(&user.email, &mut conn).await? {
+        tower_session
+            .remove::<Pending2fa>(PENDING_2FA_KEY)
+            .await
+            .context(TowerSessionSnafu)?;
+        return Ok(locked_redirect());
+    }
+
+    let accepted = match totp::accepted_step(&secret, &user.email, &code)? {
+        Some(step) => User::use_totp_step(user.id, step, &mut conn).await?,
+        None => false,
+    };
+    if !accepted {
+        let security_config = state.config().security_config();
+        let locked = login_attempts::record_failure(
+            &user.email,
+            security_config.max_failed_logins,
+            security_config.lockout_minutes,
+            &mut conn,
+        )
+        .await?;
+        if locked {
+            tower_session
+                .remove::<Pending2fa>(PENDING_2FA_KEY)
+                .await
+                .context(TowerSessionSnafu)?;
+            return Ok(locked_redirect());
+        }
+
+        return Ok(Redirect::to("/login/2fa?failed=true"));
     }
+
+    login_attempts::clear(&user.email, &mut conn).await?;
+    drop(conn);
+    tower_session
+        .remove::<Pending2fa>(PENDING_2FA_KEY)
+        .await
+        .context(TowerSessionSnafu)?;
+    session.login(&user).await?;
+    METRICS.record_login();
+
+    Ok(redirect_after_login(&state, &user, pending.next.as_deref()))
 }
 
 pub async fn post_logout(mut session: DenimSession) -> DenimResult<impl IntoResponse> {
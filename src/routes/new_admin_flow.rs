@@ -1,8 +1,12 @@
 use crate::{
     auth::{AuthUtilities, DenimSession, PermissionsTarget},
-    config::{auth::AuthConfig, date_locale::DateLocaleConfig},
+    config::{
+        auth::{AuthConfig, MAX_SEPARATOR_LEN, MAX_WORD_COUNT},
+        date_locale::DateLocaleConfig,
+    },
     data::{
         DataType,
+        audit_log::{AuditAction, AuditTarget},
         user::{AddPerson, AddUserKind, User},
     },
     error::{
@@ -180,6 +184,7 @@ pub async fn internal_post_add_new_admin(
             password: Some(password),
             current_password_is_default: false,
             user_kind: AddUserKind::Dev,
+            bcrypt_cost: state.config().security_config().bcrypt_cost,
         },
         &mut transaction,
     )
@@ -191,12 +196,20 @@ pub async fn internal_post_add_new_admin(
     transaction.commit().await.context(CommitTransactionSnafu)?;
 
     session.login(&user).await?;
+    state
+        .audit(
+            &session,
+            AuditAction::Create,
+            AuditTarget::Person(id),
+            Some("first admin account"),
+        )
+        .await;
 
     internal_get_setup_s3(State(state), session, S3Failure::empty()).await
 }
 
 bitflags! {
-    #[derive(Eq, PartialEq)]
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
     pub struct S3Failure: u8 {
         const EMPTY_ACCESS_ID =  0b0000_0010;
         const EMPTY_ACCESS_KEY = 0b0000_0100;
@@ -229,8 +242,6 @@ async fn internal_get_setup_s3(
     session: DenimSession,
     failure: S3Failure,
 ) -> DenimResult<Markup> {
-    //TODO: ensure users can somehow change the S3-settable values after the first run-through
-
     if state.config().s3_bucket().exists() {
         return internal_get_setup_auth_config(State(state), session, AuthConfigFailure::empty())
             .await;
@@ -257,47 +268,67 @@ async fn internal_get_setup_s3(
 
         br;
         form hx-post="/internal/onboarding/setup_s3" hx-target="#current_section" {
-            (simple_form_element(
-                "access_key_id",
-                "S3 Access Key ID",
-                true,
-                Some("password"),
-                None
-            ))
-            (simple_form_element(
-                "secret_access_key",
-                "S3 Secret Access Key",
-                true,
-                Some("password"),
-                None
-            ))
-            (simple_form_element(
-                "endpoint",
-                "S3 Endpoint URL",
-                true,
-                None,
-                None
-            ))
-            (simple_form_element(
-                "region",
-                "S3 Region",
-                true,
-                None,
-                None
-            ))
-            (simple_form_element(
-                "bucket",
-                "S3 Bucket Name",
-                true,
-                None,
-                None
-            ))
+            (s3_form_elements(None))
 
             (form_submit_button(Some("Add S3 Bucket")))
         }
     })
 }
 
+///the credentials are never pre-filled, but the endpoint/region/name are if we've already got a bucket
+pub fn s3_form_elements(current: Option<&Bucket>) -> Markup {
+    let (endpoint, region, bucket_name) =
+        match current.map(|bucket| (bucket.region(), bucket.name())) {
+            Some((Region::Custom { region, endpoint }, name)) => {
+                (Some(endpoint), Some(region), Some(name))
+            }
+            Some((region, name)) => (
+                Some(region.endpoint()),
+                Some(region.to_string()),
+                Some(name),
+            ),
+            None => (None, None, None),
+        };
+
+    html! {
+        (simple_form_element(
+            "access_key_id",
+            "S3 Access Key ID",
+            true,
+            Some("password"),
+            None
+        ))
+        (simple_form_element(
+            "secret_access_key",
+            "S3 Secret Access Key",
+            true,
+            Some("password"),
+            None
+        ))
+        (simple_form_element(
+            "endpoint",
+            "S3 Endpoint URL",
+            true,
+            None,
+            endpoint.as_deref()
+        ))
+        (simple_form_element(
+            "region",
+            "S3 Region",
+            true,
+            None,
+            region.as_deref()
+        ))
+        (simple_form_element(
+            "bucket",
+            "S3 Bucket Name",
+            true,
+            None,
+            bucket_name.as_deref()
+        ))
+    }
+}
+
 #[derive(Deserialize)]
 pub struct S3Details {
     access_key_id: String,
@@ -310,13 +341,7 @@ pub struct S3Details {
 pub async fn internal_post_setup_s3(
     State(state): State<DenimState>,
     session: DenimSession,
-    Form(S3Details {
-        access_key_id,
-        secret_access_key,
-        endpoint,
-        region,
-        bucket,
-    }): Form<S3Details>,
+    Form(details): Form<S3Details>,
 ) -> DenimResult<Markup> {
     session.ensure_can(PermissionsTarget::RUN_ONBOARDING)?;
     if state.config().s3_bucket().exists() {
@@ -324,28 +349,65 @@ pub async fn internal_post_setup_s3(
             .await;
     }
 
+    let bucket = match bucket_from_details(details).await? {
+        Ok(bucket) => bucket,
+        Err(failure) => return internal_get_setup_s3(State(state), session, failure).await,
+    };
+
+    if state.config().s3_bucket().set(*bucket).is_err() {
+        error!("Tried to add new S3 bucket when one already existed...");
+    } else {
+        info!("Successfully added bucket");
+        state
+            .audit(
+                &session,
+                AuditAction::Create,
+                AuditTarget::Config("s3"),
+                None,
+            )
+            .await;
+    }
+
+    internal_get_setup_auth_config(State(state), session, AuthConfigFailure::empty()).await
+}
+
+fn validate_s3_details(details: &S3Details) -> S3Failure {
     let mut errors = S3Failure::empty();
-    if access_key_id.trim().is_empty() {
+    if details.access_key_id.trim().is_empty() {
         errors |= S3Failure::EMPTY_ACCESS_ID;
     }
-    if secret_access_key.trim().is_empty() {
+    if details.secret_access_key.trim().is_empty() {
         errors |= S3Failure::EMPTY_ACCESS_KEY;
     }
-    if endpoint.trim().is_empty() {
+    if details.endpoint.trim().is_empty() {
         errors |= S3Failure::EMPTY_ENDPOINT;
     }
-    if region.trim().is_empty() {
-        errors |= S3Failure::EMPTY_ENDPOINT;
+    if details.region.trim().is_empty() {
+        errors |= S3Failure::EMPTY_REGION;
     }
-    if bucket.trim().is_empty() {
+    if details.bucket.trim().is_empty() {
         errors |= S3Failure::EMPTY_BUCKET;
     }
+    errors
+}
 
-    let (access_key_id, secret_access_key, endpoint, region, bucket) = if errors.is_empty() {
-        (access_key_id, secret_access_key, endpoint, region, bucket)
-    } else {
-        return internal_get_setup_s3(State(state), session, errors).await;
-    };
+///outer result: did we fail to even make the bucket?
+///inner result: did the details pass validation, and does the bucket actually exist?
+pub async fn bucket_from_details(
+    details: S3Details,
+) -> DenimResult<Result<Box<Bucket>, S3Failure>> {
+    let errors = validate_s3_details(&details);
+    if !errors.is_empty() {
+        return Ok(Err(errors));
+    }
+
+    let S3Details {
+        access_key_id,
+        secret_access_key,
+        endpoint,
+        region,
+        bucket,
+    } = details;
 
     let creds = Credentials::new(
         Some(&access_key_id),
@@ -366,29 +428,26 @@ pub async fn internal_post_setup_s3(
             Some(S3Failure::OTHER_S3_ERROR)
         }
     };
-    if let Some(bucket_is_bad) = bucket_is_bad {
-        return internal_get_setup_s3(State(state), session, bucket_is_bad).await;
-    }
 
-    if state.config().s3_bucket().set(*bucket).is_err() {
-        error!("Tried to add new S3 bucket when one already existed...");
-    } else {
-        info!("Successfully added bucket");
-    }
-
-    internal_get_setup_auth_config(State(state), session, AuthConfigFailure::empty()).await
+    Ok(bucket_is_bad.map_or(Ok(bucket), Err))
 }
 
 bitflags! {
-    #[derive(Eq, PartialEq)]
-    struct AuthConfigFailure: u8 {
-        const WL_OOR =     0b0000_0001;
-        const PARSE_WL_L = 0b0000_0010;
-        const PARSE_WL_U = 0b0000_0100;
-
-        const NR_OOR =     0b0001_0000;
-        const PARSE_NR_L = 0b0010_0000;
-        const PARSE_NR_U = 0b0100_0000;
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    pub struct AuthConfigFailure: u16 {
+        const WL_OOR =      0b0000_0000_0001;
+        const PARSE_WL_L =  0b0000_0000_0010;
+        const PARSE_WL_U =  0b0000_0000_0100;
+        const BAD_SEP =     0b0000_0000_1000;
+
+        const NR_OOR =      0b0000_0001_0000;
+        const PARSE_NR_L =  0b0000_0010_0000;
+        const PARSE_NR_U =  0b0000_0100_0000;
+        const WC_OOR =      0b0000_1000_0000;
+
+        const WL_NO_WORDS = 0b0001_0000_0000;
+        const BAD_WORD_LIST = 0b0010_0000_0000;
+        const WORD_LIST_NO_WORDS = 0b0100_0000_0000;
     }
 }
 
@@ -401,6 +460,19 @@ impl AuthConfigFailure {
             Self::PARSE_NR_U => Some("Number Range - Upper Bound: Parse Error"),
             Self::WL_OOR => Some("Word Length: Invalid Range"),
             Self::NR_OOR => Some("Number Range: Invalid Range"),
+            Self::BAD_SEP => {
+                Some("Separator: Must be at most 3 characters, without spaces, commas or quotes")
+            }
+            Self::WC_OOR => Some("Number of Words: Must be between 1 and 4"),
+            Self::WL_NO_WORDS => {
+                Some("Word Length: There aren't any words for some of the lengths in that range")
+            }
+            Self::BAD_WORD_LIST => {
+                Some("Word List: Must be a plain text file with one word per line")
+            }
+            Self::WORD_LIST_NO_WORDS => Some(
+                "Word List: There aren't any words for some of the lengths in the current word length range",
+            ),
             _ => None,
         })
     }
@@ -426,8 +498,37 @@ async fn internal_get_setup_auth_config(
         }
     }
 
-    let auth_config = AuthConfig::default();
+    Ok(html! {
+        (title("Setup Auth Config"))
+        p {"Now that S3's done, we can get the auth config setup - this is how the default passwords are generated"}
+        br;
+        p {
+            "Passwords are generated in the following format: "
+            span class="italic" {"word_number"}
+            ". The length of the word is controlled by the range and picked randomly, and the number is picked randomly from within a different range."
+        }
+        br;
+        p {
+            "You can also use more than one word, and change what goes between each part - eg. "
+            span class="italic" {"word-word-number"}
+            "."
+        }
+
+        @if !failure.is_empty() {
+            br;
+            (errors_list(Some("Validation Errors"), failure.as_nice_list()))
+        }
+
+        br;
+        form hx-post="/internal/onboarding/setup_auth_config" hx-target="#current_section" {
+            (auth_config_form_elements(&AuthConfig::default()))
 
+            (form_submit_button(Some("Submit Ranges for Passwords")))
+        }
+    })
+}
+
+pub fn auth_config_form_elements(auth_config: &AuthConfig) -> Markup {
     let [
         wordlen_lower,
         worldlen_upper,
@@ -454,31 +555,16 @@ async fn internal_get_setup_auth_config(
         )
     };
 
-    Ok(html! {
-        (title("Setup Auth Config"))
-        p {"Now that S3's done, we can get the auth config setup - this is how the default passwords are generated"}
-        br;
-        p {
-            "Passwords are generated in the following format: "
-            span class="italic" {"word_number"}
-            ". The length of the word is controlled by the range and picked randomly, and the number is picked randomly from within a different range."
-        }
-
-        @if !failure.is_empty() {
-            br;
-            (errors_list(Some("Validation Errors"), failure.as_nice_list()))
-        }
-
-        br;
-        form hx-post="/internal/onboarding/setup_auth_config" hx-target="#current_section" {
-            (ranged_number_input("wordlen_lower", "Word Length - Lower (1 - 32)", wordlen_lower, 1, 32))
-            (ranged_number_input("wordlen_upper", "Word Length - Upper (1 - 32)", worldlen_upper, 1, 32))
-            (ranged_number_input("numberrange_lower", "Word Length - Lower (0 - 1,000,000,000)", numberrange_lower, 0, 1_000_000_000))
-            (ranged_number_input("numberrange_upper", "Word Length - Upper (0 - 1,000,000,000)", numberrange_upper, 0, 1_000_000_000))
-
-            (form_submit_button(Some("Submit Ranges for Passwords")))
-        }
-    })
+    html! {
+        (ranged_number_input("word_count", "Number of Words (1 - 4)", auth_config.word_count, 1, MAX_WORD_COUNT))
+        (form_element("separator", "Separator (up to 3 characters)", html! {
+            input value=(auth_config.separator) type="text" id="separator" name="separator" maxlength=(MAX_SEPARATOR_LEN) class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600" {}
+        }))
+        (ranged_number_input("wordlen_lower", "Word Length - Lower (1 - 32)", wordlen_lower, 1, 32))
+        (ranged_number_input("wordlen_upper", "Word Length - Upper (1 - 32)", worldlen_upper, 1, 32))
+        (ranged_number_input("numberrange_lower", "Number Range - Lower (0 - 1,000,000,000)", numberrange_lower, 0, 1_000_000_000))
+        (ranged_number_input("numberrange_upper", "Number Range - Upper (0 - 1,000,000,000)", numberrange_upper, 0, 1_000_000_000))
+    }
 }
 
 #[derive(Deserialize)]
@@ -487,6 +573,8 @@ pub struct AuthConfigForm {
     wordlen_upper: String,
     numberrange_lower: String,
     numberrange_upper: String,
+    word_count: String,
+    separator: String,
 }
 
 pub async fn internal_post_setup_auth_config(
@@ -496,8 +584,32 @@ pub async fn internal_post_setup_auth_config(
 ) -> DenimResult<Markup> {
     session.ensure_can(PermissionsTarget::RUN_ONBOARDING)?;
 
+    let current_config = match parse_auth_config_form(&input, None) {
+        Ok(config) => config,
+        Err(errors) => return internal_get_setup_auth_config(State(state), session, errors).await,
+    };
+
+    if state.config().auth_config().set(current_config).is_ok() {
+        state
+            .audit(
+                &session,
+                AuditAction::Create,
+                AuditTarget::Config("auth_config"),
+                None,
+            )
+            .await;
+    }
+
+    internal_get_setup_timezone(State(state), session).await
+}
+
+///`current` is used as the base so that anything not in the form (eg. a custom word list) is kept
+pub fn parse_auth_config_form(
+    input: &AuthConfigForm,
+    current: Option<&AuthConfig>,
+) -> Result<AuthConfig, AuthConfigFailure> {
     let mut errors = AuthConfigFailure::empty();
-    let mut current_config = AuthConfig::default();
+    let mut current_config = current.cloned().unwrap_or_default();
 
     {
         let lower = match input.wordlen_lower.parse() {
@@ -515,8 +627,11 @@ pub async fn internal_post_setup_auth_config(
             }
         };
 
-        if lower > upper || lower == 0 || upper == 0 || upper > 32 {
+        //the ranges are exclusive, so they'd be empty (and panic when generating) if lower == upper
+        if lower >= upper || lower == 0 || upper > 32 {
             errors |= AuthConfigFailure::WL_OOR;
+        } else if !current_config.word_len_range_is_usable(&(lower..upper)) {
+            errors |= AuthConfigFailure::WL_NO_WORDS;
         } else {
             current_config.word_len_range = lower..upper;
         }
@@ -537,20 +652,32 @@ pub async fn internal_post_setup_auth_config(
             }
         };
 
-        if lower > upper || lower == 0 || upper == 0 || upper > 1_000_000_000 {
-            errors |= AuthConfigFailure::WL_OOR;
+        if lower >= upper || upper > 1_000_000_000 {
+            errors |= AuthConfigFailure::NR_OOR;
         } else {
             current_config.numbers_range = lower..upper;
         }
     }
+    {
+        match input.word_count.parse() {
+            Ok(word_count) if (1..=MAX_WORD_COUNT).contains(&word_count) => {
+                current_config.word_count = word_count;
+            }
+            _ => errors |= AuthConfigFailure::WC_OOR,
+        }
 
-    if !errors.is_empty() {
-        return internal_get_setup_auth_config(State(state), session, errors).await;
+        if AuthConfig::separator_is_valid(&input.separator) {
+            current_config.separator.clone_from(&input.separator);
+        } else {
+            errors |= AuthConfigFailure::BAD_SEP;
+        }
     }
 
-    let _ = state.config().auth_config().set(current_config);
-
-    internal_get_setup_timezone(State(state), session).await
+    if errors.is_empty() {
+        Ok(current_config)
+    } else {
+        Err(errors)
+    }
 }
 
 async fn internal_get_setup_timezone(
@@ -578,37 +705,44 @@ async fn internal_get_setup_timezone(
 
         br;
         form hx-post="/internal/onboarding/setup_timezone" hx-target="#current_section" {
-            (timezone_picker(None))
-            (form_element("hour_cycle", "Hour Cycle", html!{
-                select required id="hour_cycle" name="hour_cycle" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600" {
-                    option selected value="h23" {"24-hour"}
-                    option value="h12" {"12-hour (standard)"}
-                    option value="h11" {"12-hour (Japanese variant)"}
-                }
-            }))
-            (form_element("calendar_algorithm", "Calendar", html!{
-                select required id="calendar_algorithm" name="calendar_algorithm" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600" {
-                    option selected value="gregorian" {"Gregorian (ISO 8601 Standard Western)"}
-                    option value="buddhist" {"Buddhist"}
-                    option value="chinese" {"Chinese"}
-                    option value="japanese" {"Japanese"}
-                    option value="hebrew" {"Hebrew"}
-                    option value="dangi" {"Dangi"}
-                }
-            }))
-            (simple_form_element("locale", "Locale", true, None, Some("en-GB")))
+            (date_locale_form_elements(None))
 
             (form_submit_button(Some("Submit Timezone")))
         }
     })
 }
 
+pub fn date_locale_form_elements(current: Option<&DateLocaleConfig>) -> Markup {
+    let hour_cycle = current.map_or("h23", DateLocaleConfig::hour_cycle);
+    let calendar_algorithm = current.map_or("gregorian", DateLocaleConfig::calendar_algorithm);
+    let locale = current.map_or_else(|| "en-GB".to_string(), |config| config.locale.to_string());
+
+    html! {
+        (timezone_picker(current.map(|config| config.timezone.clone())))
+        (form_element("hour_cycle", "Hour Cycle", html!{
+            select required id="hour_cycle" name="hour_cycle" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600" {
+                @for (value, name) in [("h23", "24-hour"), ("h12", "12-hour (standard)"), ("h11", "12-hour (Japanese variant)")] {
+                    option selected[value == hour_cycle] value=(value) {(name)}
+                }
+            }
+        }))
+        (form_element("calendar_algorithm", "Calendar", html!{
+            select required id="calendar_algorithm" name="calendar_algorithm" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600" {
+                @for (value, name) in [("gregorian", "Gregorian (ISO 8601 Standard Western)"), ("buddhist", "Buddhist"), ("chinese", "Chinese"), ("japanese", "Japanese"), ("hebrew", "Hebrew"), ("dangi", "Dangi")] {
+                    option selected[value == calendar_algorithm] value=(value) {(name)}
+                }
+            }
+        }))
+        (simple_form_element("locale", "Locale", true, None, Some(&locale)))
+    }
+}
+
 #[derive(Deserialize)]
 pub struct SetupTzForm {
-    tz: String,
-    hour_cycle: String,
-    calendar_algorithm: String,
-    locale: String,
+    pub tz: String,
+    pub hour_cycle: String,
+    pub calendar_algorithm: String,
+    pub locale: String,
 }
 
 pub async fn internal_post_setup_timezone(
@@ -626,7 +760,7 @@ pub async fn internal_post_setup_timezone(
         return Ok(get_all_finished());
     }
 
-    let _ = state
+    if state
         .config()
         .date_locale_config()
         .set(DateLocaleConfig::new(
@@ -634,7 +768,18 @@ pub async fn internal_post_setup_timezone(
             locale,
             hour_cycle,
             calendar_algorithm,
-        )?);
+        )?)
+        .is_ok()
+    {
+        state
+            .audit(
+                &session,
+                AuditAction::Create,
+                AuditTarget::Config("date_locale"),
+                None,
+            )
+            .await;
+    }
 
     Ok(get_all_finished())
 }
@@ -656,3 +801,173 @@ fn get_all_finished() -> Markup {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AuthConfigFailure, AuthConfigForm, S3Details, S3Failure, parse_auth_config_form,
+        validate_s3_details,
+    };
+    use crate::config::auth::AuthConfig;
+
+    fn details() -> S3Details {
+        S3Details {
+            access_key_id: "id".into(),
+            secret_access_key: "key".into(),
+            endpoint: "https://s3.example.com".into(),
+            region: "eu-west-2".into(),
+            bucket: "denim".into(),
+        }
+    }
+
+    #[test]
+    fn filled_s3_details_are_valid() {
+        assert_eq!(validate_s3_details(&details()), S3Failure::empty());
+    }
+
+    #[test]
+    fn each_empty_s3_field_sets_its_own_flag() {
+        let failures_with = |blank: fn(&mut S3Details)| {
+            let mut details = details();
+            blank(&mut details);
+            validate_s3_details(&details)
+        };
+
+        assert_eq!(
+            failures_with(|d| d.access_key_id = " ".into()),
+            S3Failure::EMPTY_ACCESS_ID
+        );
+        assert_eq!(
+            failures_with(|d| d.secret_access_key = " ".into()),
+            S3Failure::EMPTY_ACCESS_KEY
+        );
+        assert_eq!(
+            failures_with(|d| d.endpoint = " ".into()),
+            S3Failure::EMPTY_ENDPOINT
+        );
+        assert_eq!(
+            failures_with(|d| d.region = " ".into()),
+            S3Failure::EMPTY_REGION
+        );
+        assert_eq!(
+            failures_with(|d| d.bucket = " ".into()),
+            S3Failure::EMPTY_BUCKET
+        );
+    }
+
+    #[test]
+    fn all_empty_s3_fields_are_reported_together() {
+        let details = S3Details {
+            access_key_id: String::new(),
+            secret_access_key: String::new(),
+            endpoint: String::new(),
+            region: String::new(),
+            bucket: String::new(),
+        };
+
+        assert_eq!(
+            validate_s3_details(&details),
+            S3Failure::EMPTY_ACCESS_ID
+                | S3Failure::EMPTY_ACCESS_KEY
+                | S3Failure::EMPTY_ENDPOINT
+                | S3Failure::EMPTY_REGION
+                | S3Failure::EMPTY_BUCKET
+        );
+    }
+
+    fn auth_form() -> AuthConfigForm {
+        AuthConfigForm {
+            wordlen_lower: "5".into(),
+            wordlen_upper: "9".into(),
+            numberrange_lower: "1000".into(),
+            numberrange_upper: "10000".into(),
+            word_count: "2".into(),
+            separator: "-".into(),
+        }
+    }
+
+    fn auth_failures(form: &AuthConfigForm) -> AuthConfigFailure {
+        parse_auth_config_form(form, None).map_or_else(|e| e, |_| AuthConfigFailure::empty())
+    }
+
+    #[test]
+    fn valid_auth_config_form_is_applied() {
+        let config = parse_auth_config_form(&auth_form(), None).expect("form should be valid");
+        assert_eq!(config.word_len_range, 5..9);
+        assert_eq!(config.numbers_range, 1_000..10_000);
+        assert_eq!(config.word_count, 2);
+        assert_eq!(config.separator, "-");
+    }
+
+    #[test]
+    fn word_length_range_must_be_in_bounds() {
+        for (lower, upper) in [("6", "6"), ("7", "6"), ("0", "4"), ("5", "33")] {
+            let form = AuthConfigForm {
+                wordlen_lower: lower.into(),
+                wordlen_upper: upper.into(),
+                ..auth_form()
+            };
+            assert_eq!(auth_failures(&form), AuthConfigFailure::WL_OOR);
+        }
+    }
+
+    #[test]
+    fn number_range_must_be_in_bounds() {
+        for (lower, upper) in [("10", "10"), ("11", "10"), ("0", "1000000001")] {
+            let form = AuthConfigForm {
+                numberrange_lower: lower.into(),
+                numberrange_upper: upper.into(),
+                ..auth_form()
+            };
+            assert_eq!(auth_failures(&form), AuthConfigFailure::NR_OOR);
+        }
+    }
+
+    #[test]
+    fn unparseable_bounds_are_reported() {
+        let form = AuthConfigForm {
+            wordlen_lower: "five".into(),
+            numberrange_upper: "-1".into(),
+            ..auth_form()
+        };
+        assert_eq!(
+            auth_failures(&form),
+            AuthConfigFailure::PARSE_WL_L
+                | AuthConfigFailure::WL_OOR
+                | AuthConfigFailure::PARSE_NR_U
+                | AuthConfigFailure::NR_OOR
+        );
+    }
+
+    #[test]
+    fn word_count_and_separator_are_checked() {
+        for word_count in ["0", "5"] {
+            let form = AuthConfigForm {
+                word_count: word_count.into(),
+                ..auth_form()
+            };
+            assert_eq!(auth_failures(&form), AuthConfigFailure::WC_OOR);
+        }
+
+        let form = AuthConfigForm {
+            separator: ",".into(),
+            ..auth_form()
+        };
+        assert_eq!(auth_failures(&form), AuthConfigFailure::BAD_SEP);
+    }
+
+    #[test]
+    fn word_length_range_needs_words_for_every_length() {
+        let current = AuthConfig::default()
+            .with_custom_words(Some(AuthConfig::parse_words("cat\ndog\nhorse")));
+        let form = AuthConfigForm {
+            wordlen_lower: "3".into(),
+            wordlen_upper: "6".into(),
+            ..auth_form()
+        };
+
+        let failures = parse_auth_config_form(&form, Some(&current))
+            .map_or_else(|e| e, |_| AuthConfigFailure::empty());
+        assert_eq!(failures, AuthConfigFailure::WL_NO_WORDS);
+    }
+}
@@ -0,0 +1,268 @@
+use crate::{
+    auth::{DenimSession, add_password, api_token::hash_api_token},
+    error::{DenimResult, MakeQuerySnafu},
+    maud_conveniences::{
+        csrf_field, errors_list, form_submit_button, simple_form_element, supertitle,
+    },
+    routes::set_new_password::ReplaceDefaultPasswordValidationError,
+    state::DenimState,
+};
+use axum::{
+    Form,
+    extract::{Query, State},
+};
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use email_address::EmailAddress;
+use maud::{Markup, html};
+use rand::{Rng, rng};
+use secrecy::{ExposeSecret, SecretString};
+use serde::Deserialize;
+use snafu::ResultExt;
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+pub async fn get_forgot_password(State(state): State<DenimState>, session: DenimSession) -> Markup {
+    let can_send_emails = state.config().mailer().is_some() && state.config().base_url().is_some();
+
+    state.render(session, html! {
+        div class="bg-gray-800 shadow-md rounded px-8 pt-6 pb-8 mb-4 w-full max-w-sm" {
+            (supertitle("Forgot Password"))
+            @if can_send_emails {
+                form method="post" {
+                    (csrf_field())
+                    (simple_form_element("email", "Email", true, Some("email"), None))
+                    (form_submit_button(Some("Send Reset Link")))
+                }
+            } @else {
+                p {"Password resets over email aren't set up - please contact your admin to reset your password."}
+            }
+        }
+    })
+}
+
+#[derive(Deserialize)]
+pub struct ForgotPasswordForm {
+    email: EmailAddress,
+}
+
+pub async fn post_forgot_password(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Form(ForgotPasswordForm { email }): Form<ForgotPasswordForm>,
+) -> DenimResult<Markup> {
+    //everything happens in the background so that the response takes just as long whether or not the account exists
+    tokio::spawn({
+        let state = state.clone();
+        async move {
+            if let Err(e) = send_reset_link(&state, &email).await {
+                warn!(?e, "Unable to send password reset link");
+            }
+        }
+    });
+
+    //same response whether or not the account exists, so this can't be used to find out who has an account
+    Ok(state.render(session, html! {
+        div class="bg-gray-800 shadow-md rounded px-8 pt-6 pb-8 mb-4 w-full max-w-sm" {
+            (supertitle("Forgot Password"))
+            p {"If an account exists with that email, a reset link has been sent to it. The link expires in an hour."}
+        }
+    }))
+}
+
+///only the hash gets stored, so reading the table doesn't hand out working links
+async fn create_reset_token(user_id: Uuid, conn: &mut PgConnection) -> DenimResult<String> {
+    let token = BASE64_URL_SAFE_NO_PAD.encode(rng().random::<[u8; 32]>());
+
+    //only the latest link should work
+    sqlx::query!(
+        "DELETE FROM public.password_resets WHERE user_id = $1",
+        user_id
+    )
+    .execute(&mut *conn)
+    .await
+    .context(MakeQuerySnafu)?;
+    sqlx::query!(
+        "INSERT INTO public.password_resets (token_hash, user_id, expires_at) VALUES ($1, $2, NOW() + INTERVAL '1 hour')",
+        hash_api_token(&token),
+        user_id
+    )
+    .execute(&mut *conn)
+    .await
+    .context(MakeQuerySnafu)?;
+
+    Ok(token)
+}
+
+///gives back who the token was for if it's still valid - deleting it here makes sure that each token only gets used once
+async fn redeem_reset_token(token: &str, conn: &mut PgConnection) -> DenimResult<Option<Uuid>> {
+    Ok(sqlx::query!(
+        "DELETE FROM public.password_resets WHERE token_hash = $1 RETURNING user_id, expires_at > NOW() as \"is_valid!\"",
+        hash_api_token(token)
+    )
+    .fetch_optional(&mut *conn)
+    .await
+    .context(MakeQuerySnafu)?
+    .filter(|reset| reset.is_valid)
+    .map(|reset| reset.user_id))
+}
+
+async fn send_reset_link(state: &DenimState, email: &EmailAddress) -> DenimResult<()> {
+    let Some((mailer, base_url)) = state.config().mailer().zip(state.config().base_url()) else {
+        return Ok(());
+    };
+    let mut conn = state.get_connection().await?;
+
+    let Some(user) = sqlx::query!(
+        "SELECT id FROM public.users WHERE email = $1",
+        email.as_str()
+    )
+    .fetch_optional(&mut *conn)
+    .await
+    .context(MakeQuerySnafu)?
+    else {
+        return Ok(());
+    };
+
+    let token = create_reset_token(user.id, &mut conn).await?;
+    drop(conn);
+
+    mailer
+        .send(
+            email,
+            &format!("Reset your {} password", state.config().app_name()),
+            format!("Someone (hopefully you!) asked to reset your password. If that was you, use this link within the next hour:\n\n{base_url}/reset_password?token={token}\n\nIf it wasn't, you can safely ignore this email."),
+        )
+        .await
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordQuery {
+    token: String,
+}
+
+pub async fn get_reset_password(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Query(ResetPasswordQuery { token }): Query<ResetPasswordQuery>,
+) -> Markup {
+    reset_password_page(
+        &state,
+        session,
+        &token,
+        ReplaceDefaultPasswordValidationError::empty(),
+    )
+}
+
+fn reset_password_page(
+    state: &DenimState,
+    session: DenimSession,
+    token: &str,
+    errors: ReplaceDefaultPasswordValidationError,
+) -> Markup {
+    state.render(session, html! {
+        div class="bg-gray-800 shadow-md rounded px-8 pt-6 pb-8 mb-4 w-full max-w-md" {
+            (supertitle("Reset Password"))
+            @if !errors.is_empty() {
+                (errors_list(None, errors.as_nice_list()))
+            }
+            form method="post" {
+                (csrf_field())
+                input type="hidden" name="token" value=(token);
+                (simple_form_element("new_password", "New Password", true, Some("password"), None))
+                (simple_form_element("confirmed_password", "Confirm Password", true, Some("password"), None))
+                (form_submit_button(Some("Set New Password")))
+            }
+        }
+    })
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordForm {
+    token: String,
+    new_password: SecretString,
+    confirmed_password: SecretString,
+}
+
+pub async fn post_reset_password(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Form(ResetPasswordForm {
+        token,
+        new_password,
+        confirmed_password,
+    }): Form<ResetPasswordForm>,
+) -> DenimResult<Markup> {
+    let mut errors = ReplaceDefaultPasswordValidationError::empty();
+    if new_password.expose_secret() != confirmed_password.expose_secret() {
+        errors |= ReplaceDefaultPasswordValidationError::DIDNT_MATCH;
+    }
+    if new_password.expose_secret().trim().is_empty() {
+        errors |= ReplaceDefaultPasswordValidationError::EMPTY;
+    }
+    errors |= ReplaceDefaultPasswordValidationError::check_strength(&state, &new_password);
+    if !errors.is_empty() {
+        return Ok(reset_password_page(&state, session, &token, errors));
+    }
+
+    let mut conn = state.get_connection().await?;
+
+    let Some(user_id) = redeem_reset_token(&token, &mut conn).await? else {
+        return Ok(state.render(session, html! {
+            div class="bg-gray-800 shadow-md rounded px-8 pt-6 pb-8 mb-4 w-full max-w-sm" {
+                (errors_list(Some("This reset link is invalid or has expired."), std::iter::empty::<String>()))
+                a href="/forgot_password" class="underline hover:text-blue-300" {"Get a new link"}
+            }
+        }));
+    };
+
+    add_password(
+        user_id.into(),
+        new_password,
+        state.config().security_config().bcrypt_cost,
+        &mut conn,
+        false,
+    )
+    .await?;
+
+    Ok(state.render(
+        session,
+        html! {
+            div class="bg-gray-800 shadow-md rounded px-8 pt-6 pb-8 mb-4 w-full max-w-sm" {
+                (supertitle("Reset Password"))
+                p {"Your password has been reset."}
+                a href="/login" class="underline hover:text-blue-300" {"Login"}
+            }
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{create_reset_token, redeem_reset_token};
+    use crate::data::testing::insert_staff;
+    use sqlx::PgPool;
+
+    #[sqlx::test]
+    async fn reset_tokens_are_stored_hashed_and_only_work_once(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let user = insert_staff(None, &mut conn).await;
+
+        let token = create_reset_token(user, &mut conn).await.unwrap();
+        let stored = sqlx::query_scalar!(
+            "SELECT token_hash FROM public.password_resets WHERE user_id = $1",
+            user
+        )
+        .fetch_one(&mut *conn)
+        .await
+        .unwrap();
+        assert_ne!(stored, token);
+        assert_eq!(redeem_reset_token(&stored, &mut conn).await.unwrap(), None);
+
+        let token = create_reset_token(user, &mut conn).await.unwrap();
+        assert_eq!(
+            redeem_reset_token(&token, &mut conn).await.unwrap(),
+            Some(user)
+        );
+        assert_eq!(redeem_reset_token(&token, &mut conn).await.unwrap(), None);
+    }
+}
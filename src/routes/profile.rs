@@ -1,15 +1,26 @@
 #![allow(clippy::unused_async)]
 
 use crate::{
-    auth::{AuthUtilities, DenimSession, PasswordUserId, PermissionsTarget, add_password},
+    auth::{
+        AuthUtilities, DenimSession, PasswordUserId, PermissionsTarget, add_password,
+        api_token::{generate_api_token, hash_api_token},
+        postgres_store::PostgresSessionStore, totp,
+    },
     data::{
         DataType,
+        audit_log::{AuditAction, AuditTarget},
         event::Event,
+        student_groups::{HouseGroup, TutorGroup},
         user::{FullUserNameDisplay, User, UserKind, UsernameDisplay},
     },
-    error::{BcryptSnafu, DenimError, DenimResult, MakeQuerySnafu, UnableToFindUserInfoSnafu},
+    error::{
+        BcryptSnafu, DenimError, DenimResult, InvalidTimezoneSnafu, MakeQuerySnafu,
+        MissingTutorGroupSnafu, MissingUserSnafu, TowerSessionSnafu, UnableToFindUserInfoSnafu,
+        UnrepresentableTimeSnafu,
+    },
     maud_conveniences::{
-        Email, errors_list, form_submit_button, simple_form_element, subtitle, supertitle, table,
+        Email, ToastKind, csrf_field, errors_list, form_element, form_submit_button,
+        simple_form_element, subtitle, supertitle, table, timezone_picker, toast,
     },
     routes::sse::SseEvent,
     state::DenimState,
@@ -17,17 +28,21 @@ use crate::{
 use axum::{
     Form,
     body::Body,
-    extract::State,
+    extract::{Path, Query, State},
     http::Response,
     response::{IntoResponse, Redirect},
 };
+use axum_login::tower_sessions::Session;
 use bcrypt::verify;
 use bitflags::bitflags;
 use email_address::EmailAddress;
+use jiff::{Timestamp, tz::TimeZone};
 use maud::{Markup, Render, html};
 use secrecy::{ExposeSecret, SecretString};
 use serde::Deserialize;
 use snafu::{OptionExt, ResultExt};
+use std::collections::HashMap;
+use uuid::Uuid;
 
 pub async fn get_profile(
     State(state): State<DenimState>,
@@ -46,9 +61,8 @@ pub async fn get_profile(
         }
     };
 
-    let can_edit = user
-        .get_permissions()
-        .contains(PermissionsTarget::CRUD_USERS);
+    let permissions = user.get_permissions();
+    let can_edit = permissions.contains(PermissionsTarget::CRUD_USERS);
 
     Ok(state
         .render(
@@ -58,6 +72,9 @@ pub async fn get_profile(
                     (username)
                     br;
                 }
+                div hx-ext="sse" sse-connect="/sse_feed/me" {
+                    p sse-swap="password_reset" class="text-yellow-300 font-semibold" {}
+                }
                 @if can_edit {
                     div class="gap-x-4 mb-4 flex flex-col items-center justify-between space-y-4 container mx-auto bg-gray-800 rounded-md p-4 w-xl" {
                         div class="flex flex-row items-center justify-between space-x-4" {
@@ -94,6 +111,22 @@ pub async fn get_profile(
                         }
                     }
                 }
+                div class="border-b border-gray-200 dark:border-gray-700 w-xl" {}
+                div id="totp_section" hx-trigger="load" hx-get="/internal/profile/totp" class="w-xl my-4" {}
+                div class="border-b border-gray-200 dark:border-gray-700 w-xl" {}
+                div id="timezone_section" hx-trigger="load" hx-get="/internal/profile/timezone" class="w-xl my-4" {}
+                @if load_user_specific {
+                    div class="border-b border-gray-200 dark:border-gray-700 w-xl" {}
+                    div id="signup_emails_section" hx-trigger="load" hx-get="/internal/profile/signup_emails" class="w-xl my-4" {}
+                }
+                div class="border-b border-gray-200 dark:border-gray-700 w-xl" {}
+                div id="api_token_section" hx-trigger="load" hx-get="/internal/profile/api_token" class="w-xl my-4" {}
+                div class="border-b border-gray-200 dark:border-gray-700 w-xl" {}
+                div hx-trigger="load" hx-get="/internal/profile/sessions" class="w-xl my-4" {}
+                div class="border-b border-gray-200 dark:border-gray-700 w-xl" {}
+                div class="w-xl my-4" {
+                    (get_permissions_display(permissions))
+                }
                 @if load_user_specific {
                     div class="border-b border-gray-200 dark:border-gray-700 w-xl" {}
                     div hx-trigger="load" hx-get="/internal/profile/get_user_specific" class="w-xl my-4" {}
@@ -107,46 +140,70 @@ pub async fn internal_get_profile_student_display(
     State(state): State<DenimState>,
     session: DenimSession,
 ) -> DenimResult<Markup> {
+    let user = session.clone().user.context(UnableToFindUserInfoSnafu)?;
     let UserKind::Student {
         tutor_group: _,
         house: _,
         events_participated,
-    } = session
-        .clone()
-        .user
-        .context(UnableToFindUserInfoSnafu)?
-        .kind
+    } = user.kind
     else {
         return Err(DenimError::UnableToFindUserInfo);
     };
 
     let mut event_details = Vec::with_capacity(events_participated.len());
+    let mut attended = 0;
+    let mut signed_up = 0;
 
-    let dlc = state.config().date_locale_config().get()?;
+    let dlc = state
+        .config()
+        .date_locale_config()
+        .get()?
+        .for_user(session.user.as_ref());
 
     for event in events_participated {
         if let Some(event) =
             Event::get_from_db_by_id(event, &mut *state.get_connection().await?).await?
         {
+            //waitlisted events don't count towards the totals, as they never had a space
+            let status = if event.verified.contains(&user.id) {
+                attended += 1;
+                signed_up += 1;
+                html! {span class="text-green-300" {"Verified"}}
+            } else if event.waitlisted.contains(&user.id) {
+                html! {span class="text-gray-400 italic" {"Waitlisted"}}
+            } else {
+                signed_up += 1;
+                html! {"Signed Up"}
+            };
+
             event_details.push([
                 html! {
                     a href={"/event/" (event.id)} class="underline hover:text-blue-300" {(event.name)}
                 },
                 html! {
-                    (dlc.short_ymd(&event.datetime)?)
+                    (dlc.short_ymd(&event.datetime))
                 },
+                status,
             ]);
         }
     }
 
     let form_house_display = internal_get_profile_student_form_house_display(session).await?;
-    let events_table = table(subtitle("Events"), ["Event", "Date"], event_details);
+    let events_table = table(
+        subtitle("Events"),
+        ["Event", "Date", "Status"],
+        event_details,
+    );
 
     Ok(html! {
         div class="mb-4 flex flex-col items-center justify-between space-x-4 container mx-auto bg-gray-800 rounded-md p-4 rounded-lg" {
             (subtitle("Student Information"))
             (form_house_display)
             br;
+            p class="text-gray-200" {
+                "Attended " (attended) " of " (signed_up) " signed-up events"
+            }
+            br;
             (events_table)
         }
     })
@@ -155,6 +212,8 @@ pub async fn internal_get_profile_student_display(
 pub async fn internal_get_profile_student_form_house_display(
     session: DenimSession,
 ) -> DenimResult<Markup> {
+    let can_edit = session.can(PermissionsTarget::CRUD_USERS);
+
     let UserKind::Student {
         tutor_group,
         house,
@@ -164,18 +223,565 @@ pub async fn internal_get_profile_student_form_house_display(
         return Err(DenimError::UnableToFindUserInfo);
     };
 
-    //TODO: link to tg/house pages
     Ok(html! {
         div class="flex flex-col gap-4" {
             div class="flex flex-row gap-2" {
-                p class="text-gray-200" {"Tutor Group: " (tutor_group.staff_member)}
-                p class="text-gray-200" {"House: " (house.name)}
+                p class="text-gray-200" {
+                    "Tutor Group: "
+                    a href={"/tutor_group/" (tutor_group.id)} class="underline hover:text-blue-300" {(tutor_group.staff_member)}
+                }
+                p class="text-gray-200" {
+                    "House: "
+                    a href={"/house/" (house.id)} class="underline hover:text-blue-300" {(house.name)}
+                }
+            }
+            @if can_edit {
+                button hx-get="/internal/profile/edit_tutor_group" hx-target="#form_contents" class="bg-gray-700 hover:bg-gray-600 text-gray-300 font-bold py-2 px-4 rounded focus:outline-none focus:shadow-outline" {"Edit Form/House"}
+            }
+        }
+    })
+}
+
+#[derive(Deserialize)]
+pub struct EditTutorGroupQuery {
+    ///the student to move - defaults to the current user
+    id: Option<Uuid>,
+}
+
+pub async fn internal_get_profile_edit_tutor_group(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Query(EditTutorGroupQuery { id }): Query<EditTutorGroupQuery>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::CRUD_USERS)?;
+
+    let student_id = match id {
+        Some(id) => id,
+        None => session.user.as_ref().context(UnableToFindUserInfoSnafu)?.id,
+    };
+
+    get_edit_tutor_group_form(&state, &session, student_id, None).await
+}
+
+async fn get_edit_tutor_group_form(
+    state: &DenimState,
+    session: &DenimSession,
+    student_id: Uuid,
+    message: Option<&'static str>,
+) -> DenimResult<Markup> {
+    let student = User::get_from_db_by_id(student_id, &mut *state.get_connection().await?)
+        .await?
+        .context(MissingUserSnafu { id: student_id })?;
+    session.ensure_can_for_house(PermissionsTarget::CRUD_USERS, student.house_id())?;
+    let UserKind::Student {
+        tutor_group: current_tutor_group,
+        ..
+    } = &student.kind
+    else {
+        return Err(DenimError::UnableToFindUserInfo);
+    };
+
+    let house_names_by_id: HashMap<_, _> = HouseGroup::get_all(state)
+        .await?
+        .into_iter()
+        .map(|house| (house.id, house.name))
+        .collect();
+    let staff_by_id: HashMap<_, _> = User::get_all_staff(state)
+        .await?
+        .into_iter()
+        .map(|staff| (staff.id, staff))
+        .collect();
+    let tutor_groups: Vec<_> = TutorGroup::get_all(state)
+        .await?
+        .into_iter()
+        .filter(|tutor_group| {
+            session.can_for_house(PermissionsTarget::CRUD_USERS, Some(tutor_group.house_id))
+        })
+        .collect();
+
+    Ok(html! {
+        div id="edit_tutor_group" {
+            (supertitle("Change Tutor Group"))
+            p class="text-gray-300 mb-4" {"Moving " (student)}
+
+            @if let Some(message) = message {
+                (toast(ToastKind::Success, message))
+            }
+
+            form hx-post="/internal/profile/edit_tutor_group" hx-trigger="submit" class="p-4" hx-target="#edit_tutor_group" hx-swap="outerHTML" {
+                input type="hidden" name="student_id" value=(student_id);
+                (form_element("tutor_group", "Tutor Group", html!{
+                    select id="tutor_group" name="tutor_group" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600" {
+                        @for tutor_group in tutor_groups {
+                            option value=(tutor_group.id) selected[tutor_group.id == current_tutor_group.id] {
+                                (house_names_by_id.get(&tutor_group.house_id).map_or("Unknown House", String::as_str))
+                                " - "
+                                @if let Some(staff) = staff_by_id.get(&tutor_group.staff_member) {
+                                    (staff)
+                                } @else {
+                                    "Unknown Staff Member"
+                                }
+                            }
+                        }
+                    }
+                }))
+
+                (form_submit_button(Some("Change Tutor Group")))
             }
-            button class="bg-gray-700 hover:bg-gray-600 text-gray-300 font-bold py-2 px-4 rounded focus:outline-none focus:shadow-outline" {"Edit Form/House"}
         }
     })
 }
 
+#[derive(Deserialize)]
+pub struct EditTutorGroupForm {
+    student_id: Uuid,
+    tutor_group: Uuid,
+}
+
+pub async fn internal_post_profile_edit_tutor_group(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Form(EditTutorGroupForm {
+        student_id,
+        tutor_group,
+    }): Form<EditTutorGroupForm>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::CRUD_USERS)?;
+
+    let mut conn = state.get_connection().await?;
+    let student = User::get_from_db_by_id(student_id, &mut conn)
+        .await?
+        .context(MissingUserSnafu { id: student_id })?;
+    let new_tutor_group = TutorGroup::get_from_db_by_id(tutor_group, &mut conn)
+        .await?
+        .context(MissingTutorGroupSnafu { id: tutor_group })?;
+    //scoped staff can't move students out of their house, nor bring them in from another one
+    session.ensure_can_for_house(PermissionsTarget::CRUD_USERS, student.house_id())?;
+    session.ensure_can_for_house(
+        PermissionsTarget::CRUD_USERS,
+        Some(new_tutor_group.house_id),
+    )?;
+
+    //the house comes from the tutor group, so that stays consistent for free
+    let rows_affected = sqlx::query!(
+        "UPDATE public.students SET tutor_group_id = $1 WHERE user_id = $2",
+        tutor_group,
+        student_id
+    )
+    .execute(&mut *conn)
+    .await
+    .context(MakeQuerySnafu)?
+    .rows_affected();
+    drop(conn);
+
+    if rows_affected == 0 {
+        return Err(DenimError::MissingUser { id: student_id });
+    }
+
+    state
+        .audit(
+            &session,
+            AuditAction::Edit,
+            AuditTarget::Person(student_id),
+            Some("tutor group"),
+        )
+        .await;
+    state.send_sse_event(SseEvent::CrudPerson);
+
+    get_edit_tutor_group_form(&state, &session, student_id, Some("Tutor group updated!")).await
+}
+
+pub async fn internal_get_profile_sessions(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    tower_session: Session,
+) -> DenimResult<Markup> {
+    let user = session.user.context(UnableToFindUserInfoSnafu)?;
+    let dlc = state
+        .config()
+        .date_locale_config()
+        .get()?
+        .for_user(Some(&user));
+    let current_id = tower_session.id().map(|id| id.to_string());
+
+    let sessions =
+        PostgresSessionStore::get_sessions_for_user(user.id, &mut *state.get_connection().await?)
+            .await?;
+
+    let mut rows = vec![];
+    for user_session in sessions {
+        let is_current = current_id.as_deref() == Some(user_session.id.as_str());
+        let expires = Timestamp::from_second(user_session.expiry_date.unix_timestamp())
+            .context(UnrepresentableTimeSnafu)?
+            .to_zoned(dlc.timezone.clone());
+
+        rows.push([
+            html! {
+                (dlc.short_ymdet(&expires))
+                @if is_current {
+                    " "
+                    span class="bg-blue-600 text-xs font-semibold px-2 py-1 rounded" {"This Device"}
+                }
+            },
+            html! {
+                form method="post" action={"/profile/sessions/" (user_session.id) "/revoke"} {
+                    (csrf_field())
+                    input type="submit" value=(if is_current {"Log Out"} else {"Revoke"}) class="bg-red-600 hover:bg-red-800 font-bold py-1 px-3 rounded cursor-pointer";
+                }
+            },
+        ]);
+    }
+
+    Ok(table(subtitle("Active Sessions"), ["Expires", ""], rows))
+}
+
+pub async fn post_revoke_session(
+    State(state): State<DenimState>,
+    mut session: DenimSession,
+    tower_session: Session,
+    Path(id): Path<String>,
+) -> DenimResult<Redirect> {
+    let user = session.user.clone().context(UnableToFindUserInfoSnafu)?;
+
+    //revoking this one is just logging out, and we can't stay on the profile page afterwards
+    if tower_session
+        .id()
+        .is_some_and(|current| current.to_string() == id)
+    {
+        session.logout().await?;
+        return Ok(Redirect::to("/login"));
+    }
+
+    PostgresSessionStore::revoke_session_for_user(
+        &id,
+        user.id,
+        &mut *state.get_connection().await?,
+    )
+    .await?;
+
+    Ok(Redirect::to("/profile"))
+}
+
+const PENDING_TOTP_SECRET_KEY: &str = "pending_totp_secret";
+
+#[derive(Deserialize)]
+pub struct TotpCodeForm {
+    code: String,
+}
+
+fn get_timezone_display(state: &DenimState, user: &User, saved: bool) -> DenimResult<Markup> {
+    let global_tz = state.config().date_locale_config().get()?.timezone.clone();
+    let global_tz_name = global_tz.iana_name().unwrap_or("UTC").to_string();
+
+    Ok(html! {
+        (subtitle("Timezone"))
+        @if saved {
+            (toast(ToastKind::Success, "Timezone updated!"))
+        }
+        @match user.timezone.as_ref().and_then(TimeZone::iana_name) {
+            Some(tz) => p class="text-gray-300 mb-2" {"Dates & times are shown in " span class="italic" {(tz)} "."},
+            None => p class="text-gray-300 mb-2" {"Dates & times are shown in the default timezone (" span class="italic" {(global_tz_name)} ")."},
+        }
+        form hx-post="/internal/profile/timezone" hx-target="#timezone_section" class="p-4" {
+            (timezone_picker(Some(user.timezone.clone().unwrap_or(global_tz))))
+            (form_submit_button(Some("Save Timezone")))
+        }
+        @if user.timezone.is_some() {
+            button hx-post="/internal/profile/timezone" hx-vals=r#"{"tz": ""}"# hx-target="#timezone_section" class="bg-gray-700 hover:bg-gray-600 text-gray-300 font-bold py-2 px-4 rounded focus:outline-none focus:shadow-outline" {"Use Default Timezone"}
+        }
+    })
+}
+
+pub async fn internal_get_profile_timezone(
+    State(state): State<DenimState>,
+    session: DenimSession,
+) -> DenimResult<Markup> {
+    let user = session.user.context(UnableToFindUserInfoSnafu)?;
+    get_timezone_display(&state, &user, false)
+}
+
+#[derive(Deserialize)]
+pub struct TimezoneForm {
+    tz: String,
+}
+
+pub async fn internal_post_profile_timezone(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Form(TimezoneForm { tz }): Form<TimezoneForm>,
+) -> DenimResult<Markup> {
+    let mut user = session.user.context(UnableToFindUserInfoSnafu)?;
+
+    //an empty timezone means going back to the global one
+    let timezone = if tz.is_empty() {
+        None
+    } else {
+        Some(TimeZone::get(&tz).context(InvalidTimezoneSnafu { tz })?)
+    };
+
+    User::set_timezone(
+        user.id,
+        timezone.as_ref(),
+        &mut *state.get_connection().await?,
+    )
+    .await?;
+    user.timezone = timezone;
+
+    get_timezone_display(&state, &user, true)
+}
+
+fn get_signup_emails_display(wants_signup_emails: bool, saved: bool) -> Markup {
+    html! {
+        (subtitle("Sign-Up Emails"))
+        @if saved {
+            (toast(ToastKind::Success, "Email preference updated!"))
+        }
+        @if wants_signup_emails {
+            p class="text-gray-300 mb-2" {"You'll get an email whenever someone else signs you up to an event."}
+            button hx-post="/internal/profile/signup_emails" hx-vals=r#"{"wants_signup_emails": false}"# hx-target="#signup_emails_section" class="bg-gray-700 hover:bg-gray-600 text-gray-300 font-bold py-2 px-4 rounded focus:outline-none focus:shadow-outline" {"Stop Emailing Me"}
+        } @else {
+            p class="text-gray-300 mb-2" {"You won't get emailed when someone else signs you up to an event."}
+            button hx-post="/internal/profile/signup_emails" hx-vals=r#"{"wants_signup_emails": true}"# hx-target="#signup_emails_section" class="bg-blue-500 hover:bg-blue-700 font-bold py-2 px-4 rounded focus:outline-none focus:shadow-outline" {"Email Me"}
+        }
+    }
+}
+
+pub async fn internal_get_profile_signup_emails(session: DenimSession) -> DenimResult<Markup> {
+    let user = session.user.context(UnableToFindUserInfoSnafu)?;
+    Ok(get_signup_emails_display(user.wants_signup_emails, false))
+}
+
+#[derive(Deserialize)]
+pub struct SignupEmailsForm {
+    wants_signup_emails: bool,
+}
+
+pub async fn internal_post_profile_signup_emails(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Form(SignupEmailsForm {
+        wants_signup_emails,
+    }): Form<SignupEmailsForm>,
+) -> DenimResult<Markup> {
+    let user = session.user.context(UnableToFindUserInfoSnafu)?;
+
+    User::set_wants_signup_emails(
+        user.id,
+        wants_signup_emails,
+        &mut *state.get_connection().await?,
+    )
+    .await?;
+
+    Ok(get_signup_emails_display(wants_signup_emails, true))
+}
+
+fn get_permissions_display(permissions: PermissionsTarget) -> Markup {
+    html! {
+        (subtitle("Permissions"))
+        p class="text-gray-300 mb-2" {"What you're allowed to do, from your built-in role and any extra role you've been given."}
+        div class="flex flex-row flex-wrap gap-1" {
+            @for (name, permission) in PermissionsTarget::all().iter_names() {
+                @if permissions.contains(permission) {
+                    span class="bg-green-900 text-green-200 text-xs font-medium px-2 py-0.5 rounded-full" title="Enabled" {(name)}
+                } @else {
+                    span class="bg-gray-700 text-gray-400 text-xs font-medium px-2 py-0.5 rounded-full line-through" title="Disabled" {(name)}
+                }
+            }
+        }
+    }
+}
+
+fn get_api_token_display(has_token: bool, new_token: Option<&str>) -> Markup {
+    html! {
+        (subtitle("API Token"))
+        p class="text-gray-300 mb-2" {"Lets other programs use the API as you, with all of your permissions."}
+        @if let Some(new_token) = new_token {
+            div class="py-2" {
+                p class="text-gray-200 font-semibold" {"New token (not shown again):"}
+                code class="block break-all bg-gray-700 rounded p-2" {(new_token)}
+            }
+        }
+        div class="flex flex-row space-x-2" {
+            button hx-post="/profile/api_tokens" hx-target="#api_token_section" hx-confirm=[has_token.then_some("This will replace your current token - are you sure?")] class="bg-blue-500 hover:bg-blue-700 font-bold py-2 px-4 rounded focus:outline-none focus:shadow-outline" {
+                @if has_token {"Regenerate Token"} @else {"Generate Token"}
+            }
+            @if has_token {
+                button hx-delete="/profile/api_tokens" hx-target="#api_token_section" class="bg-red-600 hover:bg-red-800 font-bold py-2 px-4 rounded focus:outline-none focus:shadow-outline" {"Revoke Token"}
+            }
+        }
+    }
+}
+
+pub async fn internal_get_profile_api_token(session: DenimSession) -> DenimResult<Markup> {
+    let user = session.user.context(UnableToFindUserInfoSnafu)?;
+    Ok(get_api_token_display(user.access_token.is_some(), None))
+}
+
+pub async fn post_profile_api_token(
+    State(state): State<DenimState>,
+    session: DenimSession,
+) -> DenimResult<Markup> {
+    let user = session.user.context(UnableToFindUserInfoSnafu)?;
+
+    let token = generate_api_token();
+    User::set_api_token_hash(
+        user.id,
+        Some(&hash_api_token(&token)),
+        &mut *state.get_connection().await?,
+    )
+    .await?;
+
+    Ok(get_api_token_display(true, Some(&token)))
+}
+
+pub async fn delete_profile_api_token(
+    State(state): State<DenimState>,
+    session: DenimSession,
+) -> DenimResult<Markup> {
+    let user = session.user.context(UnableToFindUserInfoSnafu)?;
+
+    User::set_api_token_hash(user.id, None, &mut *state.get_connection().await?).await?;
+
+    Ok(get_api_token_display(false, None))
+}
+
+fn totp_code_form(action: &str, button_text: &'static str) -> Markup {
+    html! {
+        form hx-post=(action) hx-target="#totp_section" class="p-4" {
+            (form_element("code", "Code from your authenticator app", html! {
+                input required type="text" id="code" name="code" inputmode="numeric" pattern="[0-9]{6}" autocomplete="one-time-code" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600" {}
+            }))
+            (form_submit_button(Some(button_text)))
+        }
+    }
+}
+
+fn get_totp_display(enabled: bool, error: Option<&'static str>) -> Markup {
+    html! {
+        (subtitle("Two-Factor Authentication"))
+        @if let Some(error) = error {
+            (errors_list(None, std::iter::once(error)))
+        }
+        @if enabled {
+            p class="text-gray-300 mb-2" {"Two-factor authentication is enabled. Enter a code to turn it off."}
+            (totp_code_form("/internal/profile/totp/disable", "Disable 2FA"))
+        } @else {
+            p class="text-gray-300 mb-2" {"Two-factor authentication is not enabled."}
+            button hx-get="/internal/profile/totp/enrol" hx-target="#totp_section" class="bg-blue-500 hover:bg-blue-700 font-bold py-2 px-4 rounded focus:outline-none focus:shadow-outline" {"Set up 2FA"}
+        }
+    }
+}
+
+pub async fn internal_get_profile_totp(session: DenimSession) -> DenimResult<Markup> {
+    let user = session.user.context(UnableToFindUserInfoSnafu)?;
+    Ok(get_totp_display(user.totp_secret.is_some(), None))
+}
+
+fn get_totp_enrol_form(
+    state: &DenimState,
+    secret: &SecretString,
+    account: &EmailAddress,
+    error: Option<&'static str>,
+) -> DenimResult<Markup> {
+    let app_name = state.config().app_name();
+    let totp = totp::make_totp(secret, account, Some(&app_name))?;
+    let qr_code = totp::qr_code_base64(secret, account, &app_name)?;
+
+    Ok(html! {
+        (subtitle("Set up Two-Factor Authentication"))
+        @if let Some(error) = error {
+            (errors_list(None, std::iter::once(error)))
+        }
+        p class="text-gray-300 mb-2" {"Scan this QR code with your authenticator app, then enter the code it shows to finish setting up."}
+        img src={"data:image/png;base64," (qr_code)} alt="TOTP QR Code" class="bg-white p-2 rounded mx-auto";
+        details class="my-2 text-gray-300" {
+            summary class="cursor-pointer" {"Can't scan the code?"}
+            p class="break-all font-mono text-sm" {(totp.get_url())}
+            p class="break-all font-mono text-sm" {"Secret: " (totp.get_secret_base32())}
+        }
+        (totp_code_form("/internal/profile/totp/enable", "Enable 2FA"))
+    })
+}
+
+pub async fn internal_get_profile_totp_enrol(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    tower_session: Session,
+) -> DenimResult<Markup> {
+    let user = session.user.clone().context(UnableToFindUserInfoSnafu)?;
+    if user.totp_secret.is_some() {
+        return Ok(get_totp_display(true, None));
+    }
+
+    let secret = totp::generate_secret();
+    tower_session
+        .insert(PENDING_TOTP_SECRET_KEY, secret.expose_secret())
+        .await
+        .context(TowerSessionSnafu)?;
+
+    get_totp_enrol_form(&state, &secret, &user.email, None)
+}
+
+pub async fn internal_post_profile_totp_enable(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    tower_session: Session,
+    Form(TotpCodeForm { code }): Form<TotpCodeForm>,
+) -> DenimResult<Markup> {
+    let user = session.user.clone().context(UnableToFindUserInfoSnafu)?;
+    let Some(secret) = tower_session
+        .get::<String>(PENDING_TOTP_SECRET_KEY)
+        .await
+        .context(TowerSessionSnafu)?
+        .map(SecretString::from)
+    else {
+        return Ok(get_totp_display(
+            user.totp_secret.is_some(),
+            Some("Setup expired, please try again"),
+        ));
+    };
+
+    let Some(step) = totp::accepted_step(&secret, &user.email, &code)? else {
+        return get_totp_enrol_form(&state, &secret, &user.email, Some("Incorrect code"));
+    };
+
+    let mut conn = state.get_connection().await?;
+    User::set_totp_secret(user.id, Some(&secret), &mut conn).await?;
+    //so the code used to set it up can't then be used to log in
+    User::use_totp_step(user.id, step, &mut conn).await?;
+    drop(conn);
+    tower_session
+        .remove::<String>(PENDING_TOTP_SECRET_KEY)
+        .await
+        .context(TowerSessionSnafu)?;
+
+    Ok(get_totp_display(true, None))
+}
+
+pub async fn internal_post_profile_totp_disable(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Form(TotpCodeForm { code }): Form<TotpCodeForm>,
+) -> DenimResult<Markup> {
+    let user = session.user.context(UnableToFindUserInfoSnafu)?;
+    let Some(secret) = user.totp_secret else {
+        return Ok(get_totp_display(false, None));
+    };
+
+    let mut conn = state.get_connection().await?;
+    let accepted = match totp::accepted_step(&secret, &user.email, &code)? {
+        Some(step) => User::use_totp_step(user.id, step, &mut conn).await?,
+        None => false,
+    };
+    if !accepted {
+        return Ok(get_totp_display(true, Some("Incorrect code")));
+    }
+
+    User::set_totp_secret(user.id, None, &mut conn).await?;
+
+    Ok(get_totp_display(false, None))
+}
+
 fn get_edit_password_form(errors: ValidationError) -> Markup {
     html! {
         (supertitle("Change Password"))
@@ -199,7 +805,7 @@ pub fn internal_get_profile_edit_password() -> Markup {
 }
 
 fn get_one_item_form(
-    action: &'static str,
+    action: &str,
     form_title: &'static str,
     current: &str,
     label: impl Render,
@@ -294,6 +900,8 @@ bitflags! {
         const CURRENT_PASSWORD_INCORRECT = 0b0000_0100;
         const ALREADY_TAKEN_EMAIL =        0b0000_1000;
         const SAME_AS_BEFORE =             0b0010_0000;
+        const PASSWORD_TOO_SHORT =         0b0100_0000;
+        const PASSWORD_TOO_SIMPLE =        0b1000_0000;
     }
 }
 
@@ -305,6 +913,10 @@ impl ValidationError {
             Self::CURRENT_PASSWORD_INCORRECT => Some("Provided current password was incorrect"),
             Self::ALREADY_TAKEN_EMAIL => Some("Provided email is already in use"),
             Self::SAME_AS_BEFORE => Some("Field was the same as before"),
+            Self::PASSWORD_TOO_SHORT => Some("Provided password was too short"),
+            Self::PASSWORD_TOO_SIMPLE => Some(
+                "Provided password needs at least 3 of: lowercase letters, uppercase letters, numbers & symbols",
+            ),
             _ => None,
         })
     }
@@ -394,6 +1006,13 @@ pub async fn internal_post_profile_edit_password(
         if new.expose_secret() != confirmed.expose_secret() {
             errors |= ValidationError::PASSWORDS_NOT_MATCH;
         }
+        let security_config = state.config().security_config();
+        if security_config.password_is_too_short(new.expose_secret()) {
+            errors |= ValidationError::PASSWORD_TOO_SHORT;
+        }
+        if security_config.password_is_too_simple(new.expose_secret()) {
+            errors |= ValidationError::PASSWORD_TOO_SIMPLE;
+        }
 
         if !errors.is_empty() {
             return Err(ValidationResult::Invalid(errors));
@@ -402,6 +1021,7 @@ pub async fn internal_post_profile_edit_password(
         let PasswordUserId::FullUser(user) = add_password(
             current_user.into(),
             new,
+            state.config().security_config().bcrypt_cost,
             &mut *state.get_connection().await?,
             false,
         )
@@ -425,40 +1045,40 @@ pub async fn internal_post_profile_edit_password(
     .await
 }
 
-pub async fn internal_post_profile_edit_first_name(
-    session: DenimSession,
-    State(state): State<DenimState>,
-    Form(SingleItemForm { item }): Form<SingleItemForm>,
-) -> DenimResult<Markup> {
-    async fn change_first_name(
-        first_name: String,
-        state: DenimState,
-        mut current_user: User,
-    ) -> Result<User, ValidationResult> {
-        if first_name.is_empty() {
-            return Err(ValidationResult::Invalid(ValidationError::EMPTY));
-        }
-        if first_name == current_user.first_name {
-            return Err(ValidationResult::Invalid(ValidationError::SAME_AS_BEFORE));
-        }
+async fn change_first_name(
+    first_name: String,
+    state: DenimState,
+    mut current_user: User,
+) -> Result<User, ValidationResult> {
+    if first_name.is_empty() {
+        return Err(ValidationResult::Invalid(ValidationError::EMPTY));
+    }
+    if first_name == current_user.first_name {
+        return Err(ValidationResult::Invalid(ValidationError::SAME_AS_BEFORE));
+    }
 
-        let mut conn = state.get_connection().await?;
+    let mut conn = state.get_connection().await?;
 
-        sqlx::query!(
-            "UPDATE users SET first_name = $1 WHERE id = $2",
-            first_name,
-            current_user.id
-        )
-        .execute(&mut *conn)
-        .await
-        .context(MakeQuerySnafu)?;
+    sqlx::query!(
+        "UPDATE users SET first_name = $1 WHERE id = $2",
+        first_name,
+        current_user.id
+    )
+    .execute(&mut *conn)
+    .await
+    .context(MakeQuerySnafu)?;
 
-        current_user.first_name = first_name;
-        state.send_sse_event(SseEvent::CrudPerson);
+    current_user.first_name = first_name;
+    state.send_sse_event(SseEvent::CrudPerson);
 
-        Ok(current_user)
-    }
+    Ok(current_user)
+}
 
+pub async fn internal_post_profile_edit_first_name(
+    session: DenimSession,
+    State(state): State<DenimState>,
+    Form(SingleItemForm { item }): Form<SingleItemForm>,
+) -> DenimResult<Markup> {
     session.ensure_can(PermissionsTarget::CRUD_USERS)?;
     let user = session
         .user
@@ -483,43 +1103,44 @@ pub async fn internal_post_profile_edit_first_name(
     )
     .await
 }
-pub async fn internal_post_profile_edit_pref_name(
-    session: DenimSession,
-    State(state): State<DenimState>,
-    Form(SingleItemForm { item }): Form<SingleItemForm>,
-) -> DenimResult<Markup> {
-    async fn change_pref_name(
-        pref_name: String,
-        state: DenimState,
-        mut current_user: User,
-    ) -> Result<User, ValidationResult> {
-        let pref_name = if pref_name.is_empty() {
-            None
-        } else {
-            Some(pref_name)
-        };
 
-        if pref_name.as_deref() == current_user.pref_name.as_deref() {
-            return Err(ValidationResult::Invalid(ValidationError::SAME_AS_BEFORE));
-        }
+async fn change_pref_name(
+    pref_name: String,
+    state: DenimState,
+    mut current_user: User,
+) -> Result<User, ValidationResult> {
+    let pref_name = if pref_name.is_empty() {
+        None
+    } else {
+        Some(pref_name)
+    };
 
-        let mut conn = state.get_connection().await?;
+    if pref_name.as_deref() == current_user.pref_name.as_deref() {
+        return Err(ValidationResult::Invalid(ValidationError::SAME_AS_BEFORE));
+    }
 
-        sqlx::query!(
-            "UPDATE users SET pref_name = $1 WHERE id = $2",
-            pref_name,
-            current_user.id
-        )
-        .execute(&mut *conn)
-        .await
-        .context(MakeQuerySnafu)?;
+    let mut conn = state.get_connection().await?;
+
+    sqlx::query!(
+        "UPDATE users SET pref_name = $1 WHERE id = $2",
+        pref_name,
+        current_user.id
+    )
+    .execute(&mut *conn)
+    .await
+    .context(MakeQuerySnafu)?;
 
-        current_user.pref_name = pref_name;
-        state.send_sse_event(SseEvent::CrudPerson);
+    current_user.pref_name = pref_name;
+    state.send_sse_event(SseEvent::CrudPerson);
 
-        Ok(current_user)
-    }
+    Ok(current_user)
+}
 
+pub async fn internal_post_profile_edit_pref_name(
+    session: DenimSession,
+    State(state): State<DenimState>,
+    Form(SingleItemForm { item }): Form<SingleItemForm>,
+) -> DenimResult<Markup> {
     session.ensure_can(PermissionsTarget::CRUD_USERS)?;
     let user = session.user.clone().context(UnableToFindUserInfoSnafu)?;
     let backup_pref_name = user.pref_name.clone();
@@ -541,40 +1162,41 @@ pub async fn internal_post_profile_edit_pref_name(
     )
     .await
 }
-pub async fn internal_post_profile_edit_surname(
-    session: DenimSession,
-    State(state): State<DenimState>,
-    Form(SingleItemForm { item }): Form<SingleItemForm>,
-) -> DenimResult<Markup> {
-    async fn change_surname(
-        surname: String,
-        state: DenimState,
-        mut current_user: User,
-    ) -> Result<User, ValidationResult> {
-        if surname.is_empty() {
-            return Err(ValidationResult::Invalid(ValidationError::EMPTY));
-        }
-        if surname == current_user.surname {
-            return Err(ValidationResult::Invalid(ValidationError::SAME_AS_BEFORE));
-        }
 
-        let mut conn = state.get_connection().await?;
+async fn change_surname(
+    surname: String,
+    state: DenimState,
+    mut current_user: User,
+) -> Result<User, ValidationResult> {
+    if surname.is_empty() {
+        return Err(ValidationResult::Invalid(ValidationError::EMPTY));
+    }
+    if surname == current_user.surname {
+        return Err(ValidationResult::Invalid(ValidationError::SAME_AS_BEFORE));
+    }
 
-        sqlx::query!(
-            "UPDATE users SET surname = $1 WHERE id = $2",
-            surname,
-            current_user.id
-        )
-        .execute(&mut *conn)
-        .await
-        .context(MakeQuerySnafu)?;
+    let mut conn = state.get_connection().await?;
 
-        current_user.surname = surname;
-        state.send_sse_event(SseEvent::CrudPerson);
+    sqlx::query!(
+        "UPDATE users SET surname = $1 WHERE id = $2",
+        surname,
+        current_user.id
+    )
+    .execute(&mut *conn)
+    .await
+    .context(MakeQuerySnafu)?;
 
-        Ok(current_user)
-    }
+    current_user.surname = surname;
+    state.send_sse_event(SseEvent::CrudPerson);
 
+    Ok(current_user)
+}
+
+pub async fn internal_post_profile_edit_surname(
+    session: DenimSession,
+    State(state): State<DenimState>,
+    Form(SingleItemForm { item }): Form<SingleItemForm>,
+) -> DenimResult<Markup> {
     session.ensure_can(PermissionsTarget::CRUD_USERS)?;
     let user = session.user.clone().context(UnableToFindUserInfoSnafu)?;
     let backup_surname = user.surname.clone();
@@ -599,47 +1221,50 @@ pub async fn internal_post_profile_edit_surname(
 
 #[derive(Deserialize)]
 pub struct EmailForm {
+    #[serde(rename = "item")] //to match `get_one_item_form`
     email: EmailAddress,
 }
 
-pub async fn internal_post_profile_edit_email(
-    session: DenimSession,
-    State(state): State<DenimState>,
-    Form(EmailForm { email }): Form<EmailForm>,
-) -> DenimResult<Markup> {
-    async fn change_email(
-        email: EmailAddress,
-        state: DenimState,
-        mut current_user: User,
-    ) -> Result<User, ValidationResult> {
-        let mut errors = ValidationError::empty();
-
-        if email == current_user.email {
-            errors |= ValidationError::SAME_AS_BEFORE;
-            //theoretically we can't get both lol tho
-        }
-
-        if !errors.is_empty() {
-            return Err(ValidationResult::Invalid(errors));
-        }
+async fn change_email(
+    email: EmailAddress,
+    state: DenimState,
+    mut current_user: User,
+) -> Result<User, ValidationResult> {
+    let mut errors = ValidationError::empty();
+
+    let mut conn = state.get_connection().await?;
+
+    if email == current_user.email {
+        errors |= ValidationError::SAME_AS_BEFORE;
+        //theoretically we can't get both lol tho
+    } else if User::email_is_taken(&email, Some(current_user.id), &mut conn).await? {
+        errors |= ValidationError::ALREADY_TAKEN_EMAIL;
+    }
 
-        let mut conn = state.get_connection().await?;
+    if !errors.is_empty() {
+        return Err(ValidationResult::Invalid(errors));
+    }
 
-        sqlx::query!(
-            "UPDATE users SET email = $1 WHERE id = $2",
-            email.as_str(),
-            current_user.id
-        )
-        .execute(&mut *conn)
-        .await
-        .context(MakeQuerySnafu)?;
+    sqlx::query!(
+        "UPDATE users SET email = $1 WHERE id = $2",
+        email.as_str(),
+        current_user.id
+    )
+    .execute(&mut *conn)
+    .await
+    .context(MakeQuerySnafu)?;
 
-        current_user.email = email;
-        state.send_sse_event(SseEvent::CrudPerson);
+    current_user.email = email;
+    state.send_sse_event(SseEvent::CrudPerson);
 
-        Ok(current_user)
-    }
+    Ok(current_user)
+}
 
+pub async fn internal_post_profile_edit_email(
+    session: DenimSession,
+    State(state): State<DenimState>,
+    Form(EmailForm { email }): Form<EmailForm>,
+) -> DenimResult<Markup> {
     session.ensure_can(PermissionsTarget::CRUD_USERS)?;
     let user = session.user.clone().context(UnableToFindUserInfoSnafu)?;
     let backup_email = user.email.clone();
@@ -661,3 +1286,218 @@ pub async fn internal_post_profile_edit_email(
     )
     .await
 }
+
+///gets someone else's details for an admin to edit, making sure that they're allowed to
+async fn get_person_to_edit(
+    state: &DenimState,
+    session: &DenimSession,
+    id: Uuid,
+) -> DenimResult<User> {
+    session.ensure_can(PermissionsTarget::CRUD_USERS)?;
+
+    let person = User::get_from_db_by_id(id, &mut *state.get_connection().await?)
+        .await?
+        .context(MissingUserSnafu { id })?;
+    if matches!(person.kind, UserKind::Admin) {
+        session.ensure_can(PermissionsTarget::CRUD_ADMINS)?;
+    }
+    session.ensure_can_for_house(PermissionsTarget::CRUD_USERS, person.house_id())?;
+
+    Ok(person)
+}
+
+async fn audit_person_change(
+    state: &DenimState,
+    session: &DenimSession,
+    id: Uuid,
+    res: &Result<User, ValidationResult>,
+    field: &'static str,
+) {
+    if res.is_ok() {
+        state
+            .audit(
+                session,
+                AuditAction::Edit,
+                AuditTarget::Person(id),
+                Some(field),
+            )
+            .await;
+    }
+}
+
+fn handle_person_change_result(
+    res: Result<User, ValidationResult>,
+    form: impl Fn(ValidationError) -> Markup,
+) -> DenimResult<Markup> {
+    match res {
+        Ok(_) => Ok(html! {}), //the person view refreshes itself from the SSE event
+        Err(ValidationResult::InternalError(e)) => Err(e),
+        Err(ValidationResult::Invalid(e)) => Ok(form(e)),
+    }
+}
+
+pub async fn internal_get_person_edit_first_name(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(id): Path<Uuid>,
+) -> DenimResult<Markup> {
+    let person = get_person_to_edit(&state, &session, id).await?;
+
+    Ok(get_one_item_form(
+        &format!("/internal/people/{id}/edit_first_name"),
+        "Change First Name",
+        &person.first_name,
+        "First Name",
+        None,
+        true,
+        ValidationError::empty(),
+    ))
+}
+pub async fn internal_get_person_edit_pref_name(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(id): Path<Uuid>,
+) -> DenimResult<Markup> {
+    let person = get_person_to_edit(&state, &session, id).await?;
+
+    Ok(get_one_item_form(
+        &format!("/internal/people/{id}/edit_pref_name"),
+        "Change Preferred Name",
+        person.pref_name.as_deref().unwrap_or(""),
+        "Preferred Name",
+        None,
+        false,
+        ValidationError::empty(),
+    ))
+}
+pub async fn internal_get_person_edit_surname(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(id): Path<Uuid>,
+) -> DenimResult<Markup> {
+    let person = get_person_to_edit(&state, &session, id).await?;
+
+    Ok(get_one_item_form(
+        &format!("/internal/people/{id}/edit_surname"),
+        "Change Surname",
+        &person.surname,
+        "Surname",
+        None,
+        true,
+        ValidationError::empty(),
+    ))
+}
+pub async fn internal_get_person_edit_email(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(id): Path<Uuid>,
+) -> DenimResult<Markup> {
+    let person = get_person_to_edit(&state, &session, id).await?;
+
+    Ok(get_one_item_form(
+        &format!("/internal/people/{id}/edit_email"),
+        "Change Email",
+        person.email.as_str(),
+        "Email",
+        Some("email"),
+        true,
+        ValidationError::empty(),
+    ))
+}
+
+pub async fn internal_post_person_edit_first_name(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(id): Path<Uuid>,
+    Form(SingleItemForm { item }): Form<SingleItemForm>,
+) -> DenimResult<Markup> {
+    let person = get_person_to_edit(&state, &session, id).await?;
+    let backup_first_name = person.first_name.clone();
+
+    let res = change_first_name(item, state.clone(), person).await;
+    audit_person_change(&state, &session, id, &res, "first name").await;
+
+    handle_person_change_result(res, |e| {
+        get_one_item_form(
+            &format!("/internal/people/{id}/edit_first_name"),
+            "Change First Name",
+            &backup_first_name,
+            "First Name",
+            None,
+            true,
+            e,
+        )
+    })
+}
+pub async fn internal_post_person_edit_pref_name(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(id): Path<Uuid>,
+    Form(SingleItemForm { item }): Form<SingleItemForm>,
+) -> DenimResult<Markup> {
+    let person = get_person_to_edit(&state, &session, id).await?;
+    let backup_pref_name = person.pref_name.clone();
+
+    let res = change_pref_name(item, state.clone(), person).await;
+    audit_person_change(&state, &session, id, &res, "preferred name").await;
+
+    handle_person_change_result(res, |e| {
+        get_one_item_form(
+            &format!("/internal/people/{id}/edit_pref_name"),
+            "Change Preferred Name",
+            backup_pref_name.as_deref().unwrap_or(""),
+            "Preferred Name",
+            None,
+            false,
+            e,
+        )
+    })
+}
+pub async fn internal_post_person_edit_surname(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(id): Path<Uuid>,
+    Form(SingleItemForm { item }): Form<SingleItemForm>,
+) -> DenimResult<Markup> {
+    let person = get_person_to_edit(&state, &session, id).await?;
+    let backup_surname = person.surname.clone();
+
+    let res = change_surname(item, state.clone(), person).await;
+    audit_person_change(&state, &session, id, &res, "surname").await;
+
+    handle_person_change_result(res, |e| {
+        get_one_item_form(
+            &format!("/internal/people/{id}/edit_surname"),
+            "Change Surname",
+            &backup_surname,
+            "Surname",
+            None,
+            true,
+            e,
+        )
+    })
+}
+pub async fn internal_post_person_edit_email(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(id): Path<Uuid>,
+    Form(EmailForm { email }): Form<EmailForm>,
+) -> DenimResult<Markup> {
+    let person = get_person_to_edit(&state, &session, id).await?;
+    let backup_email = person.email.clone();
+
+    let res = change_email(email, state.clone(), person).await;
+    audit_person_change(&state, &session, id, &res, "email").await;
+
+    handle_person_change_result(res, |e| {
+        get_one_item_form(
+            &format!("/internal/people/{id}/edit_email"),
+            "Change Email",
+            backup_email.as_str(),
+            "Email",
+            Some("email"),
+            true,
+            e,
+        )
+    })
+}
@@ -0,0 +1,282 @@
+use crate::{
+    auth::{AuthUtilities, DenimSession, PermissionsTarget},
+    data::{
+        DataType,
+        audit_log::{AuditAction, AuditTarget},
+        role::{NewRole, Role},
+        user::User,
+    },
+    error::{
+        BuiltInRoleSnafu, CommitTransactionSnafu, DenimError, DenimResult, MissingRoleSnafu,
+        ParseRoleIdSnafu,
+    },
+    maud_conveniences::{errors_list, form_submit_button, simple_form_element, subtitle, title},
+    routes::sse::SseEvent,
+    state::DenimState,
+};
+use axum::{
+    Form,
+    extract::{Path, State},
+};
+use maud::{Markup, html};
+use serde::Deserialize;
+use snafu::{OptionExt, ResultExt, ensure};
+use uuid::Uuid;
+
+pub async fn get_roles(
+    State(state): State<DenimState>,
+    session: DenimSession,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::CRUD_ADMINS)?;
+
+    let roles = roles_markup(&state, vec![]).await?;
+
+    Ok(state.render(session, html! {
+        div class="mx-auto bg-gray-800 p-8 rounded shadow-md max-w-4xl w-full flex flex-col space-y-4" {
+            (roles)
+        }
+    }))
+}
+
+pub async fn internal_get_roles(
+    State(state): State<DenimState>,
+    session: DenimSession,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::CRUD_ADMINS)?;
+    roles_markup(&state, vec![]).await
+}
+
+async fn roles_markup(state: &DenimState, errors: Vec<String>) -> DenimResult<Markup> {
+    let roles = Role::get_all(state).await?;
+
+    Ok(html! {
+        div id="roles" class="flex flex-col p-4 space-y-4" {
+            (title("Roles"))
+            p class="text-gray-400 text-sm" {
+                "Everyone has the permissions of the built-in role for their kind, along with those of any extra role they've been given from the People page."
+            }
+            @if !errors.is_empty() {
+                (errors_list(None, errors.into_iter()))
+            }
+            @for role in roles {
+                //admins can always do everything, so that no-one gets locked out of this page
+                @let editable = role.kind.as_deref() != Some(Role::ADMIN_KIND);
+                div class="rounded-lg bg-gray-700 p-4 flex flex-col space-y-2" {
+                    div class="flex flex-row items-center justify-between" {
+                        (subtitle(html! {
+                            (role.name)
+                            @if role.is_built_in() {
+                                span class="text-sm text-gray-400 italic" {" (built-in)"}
+                            }
+                        }))
+                        @if !role.is_built_in() {
+                            button class="bg-red-600 hover:bg-red-800 font-bold py-1 px-3 rounded" hx-delete={"/internal/roles/" (role.id)} hx-confirm={"Delete the " (role.name) " role? Anyone with it will lose its permissions."} hx-target="#roles" hx-swap="outerHTML" {
+                                "Delete"
+                            }
+                        }
+                    }
+                    form hx-put={"/internal/roles/" (role.id) "/permissions"} hx-target="#roles" hx-swap="outerHTML" class="flex flex-col space-y-2" {
+                        div class="grid grid-cols-1 sm:grid-cols-2 gap-2" {
+                            @for (name, permission) in PermissionsTarget::all().iter_names() {
+                                label class="flex flex-row items-center space-x-2 text-sm text-gray-300" {
+                                    input type="checkbox" name="permissions" value=(name) checked[role.permissions.contains(permission)] disabled[!editable] class="leading-tight";
+                                    span {(name)}
+                                }
+                            }
+                        }
+                        @if editable {
+                            (form_submit_button(Some("Save Permissions")))
+                        }
+                    }
+                }
+            }
+            form hx-put="/internal/roles" hx-target="#roles" hx-swap="outerHTML" {
+                (simple_form_element("name", "New Role Name", true, None, None))
+                (form_submit_button(Some("Add Role")))
+            }
+        }
+    })
+}
+
+pub async fn internal_put_new_role(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Form(NewRole { name }): Form<NewRole>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::CRUD_ADMINS)?;
+
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return roles_markup(&state, vec!["Role names cannot be empty".to_string()]).await;
+    }
+
+    let mut conn = state.get_connection().await?;
+    if Role::name_is_taken(&name, &mut conn).await? {
+        drop(conn);
+        return roles_markup(
+            &state,
+            vec![format!("A role called {name:?} already exists")],
+        )
+        .await;
+    }
+
+    let id = Role::insert_into_database(NewRole { name: name.clone() }, &mut conn).await?;
+    drop(conn);
+    state
+        .audit(
+            &session,
+            AuditAction::Create,
+            AuditTarget::Role(id),
+            Some(&name),
+        )
+        .await;
+
+    roles_markup(&state, vec![]).await
+}
+
+pub async fn internal_put_role_permissions(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(id): Path<i32>,
+    Form(params): Form<Vec<(String, String)>>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::CRUD_ADMINS)?;
+
+    let mut conn = state.get_connection().await?;
+    let role = Role::get_from_db_by_id(id, &mut conn)
+        .await?
+        .context(MissingRoleSnafu { id })?;
+    drop(conn);
+    ensure!(
+        role.kind.as_deref() != Some(Role::ADMIN_KIND),
+        BuiltInRoleSnafu {
+            name: role.name,
+            action: "change the permissions of"
+        }
+    );
+
+    let permissions: PermissionsTarget = params
+        .into_iter()
+        .filter(|(key, _)| key == "permissions")
+        .filter_map(|(_, name)| PermissionsTarget::from_name(&name))
+        .collect();
+
+    let mut transaction = state.get_transaction().await?;
+    Role::set_permissions(id, permissions, &mut transaction).await?;
+    transaction.commit().await.context(CommitTransactionSnafu)?;
+
+    let names: Vec<_> = permissions.iter_names().map(|(name, _)| name).collect();
+    state
+        .audit(
+            &session,
+            AuditAction::Edit,
+            AuditTarget::Role(id),
+            Some(&format!("permissions set to {}", names.join(", "))),
+        )
+        .await;
+
+    roles_markup(&state, vec![]).await
+}
+
+pub async fn delete_role(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(id): Path<i32>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::CRUD_ADMINS)?;
+
+    let mut conn = state.get_connection().await?;
+    let role = Role::get_from_db_by_id(id, &mut conn)
+        .await?
+        .context(MissingRoleSnafu { id })?;
+    ensure!(
+        !role.is_built_in(),
+        BuiltInRoleSnafu {
+            name: role.name,
+            action: "delete"
+        }
+    );
+
+    //anyone with the role just goes back to their built-in one
+    Role::remove_from_database(id, &mut conn).await?;
+    drop(conn);
+    state
+        .audit(
+            &session,
+            AuditAction::Delete,
+            AuditTarget::Role(id),
+            Some(&role.name),
+        )
+        .await;
+    state.send_sse_event(SseEvent::CrudPerson);
+
+    roles_markup(&state, vec![]).await
+}
+
+#[derive(Deserialize)]
+pub struct PersonRoleForm {
+    ///empty for no extra role
+    pub role_id: String,
+}
+
+pub async fn internal_put_person_role(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(user_id): Path<Uuid>,
+    Form(PersonRoleForm { role_id }): Form<PersonRoleForm>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::CRUD_ADMINS)?;
+
+    let role_id = if role_id.is_empty() {
+        None
+    } else {
+        Some(
+            role_id
+                .parse::<i32>()
+                .context(ParseRoleIdSnafu { original: role_id })?,
+        )
+    };
+
+    let mut conn = state.get_connection().await?;
+    if User::get_from_db_by_id(user_id, &mut conn).await?.is_none() {
+        return Err(DenimError::MissingUser { id: user_id });
+    }
+    let role = match role_id {
+        Some(id) => {
+            let role = Role::get_from_db_by_id(id, &mut conn)
+                .await?
+                .context(MissingRoleSnafu { id })?;
+            //everyone already has their built-in role, so these can't be handed out
+            ensure!(
+                !role.is_built_in(),
+                BuiltInRoleSnafu {
+                    name: role.name,
+                    action: "assign"
+                }
+            );
+            Some(role)
+        }
+        None => None,
+    };
+
+    User::set_role(user_id, role_id, &mut conn).await?;
+    drop(conn);
+
+    let detail = role.as_ref().map_or_else(
+        || "removed extra role".to_string(),
+        |role| format!("role set to {:?}", role.name),
+    );
+    state
+        .audit(
+            &session,
+            AuditAction::Edit,
+            AuditTarget::Person(user_id),
+            Some(&detail),
+        )
+        .await;
+    state.send_sse_event(SseEvent::CrudPerson);
+
+    Ok(html! {
+        span class="text-sm text-green-400" {"Saved."}
+    })
+}
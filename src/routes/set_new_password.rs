@@ -1,7 +1,7 @@
 use crate::{
     auth::{DenimSession, PasswordUserId, add_password},
     error::{BcryptSnafu, DenimResult},
-    maud_conveniences::{errors_list, supertitle},
+    maud_conveniences::{csrf_field, errors_list, supertitle},
     state::DenimState,
 };
 use axum::{
@@ -24,15 +24,35 @@ bitflags! {
         const SAME_AS_BEFORE = 0b0000_0001;
         const DIDNT_MATCH =    0b0000_0010;
         const EMPTY =          0b0000_0100;
+        const TOO_SHORT =      0b0000_1000;
+        const TOO_SIMPLE =     0b0001_0000;
     }
 }
 
 impl ReplaceDefaultPasswordValidationError {
+    pub fn check_strength(state: &DenimState, password: &SecretString) -> Self {
+        let security_config = state.config().security_config();
+        let mut errors = Self::empty();
+
+        if security_config.password_is_too_short(password.expose_secret()) {
+            errors |= Self::TOO_SHORT;
+        }
+        if security_config.password_is_too_simple(password.expose_secret()) {
+            errors |= Self::TOO_SIMPLE;
+        }
+
+        errors
+    }
+
     pub fn as_nice_list(&self) -> impl Iterator<Item = &'static str> {
         self.iter().filter_map(|e| match e {
             Self::SAME_AS_BEFORE => Some("Provided password was same as default"),
             Self::DIDNT_MATCH => Some("Provided passwords didn't match"),
             Self::EMPTY => Some("Provided password was empty"),
+            Self::TOO_SHORT => Some("Provided password was too short"),
+            Self::TOO_SIMPLE => Some(
+                "Provided password needs at least 3 of: lowercase letters, uppercase letters, numbers & symbols",
+            ),
             _ => None,
         })
     }
@@ -72,6 +92,7 @@ pub async fn get_replace_default_password(
                 (errors_list(None, validation_errors.as_nice_list()))
             }
             form method="post" {
+                (csrf_field())
                 input type="hidden" id="next" name="next" value={(next)};
                 div class="mb-4" {
                     label for="new_password" class="block text-sm font-bold mb-2 text-gray-300" {"New Password"}
@@ -119,6 +140,7 @@ pub async fn post_replace_default_password(
     if new_password.expose_secret().trim().is_empty() {
         errors |= ReplaceDefaultPasswordValidationError::EMPTY;
     }
+    errors |= ReplaceDefaultPasswordValidationError::check_strength(&state, &new_password);
     let password_is_same_as_before = {
         if let Some(bcrypt_hashed_password) = user.bcrypt_hashed_password.clone() {
             let new_password = new_password.clone();
@@ -147,8 +169,14 @@ pub async fn post_replace_default_password(
 
     let mut conn = state.get_connection().await?;
 
-    let PasswordUserId::FullUser(user) =
-        add_password(user.into(), new_password, &mut conn, false).await?
+    let PasswordUserId::FullUser(user) = add_password(
+        user.into(),
+        new_password,
+        state.config().security_config().bcrypt_cost,
+        &mut conn,
+        false,
+    )
+    .await?
     else {
         unreachable!("passed in a user")
     };
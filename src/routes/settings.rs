@@ -0,0 +1,321 @@
+use crate::{
+    auth::{AuthUtilities, DenimSession, PermissionsTarget},
+    config::{auth::AuthConfig, date_locale::DateLocaleConfig},
+    data::audit_log::{AuditAction, AuditTarget},
+    error::{DenimResult, MultipartSnafu},
+    maud_conveniences::{ToastKind, errors_list, form_submit_button, supertitle, title, toast},
+    routes::new_admin_flow::{
+        AuthConfigFailure, AuthConfigForm, S3Details, S3Failure, SetupTzForm,
+        auth_config_form_elements, bucket_from_details, date_locale_form_elements,
+        parse_auth_config_form, s3_form_elements,
+    },
+    state::DenimState,
+};
+use axum::{
+    Form,
+    extract::{Multipart, State},
+};
+use maud::{Markup, html};
+use snafu::ResultExt;
+
+pub async fn get_settings(
+    State(state): State<DenimState>,
+    session: DenimSession,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::RUN_ONBOARDING)?;
+
+    let auth_config = auth_config_section(&state, AuthConfigFailure::empty(), false)?;
+    let date_locale = date_locale_section(&state, None, false)?;
+    let s3 = s3_section(&state, S3Failure::empty(), false)?;
+
+    Ok(state.render(
+        session,
+        html! {
+            div class="flex flex-col items-center justify-center space-y-4 w-full max-w-md" {
+                (supertitle("Settings"))
+                div id="auth_config_section" class="bg-gray-800 p-8 rounded-lg shadow-xl w-full" {
+                    (auth_config)
+                }
+                div id="date_locale_section" class="bg-gray-800 p-8 rounded-lg shadow-xl w-full" {
+                    (date_locale)
+                }
+                div id="s3_section" class="bg-gray-800 p-8 rounded-lg shadow-xl w-full" {
+                    (s3)
+                }
+            }
+        },
+    ))
+}
+
+fn auth_config_section(
+    state: &DenimState,
+    failure: AuthConfigFailure,
+    saved: bool,
+) -> DenimResult<Markup> {
+    let auth_config = state.config().auth_config().get()?;
+
+    Ok(html! {
+        (title("Default Passwords"))
+        p {
+            "Passwords are generated in the following format: "
+            span class="italic" {(auth_config.format_example())}
+            "."
+        }
+        br;
+
+        @if saved {
+            (toast(ToastKind::Success, "Saved!"))
+        }
+        @if !failure.is_empty() {
+            (errors_list(Some("Validation Errors"), failure.as_nice_list()))
+            br;
+        }
+
+        form hx-post="/internal/settings/auth_config" hx-target="#auth_config_section" {
+            (auth_config_form_elements(&auth_config))
+            (form_submit_button(Some("Save")))
+        }
+
+        br;
+        p {
+            @if let Some(count) = auth_config.custom_word_count() {
+                "Words are picked from a custom list of " (count) " words."
+            } @else {
+                "Words are picked from the bundled list."
+            }
+        }
+        br;
+        form hx-post="/internal/settings/word_list" hx-target="#auth_config_section" hx-encoding="multipart/form-data" {
+            label for="word_list" class="block text-sm font-medium text-gray-400 mb-2" {"Upload Word List (one word per line)"}
+            input type="file" name="word_list" id="word_list" required accept=".txt,text/plain" class="block w-full text-sm text-gray-300 file:mr-4 file:py-2 file:px-4 file:rounded file:border-0 file:text-sm file:font-semibold file:bg-violet-50 file:text-violet-700 hover:file:bg-violet-100 mb-4";
+            (form_submit_button(Some("Upload")))
+        }
+        @if auth_config.custom_word_count().is_some() {
+            br;
+            button hx-delete="/internal/settings/word_list" hx-target="#auth_config_section" hx-confirm="Go back to using the bundled word list?" class="bg-yellow-600 hover:bg-yellow-800 font-bold py-2 px-4 rounded w-full" {
+                "Use Bundled List"
+            }
+        }
+    })
+}
+
+pub async fn internal_post_settings_auth_config(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Form(input): Form<AuthConfigForm>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::RUN_ONBOARDING)?;
+
+    let current_config = state.config().auth_config().get()?;
+    let new_config = match parse_auth_config_form(&input, Some(&current_config)) {
+        Ok(config) => config,
+        Err(failure) => return auth_config_section(&state, failure, false),
+    };
+
+    state
+        .config()
+        .auth_config()
+        .replace(new_config, &*state.config().s3_bucket().get()?)
+        .await?;
+    state
+        .audit(
+            &session,
+            AuditAction::Edit,
+            AuditTarget::Config("auth_config"),
+            None,
+        )
+        .await;
+
+    auth_config_section(&state, AuthConfigFailure::empty(), true)
+}
+
+pub async fn internal_post_settings_word_list(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    mut multipart: Multipart,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::RUN_ONBOARDING)?;
+
+    let mut uploaded = None;
+    while let Some(field) = multipart.next_field().await.context(MultipartSnafu)? {
+        if field.name() == Some("word_list") {
+            uploaded = Some(field.bytes().await.context(MultipartSnafu)?);
+        }
+    }
+
+    let Some(words) = uploaded
+        .and_then(|bytes| String::from_utf8(bytes.to_vec()).ok())
+        .map(|all_words| AuthConfig::parse_words(&all_words))
+        .filter(|words| !words.is_empty())
+    else {
+        return auth_config_section(&state, AuthConfigFailure::BAD_WORD_LIST, false);
+    };
+
+    let new_config = state
+        .config()
+        .auth_config()
+        .get()?
+        .with_custom_words(Some(words));
+    if !new_config.word_len_range_is_usable(&new_config.word_len_range) {
+        return auth_config_section(&state, AuthConfigFailure::WORD_LIST_NO_WORDS, false);
+    }
+
+    state
+        .config()
+        .auth_config()
+        .replace(new_config, &*state.config().s3_bucket().get()?)
+        .await?;
+    state
+        .audit(
+            &session,
+            AuditAction::Edit,
+            AuditTarget::Config("word_list"),
+            None,
+        )
+        .await;
+
+    auth_config_section(&state, AuthConfigFailure::empty(), true)
+}
+
+pub async fn delete_settings_word_list(
+    State(state): State<DenimState>,
+    session: DenimSession,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::RUN_ONBOARDING)?;
+
+    let new_config = state.config().auth_config().get()?.with_custom_words(None);
+    //the bundled list might not cover a range that was picked for a custom list
+    if !new_config.word_len_range_is_usable(&new_config.word_len_range) {
+        return auth_config_section(&state, AuthConfigFailure::WL_NO_WORDS, false);
+    }
+
+    state
+        .config()
+        .auth_config()
+        .replace(new_config, &*state.config().s3_bucket().get()?)
+        .await?;
+    state
+        .audit(
+            &session,
+            AuditAction::Delete,
+            AuditTarget::Config("word_list"),
+            None,
+        )
+        .await;
+
+    auth_config_section(&state, AuthConfigFailure::empty(), true)
+}
+
+fn date_locale_section(
+    state: &DenimState,
+    error: Option<String>,
+    saved: bool,
+) -> DenimResult<Markup> {
+    let date_locale_config = state.config().date_locale_config().get()?;
+
+    Ok(html! {
+        (title("Timezone & Locale"))
+        p {"This is used as the default for adding events, and for displaying dates & times."}
+        br;
+
+        @if saved {
+            (toast(ToastKind::Success, "Saved!"))
+        }
+        @if let Some(error) = error {
+            (errors_list(Some("Validation Errors"), std::iter::once(error)))
+            br;
+        }
+
+        form hx-post="/internal/settings/date_locale" hx-target="#date_locale_section" {
+            (date_locale_form_elements(Some(&date_locale_config)))
+            (form_submit_button(Some("Save")))
+        }
+    })
+}
+
+pub async fn internal_post_settings_date_locale(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Form(SetupTzForm {
+        tz,
+        hour_cycle,
+        calendar_algorithm,
+        locale,
+    }): Form<SetupTzForm>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::RUN_ONBOARDING)?;
+
+    let new_config = match DateLocaleConfig::new(tz, locale, hour_cycle, calendar_algorithm) {
+        Ok(config) => config,
+        Err(e) => return date_locale_section(&state, Some(e.to_string()), false),
+    };
+
+    state
+        .config()
+        .date_locale_config()
+        .replace(new_config, &*state.config().s3_bucket().get()?)
+        .await?;
+    state
+        .audit(
+            &session,
+            AuditAction::Edit,
+            AuditTarget::Config("date_locale"),
+            None,
+        )
+        .await;
+
+    date_locale_section(&state, None, true)
+}
+
+fn s3_section(state: &DenimState, failure: S3Failure, saved: bool) -> DenimResult<Markup> {
+    let bucket = state.config().s3_bucket().get()?;
+
+    Ok(html! {
+        (title("External Storage"))
+        p {"The credentials aren't shown, so they need to be re-entered to make any changes."}
+        br;
+        p class="italic" {
+            "NB: Existing photos are not copied over to a new bucket."
+        }
+        br;
+
+        @if saved {
+            (toast(ToastKind::Success, "Saved!"))
+        }
+        @if !failure.is_empty() {
+            (errors_list(Some("Validation Errors"), failure.as_nice_list()))
+            br;
+        }
+
+        form hx-post="/internal/settings/s3" hx-target="#s3_section" {
+            (s3_form_elements(Some(&bucket)))
+            (form_submit_button(Some("Save")))
+        }
+    })
+}
+
+pub async fn internal_post_settings_s3(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Form(details): Form<S3Details>,
+) -> DenimResult<Markup> {
+    session.ensure_can(PermissionsTarget::RUN_ONBOARDING)?;
+
+    let bucket = match bucket_from_details(details).await? {
+        Ok(bucket) => bucket,
+        Err(failure) => return s3_section(&state, failure, false),
+    };
+
+    //the bucket itself doesn't get saved anywhere, but the other config does so copy that across
+    state
+        .config()
+        .s3_bucket()
+        .replace(*bucket.clone(), &bucket)
+        .await?;
+    state.config().save().await?;
+    state
+        .audit(&session, AuditAction::Edit, AuditTarget::Config("s3"), None)
+        .await;
+
+    s3_section(&state, S3Failure::empty(), true)
+}
@@ -1,13 +1,21 @@
-use crate::state::DenimState;
+use crate::{
+    auth::DenimSession,
+    error::{DenimResult, UnableToFindUserInfoSnafu},
+    state::DenimState,
+};
 use axum::{
     extract::State,
+    http::HeaderMap,
     response::{
         Sse,
         sse::{Event as AxumSseEvent, KeepAlive},
     },
 };
-use futures::Stream;
-use std::convert::Infallible;
+use futures::{Stream, stream};
+use jiff::Timestamp;
+use snafu::OptionExt;
+use std::{collections::VecDeque, convert::Infallible};
+use tokio::sync::broadcast::{Receiver, error::RecvError};
 use tokio_stream::{StreamExt, wrappers::BroadcastStream};
 use uuid::Uuid;
 
@@ -15,8 +23,13 @@ use uuid::Uuid;
 pub enum SseEvent {
     CrudEvent,
     CrudPerson,
+    CrudGroups,
     ChangeSignUp { event_id: Uuid },
-    ChangePhotos { event_id: Uuid }
+    ChangePhotos { event_id: Uuid },
+    ChangeHousePoints,
+    ImportProgress { done: usize, total: usize },
+    ///only sent to the user whose password it was
+    PasswordReset,
 }
 
 impl From<SseEvent> for AxumSseEvent {
@@ -24,21 +37,174 @@ impl From<SseEvent> for AxumSseEvent {
         match value {
             SseEvent::CrudEvent => Self::default().event("crud_event").data(""),
             SseEvent::CrudPerson => Self::default().event("crud_person").data(""),
+            SseEvent::CrudGroups => Self::default().event("crud_groups").data(""),
+            //the id is in the name for htmx triggers, and in the data for anything listening generically
             SseEvent::ChangeSignUp { event_id } => Self::default()
-                .event(format!("change_sign_up_{event_id}")).data(""),
+                .event(format!("change_sign_up_{event_id}"))
+                .data(event_id.to_string()),
             SseEvent::ChangePhotos { event_id } => Self::default()
-                .event(format!("change_photos_{event_id}")).data("")
+                .event(format!("change_photos_{event_id}"))
+                .data(event_id.to_string()),
+            SseEvent::ChangeHousePoints => Self::default().event("change_house_points").data(""),
+            SseEvent::ImportProgress { done, total } => Self::default()
+                .event("import_progress")
+                .data(format!("{done}/{total}")),
+            SseEvent::PasswordReset => Self::default()
+                .event("password_reset")
+                .data("Your password was just reset by an administrator."),
+        }
+    }
+}
+
+///an event, with the id it was sent out with
+pub type IdentifiedSseEvent = (u64, SseEvent);
 
+///the most recent events sent out, so that clients who briefly disconnect can catch up using `Last-Event-ID`
+#[derive(Debug)]
+pub struct SseHistory {
+    next_id: u64,
+    capacity: usize,
+    events: VecDeque<IdentifiedSseEvent>,
+}
+
+impl SseHistory {
+    pub fn new(capacity: usize) -> Self {
+        //start from the current time so that ids keep going up across restarts, and clients
+        //reconnecting to a new instance don't get sent old events again
+        let next_id = u64::try_from(Timestamp::now().as_millisecond()).unwrap_or_default();
+
+        Self {
+            next_id,
+            capacity,
+            events: VecDeque::with_capacity(capacity),
         }
     }
+
+    pub fn push(&mut self, event: SseEvent) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        if self.capacity > 0 {
+            if self.events.len() == self.capacity {
+                self.events.pop_front();
+            }
+            self.events.push_back((id, event));
+        }
+
+        id
+    }
+
+    pub fn events_after(&self, last_id: u64) -> Vec<IdentifiedSseEvent> {
+        self.events
+            .iter()
+            .filter(|(id, _)| *id > last_id)
+            .copied()
+            .collect()
+    }
+}
+
+fn to_axum_event((id, sse_event): IdentifiedSseEvent) -> AxumSseEvent {
+    AxumSseEvent::from(sse_event).id(id.to_string())
 }
 
 pub async fn sse_feed(
     State(state): State<DenimState>,
+    headers: HeaderMap,
 ) -> Sse<impl Stream<Item = Result<AxumSseEvent, Infallible>>> {
-    let stream = BroadcastStream::new(state.subscribe_to_sse_feed())
-        .filter_map(Result::ok)
-        .map(|sse_event| Ok(sse_event.into()));
+    let last_event_id = headers
+        .get("Last-Event-ID")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok());
+
+    let (missed, rx) = state.subscribe_to_sse_feed(last_event_id);
+    let stream = tokio_stream::iter(missed)
+        .chain(BroadcastStream::new(rx).filter_map(Result::ok))
+        .map(|sse_event| Ok(to_axum_event(sse_event)));
 
     Sse::new(stream).keep_alive(KeepAlive::default())
 }
+
+///owns one of a user's receivers, and cleans up their channel when the last one goes
+struct UserSseSubscription {
+    state: DenimState,
+    user_id: Uuid,
+    rx: Receiver<SseEvent>,
+}
+
+impl Drop for UserSseSubscription {
+    fn drop(&mut self) {
+        self.state.remove_unused_user_sse_channel(self.user_id);
+    }
+}
+
+pub async fn sse_feed_me(
+    State(state): State<DenimState>,
+    session: DenimSession,
+) -> DenimResult<Sse<impl Stream<Item = Result<AxumSseEvent, Infallible>>>> {
+    let user_id = session.user.as_ref().context(UnableToFindUserInfoSnafu)?.id;
+
+    let subscription = UserSseSubscription {
+        rx: state.subscribe_to_user_sse_feed(user_id),
+        state,
+        user_id,
+    };
+    let stream = stream::unfold(subscription, |mut subscription| async move {
+        loop {
+            match subscription.rx.recv().await {
+                Ok(sse_event) => return Some((Ok(sse_event.into()), subscription)),
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SseEvent;
+    use axum::{
+        body::to_bytes,
+        response::{IntoResponse, Sse, sse::Event as AxumSseEvent},
+    };
+    use std::convert::Infallible;
+    use uuid::Uuid;
+
+    async fn serialise(event: SseEvent) -> String {
+        let response = Sse::new(futures::stream::iter([Ok::<_, Infallible>(
+            AxumSseEvent::from(event),
+        )]))
+        .into_response();
+        let bytes = to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body is all in memory");
+        String::from_utf8(bytes.to_vec()).expect("events are text")
+    }
+
+    #[tokio::test]
+    async fn event_ids_are_in_the_name_and_data() {
+        let event_id = Uuid::new_v4();
+
+        assert_eq!(
+            serialise(SseEvent::ChangeSignUp { event_id }).await,
+            format!("event: change_sign_up_{event_id}\ndata: {event_id}\n\n")
+        );
+        assert_eq!(
+            serialise(SseEvent::ChangePhotos { event_id }).await,
+            format!("event: change_photos_{event_id}\ndata: {event_id}\n\n")
+        );
+    }
+
+    #[tokio::test]
+    async fn other_events_have_fixed_names() {
+        assert_eq!(
+            serialise(SseEvent::CrudEvent).await,
+            "event: crud_event\ndata: \n\n"
+        );
+        assert_eq!(
+            serialise(SseEvent::ImportProgress { done: 3, total: 10 }).await,
+            "event: import_progress\ndata: 3/10\n\n"
+        );
+    }
+}
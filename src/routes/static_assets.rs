@@ -0,0 +1,20 @@
+use axum::{
+    http::header,
+    response::{IntoResponse, Response},
+};
+
+///precompiled by `build.rs` - empty if the tailwind CLI wasn't around for a debug build
+pub const DENIM_CSS: &str = include_str!(concat!(env!("OUT_DIR"), "/denim.css"));
+pub const DENIM_CSS_URL: &str = concat!("/static/denim.css?v=", env!("DENIM_CSS_VERSION"));
+
+pub async fn get_denim_css() -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "text/css; charset=utf-8"),
+            //the URL changes whenever the contents do
+            (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+        ],
+        DENIM_CSS,
+    )
+        .into_response()
+}
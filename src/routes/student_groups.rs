@@ -0,0 +1,427 @@
+use crate::{
+    auth::{AuthUtilities, DenimSession, PermissionsTarget},
+    data::{
+        DataType, IdForm, IntIdForm,
+        audit_log::{AuditAction, AuditTarget},
+        student_groups::{HouseGroup, NewHouse, NewTutorGroup, TutorGroup},
+        user::User,
+    },
+    error::{
+        DenimResult, HouseStillInUseSnafu, MissingHouseGroupSnafu, MissingTutorGroupSnafu,
+        TutorGroupStillInUseSnafu,
+    },
+    maud_conveniences::{
+        errors_list, form_element, form_submit_button, simple_form_element, subtitle, supertitle,
+        table, title,
+    },
+    routes::sse::SseEvent,
+    state::DenimState,
+};
+use axum::{
+    Form,
+    extract::{Path, Query, State},
+};
+use maud::{Markup, html};
+use snafu::{OptionExt, ensure};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+pub async fn get_house(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(id): Path<i32>,
+) -> DenimResult<Markup> {
+    //house-scoped staff only get to see who's in their own house
+    let can_view_members =
+        session.can_for_house(PermissionsTarget::VIEW_SENSITIVE_DETAILS, Some(id));
+
+    let mut conn = state.get_connection().await?;
+    let house = HouseGroup::get_from_db_by_id(id, &mut conn)
+        .await?
+        .context(MissingHouseGroupSnafu { id })?;
+
+    let tutor_groups = TutorGroup::get_by_house(id, &mut conn).await?;
+    let tutor_ids: Vec<_> = tutor_groups
+        .iter()
+        .map(|tutor_group| tutor_group.staff_member)
+        .collect();
+    let mut tutors = User::get_many_by_ids(&tutor_ids, &mut conn).await?;
+    tutors.sort_by(|a, b| a.surname.cmp(&b.surname));
+
+    let students = if can_view_members {
+        User::get_students_by_house(id, &mut conn).await?
+    } else {
+        vec![]
+    };
+    drop(conn);
+
+    Ok(state.render(session, html! {
+        div class="mx-auto bg-gray-800 p-8 rounded shadow-md max-w-4xl w-full flex flex-col space-y-4" {
+            (supertitle(&house.name))
+
+            div {
+                (subtitle("Tutor Groups"))
+                @if tutors.is_empty() {
+                    p class="italic text-gray-400" {"This house doesn't have any tutor groups yet."}
+                } @else {
+                    ul class="list-disc list-inside" {
+                        @for tutor in tutors {
+                            li {(tutor)}
+                        }
+                    }
+                }
+            }
+
+            @if can_view_members {
+                div {
+                    (subtitle(html! {"Students (" (students.len()) ")"}))
+                    @if students.is_empty() {
+                        p class="italic text-gray-400" {"This house doesn't have any students yet."}
+                    } @else {
+                        div class="grid grid-cols-1 sm:grid-cols-2 md:grid-cols-3 lg:grid-cols-4 gap-4" {
+                            @for student in students {
+                                div class="block rounded-lg shadow-md p-4 text-center bg-gray-700" {
+                                    (student)
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }))
+}
+
+pub async fn get_tutor_group(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(id): Path<Uuid>,
+) -> DenimResult<Markup> {
+    let mut conn = state.get_connection().await?;
+    let tutor_group = TutorGroup::get_from_db_by_id(id, &mut conn)
+        .await?
+        .context(MissingTutorGroupSnafu { id })?;
+    let can_view_members = session.can_for_house(
+        PermissionsTarget::VIEW_SENSITIVE_DETAILS,
+        Some(tutor_group.house_id),
+    );
+    let house = HouseGroup::get_from_db_by_id(tutor_group.house_id, &mut conn)
+        .await?
+        .context(MissingHouseGroupSnafu {
+            id: tutor_group.house_id,
+        })?;
+    let tutor = User::get_from_db_by_id(tutor_group.staff_member, &mut conn).await?;
+
+    let students = if can_view_members {
+        User::get_students_by_tutor_group(id, &mut conn).await?
+    } else {
+        vec![]
+    };
+    drop(conn);
+
+    Ok(state.render(session, html! {
+        div class="mx-auto bg-gray-800 p-8 rounded shadow-md max-w-4xl w-full flex flex-col space-y-4" {
+            (supertitle("Tutor Group"))
+
+            div {
+                p class="text-gray-200 font-semibold" {
+                    "Tutor: "
+                    @if let Some(tutor) = tutor {
+                        span class="font-medium" {(tutor)}
+                    } @else {
+                        span class="font-medium italic" {"Unknown Staff Member"}
+                    }
+                }
+                p class="text-gray-200 font-semibold" {
+                    "House: "
+                    a href={"/house/" (house.id)} class="font-medium underline hover:text-blue-300" {(house.name)}
+                }
+            }
+
+            @if can_view_members {
+                div {
+                    (subtitle(html! {"Students (" (students.len()) ")"}))
+                    @if students.is_empty() {
+                        p class="italic text-gray-400" {"This tutor group doesn't have any students yet."}
+                    } @else {
+                        div class="grid grid-cols-1 sm:grid-cols-2 md:grid-cols-3 lg:grid-cols-4 gap-4" {
+                            @for student in students {
+                                div class="block rounded-lg shadow-md p-4 text-center bg-gray-700" {
+                                    (student)
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }))
+}
+
+pub async fn internal_get_houses(
+    State(state): State<DenimState>,
+    session: DenimSession,
+) -> DenimResult<Markup> {
+    //house-scoped staff only get to manage the students, not the houses themselves
+    session.ensure_can_for_house(PermissionsTarget::CRUD_USERS, None)?;
+
+    let mut houses = HouseGroup::get_all(&state).await?;
+    houses.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(html! {
+        div id="houses" hx-get="/internal/houses" hx-trigger="sse:crud_groups" hx-swap="outerHTML" class="flex flex-col p-4" {
+            div id="houses_errors" {}
+            (table(
+                title("Houses"),
+                ["Name", ""],
+                houses.into_iter().map(|house| [
+                    html! {
+                        form hx-post={"/internal/houses/" (house.id) "/rename"} hx-target="#houses_errors" class="flex flex-row space-x-2" {
+                            input type="text" name="name" value=(house.name) required class="shadow appearance-none border rounded w-full py-1 px-2 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600";
+                            input type="submit" value="Rename" class="bg-blue-500 hover:bg-blue-700 font-bold py-1 px-3 rounded cursor-pointer";
+                        }
+                    },
+                    html! {
+                        button class="bg-red-600 hover:bg-red-800 font-bold py-1 px-3 rounded" hx-delete="/houses" hx-vals={"{\"id\": " (house.id) "}"} hx-target="#houses_errors" {
+                            "Delete"
+                        }
+                    },
+                ]).collect()
+            ))
+
+            form hx-put="/internal/houses" hx-target="#houses" hx-swap="outerHTML" class="mt-4" {
+                (simple_form_element("name", "New House Name", true, None, None))
+                (form_submit_button(Some("Add House")))
+            }
+        }
+    })
+}
+
+pub async fn internal_put_new_house(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Form(NewHouse { name }): Form<NewHouse>,
+) -> DenimResult<Markup> {
+    session.ensure_can_for_house(PermissionsTarget::CRUD_USERS, None)?;
+
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Ok(errors_list(
+            None,
+            std::iter::once("House names cannot be empty"),
+        ));
+    }
+
+    let id = HouseGroup::insert_into_database(
+        NewHouse { name: name.clone() },
+        &mut *state.get_connection().await?,
+    )
+    .await?;
+    state
+        .audit(
+            &session,
+            AuditAction::Create,
+            AuditTarget::House(id),
+            Some(&name),
+        )
+        .await;
+    state.send_sse_event(SseEvent::CrudGroups);
+
+    internal_get_houses(State(state), session).await
+}
+
+pub async fn internal_post_rename_house(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Path(id): Path<i32>,
+    Form(NewHouse { name }): Form<NewHouse>,
+) -> DenimResult<Markup> {
+    session.ensure_can_for_house(PermissionsTarget::CRUD_USERS, None)?;
+
+    let name = name.trim();
+    if name.is_empty() {
+        return Ok(errors_list(
+            None,
+            std::iter::once("House names cannot be empty"),
+        ));
+    }
+
+    let mut conn = state.get_connection().await?;
+    if HouseGroup::name_is_taken(name, Some(id), &mut conn).await? {
+        return Ok(errors_list(
+            None,
+            std::iter::once(format!("A house called {name:?} already exists")),
+        ));
+    }
+
+    HouseGroup::update_name(id, name, &mut conn).await?;
+    drop(conn);
+    state
+        .audit(
+            &session,
+            AuditAction::Edit,
+            AuditTarget::House(id),
+            Some(&format!("renamed to {name:?}")),
+        )
+        .await;
+    state.send_sse_event(SseEvent::CrudGroups);
+    state.send_sse_event(SseEvent::CrudPerson);
+
+    Ok(html! {})
+}
+
+pub async fn delete_house(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Query(IntIdForm { id }): Query<IntIdForm>,
+) -> DenimResult<Markup> {
+    session.ensure_can_for_house(PermissionsTarget::CRUD_USERS, None)?;
+
+    let mut conn = state.get_connection().await?;
+    let house = HouseGroup::get_from_db_by_id(id, &mut conn)
+        .await?
+        .context(MissingHouseGroupSnafu { id })?;
+
+    //tutor groups would cascade, but students can't, so make sure nothing is left
+    let (tutor_groups, students) = HouseGroup::count_members(id, &mut conn).await?;
+    ensure!(
+        tutor_groups == 0 && students == 0,
+        HouseStillInUseSnafu {
+            name: house.name,
+            tutor_groups,
+            students
+        }
+    );
+
+    HouseGroup::remove_from_database(id, &mut conn).await?;
+    drop(conn);
+    state
+        .audit(
+            &session,
+            AuditAction::Delete,
+            AuditTarget::House(id),
+            Some(&house.name),
+        )
+        .await;
+    state.send_sse_event(SseEvent::CrudGroups);
+
+    Ok(html! {})
+}
+
+pub async fn internal_get_tutor_groups(
+    State(state): State<DenimState>,
+    session: DenimSession,
+) -> DenimResult<Markup> {
+    session.ensure_can_for_house(PermissionsTarget::CRUD_USERS, None)?;
+
+    let mut houses = HouseGroup::get_all(&state).await?;
+    houses.sort_by(|a, b| a.name.cmp(&b.name));
+    let staff = User::get_all_staff(&state).await?;
+
+    let house_names_by_id: HashMap<i32, &str> = houses
+        .iter()
+        .map(|house| (house.id, house.name.as_str()))
+        .collect();
+    let staff_by_id: HashMap<_, _> = staff.iter().map(|staff| (staff.id, staff)).collect();
+
+    let mut tutor_groups = TutorGroup::get_all(&state).await?;
+    tutor_groups.sort_by_key(|tutor_group| house_names_by_id.get(&tutor_group.house_id).copied());
+
+    Ok(html! {
+        div id="tutor_groups" hx-get="/internal/tutor_groups" hx-trigger="sse:crud_groups" hx-swap="outerHTML" class="flex flex-col p-4" {
+            div id="tutor_groups_errors" {}
+            (table(
+                title("Tutor Groups"),
+                ["House", "Tutor", ""],
+                tutor_groups.into_iter().map(|tutor_group| [
+                    html! {(house_names_by_id.get(&tutor_group.house_id).unwrap_or(&"Unknown House"))},
+                    html! {
+                        @if let Some(staff) = staff_by_id.get(&tutor_group.staff_member) {
+                            (staff)
+                        } @else {
+                            "Unknown Staff Member"
+                        }
+                    },
+                    html! {
+                        button class="bg-red-600 hover:bg-red-800 font-bold py-1 px-3 rounded" hx-delete="/tutor_groups" hx-vals={"{\"id\": \"" (tutor_group.id) "\"}"} hx-target="#tutor_groups_errors" {
+                            "Delete"
+                        }
+                    },
+                ]).collect()
+            ))
+
+            @if houses.is_empty() || staff.is_empty() {
+                p class="italic mt-4" {"You need at least one house and one staff member to add a tutor group."}
+            } @else {
+                form hx-put="/internal/tutor_groups" hx-target="#tutor_groups" hx-swap="outerHTML" class="mt-4" {
+                    (form_element("staff_id", "Tutor", html!{
+                        select id="staff_id" name="staff_id" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600" {
+                            @for staff in &staff {
+                                option value=(staff.id) {(staff)}
+                            }
+                        }
+                    }))
+                    (form_element("house_id", "House", html!{
+                        select id="house_id" name="house_id" class="shadow appearance-none border rounded w-full py-2 px-3 leading-tight focus:outline-none focus:shadow-outline bg-gray-700 border-gray-600" {
+                            @for house in &houses {
+                                option value=(house.id) {(house.name)}
+                            }
+                        }
+                    }))
+                    (form_submit_button(Some("Add Tutor Group")))
+                }
+            }
+        }
+    })
+}
+
+pub async fn internal_put_new_tutor_group(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Form(form): Form<NewTutorGroup>,
+) -> DenimResult<Markup> {
+    session.ensure_can_for_house(PermissionsTarget::CRUD_USERS, None)?;
+
+    let id = TutorGroup::insert_into_database(form, &mut *state.get_connection().await?).await?;
+    state
+        .audit(
+            &session,
+            AuditAction::Create,
+            AuditTarget::TutorGroup(id),
+            None,
+        )
+        .await;
+    state.send_sse_event(SseEvent::CrudGroups);
+
+    internal_get_tutor_groups(State(state), session).await
+}
+
+pub async fn delete_tutor_group(
+    State(state): State<DenimState>,
+    session: DenimSession,
+    Query(IdForm { id }): Query<IdForm>,
+) -> DenimResult<Markup> {
+    session.ensure_can_for_house(PermissionsTarget::CRUD_USERS, None)?;
+
+    let mut conn = state.get_connection().await?;
+    TutorGroup::get_from_db_by_id(id, &mut conn)
+        .await?
+        .context(MissingTutorGroupSnafu { id })?;
+
+    let students = TutorGroup::count_students(id, &mut conn).await?;
+    ensure!(students == 0, TutorGroupStillInUseSnafu { id, students });
+
+    TutorGroup::remove_from_database(id, &mut conn).await?;
+    drop(conn);
+    state
+        .audit(
+            &session,
+            AuditAction::Delete,
+            AuditTarget::TutorGroup(id),
+            None,
+        )
+        .await;
+    state.send_sse_event(SseEvent::CrudGroups);
+
+    Ok(html! {})
+}
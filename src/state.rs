@@ -1,16 +1,28 @@
 use crate::{
     auth::{AuthUtilities, DenimSession, PermissionsTarget},
     config::RuntimeConfiguration,
+    data::{
+        audit_log::{AuditAction, AuditEntry, AuditTarget},
+        event::Event,
+        photo::Photo,
+        user::UserKind,
+    },
     error::{DenimResult, GetDatabaseConnectionSnafu, MigrateSnafu, OpenDatabaseSnafu},
-    routes::sse::SseEvent,
+    maud_conveniences::{csrf_field, csrf_hx_headers},
+    routes::{
+        sse::{IdentifiedSseEvent, SseEvent, SseHistory},
+        static_assets::{DENIM_CSS, DENIM_CSS_URL},
+    },
+    theme::{LIGHT_THEME_STYLE, Theme, current_theme},
 };
-use maud::{DOCTYPE, Markup, html};
+use maud::{DOCTYPE, Markup, PreEscaped, html};
 use snafu::ResultExt;
 use sqlx::{Pool, Postgres, Transaction, pool::PoolConnection, postgres::PgPoolOptions};
 use std::{
+    collections::HashMap,
     ops::Deref,
     sync::{
-        Arc,
+        Arc, Mutex as StdMutex,
         atomic::{AtomicBool, Ordering},
     },
     time::Duration,
@@ -23,14 +35,22 @@ use tokio::{
     },
     task::JoinHandle,
 };
+use uuid::Uuid;
 
 type LongJobResult = JoinHandle<DenimResult<Markup>>;
 
+///how long an in-progress import gets to finish when shutting down
+const IMPORT_JOB_SHUTDOWN_GRACE: Duration = Duration::from_secs(30);
+const DELETED_EVENTS_SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+const STALE_PHOTO_UPLOADS_SWEEP_INTERVAL: Duration = Duration::from_mins(1);
+
 #[derive(Clone, Debug)]
 pub struct DenimState {
     pool: Pool<Postgres>,
     config: RuntimeConfiguration,
-    sse_events_sender: Sender<SseEvent>,
+    sse_events_sender: Sender<IdentifiedSseEvent>,
+    sse_history: Arc<StdMutex<SseHistory>>,
+    user_sse_senders: Arc<StdMutex<HashMap<Uuid, Sender<SseEvent>>>>,
     #[allow(clippy::type_complexity)]
     import_students_job: Arc<Mutex<Option<(LongJobResult, WatchRx<(usize, usize)>)>>>,
     submit_students_job_token: Arc<AtomicBool>,
@@ -71,20 +91,19 @@ impl DenimState {
 
         Ok(Self {
             pool,
-            config,
             sse_events_sender: tx,
+            sse_history: Arc::new(StdMutex::new(SseHistory::new(config.sse_replay_capacity()))),
             import_students_job: Arc::new(Mutex::new(None)),
             submit_students_job_token: Arc::new(AtomicBool::new(false)),
+            user_sse_senders: Arc::new(StdMutex::new(HashMap::new())),
+            config,
         })
     }
 
     pub async fn take_import_students_job_result(&self) -> Option<DenimResult<Markup>> {
         let mut lock = self.import_students_job.lock().await;
 
-        let mut is_finished = false;
-        if let Some((job, _rx)) = lock.as_mut() {
-            is_finished = job.is_finished();
-        }
+        let is_finished = lock.as_mut().is_some_and(|(job, _rx)| job.is_finished());
 
         if is_finished {
             //looks like there's no other way of doing this, because we only want to take it if it's finished
@@ -126,27 +145,37 @@ impl DenimState {
         }
     }
 
-    #[allow(clippy::unused_self, clippy::needless_pass_by_value)] //in case self is ever needed :), and to allow direct html! usage
+    #[allow(clippy::needless_pass_by_value)] //to allow direct html! usage
     pub fn render(&self, auth_session: DenimSession, markup: Markup) -> Markup {
-        let (height, nav) = render_nav(&auth_session);
+        let app_name = self.config.app_name();
+        let (height, nav) = render_nav(&auth_session, &app_name, self.config.logo_url().as_deref());
 
         let top_padding = format!("h-{}", height + 4);
 
         html! {
             (DOCTYPE)
-            html {
+            html data-theme=(current_theme().as_str()) {
                 head {
                     meta charset="UTF-8" {}
                     meta name="viewport" content="width=device-width, initial-scale=1.0" {}
                     script src="https://unpkg.com/htmx.org@2.0.4" integrity="sha384-HGfztofotfshcF7+8n44JQL2oJmowVChPTg48S+jvZoztPfvwD79OC/LTtG6dMp+" crossorigin="anonymous" {}
                     script src="https://unpkg.com/htmx-ext-sse@2.2.3" integrity="sha384-Y4gc0CK6Kg+hmulDc6rZPJu0tqvk7EWlih0Oh+2OkAi1ZDlCbBDCQEE2uVk472Ky" crossorigin="anonymous" {}
-                    script src="https://cdn.jsdelivr.net/npm/@tailwindcss/browser@4" {}
-                    title { "Denim?" }
+                    @if DENIM_CSS.is_empty() {
+                        script src="https://cdn.jsdelivr.net/npm/@tailwindcss/browser@4" {}
+                    } @else {
+                        link rel="stylesheet" href=(DENIM_CSS_URL);
+                    }
+                    style {(PreEscaped(LIGHT_THEME_STYLE))}
+                    title { (app_name) }
                 }
-                body hx-ext="sse" class="bg-gray-900 flex flex-col items-center text-white" {
+                body hx-ext="sse" hx-headers=[csrf_hx_headers()] class="bg-gray-900 flex flex-col items-center text-white" {
                     (nav)
                     div class={(top_padding) " bg-transparent"} {""}
+                    @if auth_session.can(PermissionsTarget::IMPORT_CSVS) {
+                        div id="unsaved_passwords_banner" hx-get="/internal/import_export/unsaved_passwords" hx-trigger="load" hx-swap="outerHTML" {}
+                    }
                     (markup)
+                    div id="toasts" aria-live="polite" class="fixed bottom-4 right-4 z-20 flex flex-col space-y-2 max-w-sm" {}
                 }
             }
         }
@@ -159,8 +188,7 @@ impl DenimState {
             .context(GetDatabaseConnectionSnafu)
     }
 
-    #[allow(dead_code)]
-    pub async fn get_transaction(&self) -> DenimResult<Transaction<Postgres>> {
+    pub async fn get_transaction(&self) -> DenimResult<Transaction<'_, Postgres>> {
         self.pool.begin().await.context(GetDatabaseConnectionSnafu)
     }
 
@@ -168,23 +196,159 @@ impl DenimState {
         &self.config
     }
 
-    pub fn subscribe_to_sse_feed(&self) -> Receiver<SseEvent> {
-        self.sse_events_sender.subscribe()
+    ///also gives back any buffered events after `last_event_id`, which the receiver won't see
+    pub fn subscribe_to_sse_feed(
+        &self,
+        last_event_id: Option<u64>,
+    ) -> (Vec<IdentifiedSseEvent>, Receiver<IdentifiedSseEvent>) {
+        //subscribing with the history locked means nothing can get sent in-between, so nothing gets missed or doubled up
+        let history = self.sse_history.lock().expect("sse history lock poisoned");
+        let rx = self.sse_events_sender.subscribe();
+        let missed = last_event_id.map_or_else(Vec::new, |last_event_id| {
+            history.events_after(last_event_id)
+        });
+        drop(history);
+
+        (missed, rx)
     }
 
+    #[allow(clippy::significant_drop_tightening)] //held while sending so ids go out in order
     pub fn send_sse_event(&self, event: SseEvent) {
-        let _ = self.sse_events_sender.send(event);
+        let mut history = self.sse_history.lock().expect("sse history lock poisoned");
+        let id = history.push(event);
+        let _ = self.sse_events_sender.send((id, event));
+    }
+
+    pub fn subscribe_to_user_sse_feed(&self, user_id: Uuid) -> Receiver<SseEvent> {
+        self.user_sse_senders
+            .lock()
+            .expect("user sse senders lock poisoned")
+            .entry(user_id)
+            .or_insert_with(|| channel(16).0)
+            .subscribe()
+    }
+
+    ///only reaches the user if they're currently connected to `/sse_feed/me`
+    pub fn send_sse_event_to_user(&self, user_id: Uuid, event: SseEvent) {
+        let senders = self
+            .user_sse_senders
+            .lock()
+            .expect("user sse senders lock poisoned");
+        if let Some(sender) = senders.get(&user_id) {
+            let _ = sender.send(event);
+        }
+    }
+
+    ///called by a subscription as it gets dropped, so its own receiver is still counted
+    pub fn remove_unused_user_sse_channel(&self, user_id: Uuid) {
+        let mut senders = self
+            .user_sse_senders
+            .lock()
+            .expect("user sse senders lock poisoned");
+        if senders
+            .get(&user_id)
+            .is_some_and(|sender| sender.receiver_count() <= 1)
+        {
+            senders.remove(&user_id);
+        }
     }
 
     pub fn delayed_send_sse_event(&self, event: SseEvent, millis: u64) {
-        let sender = self.sse_events_sender.clone();
+        let state = self.clone();
         tokio::task::spawn(async move {
             tokio::time::sleep(Duration::from_millis(millis)).await;
-            let _ = sender.send(event);
+            state.send_sse_event(event);
         });
     }
 
+    ///hard-deletes events once they're past the undo window - never returns, so should be spawned
+    pub async fn purge_deleted_events(self) {
+        let mut interval = tokio::time::interval(DELETED_EVENTS_SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let result = match self.get_connection().await {
+                Ok(mut conn) => Event::purge_deleted(&mut conn).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(0) => {}
+                Ok(purged) => info!(?purged, "Purged deleted events"),
+                Err(e) => error!(?e, "Unable to purge deleted events"),
+            }
+        }
+    }
+
+    pub async fn purge_stale_photo_uploads(self) {
+        let mut interval = tokio::time::interval(STALE_PHOTO_UPLOADS_SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            //nothing can have been uploaded before there's a bucket
+            let Ok(bucket) = self.config.s3_bucket().get() else {
+                continue;
+            };
+            let result = match self.get_connection().await {
+                Ok(mut conn) => Photo::purge_stale_direct_uploads(&bucket, &mut conn).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(0) => {}
+                Ok(purged) => info!(?purged, "Purged stale photo uploads"),
+                Err(e) => error!(?e, "Unable to purge stale photo uploads"),
+            }
+        }
+    }
+
+    ///records who did what - failures only get logged, as whatever's being audited has already happened
+    pub async fn audit(
+        &self,
+        session: &DenimSession,
+        action: AuditAction,
+        target: AuditTarget,
+        detail: Option<&str>,
+    ) {
+        let actor_id = session.user.as_ref().map(|user| user.id);
+        self.audit_as(actor_id, action, target, detail).await;
+    }
+
+    ///for when there's no session, eg. requests to the API
+    pub async fn audit_as(
+        &self,
+        actor_id: Option<Uuid>,
+        action: AuditAction,
+        target: AuditTarget,
+        detail: Option<&str>,
+    ) {
+        let result = match self.get_connection().await {
+            Ok(mut conn) => AuditEntry::record(actor_id, action, target, detail, &mut conn).await,
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = result {
+            error!(?e, ?action, ?target, "Unable to write to audit log");
+        }
+    }
+
     pub async fn sensible_shutdown(&self) -> DenimResult<()> {
+        //take it out so nothing else can grab the result while we're waiting on it
+        let job = self.import_students_job.lock().await.take();
+        if let Some((mut job, _rx)) = job {
+            info!("Waiting for the student import job to finish before shutting down");
+            if tokio::time::timeout(IMPORT_JOB_SHUTDOWN_GRACE, &mut job)
+                .await
+                .is_ok()
+            {
+                info!("Student import job finished");
+            } else {
+                warn!(
+                    ?IMPORT_JOB_SHUTDOWN_GRACE,
+                    "Student import job didn't finish in time, aborting it - nothing from it will have been committed"
+                );
+                job.abort();
+            }
+        }
+
         self.config.save().await?;
 
         Ok(())
@@ -199,11 +363,17 @@ impl Deref for DenimState {
     }
 }
 
-fn render_nav(session: &DenimSession) -> (u32, Markup) {
+fn render_nav(session: &DenimSession, app_name: &str, logo_url: Option<&str>) -> (u32, Markup) {
     let can_view_people = session.can(PermissionsTarget::VIEW_SENSITIVE_DETAILS);
     let can_import_export = session.can(PermissionsTarget::IMPORT_CSVS);
+    let can_change_settings = session.can(PermissionsTarget::RUN_ONBOARDING);
+    let can_view_audit_log = session.can(PermissionsTarget::CRUD_ADMINS);
+    let can_manage_roles = session.can(PermissionsTarget::CRUD_ADMINS);
 
     let logged_in_user = session.user.as_ref();
+    let is_staff = logged_in_user.is_some_and(|user| matches!(user.kind, UserKind::Staff));
+    let is_student =
+        logged_in_user.is_some_and(|user| matches!(user.kind, UserKind::Student { .. }));
 
     let height = if logged_in_user.is_some() { 24 } else { 16 };
 
@@ -215,18 +385,46 @@ fn render_nav(session: &DenimSession) -> (u32, Markup) {
                     @let height_class = format!("h-{height}");
                     div class={"flex items-center justify-center space-x-4 " (height_class)} {
                         a href="/events" class="text-gray-300 bg-slate-900 hover:bg-slate-700 px-3 py-2 rounded-md text-sm font-medium" {"Events"}
+                        a href="/leaderboard" class="text-gray-300 bg-slate-900 hover:bg-slate-700 px-3 py-2 rounded-md text-sm font-medium" {"Leaderboard"}
+                        @if is_student {
+                            a href="/my_signups" class="text-gray-300 bg-slate-900 hover:bg-slate-700 px-3 py-2 rounded-md text-sm font-medium" {"My Sign-Ups"}
+                        }
+                        @if is_staff {
+                            a href="/my_events" class="text-gray-300 bg-slate-900 hover:bg-slate-700 px-3 py-2 rounded-md text-sm font-medium" {"My Events"}
+                        }
                         @if can_view_people {
                             a href="/people" class="text-gray-300 bg-slate-900 hover:bg-slate-700 px-3 py-2 rounded-md text-sm font-medium" {"People"}
                         }
                         @if can_import_export {
                             a href="/import_export" class="text-gray-300 bg-slate-900 hover:bg-slate-700 px-3 py-2 rounded-md text-sm font-medium" {"Import/Export CSVs"}
                         }
-                        a href="/" class="text-gray-300 bg-fuchsia-900 hover:bg-fuchsia-700 px-3 py-2 rounded-md text-md font-bold" {"Denim"}
+                        @if can_change_settings {
+                            a href="/settings" class="text-gray-300 bg-slate-900 hover:bg-slate-700 px-3 py-2 rounded-md text-sm font-medium" {"Settings"}
+                        }
+                        @if can_view_audit_log {
+                            a href="/audit" class="text-gray-300 bg-slate-900 hover:bg-slate-700 px-3 py-2 rounded-md text-sm font-medium" {"Audit Log"}
+                        }
+                        @if can_manage_roles {
+                            a href="/roles" class="text-gray-300 bg-slate-900 hover:bg-slate-700 px-3 py-2 rounded-md text-sm font-medium" {"Roles"}
+                        }
+                        a href="/" class="flex flex-row items-center space-x-2 text-gray-300 bg-fuchsia-900 hover:bg-fuchsia-700 px-3 py-2 rounded-md text-md font-bold" {
+                            @if let Some(logo_url) = logo_url {
+                                img src=(logo_url) alt="" class="h-6 w-auto";
+                            }
+                            span {(app_name)}
+                        }
+                        button hx-post="/theme" class="text-gray-300 bg-slate-900 hover:bg-slate-700 px-3 py-2 rounded-md text-sm font-medium cursor-pointer" {
+                            @match current_theme() {
+                                Theme::Dark => "Light Mode",
+                                Theme::Light => "Dark Mode",
+                            }
+                        }
                         @match logged_in_user {
                             Some(logged_in_user) => {
                                 div class="flex flex-col space-y-2 text-center items-center justify-between" {
                                     a href="/profile" id="nav_username" class="text-gray-300 bg-green-900 hover:bg-green-700 px-3 py-2 rounded-md text-sm font-medium" {(logged_in_user)}
                                     form method="post" action="/logout" {
+                                        (csrf_field())
                                         input type="submit" value="Logout" class="text-gray-300 bg-red-900 hover:bg-red-700 px-3 py-2 rounded-md text-sm font-medium" {}
                                     }
                                 }
@@ -0,0 +1,91 @@
+use crate::error::{DenimResult, TowerSessionSnafu};
+use axum::{
+    extract::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_login::tower_sessions::Session;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+
+const THEME_SESSION_KEY: &str = "theme";
+
+///everything is written with dark classes, so the light theme just remaps the colours they use
+///
+///values are tailwind's own, flipped around the middle of each palette
+pub const LIGHT_THEME_STYLE: &str = r#"html[data-theme="light"] {
+    --color-white: oklch(21% 0.034 264.665);
+    --color-gray-900: oklch(98.5% 0.002 247.839);
+    --color-gray-800: oklch(96.7% 0.003 264.542);
+    --color-gray-700: oklch(92.8% 0.006 264.531);
+    --color-gray-600: oklch(87.2% 0.01 258.338);
+    --color-gray-400: oklch(44.6% 0.03 256.802);
+    --color-gray-300: oklch(37.3% 0.034 259.733);
+    --color-gray-200: oklch(27.8% 0.033 256.848);
+    --color-slate-900: oklch(92.9% 0.013 255.508);
+    --color-slate-700: oklch(86.9% 0.022 252.894);
+}"#;
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Dark => "dark",
+            Self::Light => "light",
+        }
+    }
+
+    pub const fn toggled(self) -> Self {
+        match self {
+            Self::Dark => Self::Light,
+            Self::Light => Self::Dark,
+        }
+    }
+}
+
+tokio::task_local! {
+    //same as the CSRF token - saves threading the session through to `render`
+    static THEME: Theme;
+}
+
+///the theme for the session currently being responded to
+pub fn current_theme() -> Theme {
+    THEME.try_with(|theme| *theme).unwrap_or_default()
+}
+
+async fn get_theme(session: &Session) -> DenimResult<Theme> {
+    Ok(session
+        .get::<Theme>(THEME_SESSION_KEY)
+        .await
+        .context(TowerSessionSnafu)?
+        .unwrap_or_default())
+}
+
+pub async fn theme_scope(session: Session, request: Request, next: Next) -> Response {
+    let theme = match get_theme(&session).await {
+        Ok(theme) => theme,
+        Err(e) => {
+            warn!(?e, "Unable to get theme from session, using the default");
+            Theme::default()
+        }
+    };
+
+    THEME.scope(theme, next.run(request)).await
+}
+
+pub async fn post_toggle_theme(session: Session) -> DenimResult<impl IntoResponse> {
+    let theme = get_theme(&session).await?.toggled();
+    session
+        .insert(THEME_SESSION_KEY, theme)
+        .await
+        .context(TowerSessionSnafu)?;
+
+    //every class on the page would need swapping, so easier to just reload
+    Ok([("HX-Refresh", "true")])
+}